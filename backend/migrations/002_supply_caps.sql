@@ -0,0 +1,29 @@
+-- Hard cap / soft cap enforcement
+
+-- Single locked counter row tracking everything sold so far. Purchases take a
+-- row lock on it (SELECT ... FOR UPDATE) so concurrent confirmations can never
+-- oversell the hard cap.
+CREATE TABLE presale_supply (
+    id BOOLEAN PRIMARY KEY DEFAULT TRUE CHECK (id),
+    tokens_sold DECIMAL(20, 8) NOT NULL DEFAULT 0,
+    sol_raised DECIMAL(20, 8) NOT NULL DEFAULT 0,
+    soft_cap_reached BOOLEAN NOT NULL DEFAULT FALSE,
+    soft_cap_reached_at TIMESTAMP WITH TIME ZONE,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+INSERT INTO presale_supply (id) VALUES (TRUE);
+
+-- Partial fills: what the buyer asked for vs. what was delivered, and the SOL
+-- sent back for the unfilled part
+ALTER TABLE transactions
+    ADD COLUMN amount_requested DECIMAL(20, 8),
+    ADD COLUMN refund_amount_sol DECIMAL(20, 8) NOT NULL DEFAULT 0,
+    ADD COLUMN refund_signature VARCHAR(88),
+    ADD COLUMN updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW();
+
+INSERT INTO presale_settings (key, value, description) VALUES
+('hard_cap_tokens', '1000000000', 'Maximum tokens sold during the presale'),
+('hard_cap_sol', '45000', 'Maximum SOL raised during the presale'),
+('soft_cap_sol', '5000', 'Minimum SOL raised for the presale to succeed'),
+('cap_overflow_mode', 'partial_refund', 'Purchases crossing the hard cap: partial_refund or reject');
@@ -0,0 +1,3 @@
+-- Per-wallet lifetime purchase cap for the presale rule engine
+INSERT INTO presale_settings (key, value, description) VALUES
+('max_wallet_purchase', '5000000', 'Maximum tokens a single wallet can buy across all purchases');
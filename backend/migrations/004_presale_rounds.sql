@@ -0,0 +1,45 @@
+-- Multi-round presale stages (seed, private, public, ...)
+CREATE TABLE presale_rounds (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name VARCHAR(50) NOT NULL,
+    sequence INTEGER UNIQUE NOT NULL,
+    starts_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    ends_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    price_sol DECIMAL(20, 10) NOT NULL, -- price per token in each accepted currency
+    price_usdc DECIMAL(20, 10),
+    price_usdt DECIMAL(20, 10),
+    token_allocation DECIMAL(20, 8) NOT NULL,
+    tokens_sold DECIMAL(20, 8) NOT NULL DEFAULT 0,
+    required_whitelist_tier INTEGER, -- NULL: open to everyone
+    cliff_duration_days INTEGER NOT NULL DEFAULT 30,
+    vesting_duration_days INTEGER NOT NULL DEFAULT 365,
+    activated_at TIMESTAMP WITH TIME ZONE, -- first sale in this round
+    closed_at TIMESTAMP WITH TIME ZONE, -- allocation sold out
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    CHECK (ends_at > starts_at)
+);
+
+-- Per-round breakdown of purchases that span rounds
+CREATE TABLE transaction_round_fills (
+    transaction_id UUID NOT NULL REFERENCES transactions(id),
+    round_id UUID NOT NULL REFERENCES presale_rounds(id),
+    amount_tokens DECIMAL(20, 8) NOT NULL,
+    price DECIMAL(20, 10) NOT NULL,
+    amount_paid DECIMAL(20, 8) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    PRIMARY KEY (transaction_id, round_id)
+);
+
+-- Round the purchase started in
+ALTER TABLE transactions ADD COLUMN round_id UUID REFERENCES presale_rounds(id);
+
+CREATE INDEX idx_transactions_round_id ON transactions(round_id);
+CREATE INDEX idx_transaction_round_fills_round ON transaction_round_fills(round_id);
+
+INSERT INTO presale_rounds (
+    name, sequence, starts_at, ends_at, price_sol, price_usdc, price_usdt,
+    token_allocation, required_whitelist_tier, cliff_duration_days, vesting_duration_days
+) VALUES
+('Seed', 1, '2024-01-01T00:00:00Z', '2024-02-01T00:00:00Z', 0.000030, 0.0030, 0.0030, 100000000, 3, 90, 540),
+('Private', 2, '2024-02-01T00:00:00Z', '2024-04-01T00:00:00Z', 0.000038, 0.0038, 0.0038, 250000000, 1, 60, 365),
+('Public', 3, '2024-04-01T00:00:00Z', '2024-06-01T00:00:00Z', 0.000045, 0.0045, 0.0045, 650000000, NULL, 30, 180);
@@ -0,0 +1,16 @@
+-- Notify the backend whenever presale settings change so its cached
+-- PresaleSettings can be hot-reloaded (LISTEN presale_settings_changed)
+CREATE OR REPLACE FUNCTION notify_presale_settings_changed() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        PERFORM pg_notify('presale_settings_changed', OLD.key);
+    ELSE
+        PERFORM pg_notify('presale_settings_changed', NEW.key);
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER presale_settings_changed
+    AFTER INSERT OR UPDATE OR DELETE ON presale_settings
+    FOR EACH ROW EXECUTE FUNCTION notify_presale_settings_changed();
@@ -0,0 +1,18 @@
+-- USD-denominated pricing through an on-chain Pyth SOL/USD price account
+
+-- USD price per token for each round (used when pricing_currency = 'usd')
+ALTER TABLE presale_rounds ADD COLUMN price_usd DECIMAL(20, 10);
+
+UPDATE presale_rounds SET price_usd = price_usdc;
+
+-- Oracle price used to convert each purchase to SOL
+ALTER TABLE transactions
+    ADD COLUMN oracle_price DECIMAL(20, 8), -- USD per SOL
+    ADD COLUMN oracle_publish_time TIMESTAMP WITH TIME ZONE;
+
+INSERT INTO presale_settings (key, value, description) VALUES
+('pricing_currency', 'sol', 'Currency rounds are priced in: sol or usd (converted via oracle)'),
+('oracle_max_staleness_secs', '60', 'Maximum seconds between oracle publish time and payment'),
+('oracle_max_confidence_bps', '200', 'Maximum oracle confidence interval in basis points of price');
+-- oracle_price_account (Pyth SOL/USD price account address) must be inserted
+-- before switching pricing_currency to usd
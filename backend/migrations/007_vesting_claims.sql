@@ -0,0 +1,26 @@
+-- Purchases vest instead of being delivered immediately
+
+-- One schedule per round a purchase was filled in, using that round's terms
+ALTER TABLE vesting_schedules
+    ADD COLUMN round_id UUID REFERENCES presale_rounds(id),
+    ADD COLUMN updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW();
+
+-- On-chain releases of vested tokens. A row is written, and released_tokens
+-- bumped, in the same database transaction before the pre-signed Solana
+-- transaction is sent; its signature ties the two together.
+CREATE TABLE vesting_releases (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    schedule_id UUID NOT NULL REFERENCES vesting_schedules(id),
+    user_id UUID NOT NULL REFERENCES users(id),
+    amount_tokens DECIMAL(20, 8) NOT NULL,
+    kind VARCHAR(20) NOT NULL DEFAULT 'claim', -- claim
+    status VARCHAR(20) NOT NULL DEFAULT 'pending', -- pending, confirmed, failed
+    solana_signature VARCHAR(88),
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+CREATE INDEX idx_vesting_schedules_transaction ON vesting_schedules(transaction_id);
+CREATE INDEX idx_vesting_releases_schedule ON vesting_releases(schedule_id);
+CREATE INDEX idx_vesting_releases_signature ON vesting_releases(solana_signature);
+CREATE INDEX idx_vesting_releases_status ON vesting_releases(status);
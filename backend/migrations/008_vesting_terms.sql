@@ -0,0 +1,21 @@
+-- Vesting terms beyond cliff and duration: an unlock at TGE (the start date),
+-- and how the remainder is released after the cliff
+
+ALTER TABLE presale_rounds
+    ADD COLUMN tge_unlock_percent DECIMAL(5, 2) NOT NULL DEFAULT 0
+        CHECK (tge_unlock_percent >= 0 AND tge_unlock_percent <= 100),
+    ADD COLUMN release_kind VARCHAR(20) NOT NULL DEFAULT 'linear', -- linear, monthly_step, custom
+    -- [{"offset_days": 30, "percent": 25}, ...]: cumulative percent of the
+    -- post-TGE remainder, ending at 100. Only used by custom release.
+    ADD COLUMN unlock_table JSONB;
+
+-- Schedules copy their round's terms so later round edits don't change them
+ALTER TABLE vesting_schedules
+    ADD COLUMN tge_unlock_percent DECIMAL(5, 2) NOT NULL DEFAULT 0,
+    ADD COLUMN release_kind VARCHAR(20) NOT NULL DEFAULT 'linear',
+    ADD COLUMN unlock_table JSONB,
+    ADD COLUMN source VARCHAR(20) NOT NULL DEFAULT 'purchase'; -- purchase, referral_bonus
+
+UPDATE presale_rounds SET tge_unlock_percent = 5, release_kind = 'monthly_step' WHERE name = 'Seed';
+UPDATE presale_rounds SET tge_unlock_percent = 10, release_kind = 'monthly_step' WHERE name = 'Private';
+UPDATE presale_rounds SET tge_unlock_percent = 20 WHERE name = 'Public';
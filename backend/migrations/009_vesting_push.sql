@@ -0,0 +1,21 @@
+-- Push-based vesting: a worker sends newly vested tokens to schedules in
+-- push mode instead of waiting for the holder to claim
+
+-- New schedules copy their round's mode; a schedule can be switched on its own
+ALTER TABLE presale_rounds
+    ADD COLUMN distribution_mode VARCHAR(10) NOT NULL DEFAULT 'claim'
+        CHECK (distribution_mode IN ('claim', 'push'));
+
+ALTER TABLE vesting_schedules
+    ADD COLUMN distribution_mode VARCHAR(10) NOT NULL DEFAULT 'claim'
+        CHECK (distribution_mode IN ('claim', 'push'));
+
+-- Blockhash of the pre-signed transaction, so a pending release left behind
+-- by a crash can be resolved once the blockhash has expired
+ALTER TABLE vesting_releases ADD COLUMN recent_blockhash VARCHAR(44);
+
+CREATE INDEX idx_vesting_schedules_mode ON vesting_schedules(distribution_mode);
+
+INSERT INTO presale_settings (key, value, description) VALUES
+('vesting_push_interval_secs', '3600', 'Seconds between vesting push worker runs'),
+('vesting_push_batch_size', '50', 'Wallets paid per vesting push worker run');
@@ -0,0 +1,38 @@
+-- Soft-lock launch: presale token accounts are frozen on distribution and
+-- thawed together at TGE using the mint's freeze authority
+
+-- Token accounts that have received presale tokens. is_frozen is our last
+-- known on-chain state; the TGE thaw re-reads the chain before acting on it.
+CREATE TABLE token_accounts (
+    wallet_address VARCHAR(44) PRIMARY KEY,
+    token_account VARCHAR(44) NOT NULL UNIQUE,
+    is_frozen BOOLEAN NOT NULL DEFAULT FALSE,
+    frozen_at TIMESTAMP WITH TIME ZONE,
+    thawed_at TIMESTAMP WITH TIME ZONE,
+    last_signature VARCHAR(88),
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+CREATE INDEX idx_token_accounts_frozen ON token_accounts(is_frozen);
+
+-- Progress of the TGE thaw, which works through frozen accounts in batches
+-- and picks up where it left off after a restart
+CREATE TABLE tge_thaw_runs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    status VARCHAR(20) NOT NULL DEFAULT 'running', -- running, completed, failed
+    total_accounts INTEGER NOT NULL DEFAULT 0,
+    thawed_accounts INTEGER NOT NULL DEFAULT 0,
+    batches_sent INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    started_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    finished_at TIMESTAMP WITH TIME ZONE
+);
+
+-- At most one run in progress
+CREATE UNIQUE INDEX idx_tge_thaw_runs_running ON tge_thaw_runs(status) WHERE status = 'running';
+
+INSERT INTO presale_settings (key, value, description) VALUES
+('freeze_until_tge', 'false', 'Freeze buyer token accounts on distribution until the TGE thaw'),
+('tge_thaw_batch_size', '20', 'Token accounts thawed per transaction during the TGE thaw');
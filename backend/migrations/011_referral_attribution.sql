@@ -0,0 +1,22 @@
+-- Referral attribution: a user is referred at most once, never by themselves,
+-- and the referrer can't change once set
+
+ALTER TABLE users ADD CONSTRAINT users_no_self_referral CHECK (referred_by <> id);
+
+-- One referrer per referred user
+CREATE UNIQUE INDEX idx_referrals_referred ON referrals(referred_id);
+
+CREATE INDEX idx_users_referred_by ON users(referred_by);
+
+CREATE OR REPLACE FUNCTION prevent_referrer_change() RETURNS trigger AS $$
+BEGIN
+    IF OLD.referred_by IS NOT NULL AND NEW.referred_by IS DISTINCT FROM OLD.referred_by THEN
+        RAISE EXCEPTION 'referred_by of user % is already set', OLD.id;
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER users_referrer_immutable
+    BEFORE UPDATE OF referred_by ON users
+    FOR EACH ROW EXECUTE FUNCTION prevent_referrer_change();
@@ -0,0 +1,22 @@
+-- Referral rewards ledger: one entry per referrer per qualifying purchase,
+-- paid out as vesting schedules on the purchase's round terms
+
+CREATE TABLE referral_rewards (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    referral_id UUID NOT NULL REFERENCES referrals(id),
+    referrer_id UUID NOT NULL REFERENCES users(id),
+    referred_id UUID NOT NULL REFERENCES users(id),
+    transaction_id UUID NOT NULL REFERENCES transactions(id),
+    purchase_tokens DECIMAL(20, 8) NOT NULL,
+    rate_percent DECIMAL(5, 2) NOT NULL,
+    amount_tokens DECIMAL(20, 8) NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    UNIQUE (transaction_id, referrer_id)
+);
+
+CREATE INDEX idx_referral_rewards_referrer ON referral_rewards(referrer_id);
+
+ALTER TABLE vesting_schedules ADD COLUMN referral_reward_id UUID REFERENCES referral_rewards(id);
+
+INSERT INTO presale_settings (key, value, description) VALUES
+('referral_reward_mode', 'claim', 'How referral rewards are released once vested: claim or push');
@@ -0,0 +1,34 @@
+-- Negotiated commission rates, multi-level commissions, and commissions paid
+-- in SOL as an alternative to vesting tokens
+
+-- Per-referrer terms for affiliate partners; NULL falls back to the settings
+ALTER TABLE users
+    ADD COLUMN referral_rate_percent DECIMAL(5, 2)
+        CHECK (referral_rate_percent >= 0 AND referral_rate_percent <= 100),
+    ADD COLUMN referral_payout_currency VARCHAR(10)
+        CHECK (referral_payout_currency IN ('token', 'sol'));
+
+-- Per-referral override of the direct commission rate; NULL uses the
+-- referrer's rate, then the global referral_bonus. Attribution used to copy
+-- the global default in, so existing rows go back to following it.
+ALTER TABLE referrals ALTER COLUMN bonus_percentage DROP DEFAULT;
+UPDATE referrals SET bonus_percentage = NULL;
+
+-- level 1 is the buyer's referrer, level 2 that referrer's referrer, ...
+ALTER TABLE referral_rewards
+    ADD COLUMN level INTEGER NOT NULL DEFAULT 1,
+    ADD COLUMN currency VARCHAR(10) NOT NULL DEFAULT 'token', -- token, sol
+    ADD COLUMN purchase_sol DECIMAL(20, 8),
+    ADD COLUMN amount_sol DECIMAL(20, 8) NOT NULL DEFAULT 0,
+    -- SOL commissions only: pending, sending, paid
+    ADD COLUMN payout_status VARCHAR(20),
+    ADD COLUMN payout_signature VARCHAR(88),
+    ADD COLUMN payout_blockhash VARCHAR(44),
+    ADD COLUMN paid_at TIMESTAMP WITH TIME ZONE,
+    ADD COLUMN updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW();
+
+CREATE INDEX idx_referral_rewards_payout ON referral_rewards(payout_status) WHERE payout_status IS NOT NULL;
+
+INSERT INTO presale_settings (key, value, description) VALUES
+('referral_upline_rates', '', 'Commission percentages for level 2 and up, comma separated (e.g. 2,1); their count sets the depth'),
+('referral_payout_currency', 'token', 'Default commission currency: token (vesting tokens) or sol (share of SOL paid)');
@@ -0,0 +1,19 @@
+-- Referral link clicks, for per-code conversion funnels
+
+CREATE TABLE referral_clicks (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    referrer_id UUID NOT NULL REFERENCES users(id),
+    referral_code VARCHAR(20) NOT NULL,
+    -- SHA-256 of client IP and user agent; raw addresses are never stored
+    visitor_hash VARCHAR(64) NOT NULL,
+    landing_page VARCHAR(255),
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+CREATE INDEX idx_referral_clicks_referrer ON referral_clicks(referrer_id, created_at);
+
+-- Leaderboards rank by confirmed purchase volume
+CREATE INDEX idx_transactions_user_confirmed ON transactions(user_id) WHERE status = 'confirmed';
+
+INSERT INTO presale_settings (key, value, description) VALUES
+('leaderboard_cache_secs', '60', 'How long leaderboard pages are cached, in seconds');
@@ -0,0 +1,7 @@
+-- Custom (vanity) referral codes; each wallet may claim one
+
+ALTER TABLE users ADD COLUMN referral_code_claimed_at TIMESTAMP WITH TIME ZONE;
+
+INSERT INTO presale_settings (key, value, description) VALUES
+('vanity_code_min_length', '4', 'Shortest custom referral code a user may claim'),
+('vanity_code_max_length', '16', 'Longest custom referral code a user may claim (at most 20)');
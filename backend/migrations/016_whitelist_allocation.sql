@@ -0,0 +1,22 @@
+-- Whitelist allocation is consumed inside the purchase's database
+-- transaction, recorded per entry so it can be given back
+
+UPDATE whitelist_entries SET used_allocation = 0 WHERE used_allocation IS NULL;
+
+ALTER TABLE whitelist_entries
+    ALTER COLUMN used_allocation SET NOT NULL,
+    ADD CONSTRAINT whitelist_entries_allocation_check
+        CHECK (used_allocation >= 0 AND used_allocation <= max_allocation);
+
+CREATE INDEX idx_whitelist_entries_user ON whitelist_entries(user_id);
+
+CREATE TABLE whitelist_allocation_uses (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    entry_id UUID NOT NULL REFERENCES whitelist_entries(id),
+    transaction_id UUID NOT NULL REFERENCES transactions(id),
+    amount DECIMAL(20, 8) NOT NULL,
+    released_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+CREATE INDEX idx_whitelist_allocation_uses_transaction ON whitelist_allocation_uses(transaction_id);
@@ -0,0 +1,44 @@
+-- Whitelist tiers: the numbers in users.whitelist_tier and
+-- presale_rounds.required_whitelist_tier, with the rules each tier buys under
+
+CREATE TABLE whitelist_tiers (
+    tier INTEGER PRIMARY KEY CHECK (tier >= 0),
+    name VARCHAR(50) UNIQUE NOT NULL,
+    -- Allocation given to new whitelist entries of this tier
+    default_max_allocation DECIMAL(20, 8) NOT NULL CHECK (default_max_allocation >= 0),
+    -- Overrides the global min_purchase when set
+    min_purchase DECIMAL(20, 8) CHECK (min_purchase > 0),
+    -- Members may buy this long before the presale and each round start
+    early_access_secs INTEGER NOT NULL DEFAULT 0 CHECK (early_access_secs >= 0),
+    price_discount_percent DECIMAL(5, 2) NOT NULL DEFAULT 0
+        CHECK (price_discount_percent >= 0 AND price_discount_percent < 100),
+    -- Vesting overrides; NULL keeps the round's terms
+    vesting_tge_unlock_percent DECIMAL(5, 2)
+        CHECK (vesting_tge_unlock_percent >= 0 AND vesting_tge_unlock_percent <= 100),
+    vesting_cliff_days INTEGER CHECK (vesting_cliff_days >= 0),
+    vesting_duration_days INTEGER CHECK (vesting_duration_days >= 0),
+    vesting_release_kind VARCHAR(20) CHECK (vesting_release_kind IN ('linear', 'monthly_step')),
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+INSERT INTO whitelist_tiers (tier, name, default_max_allocation, early_access_secs, price_discount_percent) VALUES
+(0, 'none', 0, 0, 0),
+(1, 'basic', 50000, 0, 0),
+(2, 'premium', 200000, 3600, 2.5),
+(3, 'vip', 1000000, 86400, 5);
+
+UPDATE users SET whitelist_tier = 0 WHERE whitelist_tier IS NULL;
+ALTER TABLE users
+    ALTER COLUMN whitelist_tier SET NOT NULL,
+    ADD CONSTRAINT users_whitelist_tier_fkey FOREIGN KEY (whitelist_tier) REFERENCES whitelist_tiers(tier);
+
+ALTER TABLE presale_rounds
+    ADD CONSTRAINT presale_rounds_required_whitelist_tier_fkey
+        FOREIGN KEY (required_whitelist_tier) REFERENCES whitelist_tiers(tier);
+
+-- Entries follow their tier's default allocation unless given a custom one
+ALTER TABLE whitelist_entries
+    ADD CONSTRAINT whitelist_entries_tier_fkey FOREIGN KEY (tier) REFERENCES whitelist_tiers(tier),
+    ADD COLUMN custom_allocation BOOLEAN NOT NULL DEFAULT FALSE;
+UPDATE whitelist_entries SET custom_allocation = TRUE;
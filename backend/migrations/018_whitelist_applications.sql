@@ -0,0 +1,27 @@
+-- Whitelist applications and their review:
+-- submitted -> under_review -> approved | rejected
+
+CREATE TABLE whitelist_applications (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id),
+    requested_tier INTEGER REFERENCES whitelist_tiers(tier),
+    answers JSONB NOT NULL DEFAULT '{}',
+    email VARCHAR(255),
+    discord_username VARCHAR(100),
+    twitter_username VARCHAR(100),
+    telegram_username VARCHAR(100),
+    status VARCHAR(20) NOT NULL DEFAULT 'submitted', -- submitted, under_review, approved, rejected
+    approved_tier INTEGER REFERENCES whitelist_tiers(tier),
+    approved_allocation DECIMAL(20, 8),
+    whitelist_entry_id UUID REFERENCES whitelist_entries(id),
+    rejection_reason TEXT,
+    submitted_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    reviewed_at TIMESTAMP WITH TIME ZONE,
+    decided_at TIMESTAMP WITH TIME ZONE,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+-- One open application per wallet; rejected wallets may apply again
+CREATE UNIQUE INDEX idx_whitelist_applications_open
+    ON whitelist_applications(user_id) WHERE status IN ('submitted', 'under_review');
+CREATE INDEX idx_whitelist_applications_status ON whitelist_applications(status, submitted_at);
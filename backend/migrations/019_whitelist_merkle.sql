@@ -0,0 +1,25 @@
+-- Versioned snapshots of the whitelist Merkle tree. A version is recorded
+-- whenever the root changes, so proofs handed out for an older root can
+-- still be looked up.
+
+CREATE TABLE whitelist_merkle_versions (
+    version SERIAL PRIMARY KEY,
+    root VARCHAR(64) NOT NULL, -- hex keccak-256
+    leaf_count INTEGER NOT NULL,
+    total_allocation DECIMAL(20, 8) NOT NULL,
+    -- Decimals of the token mint that leaf amounts are scaled by
+    token_decimals SMALLINT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+-- One leaf per whitelisted wallet: its tier and total unexpired allocation
+CREATE TABLE whitelist_merkle_leaves (
+    version INTEGER NOT NULL REFERENCES whitelist_merkle_versions(version) ON DELETE CASCADE,
+    wallet_address VARCHAR(44) NOT NULL,
+    tier INTEGER NOT NULL,
+    max_allocation DECIMAL(20, 8) NOT NULL,
+    -- max_allocation in token base units, as committed to in the leaf (u64)
+    amount_units DECIMAL(20, 0) NOT NULL,
+    leaf VARCHAR(64) NOT NULL, -- hex keccak-256
+    PRIMARY KEY (version, wallet_address)
+);
@@ -0,0 +1,39 @@
+-- KYC gating and the provider webhook's audit trail
+
+ALTER TABLE users
+    -- The provider's id for the user's applicant, learned from its webhook
+    ADD COLUMN kyc_applicant_id VARCHAR(100),
+    -- Provider time of the status in kyc_status; older events are not applied
+    ADD COLUMN kyc_updated_at TIMESTAMP WITH TIME ZONE;
+
+UPDATE users SET kyc_status = 'pending' WHERE kyc_status IS NULL;
+ALTER TABLE users
+    ALTER COLUMN kyc_status SET NOT NULL,
+    ADD CONSTRAINT users_kyc_status_check CHECK (kyc_status IN ('pending', 'approved', 'rejected'));
+
+-- Every webhook event received, applied or not
+CREATE TABLE kyc_events (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    -- The provider's event id; redelivered events are recorded once
+    event_id VARCHAR(100) UNIQUE NOT NULL,
+    user_id UUID NOT NULL REFERENCES users(id),
+    provider VARCHAR(50) NOT NULL,
+    applicant_id VARCHAR(100),
+    previous_status VARCHAR(20) NOT NULL,
+    new_status VARCHAR(20) NOT NULL, -- pending, approved, rejected
+    reason TEXT,
+    -- False when a newer event had already been applied
+    applied BOOLEAN NOT NULL,
+    occurred_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    payload JSONB NOT NULL,
+    received_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+CREATE INDEX idx_kyc_events_user ON kyc_events(user_id, received_at);
+
+INSERT INTO presale_settings (key, value, description) VALUES
+('kyc_required_tiers', '', 'Comma-separated whitelist tiers that need KYC approval to buy'),
+('kyc_provider', 'local', 'KYC provider name handed to the frontend and recorded on events'),
+('kyc_level', 'basic', 'Verification level the KYC flow is started with');
+-- kyc_threshold_tokens (cumulative tokens a wallet may buy before KYC
+-- approval is required) is unset by default: no threshold
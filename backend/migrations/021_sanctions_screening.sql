@@ -0,0 +1,47 @@
+-- Sanctions screening: a denylist of addresses (this table, plus an optional
+-- file named by DENYLIST_PATH) and an audit trail of every match
+
+CREATE TABLE denylist_entries (
+    address VARCHAR(44) PRIMARY KEY,
+    source VARCHAR(50) NOT NULL DEFAULT 'manual', -- e.g. ofac, manual
+    reason TEXT,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+CREATE TABLE screening_events (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    -- The wallet acted on: blocked, or flagged as funded by a listed address
+    wallet_address VARCHAR(44) NOT NULL,
+    role VARCHAR(20) NOT NULL, -- buyer, referrer, refund_destination, payment_source
+    context VARCHAR(20) NOT NULL, -- registration, purchase, refund, commission
+    action VARCHAR(20) NOT NULL, -- blocked, flagged
+    matched_address VARCHAR(44) NOT NULL,
+    list_source VARCHAR(100) NOT NULL,
+    -- Payment signature or transaction id involved, if any
+    reference VARCHAR(100),
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+CREATE INDEX idx_screening_events_wallet ON screening_events(wallet_address, created_at);
+
+-- Set when a payment was funded directly by a listed address
+ALTER TABLE users ADD COLUMN screening_flagged_at TIMESTAMP WITH TIME ZONE;
+
+CREATE OR REPLACE FUNCTION notify_denylist_changed() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'DELETE' THEN
+        PERFORM pg_notify('denylist_changed', OLD.address);
+    ELSE
+        PERFORM pg_notify('denylist_changed', NEW.address);
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER denylist_changed
+    AFTER INSERT OR UPDATE OR DELETE ON denylist_entries
+    FOR EACH ROW EXECUTE FUNCTION notify_denylist_changed();
+
+INSERT INTO presale_settings (key, value, description) VALUES
+('denylist_file_poll_secs', '60', 'How often the denylist file is checked for changes'),
+('screen_payment_sources', 'true', 'Flag buyers whose payment transaction moved funds from a listed address');
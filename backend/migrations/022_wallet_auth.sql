@@ -0,0 +1,28 @@
+-- Sign-In With Solana: login nonces and refresh tokens
+
+-- A nonce is handed out with the message to sign and can be used once
+CREATE TABLE auth_nonces (
+    nonce VARCHAR(64) PRIMARY KEY,
+    wallet_address VARCHAR(44) NOT NULL,
+    issued_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    used_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX idx_auth_nonces_expires ON auth_nonces(expires_at);
+
+-- Refresh tokens are opaque and stored as SHA-256 hashes. Each refresh
+-- revokes the token used and issues a new one; presenting a revoked token
+-- again revokes every session of that wallet.
+CREATE TABLE auth_refresh_tokens (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    wallet_address VARCHAR(44) NOT NULL,
+    token_hash VARCHAR(64) UNIQUE NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    revoked_at TIMESTAMP WITH TIME ZONE,
+    -- The token this one was rotated into
+    replaced_by UUID REFERENCES auth_refresh_tokens(id),
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+CREATE INDEX idx_auth_refresh_tokens_wallet ON auth_refresh_tokens(wallet_address);
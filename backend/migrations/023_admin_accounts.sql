@@ -0,0 +1,31 @@
+-- Admin accounts: bcrypt passwords, mandatory TOTP and roles
+
+CREATE TABLE admin_users (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    username VARCHAR(50) UNIQUE NOT NULL,
+    password_hash VARCHAR(100) NOT NULL,
+    role VARCHAR(20) NOT NULL CHECK (role IN ('owner', 'operator', 'reviewer', 'support')),
+    -- Base32 TOTP secret, shown once when the account is created
+    totp_secret VARCHAR(64) NOT NULL,
+    -- Time step of the last accepted code; a code can't be used twice
+    totp_last_step BIGINT,
+    is_active BOOLEAN NOT NULL DEFAULT TRUE,
+    -- Consecutive failed logins; reaching the limit locks the account
+    failed_logins INTEGER NOT NULL DEFAULT 0,
+    locked_until TIMESTAMP WITH TIME ZONE,
+    last_login_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+-- Sessions are opaque bearer tokens stored as SHA-256 hashes
+CREATE TABLE admin_sessions (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    admin_id UUID NOT NULL REFERENCES admin_users(id),
+    token_hash VARCHAR(64) UNIQUE NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    revoked_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT NOW()
+);
+
+CREATE INDEX idx_admin_sessions_admin ON admin_sessions(admin_id);
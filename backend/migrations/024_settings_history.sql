@@ -0,0 +1,43 @@
+-- Presale settings changed through the admin API: versioned history and
+-- changes scheduled for a future time
+
+-- One version per applied change set
+CREATE SEQUENCE presale_settings_version_seq;
+
+CREATE TABLE presale_setting_schedules (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    -- Key to new value; null removes the key
+    changes JSONB NOT NULL,
+    effective_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'pending', -- pending, applied, cancelled, failed
+    reason TEXT,
+    created_by UUID REFERENCES admin_users(id),
+    created_by_name VARCHAR(50) NOT NULL,
+    -- Version the change was applied as, or why it couldn't be
+    applied_version BIGINT,
+    error TEXT,
+    cancelled_by_name VARCHAR(50),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    resolved_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX idx_presale_setting_schedules_due ON presale_setting_schedules(effective_at) WHERE status = 'pending';
+
+-- Every value change, one row per key
+CREATE TABLE presale_setting_history (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    version BIGINT NOT NULL,
+    key VARCHAR(100) NOT NULL,
+    -- Null when the key was added or removed
+    old_value TEXT,
+    new_value TEXT,
+    changed_by UUID REFERENCES admin_users(id),
+    changed_by_name VARCHAR(50) NOT NULL,
+    source VARCHAR(20) NOT NULL, -- admin, schedule, system
+    schedule_id UUID REFERENCES presale_setting_schedules(id),
+    reason TEXT,
+    changed_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_presale_setting_history_version ON presale_setting_history(version);
+CREATE INDEX idx_presale_setting_history_key ON presale_setting_history(key, changed_at);
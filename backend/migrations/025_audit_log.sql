@@ -0,0 +1,40 @@
+-- Append-only audit log of every state-changing action. Each event stores
+-- the SHA-256 of the one before it, so editing, removing or reordering rows
+-- breaks the chain (see GET /api/admin/audit/verify).
+
+CREATE TABLE audit_events (
+    id BIGSERIAL PRIMARY KEY,
+    actor_type VARCHAR(20) NOT NULL CHECK (actor_type IN ('user', 'admin', 'system')),
+    -- Wallet address, admin id or worker name
+    actor_id VARCHAR(100) NOT NULL,
+    action VARCHAR(100) NOT NULL, -- e.g. purchase.confirmed, whitelist.approved, settings.changed
+    entity_type VARCHAR(50) NOT NULL, -- e.g. transaction, user, whitelist_application, settings
+    entity_id VARCHAR(100) NOT NULL,
+    before JSONB,
+    after JSONB,
+    request_id VARCHAR(100),
+    ip_address VARCHAR(64),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    -- Hash of the previous event; 64 zeros for the first
+    prev_hash CHAR(64) NOT NULL,
+    hash CHAR(64) NOT NULL UNIQUE
+);
+
+CREATE INDEX idx_audit_events_actor ON audit_events(actor_type, actor_id, id);
+CREATE INDEX idx_audit_events_entity ON audit_events(entity_type, entity_id, id);
+CREATE INDEX idx_audit_events_action ON audit_events(action, id);
+CREATE INDEX idx_audit_events_created_at ON audit_events(created_at);
+
+CREATE OR REPLACE FUNCTION reject_audit_event_change() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_events is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_events_append_only
+    BEFORE UPDATE OR DELETE ON audit_events
+    FOR EACH ROW EXECUTE FUNCTION reject_audit_event_change();
+
+CREATE TRIGGER audit_events_no_truncate
+    BEFORE TRUNCATE ON audit_events
+    FOR EACH STATEMENT EXECUTE FUNCTION reject_audit_event_change();
@@ -0,0 +1,8 @@
+-- Rounds carry their own prices (004), so the flat token price is unused
+DELETE FROM presale_settings WHERE key = 'token_price_sol';
+
+-- Every write sets updated_at; make the column say so
+UPDATE presale_settings SET updated_at = NOW() WHERE updated_at IS NULL;
+ALTER TABLE presale_settings
+    ALTER COLUMN updated_at SET DEFAULT NOW(),
+    ALTER COLUMN updated_at SET NOT NULL;
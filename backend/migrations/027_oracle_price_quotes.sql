@@ -0,0 +1,15 @@
+-- Oracle prices recorded when buyers ask for a quote, so a USD-priced
+-- purchase is converted at a price published before its payment landed
+-- rather than whatever the oracle shows when it is confirmed
+CREATE TABLE oracle_price_quotes (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    price_account VARCHAR(44) NOT NULL,
+    price DECIMAL(20, 8) NOT NULL, -- USD per SOL
+    confidence DECIMAL(20, 8) NOT NULL,
+    publish_time TIMESTAMP WITH TIME ZONE NOT NULL,
+    publish_slot BIGINT NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    UNIQUE (price_account, publish_slot)
+);
+
+CREATE INDEX idx_oracle_price_quotes_publish_time ON oracle_price_quotes(price_account, publish_time DESC);
@@ -0,0 +1,24 @@
+-- SOL owed back to buyers whose payment landed but wasn't (fully) sold
+-- against: rejected after verification, or filled only in part at the hard
+-- cap. The worker pre-signs each transfer and records it before sending,
+-- so a failed or undecided send is retried rather than lost.
+CREATE TABLE payment_refunds (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    -- One refund per payment; a refunded payment can't later be sold against
+    payment_signature VARCHAR(88) UNIQUE NOT NULL,
+    wallet_address VARCHAR(44) NOT NULL,
+    -- The purchase a partial refund belongs to; null for rejected payments
+    transaction_id UUID REFERENCES transactions(id),
+    amount_sol DECIMAL(20, 9) NOT NULL,
+    reason VARCHAR(50) NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'pending', -- pending, sending, refunded, withheld
+    refund_signature VARCHAR(88),
+    refund_blockhash VARCHAR(64),
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+    refunded_at TIMESTAMP WITH TIME ZONE
+);
+
+CREATE INDEX idx_payment_refunds_open ON payment_refunds(created_at) WHERE status IN ('pending', 'sending');
@@ -0,0 +1,5 @@
+-- request_id is now always generated by the server; an X-Request-Id sent by
+-- the caller is kept here instead, unverified
+ALTER TABLE audit_events ADD COLUMN client_request_id VARCHAR(100);
+
+CREATE INDEX idx_audit_events_client_request_id ON audit_events(client_request_id) WHERE client_request_id IS NOT NULL;
@@ -0,0 +1,5 @@
+-- Payments are verified as native SOL transfers only; rounds priced in USD
+-- use price_usd and the oracle instead
+ALTER TABLE presale_rounds
+    DROP COLUMN price_usdc,
+    DROP COLUMN price_usdt;
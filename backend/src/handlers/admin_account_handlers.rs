@@ -0,0 +1,147 @@
+use actix_web::{web, HttpResponse, Result as ActixResult};
+use chrono::Utc;
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::handlers::{AdminAuth, Owners, RequestMeta};
+use crate::models::*;
+use crate::services::*;
+use crate::{ApiResponse, AppState};
+
+/// Sign in with username, password and TOTP code for a session token
+pub async fn login_admin(
+    req: web::Json<AdminLoginRequest>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    if let Err(validation_errors) = req.validate() {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Validation error: {:?}", validation_errors),
+            data: None,
+        }));
+    }
+
+    match admin_login(&data.db, &req, Utc::now()).await {
+        Ok(session) => Ok(HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            message: "Signed in".to_string(),
+            data: Some(session),
+        })),
+        Err(e) => match e.downcast_ref::<AdminLoginRejection>() {
+            Some(rejection) => {
+                let mut response = match rejection {
+                    AdminLoginRejection::InvalidCredentials => HttpResponse::Unauthorized(),
+                    AdminLoginRejection::Locked { .. } => HttpResponse::TooManyRequests(),
+                };
+                Ok(response.json(ApiResponse {
+                    success: false,
+                    message: rejection.to_string(),
+                    data: Some(serde_json::json!({ "code": rejection.code() })),
+                }))
+            }
+            None => Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                success: false,
+                message: format!("Login error: {}", e),
+                data: None,
+            })),
+        },
+    }
+}
+
+pub async fn logout_admin(admin: AdminAuth, data: web::Data<AppState>) -> ActixResult<HttpResponse> {
+    match revoke_admin_session(&data.db, &admin.session_id).await {
+        Ok(()) => Ok(HttpResponse::Ok().json(ApiResponse::<()> {
+            success: true,
+            message: "Signed out".to_string(),
+            data: None,
+        })),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Database error: {}", e),
+            data: None,
+        })),
+    }
+}
+
+/// The signed-in admin's account
+pub async fn get_admin_me(admin: AdminAuth) -> ActixResult<HttpResponse> {
+    Ok(HttpResponse::Ok().json(ApiResponse {
+        success: true,
+        message: format!("Signed in as {}", admin.admin.username),
+        data: Some(admin.admin),
+    }))
+}
+
+pub async fn list_admins(_admin: AdminAuth<Owners>, data: web::Data<AppState>) -> ActixResult<HttpResponse> {
+    match list_admin_users(&data.db).await {
+        Ok(admins) => Ok(HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            message: format!("{} admins", admins.len()),
+            data: Some(admins),
+        })),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Database error: {}", e),
+            data: None,
+        })),
+    }
+}
+
+/// Create an admin. The response carries their TOTP secret, which is not
+/// shown again.
+pub async fn create_admin(
+    admin: AdminAuth<Owners>,
+    meta: RequestMeta,
+    req: web::Json<CreateAdminRequest>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    if let Err(validation_errors) = req.validate() {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Validation error: {:?}", validation_errors),
+            data: None,
+        }));
+    }
+
+    let audit = meta.audit(AuditActor::Admin(admin.admin.id));
+    match create_admin_user(&data.db, &req, &audit).await {
+        Ok(created) => Ok(HttpResponse::Created().json(ApiResponse {
+            success: true,
+            message: "Admin created; share the TOTP secret with them securely".to_string(),
+            data: Some(created),
+        })),
+        Err(e) => Ok(HttpResponse::BadRequest().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Admin error: {}", e),
+            data: None,
+        })),
+    }
+}
+
+/// Change an admin's role or (de)activate them
+pub async fn update_admin(
+    admin: AdminAuth<Owners>,
+    meta: RequestMeta,
+    path: web::Path<Uuid>,
+    req: web::Json<UpdateAdminRequest>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let audit = meta.audit(AuditActor::Admin(admin.admin.id));
+    match update_admin_user(&data.db, &path.into_inner(), &req, &audit).await {
+        Ok(Some(admin)) => Ok(HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            message: "Admin updated".to_string(),
+            data: Some(admin),
+        })),
+        Ok(None) => Ok(HttpResponse::NotFound().json(ApiResponse::<()> {
+            success: false,
+            message: "Admin not found".to_string(),
+            data: None,
+        })),
+        Err(e) => Ok(HttpResponse::BadRequest().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Admin error: {}", e),
+            data: None,
+        })),
+    }
+}
@@ -0,0 +1,234 @@
+use actix_web::{dev::Payload, error::InternalError, web, FromRequest, HttpRequest, HttpResponse, Result as ActixResult};
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::str::FromStr;
+use uuid::Uuid;
+
+use crate::handlers::RequestMeta;
+use crate::models::AdminUser;
+use crate::services::*;
+use crate::{ApiResponse, AppState};
+
+/// The roles an admin endpoint is open to
+pub trait AdminRoles {
+    const ALLOWED: &'static [AdminRole];
+}
+
+/// Any admin, including read-only support
+pub struct AnyAdmin;
+/// Whitelist application review
+pub struct Reviewers;
+/// Presale operations
+pub struct Operators;
+/// Admin account management
+pub struct Owners;
+
+impl AdminRoles for AnyAdmin {
+    const ALLOWED: &'static [AdminRole] = &[AdminRole::Owner, AdminRole::Operator, AdminRole::Reviewer, AdminRole::Support];
+}
+
+impl AdminRoles for Reviewers {
+    const ALLOWED: &'static [AdminRole] = &[AdminRole::Owner, AdminRole::Operator, AdminRole::Reviewer];
+}
+
+impl AdminRoles for Operators {
+    const ALLOWED: &'static [AdminRole] = &[AdminRole::Owner, AdminRole::Operator];
+}
+
+impl AdminRoles for Owners {
+    const ALLOWED: &'static [AdminRole] = &[AdminRole::Owner];
+}
+
+/// Guard for admin endpoints: `Authorization: Bearer <session token>` from
+/// an admin login, and a role the endpoint allows, e.g.
+/// `AdminAuth<Operators>`. Plain `AdminAuth` admits every role.
+pub struct AdminAuth<R: AdminRoles = AnyAdmin> {
+    pub admin: AdminUser,
+    pub session_id: Uuid,
+    roles: PhantomData<fn() -> R>,
+}
+
+fn admin_auth_error(response: HttpResponse) -> actix_web::Error {
+    InternalError::from_response("unauthorized", response).into()
+}
+
+impl<R: AdminRoles + 'static> FromRequest for AdminAuth<R> {
+    type Error = actix_web::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let data = req.app_data::<web::Data<AppState>>().cloned();
+        let token = req
+            .headers()
+            .get("Authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(|token| token.trim().to_string());
+
+        Box::pin(async move {
+            let unauthorized = || {
+                admin_auth_error(HttpResponse::Unauthorized().json(ApiResponse::<()> {
+                    success: false,
+                    message: "Admin authorization required".to_string(),
+                    data: None,
+                }))
+            };
+            let (Some(data), Some(token)) = (data, token) else {
+                return Err(unauthorized());
+            };
+
+            let (admin, session_id) = match authenticate_admin_session(&data.db, &token).await {
+                Ok(Some(session)) => session,
+                Ok(None) => return Err(unauthorized()),
+                Err(e) => {
+                    return Err(admin_auth_error(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                        success: false,
+                        message: format!("Admin session error: {}", e),
+                        data: None,
+                    })));
+                }
+            };
+
+            if !AdminRole::from_str(&admin.role).is_ok_and(|role| R::ALLOWED.contains(&role)) {
+                return Err(admin_auth_error(HttpResponse::Forbidden().json(ApiResponse {
+                    success: false,
+                    message: format!("Role {} can't use this endpoint", admin.role),
+                    data: Some(serde_json::json!({
+                        "allowed_roles": R::ALLOWED.iter().map(|role| role.as_str()).collect::<Vec<_>>(),
+                    })),
+                })));
+            }
+
+            Ok(AdminAuth { admin, session_id, roles: PhantomData })
+        })
+    }
+}
+
+/// Freeze or thaw one wallet's presale token account
+async fn set_frozen(
+    wallet: String,
+    frozen: bool,
+    audit: AuditContext,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let (action, state) = if frozen { ("freeze", "frozen") } else { ("thaw", "thawed") };
+
+    let token_account = match data.solana_service.token_account_for(&wallet) {
+        Ok(token_account) => token_account,
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(ApiResponse::<()> {
+                success: false,
+                message: e.to_string(),
+                data: None,
+            }));
+        }
+    };
+
+    let signature = match data.solana_service.set_account_frozen(&wallet, frozen).await {
+        Ok(signature) => signature,
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                success: false,
+                message: format!("Failed to {} token account: {}", action, e),
+                data: None,
+            }));
+        }
+    };
+
+    let tracked = async {
+        let mut db_tx = data.db.begin().await?;
+        track_token_account(&mut db_tx, &wallet, &token_account, frozen, signature.as_deref()).await?;
+        if let Some(signature) = &signature {
+            let entry = AuditEntry {
+                action: if frozen { "token_account.frozen" } else { "token_account.thawed" },
+                entity_type: "token_account",
+                entity_id: token_account.to_string(),
+                before: Some(serde_json::json!({ "is_frozen": !frozen })),
+                after: Some(serde_json::json!({
+                    "wallet_address": wallet,
+                    "is_frozen": frozen,
+                    "signature": signature,
+                })),
+            };
+            record_audit_event_in(&mut db_tx, &audit, entry).await?;
+        }
+        db_tx.commit().await?;
+        anyhow::Ok(())
+    };
+    if let Err(e) = tracked.await {
+        eprintln!("Failed to record {} of {}: {}", action, wallet, e);
+    }
+
+    Ok(HttpResponse::Ok().json(ApiResponse {
+        success: true,
+        message: match &signature {
+            Some(_) => format!("Token account {}", state),
+            None => format!("Token account already {}", state),
+        },
+        data: Some(serde_json::json!({
+            "wallet_address": wallet,
+            "token_account": token_account.to_string(),
+            "is_frozen": frozen,
+            "signature": signature,
+        })),
+    }))
+}
+
+pub async fn freeze_token_account(
+    admin: AdminAuth<Operators>,
+    meta: RequestMeta,
+    path: web::Path<String>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    set_frozen(path.into_inner(), true, meta.audit(AuditActor::Admin(admin.admin.id)), data).await
+}
+
+pub async fn thaw_token_account(
+    admin: AdminAuth<Operators>,
+    meta: RequestMeta,
+    path: web::Path<String>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    set_frozen(path.into_inner(), false, meta.audit(AuditActor::Admin(admin.admin.id)), data).await
+}
+
+/// Start (or resume) thawing every frozen presale account for TGE
+pub async fn start_tge(
+    admin: AdminAuth<Operators>,
+    meta: RequestMeta,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let audit = meta.audit(AuditActor::Admin(admin.admin.id));
+    match start_tge_thaw(&data.db, &data.solana_service, &data.settings, &audit).await {
+        Ok(run) => Ok(HttpResponse::Accepted().json(ApiResponse {
+            success: true,
+            message: "TGE thaw started".to_string(),
+            data: Some(run),
+        })),
+        Err(e) => Ok(HttpResponse::Conflict().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Can't start TGE thaw: {}", e),
+            data: None,
+        })),
+    }
+}
+
+/// Progress of the latest TGE thaw
+pub async fn get_tge_status(_admin: AdminAuth, data: web::Data<AppState>) -> ActixResult<HttpResponse> {
+    match latest_tge_thaw(&data.db).await {
+        Ok(run) => Ok(HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            message: match run {
+                Some(_) => "TGE thaw status".to_string(),
+                None => "TGE thaw not started".to_string(),
+            },
+            data: run,
+        })),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Database error: {}", e),
+            data: None,
+        })),
+    }
+}
@@ -0,0 +1,130 @@
+use actix_web::{dev::Payload, web, FromRequest, HttpRequest, HttpResponse, Result as ActixResult};
+use std::convert::Infallible;
+use std::env;
+use std::future::{ready, Ready};
+use std::net::IpAddr;
+use uuid::Uuid;
+
+use crate::handlers::AdminAuth;
+use crate::models::*;
+use crate::services::*;
+use crate::{ApiResponse, AppState};
+
+const REQUEST_ID_MAX_LEN: usize = 100;
+
+/// Reverse proxies (`TRUSTED_PROXIES`, comma-separated IPs) whose
+/// `X-Forwarded-For` is believed; nobody's is by default
+fn trusted_proxies() -> Vec<IpAddr> {
+    env::var("TRUSTED_PROXIES")
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|proxy| proxy.trim().parse().ok())
+        .collect()
+}
+
+/// The client's address: the connection's peer, unless that is a trusted
+/// proxy, in which case the nearest `X-Forwarded-For` hop that isn't one.
+/// Headers from anyone else are ignored, as a client can set them freely.
+pub fn client_ip(req: &HttpRequest) -> Option<IpAddr> {
+    let peer = req.peer_addr()?.ip();
+    let proxies = trusted_proxies();
+    if !proxies.contains(&peer) {
+        return Some(peer);
+    }
+
+    let forwarded: Vec<IpAddr> = req
+        .headers()
+        .get_all("X-Forwarded-For")
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|hop| hop.trim().parse().ok())
+        .collect();
+    forwarded
+        .iter()
+        .rev()
+        .find(|hop| !proxies.contains(hop))
+        .or(forwarded.first())
+        .copied()
+        .or(Some(peer))
+}
+
+/// Request id and client address recorded with audit events. The id is
+/// always generated here; an `X-Request-Id` sent along is kept separately,
+/// for correlating with the caller's logs, and is not trusted.
+pub struct RequestMeta {
+    pub request_id: String,
+    pub client_request_id: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+impl RequestMeta {
+    pub fn audit(&self, actor: AuditActor) -> AuditContext {
+        AuditContext {
+            actor,
+            request_id: Some(self.request_id.clone()),
+            client_request_id: self.client_request_id.clone(),
+            ip_address: self.ip_address.clone(),
+        }
+    }
+}
+
+impl FromRequest for RequestMeta {
+    type Error = Infallible;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let client_request_id = req
+            .headers()
+            .get("X-Request-Id")
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|id| !id.is_empty() && id.len() <= REQUEST_ID_MAX_LEN)
+            .map(str::to_string);
+
+        ready(Ok(RequestMeta {
+            request_id: Uuid::new_v4().to_string(),
+            client_request_id,
+            ip_address: client_ip(req).map(|ip| ip.to_string()),
+        }))
+    }
+}
+
+/// Audit events newest first, filtered by actor, action, entity, request and
+/// time range; page back with `before_id`
+pub async fn get_audit_events(
+    _admin: AdminAuth,
+    query: web::Query<AuditEventQuery>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    match list_audit_events(&data.db, &query).await {
+        Ok(events) => Ok(HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            message: format!("{} audit events", events.len()),
+            data: Some(events),
+        })),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Database error: {}", e),
+            data: None,
+        })),
+    }
+}
+
+/// Re-hash the audit chain and report the first event that doesn't match
+pub async fn verify_audit_log(_admin: AdminAuth, data: web::Data<AppState>) -> ActixResult<HttpResponse> {
+    match verify_audit_chain(&data.db).await {
+        Ok(report) => Ok(HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            message: match report.broken_at {
+                Some(id) => format!("Audit chain broken at event {}", id),
+                None => format!("Audit chain intact across {} events", report.events_checked),
+            },
+            data: Some(report),
+        })),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Database error: {}", e),
+            data: None,
+        })),
+    }
+}
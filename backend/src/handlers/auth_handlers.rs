@@ -0,0 +1,208 @@
+use actix_web::{dev::Payload, error::InternalError, web, FromRequest, HttpRequest, HttpResponse, Result as ActixResult};
+use chrono::Utc;
+use std::env;
+use std::future::{ready, Ready};
+use validator::Validate;
+
+use crate::models::*;
+use crate::services::*;
+use crate::{ApiResponse, AppState};
+
+/// Secret that signs access tokens; wallet sign-in is disabled without it
+fn jwt_secret() -> Option<String> {
+    env::var("JWT_SECRET").ok().filter(|secret| !secret.is_empty())
+}
+
+/// The domain sign-in messages are issued for, and the URI shown with it
+fn siws_origin() -> Option<(String, String)> {
+    let domain = env::var("SIWS_DOMAIN").ok().filter(|domain| !domain.is_empty())?;
+    let uri = env::var("SIWS_URI")
+        .ok()
+        .filter(|uri| !uri.is_empty())
+        .unwrap_or_else(|| format!("https://{}", domain));
+    Some((domain, uri))
+}
+
+fn wallet_auth_not_configured() -> HttpResponse {
+    HttpResponse::ServiceUnavailable().json(ApiResponse::<()> {
+        success: false,
+        message: "Wallet sign-in is not configured".to_string(),
+        data: None,
+    })
+}
+
+pub fn auth_rejection_response(rejection: &AuthRejection) -> HttpResponse {
+    let mut response = match rejection {
+        AuthRejection::InvalidMessage(_) => HttpResponse::BadRequest(),
+        AuthRejection::WalletMismatch => HttpResponse::Forbidden(),
+        _ => HttpResponse::Unauthorized(),
+    };
+    response.json(ApiResponse {
+        success: false,
+        message: rejection.to_string(),
+        data: Some(serde_json::json!({ "code": rejection.code() })),
+    })
+}
+
+fn auth_error_response(e: anyhow::Error) -> HttpResponse {
+    match e.downcast_ref::<AuthRejection>() {
+        Some(rejection) => auth_rejection_response(rejection),
+        None => HttpResponse::InternalServerError().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Auth error: {}", e),
+            data: None,
+        }),
+    }
+}
+
+/// Guard for a wallet's own endpoints: `Authorization: Bearer <access
+/// token>` from wallet sign-in. On routes with a `{wallet}` segment the
+/// token must be that wallet's.
+pub struct WalletAuth {
+    pub wallet_address: String,
+}
+
+impl WalletAuth {
+    /// For wallets named in the request body rather than the path
+    pub fn require_wallet(&self, wallet_address: &str) -> Result<(), AuthRejection> {
+        if self.wallet_address == wallet_address {
+            Ok(())
+        } else {
+            Err(AuthRejection::WalletMismatch)
+        }
+    }
+}
+
+impl FromRequest for WalletAuth {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let Some(secret) = jwt_secret() else {
+            return ready(Err(InternalError::from_response("unauthorized", wallet_auth_not_configured()).into()));
+        };
+
+        let authenticated = req
+            .headers()
+            .get("Authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or(AuthRejection::InvalidToken)
+            .and_then(|token| decode_wallet_token(&secret, token.trim()))
+            .and_then(|claims| match req.match_info().get("wallet") {
+                Some(wallet) if wallet != claims.sub => Err(AuthRejection::WalletMismatch),
+                _ => Ok(WalletAuth { wallet_address: claims.sub }),
+            });
+
+        ready(authenticated.map_err(|rejection| {
+            InternalError::from_response("unauthorized", auth_rejection_response(&rejection)).into()
+        }))
+    }
+}
+
+/// Start sign-in: a single-use nonce and the SIWS message to sign
+pub async fn auth_nonce(
+    req: web::Json<AuthNonceRequest>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    if let Err(validation_errors) = req.validate() {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Validation error: {:?}", validation_errors),
+            data: None,
+        }));
+    }
+
+    let (Some((domain, uri)), Some(_)) = (siws_origin(), jwt_secret()) else {
+        return Ok(wallet_auth_not_configured());
+    };
+
+    match issue_auth_nonce(&data.db, &domain, &uri, &req.wallet_address, Utc::now()).await {
+        Ok(nonce) => Ok(HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            message: "Sign the message to log in".to_string(),
+            data: Some(nonce),
+        })),
+        Err(e) => Ok(HttpResponse::BadRequest().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Nonce error: {}", e),
+            data: None,
+        })),
+    }
+}
+
+/// Finish sign-in with the signed message: access and refresh tokens
+pub async fn auth_login(
+    req: web::Json<WalletLoginRequest>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    if let Err(validation_errors) = req.validate() {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Validation error: {:?}", validation_errors),
+            data: None,
+        }));
+    }
+
+    let (Some((domain, _)), Some(secret)) = (siws_origin(), jwt_secret()) else {
+        return Ok(wallet_auth_not_configured());
+    };
+
+    match wallet_login(&data.db, &domain, &secret, &req, Utc::now()).await {
+        Ok(tokens) => Ok(HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            message: "Signed in".to_string(),
+            data: Some(tokens),
+        })),
+        Err(e) => Ok(auth_error_response(e)),
+    }
+}
+
+pub async fn auth_refresh(
+    req: web::Json<RefreshTokenRequest>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    if let Err(validation_errors) = req.validate() {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Validation error: {:?}", validation_errors),
+            data: None,
+        }));
+    }
+
+    let Some(secret) = jwt_secret() else {
+        return Ok(wallet_auth_not_configured());
+    };
+
+    match refresh_wallet_session(&data.db, &secret, &req.refresh_token, Utc::now()).await {
+        Ok(tokens) => Ok(HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            message: "Tokens refreshed".to_string(),
+            data: Some(tokens),
+        })),
+        Err(e) => Ok(auth_error_response(e)),
+    }
+}
+
+/// Revoke a refresh token; the access token lapses on its own shortly after
+pub async fn auth_logout(
+    req: web::Json<RefreshTokenRequest>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    if let Err(validation_errors) = req.validate() {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Validation error: {:?}", validation_errors),
+            data: None,
+        }));
+    }
+
+    match revoke_refresh_token(&data.db, &req.refresh_token).await {
+        Ok(revoked) => Ok(HttpResponse::Ok().json(ApiResponse::<()> {
+            success: true,
+            message: if revoked { "Signed out" } else { "Already signed out" }.to_string(),
+            data: None,
+        })),
+        Err(e) => Ok(auth_error_response(e)),
+    }
+}
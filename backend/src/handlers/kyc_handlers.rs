@@ -0,0 +1,190 @@
+use actix_web::{web, HttpRequest, HttpResponse, Result as ActixResult};
+use chrono::Utc;
+use std::env;
+use validator::Validate;
+
+use crate::handlers::{auth_rejection_response, AdminAuth, RequestMeta, WalletAuth};
+use crate::models::*;
+use crate::services::*;
+use crate::utils::*;
+use crate::{ApiResponse, AppState};
+
+/// Shared secret for webhook signatures and session tokens
+fn kyc_secret() -> Option<String> {
+    env::var("KYC_WEBHOOK_SECRET").ok().filter(|secret| !secret.is_empty())
+}
+
+fn kyc_not_configured() -> HttpResponse {
+    HttpResponse::ServiceUnavailable().json(ApiResponse::<()> {
+        success: false,
+        message: "KYC is not configured".to_string(),
+        data: None,
+    })
+}
+
+fn webhook_rejection_response(rejection: &KycWebhookRejection) -> HttpResponse {
+    let mut response = match rejection {
+        KycWebhookRejection::Signature | KycWebhookRejection::Expired => HttpResponse::Unauthorized(),
+        KycWebhookRejection::InvalidPayload(_) => HttpResponse::BadRequest(),
+        KycWebhookRejection::UnknownUser => HttpResponse::NotFound(),
+    };
+    response.json(ApiResponse {
+        success: false,
+        message: rejection.to_string(),
+        data: Some(serde_json::json!({ "code": rejection.code() })),
+    })
+}
+
+/// Status updates from the KYC provider (or a local stand-in), signed with
+/// HMAC-SHA256 in `X-Kyc-Signature`
+pub async fn kyc_webhook(
+    http_req: HttpRequest,
+    meta: RequestMeta,
+    body: web::Bytes,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let Some(secret) = kyc_secret() else {
+        return Ok(kyc_not_configured());
+    };
+
+    let header = http_req
+        .headers()
+        .get("X-Kyc-Signature")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("");
+    if let Err(rejection) = verify_kyc_signature(&secret, header, &body, Utc::now()) {
+        return Ok(webhook_rejection_response(&rejection));
+    }
+
+    let (event, payload) = match serde_json::from_slice::<serde_json::Value>(&body).and_then(|payload| {
+        serde_json::from_value::<KycWebhookEvent>(payload.clone()).map(|event| (event, payload))
+    }) {
+        Ok(parsed) => parsed,
+        Err(e) => return Ok(webhook_rejection_response(&KycWebhookRejection::InvalidPayload(e.to_string()))),
+    };
+
+    let settings = data.settings.current();
+    let audit = meta.audit(AuditActor::System("kyc_webhook".to_string()));
+    match apply_kyc_event(&data.db, &settings.kyc_provider, &event, &payload, &audit).await {
+        Ok(outcome) => Ok(HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            message: if outcome.duplicate {
+                "Event already received".to_string()
+            } else if outcome.applied {
+                format!("KYC status {}", outcome.kyc_status)
+            } else {
+                "Event recorded; a newer status is already applied".to_string()
+            },
+            data: Some(outcome),
+        })),
+        Err(e) => match e.downcast_ref::<KycWebhookRejection>() {
+            Some(rejection) => Ok(webhook_rejection_response(rejection)),
+            None => Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                success: false,
+                message: format!("KYC error: {}", e),
+                data: None,
+            })),
+        },
+    }
+}
+
+/// Start or resume verification: the provider session data for a wallet.
+/// The caller must be signed in as that wallet.
+pub async fn start_kyc_session(
+    auth: WalletAuth,
+    req: web::Json<KycSessionRequest>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    if let Err(validation_errors) = req.validate() {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Validation error: {:?}", validation_errors),
+            data: None,
+        }));
+    }
+
+    let Some(secret) = kyc_secret() else {
+        return Ok(kyc_not_configured());
+    };
+
+    if let Err(rejection) = auth.require_wallet(&req.wallet_address) {
+        return Ok(auth_rejection_response(&rejection));
+    }
+
+    let user = match get_or_create_user(&data.db, &req.wallet_address).await {
+        Ok(user) => user,
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                success: false,
+                message: format!("User error: {}", e),
+                data: None,
+            }));
+        }
+    };
+
+    let purchased = match data.db.acquire().await {
+        Ok(mut conn) => wallet_purchased_total(&mut conn, &user.id, false).await,
+        Err(e) => Err(e.into()),
+    };
+    let purchased = match purchased {
+        Ok(total) => total,
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                success: false,
+                message: format!("Database error: {}", e),
+                data: None,
+            }));
+        }
+    };
+
+    let settings = data.settings.current();
+    let session = kyc_session(&settings, &secret, &user, purchased, Utc::now());
+    Ok(HttpResponse::Ok().json(ApiResponse {
+        success: true,
+        message: format!("KYC status {}", session.kyc_status),
+        data: Some(session),
+    }))
+}
+
+/// A wallet's KYC status and webhook audit trail
+pub async fn get_kyc_events(
+    _admin: AdminAuth,
+    path: web::Path<String>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let user = match find_user_by_wallet(&data.db, &path.into_inner()).await {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            return Ok(HttpResponse::NotFound().json(ApiResponse::<()> {
+                success: false,
+                message: "User not found".to_string(),
+                data: None,
+            }));
+        }
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                success: false,
+                message: format!("User error: {}", e),
+                data: None,
+            }));
+        }
+    };
+
+    match list_kyc_events(&data.db, &user.id).await {
+        Ok(events) => Ok(HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            message: format!("KYC status {}", user.kyc_status),
+            data: Some(serde_json::json!({
+                "kyc_status": user.kyc_status,
+                "kyc_applicant_id": user.kyc_applicant_id,
+                "kyc_updated_at": user.kyc_updated_at,
+                "events": events,
+            })),
+        })),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Database error: {}", e),
+            data: None,
+        })),
+    }
+}
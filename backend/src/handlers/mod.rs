@@ -2,8 +2,28 @@ pub mod user_handlers;
 pub mod transaction_handlers;
 pub mod whitelist_handlers;
 pub mod stats_handlers;
+pub mod round_handlers;
+pub mod vesting_handlers;
+pub mod admin_handlers;
+pub mod referral_handlers;
+pub mod kyc_handlers;
+pub mod screening_handlers;
+pub mod auth_handlers;
+pub mod admin_account_handlers;
+pub mod settings_handlers;
+pub mod audit_handlers;
 
 pub use user_handlers::*;
 pub use transaction_handlers::*;
 pub use whitelist_handlers::*;
 pub use stats_handlers::*;
+pub use round_handlers::*;
+pub use vesting_handlers::*;
+pub use admin_handlers::*;
+pub use referral_handlers::*;
+pub use kyc_handlers::*;
+pub use screening_handlers::*;
+pub use auth_handlers::*;
+pub use admin_account_handlers::*;
+pub use settings_handlers::*;
+pub use audit_handlers::*;
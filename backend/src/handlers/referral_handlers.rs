@@ -0,0 +1,358 @@
+use actix_web::{web, HttpRequest, HttpResponse, Result as ActixResult};
+use chrono::Utc;
+use rust_decimal::Decimal;
+use std::str::FromStr;
+use validator::Validate;
+
+use crate::handlers::{auth_rejection_response, client_ip, AdminAuth, Operators, RequestMeta, WalletAuth};
+use crate::models::*;
+use crate::services::*;
+use crate::utils::*;
+use crate::{ApiResponse, AppState};
+
+/// Whether a referral code exists, what it earns the referrer, and how it
+/// has converted so far
+pub async fn get_referral_info(
+    path: web::Path<String>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let code = normalize_referral_code(&path.into_inner());
+
+    let referrer = match find_referrer(&data.db, &code).await {
+        Ok(Some(referrer)) => referrer,
+        Ok(None) => return Ok(unknown_code()),
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                success: false,
+                message: format!("Database error: {}", e),
+                data: None,
+            }));
+        }
+    };
+
+    let stats = match get_referral_stats(&data.db, &referrer.id).await {
+        Ok(stats) => stats,
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                success: false,
+                message: format!("Referral stats error: {}", e),
+                data: None,
+            }));
+        }
+    };
+
+    let settings = data.settings.current();
+    Ok(HttpResponse::Ok().json(ApiResponse {
+        success: true,
+        message: "Referral code is valid".to_string(),
+        data: Some(serde_json::json!({
+            "referral_code": code,
+            "referrer": referrer.wallet_address,
+            "bonus_percentage": referrer.referral_rate_percent.unwrap_or(settings.referral_bonus),
+            "upline_rates": settings.referral_upline_rates,
+            "stats": stats,
+        })),
+    }))
+}
+
+/// Count a visit through a referral link. The body is optional.
+pub async fn track_referral_click(
+    http_req: HttpRequest,
+    path: web::Path<String>,
+    req: Option<web::Json<ReferralClickRequest>>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let req = req.map(|req| req.into_inner()).unwrap_or_default();
+    if let Err(validation_errors) = req.validate() {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Validation error: {:?}", validation_errors),
+            data: None,
+        }));
+    }
+
+    let code = normalize_referral_code(&path.into_inner());
+    let referrer = match find_referrer(&data.db, &code).await {
+        Ok(Some(referrer)) => referrer,
+        Ok(None) => return Ok(unknown_code()),
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                success: false,
+                message: format!("Database error: {}", e),
+                data: None,
+            }));
+        }
+    };
+
+    let user_agent = http_req
+        .headers()
+        .get("User-Agent")
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("");
+    let client_ip = client_ip(&http_req).map(|ip| ip.to_string()).unwrap_or_default();
+    let visitor = visitor_hash(&client_ip, user_agent);
+
+    match record_referral_click(&data.db, &referrer.id, &code, &visitor, req.landing_page.as_deref()).await {
+        Ok(()) => Ok(HttpResponse::Accepted().json(ApiResponse::<()> {
+            success: true,
+            message: "Click recorded".to_string(),
+            data: None,
+        })),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Database error: {}", e),
+            data: None,
+        })),
+    }
+}
+
+/// Replace the wallet's generated referral code with a custom one. The
+/// caller must be signed in as that wallet.
+pub async fn claim_vanity_code(
+    auth: WalletAuth,
+    meta: RequestMeta,
+    req: web::Json<ClaimReferralCodeRequest>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    if let Err(validation_errors) = req.validate() {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Validation error: {:?}", validation_errors),
+            data: None,
+        }));
+    }
+
+    if let Err(rejection) = auth.require_wallet(&req.wallet_address) {
+        return Ok(auth_rejection_response(&rejection));
+    }
+
+    let user = match find_user_by_wallet(&data.db, &req.wallet_address).await {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            return Ok(HttpResponse::NotFound().json(ApiResponse::<()> {
+                success: false,
+                message: "User not found".to_string(),
+                data: None,
+            }));
+        }
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                success: false,
+                message: format!("User error: {}", e),
+                data: None,
+            }));
+        }
+    };
+
+    let settings = data.settings.current();
+    match claim_referral_code(&data.db, &user.id, &req.referral_code, &settings).await {
+        Ok(claimed) => {
+            let entry = AuditEntry {
+                action: "referral.code_claimed",
+                entity_type: "user",
+                entity_id: claimed.id.to_string(),
+                before: Some(serde_json::json!({ "referral_code": user.referral_code })),
+                after: Some(serde_json::json!({ "referral_code": claimed.referral_code })),
+            };
+            try_record_audit_event(&data.db, &meta.audit(AuditActor::User(claimed.wallet_address.clone())), entry).await;
+
+            Ok(HttpResponse::Ok().json(ApiResponse {
+                success: true,
+                message: "Referral code claimed".to_string(),
+                data: Some(serde_json::json!({
+                    "wallet_address": claimed.wallet_address,
+                    "referral_code": claimed.referral_code,
+                })),
+            }))
+        }
+        Err(e) => {
+            if let Some(rejection) = e.downcast_ref::<ReferralCodeRejection>() {
+                let mut response = match rejection {
+                    ReferralCodeRejection::Taken | ReferralCodeRejection::AlreadyClaimed => HttpResponse::Conflict(),
+                    _ => HttpResponse::BadRequest(),
+                };
+                return Ok(response.json(ApiResponse {
+                    success: false,
+                    message: rejection.to_string(),
+                    data: Some(serde_json::json!({ "code": rejection.code() })),
+                }));
+            }
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                success: false,
+                message: format!("Referral error: {}", e),
+                data: None,
+            }))
+        }
+    }
+}
+
+fn unknown_code() -> HttpResponse {
+    HttpResponse::NotFound().json(ApiResponse::<()> {
+        success: false,
+        message: "Unknown referral code".to_string(),
+        data: None,
+    })
+}
+
+/// A referrer's reward ledger and how much of it has vested and been released
+pub async fn get_referrer_rewards(
+    _auth: WalletAuth,
+    path: web::Path<String>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let wallet = path.into_inner();
+
+    let user = match find_user_by_wallet(&data.db, &wallet).await {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            return Ok(HttpResponse::NotFound().json(ApiResponse::<()> {
+                success: false,
+                message: "User not found".to_string(),
+                data: None,
+            }));
+        }
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                success: false,
+                message: format!("User error: {}", e),
+                data: None,
+            }));
+        }
+    };
+
+    let ledger = match get_referral_rewards(&data.db, &user.id).await {
+        Ok(ledger) => ledger,
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                success: false,
+                message: format!("Referral error: {}", e),
+                data: None,
+            }));
+        }
+    };
+
+    let schedules = match get_user_schedules(&data.db, &user.id).await {
+        Ok(schedules) => schedules
+            .into_iter()
+            .filter(|schedule| schedule.referral_reward_id.is_some())
+            .collect(),
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                success: false,
+                message: format!("Vesting error: {}", e),
+                data: None,
+            }));
+        }
+    };
+
+    let total_rewarded: Decimal = ledger.iter().map(|reward| reward.amount_tokens).sum();
+    let total_rewarded_sol: Decimal = ledger.iter().map(|reward| reward.amount_sol).sum();
+    let paid_sol: Decimal = ledger
+        .iter()
+        .filter(|reward| reward.payout_status.as_deref() == Some("paid"))
+        .map(|reward| reward.amount_sol)
+        .sum();
+
+    Ok(HttpResponse::Ok().json(ApiResponse {
+        success: true,
+        message: "Referral rewards".to_string(),
+        data: Some(serde_json::json!({
+            "total_rewarded_tokens": total_rewarded,
+            "total_rewarded_sol": total_rewarded_sol,
+            "paid_sol": paid_sol,
+            "rewards": ledger,
+            "vesting": summarize_vesting(&wallet, schedules, Utc::now()),
+        })),
+    }))
+}
+
+fn rate_out_of_range(rate_percent: Option<Decimal>) -> Option<HttpResponse> {
+    match rate_percent {
+        Some(rate) if rate < Decimal::ZERO || rate > Decimal::ONE_HUNDRED => {
+            Some(HttpResponse::BadRequest().json(ApiResponse::<()> {
+                success: false,
+                message: "rate_percent must be between 0 and 100".to_string(),
+                data: None,
+            }))
+        }
+        _ => None,
+    }
+}
+
+/// Set a referrer's negotiated commission rate and payout currency
+pub async fn set_referrer_terms(
+    admin: AdminAuth<Operators>,
+    meta: RequestMeta,
+    path: web::Path<String>,
+    req: web::Json<ReferrerCommissionRequest>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    if let Some(response) = rate_out_of_range(req.rate_percent) {
+        return Ok(response);
+    }
+    let payout_currency = match req.payout_currency.as_deref().map(CommissionCurrency::from_str).transpose() {
+        Ok(currency) => currency,
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(ApiResponse::<()> {
+                success: false,
+                message: format!("Invalid payout_currency: {}", e),
+                data: None,
+            }));
+        }
+    };
+
+    let audit = meta.audit(AuditActor::Admin(admin.admin.id));
+    match set_referrer_commission(&data.db, &path.into_inner(), req.rate_percent, payout_currency, &audit).await {
+        Ok(Some(user)) => Ok(HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            message: "Referrer commission updated".to_string(),
+            data: Some(serde_json::json!({
+                "wallet_address": user.wallet_address,
+                "referral_rate_percent": user.referral_rate_percent,
+                "referral_payout_currency": user.referral_payout_currency,
+            })),
+        })),
+        Ok(None) => Ok(HttpResponse::NotFound().json(ApiResponse::<()> {
+            success: false,
+            message: "User not found".to_string(),
+            data: None,
+        })),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Database error: {}", e),
+            data: None,
+        })),
+    }
+}
+
+/// Override the direct commission rate for the referral of one wallet
+pub async fn set_referral_terms(
+    admin: AdminAuth<Operators>,
+    meta: RequestMeta,
+    path: web::Path<String>,
+    req: web::Json<ReferralRateRequest>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    if let Some(response) = rate_out_of_range(req.rate_percent) {
+        return Ok(response);
+    }
+
+    let audit = meta.audit(AuditActor::Admin(admin.admin.id));
+    match set_referral_rate(&data.db, &path.into_inner(), req.rate_percent, &audit).await {
+        Ok(Some(referral)) => Ok(HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            message: "Referral rate updated".to_string(),
+            data: Some(referral),
+        })),
+        Ok(None) => Ok(HttpResponse::NotFound().json(ApiResponse::<()> {
+            success: false,
+            message: "Wallet has no referrer".to_string(),
+            data: None,
+        })),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Database error: {}", e),
+            data: None,
+        })),
+    }
+}
@@ -0,0 +1,109 @@
+use actix_web::{web, HttpResponse, Result as ActixResult};
+use chrono::{Duration, Utc};
+
+use crate::models::*;
+use crate::services::*;
+use crate::{ApiResponse, AppState};
+
+/// List presale rounds, flagging the one currently selling
+pub async fn get_rounds(data: web::Data<AppState>) -> ActixResult<HttpResponse> {
+    let rounds = match list_rounds(&data.db).await {
+        Ok(rounds) => rounds,
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                success: false,
+                message: format!("Rounds error: {}", e),
+                data: None,
+            }));
+        }
+    };
+
+    let active = active_round_index(&rounds, Utc::now(), Duration::zero());
+    let rounds: Vec<RoundResponse> = rounds
+        .into_iter()
+        .enumerate()
+        .map(|(index, round)| RoundResponse::from_round(round, Some(index) == active))
+        .collect();
+
+    Ok(HttpResponse::Ok().json(ApiResponse {
+        success: true,
+        message: "Presale rounds".to_string(),
+        data: Some(rounds),
+    }))
+}
+
+/// Quote the oracle's current SOL price for USD-priced rounds. Purchases
+/// are converted at the latest price recorded before their payment, by the
+/// oracle sampler or by this quote.
+pub async fn get_price_quote(data: web::Data<AppState>) -> ActixResult<HttpResponse> {
+    let settings = data.settings.current();
+    let (PricingCurrency::Usd, Some(price_account)) = (settings.pricing_currency, settings.oracle_price_account) else {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()> {
+            success: false,
+            message: "Rounds are priced in SOL".to_string(),
+            data: None,
+        }));
+    };
+
+    let price = match data.solana_service.get_oracle_price(&price_account).await {
+        Ok(price) => price,
+        Err(e) => {
+            return Ok(HttpResponse::ServiceUnavailable().json(ApiResponse::<()> {
+                success: false,
+                message: format!("Oracle error: {}", e),
+                data: None,
+            }));
+        }
+    };
+    if let Err(e) = price.check_usable(
+        Utc::now().timestamp(),
+        settings.oracle_max_staleness_secs,
+        settings.oracle_max_confidence_bps,
+    ) {
+        return Ok(HttpResponse::ServiceUnavailable().json(ApiResponse::<()> {
+            success: false,
+            message: e.to_string(),
+            data: None,
+        }));
+    }
+
+    let quote = match record_price_quote(&data.db, &price_account.to_string(), &price).await {
+        Ok(quote) => quote,
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                success: false,
+                message: format!("Quote error: {}", e),
+                data: None,
+            }));
+        }
+    };
+
+    let rounds = match list_rounds(&data.db).await {
+        Ok(rounds) => rounds,
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                success: false,
+                message: format!("Rounds error: {}", e),
+                data: None,
+            }));
+        }
+    };
+    let active = active_round_index(&rounds, Utc::now(), Duration::zero())
+        .map(|index| rounds[index].clone().priced_from_usd(quote.price));
+
+    Ok(HttpResponse::Ok().json(ApiResponse {
+        success: true,
+        message: "Price quote".to_string(),
+        data: Some(serde_json::json!({
+            "usd_per_sol": quote.price,
+            "confidence": quote.confidence,
+            "publish_time": quote.publish_time,
+            // Payments landing after this fall back to a newer quote, or fail
+            // if there is none
+            "valid_until": quote.publish_time + Duration::seconds(settings.oracle_max_staleness_secs),
+            "active_round": active.as_ref().map(|round| &round.name),
+            "price_sol": active.as_ref().map(|round| round.price_sol),
+            "price_usd": active.as_ref().and_then(|round| round.price_usd),
+        })),
+    }))
+}
@@ -0,0 +1,116 @@
+use actix_web::{web, HttpResponse, Result as ActixResult};
+use validator::Validate;
+
+use crate::handlers::{AdminAuth, Operators, RequestMeta};
+use crate::models::*;
+use crate::services::*;
+use crate::{ApiResponse, AppState};
+
+fn denylist_status(denylist: &DenylistService) -> serde_json::Value {
+    let current = denylist.current();
+    serde_json::json!({
+        "addresses": current.address_count(),
+        "loaded_at": current.loaded_at,
+    })
+}
+
+/// Size and age of the loaded denylist
+pub async fn get_denylist(_admin: AdminAuth, data: web::Data<AppState>) -> ActixResult<HttpResponse> {
+    Ok(HttpResponse::Ok().json(ApiResponse {
+        success: true,
+        message: "Denylist".to_string(),
+        data: Some(denylist_status(&data.denylist)),
+    }))
+}
+
+/// Add addresses to the denylist table; the list reloads on the change
+pub async fn add_to_denylist(
+    admin: AdminAuth<Operators>,
+    meta: RequestMeta,
+    req: web::Json<AddDenylistRequest>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    if let Err(validation_errors) = req.validate() {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Validation error: {:?}", validation_errors),
+            data: None,
+        }));
+    }
+
+    let audit = meta.audit(AuditActor::Admin(admin.admin.id));
+    match add_denylist_entries(&data.db, &req, &audit).await {
+        Ok(written) => Ok(HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            message: format!("{} addresses denylisted", written),
+            data: Some(serde_json::json!({ "written": written })),
+        })),
+        Err(e) => Ok(HttpResponse::BadRequest().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Denylist error: {}", e),
+            data: None,
+        })),
+    }
+}
+
+pub async fn remove_from_denylist(
+    admin: AdminAuth<Operators>,
+    meta: RequestMeta,
+    path: web::Path<String>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let audit = meta.audit(AuditActor::Admin(admin.admin.id));
+    match remove_denylist_entry(&data.db, &path.into_inner(), &audit).await {
+        Ok(true) => Ok(HttpResponse::Ok().json(ApiResponse::<()> {
+            success: true,
+            message: "Address removed from the denylist table".to_string(),
+            data: None,
+        })),
+        Ok(false) => Ok(HttpResponse::NotFound().json(ApiResponse::<()> {
+            success: false,
+            message: "Address not in the denylist table".to_string(),
+            data: None,
+        })),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Database error: {}", e),
+            data: None,
+        })),
+    }
+}
+
+/// Reload now, e.g. right after replacing the denylist file
+pub async fn reload_denylist(_admin: AdminAuth<Operators>, data: web::Data<AppState>) -> ActixResult<HttpResponse> {
+    match data.denylist.reload(&data.db).await {
+        Ok(_) => Ok(HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            message: "Denylist reloaded".to_string(),
+            data: Some(denylist_status(&data.denylist)),
+        })),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Reload failed, keeping the previous denylist: {}", e),
+            data: None,
+        })),
+    }
+}
+
+/// Blocked and flagged wallets, newest first
+pub async fn get_screening_events(
+    _admin: AdminAuth,
+    query: web::Query<ScreeningEventQuery>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    match list_screening_events(&data.db, &query).await {
+        Ok(events) => Ok(HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            message: format!("{} screening events", events.len()),
+            data: Some(events),
+        })),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Database error: {}", e),
+            data: None,
+        })),
+    }
+}
@@ -0,0 +1,149 @@
+use actix_web::{web, HttpResponse, Result as ActixResult};
+use uuid::Uuid;
+
+use crate::handlers::{AdminAuth, Operators, RequestMeta};
+use crate::models::*;
+use crate::services::*;
+use crate::{ApiResponse, AppState};
+
+fn settings_error_response(e: anyhow::Error) -> HttpResponse {
+    match e.downcast_ref::<SettingsChangeRejection>() {
+        Some(rejection) => HttpResponse::UnprocessableEntity().json(ApiResponse {
+            success: false,
+            message: rejection.to_string(),
+            data: Some(serde_json::json!({ "code": rejection.code() })),
+        }),
+        None => HttpResponse::InternalServerError().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Settings error: {}", e),
+            data: None,
+        }),
+    }
+}
+
+/// Stored settings rows and the settings version they are at
+pub async fn get_settings(_admin: AdminAuth, data: web::Data<AppState>) -> ActixResult<HttpResponse> {
+    match list_presale_settings(&data.db).await {
+        Ok((settings, version)) => Ok(HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            message: format!("{} settings", settings.len()),
+            data: Some(serde_json::json!({
+                "version": version,
+                "settings": settings,
+            })),
+        })),
+        Err(e) => Ok(settings_error_response(e)),
+    }
+}
+
+/// Change settings now. The whole change set is validated together and
+/// applied as one version.
+pub async fn update_settings(
+    admin: AdminAuth<Operators>,
+    meta: RequestMeta,
+    req: web::Json<UpdateSettingsRequest>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let audit = meta.audit(AuditActor::Admin(admin.admin.id));
+    let context = SettingsChangeContext {
+        admin_id: Some(admin.admin.id),
+        name: &admin.admin.username,
+        source: "admin",
+        schedule_id: None,
+        reason: req.reason.as_deref(),
+        audit: &audit,
+    };
+
+    match apply_settings_changes(&data.db, &req.changes, &context).await {
+        Ok(applied) => Ok(HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            message: match applied.version {
+                Some(version) => format!("Settings updated to v{}", version),
+                None => "Settings already had these values".to_string(),
+            },
+            data: Some(applied),
+        })),
+        Err(e) => Ok(settings_error_response(e)),
+    }
+}
+
+/// Versioned change history, filtered by `key`
+pub async fn get_settings_history(
+    _admin: AdminAuth,
+    query: web::Query<SettingsHistoryQuery>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    match list_settings_history(&data.db, &query).await {
+        Ok(changes) => Ok(HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            message: format!("{} changes", changes.len()),
+            data: Some(changes),
+        })),
+        Err(e) => Ok(settings_error_response(e)),
+    }
+}
+
+/// Scheduled changes, filtered by `status`
+pub async fn get_settings_schedules(
+    _admin: AdminAuth,
+    query: web::Query<SettingsScheduleQuery>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    match list_settings_schedules(&data.db, &query).await {
+        Ok(schedules) => Ok(HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            message: format!("{} scheduled changes", schedules.len()),
+            data: Some(schedules),
+        })),
+        Err(e) => Ok(settings_error_response(e)),
+    }
+}
+
+/// Schedule a change set to take effect at `effective_at`
+pub async fn schedule_settings(
+    admin: AdminAuth<Operators>,
+    meta: RequestMeta,
+    req: web::Json<ScheduleSettingsRequest>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let audit = meta.audit(AuditActor::Admin(admin.admin.id));
+    let context = SettingsChangeContext {
+        admin_id: Some(admin.admin.id),
+        name: &admin.admin.username,
+        source: "schedule",
+        schedule_id: None,
+        reason: req.reason.as_deref(),
+        audit: &audit,
+    };
+
+    match schedule_settings_changes(&data.db, &req.changes, req.effective_at, &context).await {
+        Ok(schedule) => Ok(HttpResponse::Created().json(ApiResponse {
+            success: true,
+            message: format!("Settings change scheduled for {}", schedule.effective_at),
+            data: Some(schedule),
+        })),
+        Err(e) => Ok(settings_error_response(e)),
+    }
+}
+
+pub async fn cancel_settings_change(
+    admin: AdminAuth<Operators>,
+    meta: RequestMeta,
+    path: web::Path<Uuid>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let audit = meta.audit(AuditActor::Admin(admin.admin.id));
+    match cancel_settings_schedule(&data.db, &path.into_inner(), &admin.admin.username, &audit).await {
+        Ok(Some(schedule)) => Ok(HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            message: "Scheduled change cancelled".to_string(),
+            data: Some(schedule),
+        })),
+        Ok(None) => Ok(HttpResponse::NotFound().json(ApiResponse::<()> {
+            success: false,
+            message: "No pending scheduled change with that id".to_string(),
+            data: None,
+        })),
+        Err(e) => Ok(settings_error_response(e)),
+    }
+}
@@ -0,0 +1,122 @@
+use actix_web::{web, HttpResponse, Result as ActixResult};
+use sqlx::Row;
+
+use crate::models::*;
+use crate::services::*;
+use crate::{ApiResponse, AppState};
+
+/// Presale statistics: transaction totals plus hard/soft cap progress
+pub async fn get_presale_stats(data: web::Data<AppState>) -> ActixResult<HttpResponse> {
+    let row = match sqlx::query(
+        r#"
+        SELECT
+            COUNT(*) AS total_transactions,
+            COALESCE(SUM(amount_tokens) FILTER (WHERE status = 'confirmed'), 0)::float8 AS total_tokens_sold,
+            COALESCE(SUM(amount_sol) FILTER (WHERE status = 'confirmed'), 0)::float8 AS total_sol_raised,
+            COUNT(*) FILTER (WHERE status = 'confirmed') AS successful_transactions,
+            COUNT(*) FILTER (WHERE status = 'pending') AS pending_transactions,
+            COUNT(*) FILTER (WHERE status = 'failed') AS failed_transactions
+        FROM transactions
+        "#
+    )
+    .fetch_one(&data.db)
+    .await
+    {
+        Ok(row) => row,
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                success: false,
+                message: format!("Stats error: {}", e),
+                data: None,
+            }));
+        }
+    };
+
+    let stats = TransactionStats {
+        total_transactions: row.get("total_transactions"),
+        total_tokens_sold: row.get("total_tokens_sold"),
+        total_sol_raised: row.get("total_sol_raised"),
+        successful_transactions: row.get("successful_transactions"),
+        pending_transactions: row.get("pending_transactions"),
+        failed_transactions: row.get("failed_transactions"),
+    };
+
+    let supply = match get_supply_status(&data.db, &data.settings.current()).await {
+        Ok(supply) => supply,
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                success: false,
+                message: format!("Supply error: {}", e),
+                data: None,
+            }));
+        }
+    };
+
+    Ok(HttpResponse::Ok().json(ApiResponse {
+        success: true,
+        message: "Presale statistics".to_string(),
+        data: Some(serde_json::json!({
+            "transactions": stats,
+            "supply": supply,
+        })),
+    }))
+}
+
+/// Top referrers by the purchase volume they brought in, wallets truncated
+pub async fn get_referrer_leaderboard(
+    query: web::Query<LeaderboardQuery>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let (page, per_page) = leaderboard_page(&query);
+    let key = format!("referrers:{}:{}", page, per_page);
+    let ttl = data.settings.current().leaderboard_cache_secs;
+
+    let board = match data.leaderboards.get(&key, ttl) {
+        Some(board) => Ok(board),
+        None => top_referrers(&data.db, page, per_page)
+            .await
+            .and_then(|board| Ok(serde_json::to_value(board)?))
+            .inspect(|board| data.leaderboards.put(key, board.clone(), ttl)),
+    };
+    leaderboard_response(ttl, board, "Top referrers")
+}
+
+/// Top contributors by SOL spent, wallets truncated
+pub async fn get_contributor_leaderboard(
+    query: web::Query<LeaderboardQuery>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let (page, per_page) = leaderboard_page(&query);
+    let key = format!("contributors:{}:{}", page, per_page);
+    let ttl = data.settings.current().leaderboard_cache_secs;
+
+    let board = match data.leaderboards.get(&key, ttl) {
+        Some(board) => Ok(board),
+        None => top_contributors(&data.db, page, per_page)
+            .await
+            .and_then(|board| Ok(serde_json::to_value(board)?))
+            .inspect(|board| data.leaderboards.put(key, board.clone(), ttl)),
+    };
+    leaderboard_response(ttl, board, "Top contributors")
+}
+
+fn leaderboard_response(
+    ttl: u64,
+    board: anyhow::Result<serde_json::Value>,
+    message: &str,
+) -> ActixResult<HttpResponse> {
+    match board {
+        Ok(board) => Ok(HttpResponse::Ok()
+            .insert_header(("Cache-Control", format!("public, max-age={}", ttl)))
+            .json(ApiResponse {
+                success: true,
+                message: message.to_string(),
+                data: Some(board),
+            })),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Leaderboard error: {}", e),
+            data: None,
+        })),
+    }
+}
@@ -0,0 +1,29 @@
+use actix_web::{web, HttpResponse, Result as ActixResult};
+
+use crate::handlers::WalletAuth;
+use crate::models::*;
+use crate::services::*;
+use crate::{ApiResponse, AppState};
+
+/// Purchase history for a wallet, newest first; only that wallet may read it
+pub async fn get_user_transactions(
+    _auth: WalletAuth,
+    path: web::Path<String>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    match list_wallet_transactions(&data.db, &path.into_inner()).await {
+        Ok(transactions) => {
+            let transactions: Vec<TransactionResponse> = transactions.into_iter().map(Into::into).collect();
+            Ok(HttpResponse::Ok().json(ApiResponse {
+                success: true,
+                message: format!("{} transactions", transactions.len()),
+                data: Some(transactions),
+            }))
+        }
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Transaction error: {}", e),
+            data: None,
+        })),
+    }
+}
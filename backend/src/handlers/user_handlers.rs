@@ -0,0 +1,172 @@
+use actix_web::{web, HttpResponse, Result as ActixResult};
+use rust_decimal::prelude::ToPrimitive;
+use validator::Validate;
+
+use crate::handlers::{auth_rejection_response, RequestMeta, WalletAuth};
+use crate::models::*;
+use crate::services::*;
+use crate::utils::*;
+use crate::{ApiResponse, AppState};
+
+/// Register a wallet, optionally attributing it to a referrer's code. The
+/// caller must be signed in as that wallet.
+pub async fn register_user(
+    auth: WalletAuth,
+    meta: RequestMeta,
+    req: web::Json<CreateUserRequest>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    if let Err(validation_errors) = req.validate() {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Validation error: {:?}", validation_errors),
+            data: None,
+        }));
+    }
+
+    if let Err(rejection) = auth.require_wallet(&req.wallet_address) {
+        return Ok(auth_rejection_response(&rejection));
+    }
+
+    if let Err(e) = data
+        .denylist
+        .screen(&data.db, &req.wallet_address, ScreeningRole::Buyer, "registration", None)
+        .await
+    {
+        return Ok(registration_screening_response(e));
+    }
+
+    let user = match get_or_create_user(&data.db, &req.wallet_address).await {
+        Ok(user) => user,
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                success: false,
+                message: format!("User error: {}", e),
+                data: None,
+            }));
+        }
+    };
+
+    let user = match update_user_profile(&data.db, &user.id, &req).await {
+        Ok(user) => user,
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                success: false,
+                message: format!("User error: {}", e),
+                data: None,
+            }));
+        }
+    };
+
+    let user = match &req.referral_code {
+        Some(code) => {
+            let attributed = match data.denylist.screen_referral_code(&data.db, code, "registration").await {
+                Ok(()) => attribute_referral(&data.db, &user.id, code).await,
+                Err(e) => Err(e),
+            };
+            match attributed {
+                Ok(user) => user,
+                Err(e) if e.downcast_ref::<ScreeningRejection>().is_some() => {
+                    return Ok(registration_screening_response(e));
+                }
+                Err(e) => {
+                    if let Some(rejection) = e.downcast_ref::<ReferralRejection>() {
+                        return Ok(HttpResponse::Conflict().json(ApiResponse {
+                            success: false,
+                            message: rejection.to_string(),
+                            data: Some(serde_json::json!({ "code": rejection.code() })),
+                        }));
+                    }
+                    return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                        success: false,
+                        message: format!("Referral error: {}", e),
+                        data: None,
+                    }));
+                }
+            }
+        }
+        None => user,
+    };
+
+    // Contact details stay out of the audit log, which can't be erased
+    let entry = AuditEntry {
+        action: "user.registered",
+        entity_type: "user",
+        entity_id: user.id.to_string(),
+        before: None,
+        after: Some(serde_json::json!({
+            "referral_code": user.referral_code,
+            "referred_by": user.referred_by,
+        })),
+    };
+    try_record_audit_event(&data.db, &meta.audit(AuditActor::User(user.wallet_address.clone())), entry).await;
+
+    user_response(&data, user, "User registered").await
+}
+
+fn registration_screening_response(e: anyhow::Error) -> HttpResponse {
+    match e.downcast_ref::<ScreeningRejection>() {
+        Some(rejection) => HttpResponse::Forbidden().json(ApiResponse {
+            success: false,
+            message: rejection.to_string(),
+            data: Some(serde_json::json!({ "code": rejection.code() })),
+        }),
+        None => HttpResponse::InternalServerError().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Screening error: {}", e),
+            data: None,
+        }),
+    }
+}
+
+pub async fn get_user(
+    _auth: WalletAuth,
+    path: web::Path<String>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    match find_user_by_wallet(&data.db, &path.into_inner()).await {
+        Ok(Some(user)) => user_response(&data, user, "User found").await,
+        Ok(None) => Ok(HttpResponse::NotFound().json(ApiResponse::<()> {
+            success: false,
+            message: "User not found".to_string(),
+            data: None,
+        })),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+            success: false,
+            message: format!("User error: {}", e),
+            data: None,
+        })),
+    }
+}
+
+async fn user_response(data: &AppState, user: User, message: &str) -> ActixResult<HttpResponse> {
+    let (total_purchased, referral_count) = match get_user_totals(&data.db, &user.id).await {
+        Ok(totals) => totals,
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                success: false,
+                message: format!("User error: {}", e),
+                data: None,
+            }));
+        }
+    };
+
+    Ok(HttpResponse::Ok().json(ApiResponse {
+        success: true,
+        message: message.to_string(),
+        data: Some(UserResponse {
+            id: user.id,
+            wallet_address: user.wallet_address,
+            email: user.email,
+            discord_username: user.discord_username,
+            twitter_username: user.twitter_username,
+            referral_code: user.referral_code,
+            referred_by: user.referred_by,
+            is_whitelisted: user.is_whitelisted,
+            whitelist_tier: user.whitelist_tier,
+            kyc_status: user.kyc_status,
+            total_purchased: total_purchased.to_f64(),
+            referral_count: Some(referral_count),
+        }),
+    }))
+}
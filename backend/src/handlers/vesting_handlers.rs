@@ -0,0 +1,280 @@
+use actix_web::{web, HttpResponse, Result as ActixResult};
+use chrono::{Duration, Utc};
+use rust_decimal::Decimal;
+use validator::Validate;
+
+use crate::handlers::{auth_rejection_response, RequestMeta, WalletAuth};
+use crate::models::*;
+use crate::services::*;
+use crate::utils::*;
+use crate::{ApiResponse, AppState};
+
+/// Locked, vested and claimable tokens for a wallet
+pub async fn get_vesting(
+    _auth: WalletAuth,
+    path: web::Path<String>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let wallet = path.into_inner();
+
+    let user = match find_user_by_wallet(&data.db, &wallet).await {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            return Ok(HttpResponse::NotFound().json(ApiResponse::<()> {
+                success: false,
+                message: "User not found".to_string(),
+                data: None,
+            }));
+        }
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                success: false,
+                message: format!("User error: {}", e),
+                data: None,
+            }));
+        }
+    };
+
+    match get_user_schedules(&data.db, &user.id).await {
+        Ok(schedules) => Ok(HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            message: "Vesting schedules".to_string(),
+            data: Some(summarize_vesting(&wallet, schedules, Utc::now())),
+        })),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Vesting error: {}", e),
+            data: None,
+        })),
+    }
+}
+
+/// Vesting timeline for a purchase of `amount` tokens made now, in the given
+/// round or the active one, plus the referrer's bonus on the same terms
+pub async fn preview_vesting(
+    query: web::Query<VestingPreviewQuery>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    if query.amount <= Decimal::ZERO {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()> {
+            success: false,
+            message: "Amount must be positive".to_string(),
+            data: None,
+        }));
+    }
+
+    let rounds = match list_rounds(&data.db).await {
+        Ok(rounds) => rounds,
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                success: false,
+                message: format!("Round error: {}", e),
+                data: None,
+            }));
+        }
+    };
+
+    let now = Utc::now();
+    let round = match query.round_id {
+        Some(round_id) => rounds.iter().find(|round| round.id == round_id),
+        None => active_round_index(&rounds, now, Duration::zero()).map(|index| &rounds[index]),
+    };
+    let round = match round {
+        Some(round) => round,
+        None => {
+            return Ok(HttpResponse::NotFound().json(ApiResponse::<()> {
+                success: false,
+                message: "Round not found".to_string(),
+                data: None,
+            }));
+        }
+    };
+
+    let settings = data.settings.current();
+    let commission = match preview_direct_commission(
+        &data.db,
+        &settings,
+        query.wallet.as_deref(),
+        query.referral_code.as_deref(),
+    )
+    .await
+    {
+        Ok(commission) => commission,
+        Err(e) => {
+            if let Some(rejection) = e.downcast_ref::<ReferralRejection>() {
+                return Ok(HttpResponse::BadRequest().json(ApiResponse {
+                    success: false,
+                    message: rejection.to_string(),
+                    data: Some(serde_json::json!({ "code": rejection.code() })),
+                }));
+            }
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                success: false,
+                message: format!("Referral error: {}", e),
+                data: None,
+            }));
+        }
+    };
+
+    let terms = round.vesting_terms();
+    // Token commissions vest with the purchase; SOL commissions are paid out
+    // by the worker, so only their rate is shown
+    let referral_bonus = commission.map(|(rate, currency)| match currency {
+        CommissionCurrency::Token => {
+            let bonus_tokens = (query.amount * rate / Decimal::ONE_HUNDRED).round_dp(8);
+            serde_json::json!({
+                "rate_percent": rate,
+                "currency": currency,
+                "amount_tokens": bonus_tokens,
+                "unlocks": terms.unlock_events(bonus_tokens, now),
+            })
+        }
+        CommissionCurrency::Sol => serde_json::json!({
+            "rate_percent": rate,
+            "currency": currency,
+        }),
+    });
+
+    Ok(HttpResponse::Ok().json(ApiResponse {
+        success: true,
+        message: "Vesting preview".to_string(),
+        data: Some(serde_json::json!({
+            "round_id": round.id,
+            "round_name": round.name,
+            "terms": terms,
+            "amount_tokens": query.amount,
+            "start_date": now,
+            "tge_tokens": terms.tge_amount(query.amount),
+            "cliff_ends_at": terms.cliff_ends_at(now),
+            "fully_vested_at": terms.fully_vested_at(now),
+            "unlocks": terms.unlock_events(query.amount, now),
+            "referral_bonus": referral_bonus,
+        })),
+    }))
+}
+
+/// Send a wallet its newly vested tokens.
+///
+/// `released_tokens` is bumped and the release recorded under the
+/// pre-signed transaction's signature before it is sent; if the send is
+/// known to have failed the bookkeeping is reverted.
+pub async fn claim_vesting(
+    auth: WalletAuth,
+    meta: RequestMeta,
+    req: web::Json<ClaimVestingRequest>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    if let Err(validation_errors) = req.validate() {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Validation error: {:?}", validation_errors),
+            data: None,
+        }));
+    }
+
+    if let Err(rejection) = auth.require_wallet(&req.wallet_address) {
+        return Ok(auth_rejection_response(&rejection));
+    }
+
+    let internal_error = |message: String| {
+        HttpResponse::InternalServerError().json(ApiResponse::<()> {
+            success: false,
+            message,
+            data: None,
+        })
+    };
+
+    let user = match find_user_by_wallet(&data.db, &req.wallet_address).await {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            return Ok(HttpResponse::NotFound().json(ApiResponse::<()> {
+                success: false,
+                message: "User not found".to_string(),
+                data: None,
+            }));
+        }
+        Err(e) => return Ok(internal_error(format!("User error: {}", e))),
+    };
+
+    let audit = meta.audit(AuditActor::User(user.wallet_address.clone()));
+    let mut db_tx = match data.db.begin().await {
+        Ok(db_tx) => db_tx,
+        Err(e) => return Ok(internal_error(format!("Database error: {}", e))),
+    };
+
+    let releases = match lock_claimable(&mut db_tx, &user.id, "claim", Utc::now()).await {
+        Ok(releases) => releases,
+        Err(e) => return Ok(internal_error(format!("Vesting error: {}", e))),
+    };
+
+    let amount: Decimal = releases.iter().map(|(_, amount)| *amount).sum();
+    if amount <= Decimal::ZERO {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()> {
+            success: false,
+            message: "Nothing to claim yet".to_string(),
+            data: None,
+        }));
+    }
+
+    // In a soft-lock launch claimed tokens stay frozen until the TGE thaw
+    let freeze = data.settings.current().freeze_until_tge;
+    let transaction = match data.solana_service.build_distribution(&req.wallet_address, amount, freeze).await {
+        Ok(transaction) => transaction,
+        Err(e) => return Ok(internal_error(format!("Failed to prepare claim: {}", e))),
+    };
+    let signature = transaction.signatures[0].to_string();
+    let recent_blockhash = transaction.message.recent_blockhash.to_string();
+
+    if freeze {
+        let tracked = match data.solana_service.token_account_for(&req.wallet_address) {
+            Ok(token_account) => {
+                track_token_account(&mut db_tx, &req.wallet_address, &token_account, true, Some(&signature)).await
+            }
+            Err(e) => Err(e),
+        };
+        if let Err(e) = tracked {
+            return Ok(internal_error(format!("Failed to record claim: {}", e)));
+        }
+    }
+    if let Err(e) = record_releases(&mut db_tx, &releases, "claim", &signature, &recent_blockhash, &audit).await {
+        return Ok(internal_error(format!("Failed to record claim: {}", e)));
+    }
+    if let Err(e) = db_tx.commit().await {
+        return Ok(internal_error(format!("Failed to record claim: {}", e)));
+    }
+
+    match data.solana_service.send_prepared(&transaction).await {
+        SendOutcome::Confirmed => {
+            if let Err(e) = confirm_releases(&data.db, &signature, &audit).await {
+                eprintln!("Failed to confirm vesting release {}: {}", signature, e);
+            }
+            println!("✅ Released {} vested tokens to {}, signature: {}", amount, req.wallet_address, signature);
+
+            Ok(HttpResponse::Ok().json(ApiResponse {
+                success: true,
+                message: format!("Claimed {} SBT tokens", amount),
+                data: Some(serde_json::json!({
+                    "amount_tokens": amount,
+                    "token_signature": signature,
+                    "status": "confirmed"
+                })),
+            }))
+        }
+        SendOutcome::Failed(reason) => {
+            if let Err(e) = revert_releases(&data.db, &signature, &audit).await {
+                eprintln!("Failed to revert vesting release {}: {}", signature, e);
+            }
+
+            Ok(internal_error(format!("Token transfer failed: {}", reason)))
+        }
+        SendOutcome::Unknown => Ok(HttpResponse::Accepted().json(ApiResponse {
+            success: true,
+            message: "Claim submitted; confirmation pending".to_string(),
+            data: Some(serde_json::json!({
+                "amount_tokens": amount,
+                "token_signature": signature,
+                "status": "pending"
+            })),
+        })),
+    }
+}
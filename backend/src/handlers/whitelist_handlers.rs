@@ -0,0 +1,405 @@
+use actix_web::{web, HttpResponse, Result as ActixResult};
+use uuid::Uuid;
+use validator::Validate;
+
+use crate::handlers::{auth_rejection_response, AdminAuth, Operators, RequestMeta, Reviewers, WalletAuth};
+use crate::models::*;
+use crate::services::*;
+use crate::utils::*;
+use crate::{ApiResponse, AppState};
+
+/// Whitelist tiers and the rules each buys under
+pub async fn get_whitelist_tiers(data: web::Data<AppState>) -> ActixResult<HttpResponse> {
+    match list_whitelist_tiers(&data.db).await {
+        Ok(tiers) => Ok(HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            message: "Whitelist tiers".to_string(),
+            data: Some(tiers),
+        })),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Database error: {}", e),
+            data: None,
+        })),
+    }
+}
+
+/// Create or update a tier; its members pick up the change immediately
+pub async fn save_whitelist_tier(
+    admin: AdminAuth<Operators>,
+    meta: RequestMeta,
+    path: web::Path<i32>,
+    req: web::Json<UpsertWhitelistTierRequest>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let tier = path.into_inner();
+    if tier < 0 {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()> {
+            success: false,
+            message: "Tier can't be negative".to_string(),
+            data: None,
+        }));
+    }
+    if let Err(e) = req.check() {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Validation error: {}", e),
+            data: None,
+        }));
+    }
+
+    let audit = meta.audit(AuditActor::Admin(admin.admin.id));
+    match upsert_whitelist_tier(&data.db, tier, &req, &audit).await {
+        Ok((tier, updated_entries)) => {
+            refresh_merkle_after_change(&data).await;
+            Ok(HttpResponse::Ok().json(ApiResponse {
+                success: true,
+                message: "Whitelist tier saved".to_string(),
+                data: Some(serde_json::json!({
+                    "tier": tier,
+                    "updated_entries": updated_entries,
+                })),
+            }))
+        }
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Database error: {}", e),
+            data: None,
+        })),
+    }
+}
+
+/// Snapshot the Merkle tree now rather than on the worker's next run
+async fn refresh_merkle_after_change(data: &AppState) {
+    if let Err(e) = refresh_whitelist_merkle(&data.db, &data.solana_service).await {
+        eprintln!("Whitelist Merkle refresh failed: {}", e);
+    }
+}
+
+fn application_error_response(e: anyhow::Error) -> HttpResponse {
+    match e.downcast_ref::<ApplicationRejection>() {
+        Some(rejection) => {
+            let mut response = match rejection {
+                ApplicationRejection::NotFound => HttpResponse::NotFound(),
+                ApplicationRejection::UnknownTier => HttpResponse::BadRequest(),
+                _ => HttpResponse::Conflict(),
+            };
+            response.json(ApiResponse {
+                success: false,
+                message: rejection.to_string(),
+                data: Some(serde_json::json!({ "code": rejection.code() })),
+            })
+        }
+        None => HttpResponse::InternalServerError().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Whitelist error: {}", e),
+            data: None,
+        }),
+    }
+}
+
+/// Submit a whitelist application with answers and social handles. The
+/// caller must be signed in as the applying wallet.
+pub async fn apply_whitelist(
+    auth: WalletAuth,
+    meta: RequestMeta,
+    req: web::Json<WhitelistApplicationRequest>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    if let Err(validation_errors) = req.validate() {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Validation error: {:?}", validation_errors),
+            data: None,
+        }));
+    }
+    if req.answers.as_ref().is_some_and(|answers| !answers.is_object()) {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()> {
+            success: false,
+            message: "answers must be an object of question to answer".to_string(),
+            data: None,
+        }));
+    }
+
+    if let Err(rejection) = auth.require_wallet(&req.wallet_address) {
+        return Ok(auth_rejection_response(&rejection));
+    }
+
+    let user = match get_or_create_user(&data.db, &req.wallet_address).await {
+        Ok(user) => user,
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                success: false,
+                message: format!("User error: {}", e),
+                data: None,
+            }));
+        }
+    };
+
+    let audit = meta.audit(AuditActor::User(user.wallet_address.clone()));
+    match submit_whitelist_application(&data.db, &user, &req, &audit).await {
+        Ok(application) => Ok(HttpResponse::Created().json(ApiResponse {
+            success: true,
+            message: "Whitelist application submitted".to_string(),
+            data: Some(application),
+        })),
+        Err(e) => Ok(application_error_response(e)),
+    }
+}
+
+/// Where a wallet's latest application stands
+pub async fn get_whitelist_application_status(
+    _auth: WalletAuth,
+    path: web::Path<String>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let user = match find_user_by_wallet(&data.db, &path.into_inner()).await {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            return Ok(HttpResponse::NotFound().json(ApiResponse::<()> {
+                success: false,
+                message: "User not found".to_string(),
+                data: None,
+            }));
+        }
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                success: false,
+                message: format!("User error: {}", e),
+                data: None,
+            }));
+        }
+    };
+
+    match latest_whitelist_application(&data.db, &user.id).await {
+        Ok(application) => Ok(HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            message: match &application {
+                Some(application) => format!("Application {}", application.status),
+                None => "No whitelist application".to_string(),
+            },
+            data: Some(serde_json::json!({
+                "is_whitelisted": user.is_whitelisted,
+                "whitelist_tier": user.whitelist_tier,
+                "status": application.as_ref().map(|application| &application.status),
+                "requested_tier": application.as_ref().and_then(|application| application.requested_tier),
+                "approved_tier": application.as_ref().and_then(|application| application.approved_tier),
+                "approved_allocation": application.as_ref().and_then(|application| application.approved_allocation),
+                "rejection_reason": application.as_ref().and_then(|application| application.rejection_reason.clone()),
+                "submitted_at": application.as_ref().map(|application| application.submitted_at),
+                "decided_at": application.as_ref().and_then(|application| application.decided_at),
+            })),
+        })),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Database error: {}", e),
+            data: None,
+        })),
+    }
+}
+
+/// Applications for review, filtered by `status` and `tier`
+pub async fn list_applications(
+    _admin: AdminAuth,
+    query: web::Query<WhitelistApplicationQuery>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    match list_whitelist_applications(&data.db, &query).await {
+        Ok(applications) => Ok(HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            message: format!("{} applications", applications.len()),
+            data: Some(applications),
+        })),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Database error: {}", e),
+            data: None,
+        })),
+    }
+}
+
+pub async fn review_application(
+    admin: AdminAuth<Reviewers>,
+    meta: RequestMeta,
+    path: web::Path<Uuid>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    let audit = meta.audit(AuditActor::Admin(admin.admin.id));
+    match start_application_review(&data.db, &path.into_inner(), &audit).await {
+        Ok(application) => Ok(HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            message: "Application under review".to_string(),
+            data: Some(application),
+        })),
+        Err(e) => Ok(application_error_response(e)),
+    }
+}
+
+pub async fn approve_application(
+    admin: AdminAuth<Reviewers>,
+    meta: RequestMeta,
+    path: web::Path<Uuid>,
+    req: web::Json<ApproveApplicationRequest>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    if req.max_allocation.is_some_and(|allocation| allocation < rust_decimal::Decimal::ZERO) {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()> {
+            success: false,
+            message: "max_allocation can't be negative".to_string(),
+            data: None,
+        }));
+    }
+
+    let audit = meta.audit(AuditActor::Admin(admin.admin.id));
+    match approve_whitelist_application(&data.db, &path.into_inner(), &req, &audit).await {
+        Ok(application) => {
+            refresh_merkle_after_change(&data).await;
+            Ok(HttpResponse::Ok().json(ApiResponse {
+                success: true,
+                message: "Application approved".to_string(),
+                data: Some(application),
+            }))
+        }
+        Err(e) => Ok(application_error_response(e)),
+    }
+}
+
+pub async fn reject_application(
+    admin: AdminAuth<Reviewers>,
+    meta: RequestMeta,
+    path: web::Path<Uuid>,
+    req: web::Json<RejectApplicationRequest>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    if let Err(validation_errors) = req.validate() {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Validation error: {:?}", validation_errors),
+            data: None,
+        }));
+    }
+
+    let audit = meta.audit(AuditActor::Admin(admin.admin.id));
+    match reject_whitelist_application(&data.db, &path.into_inner(), req.reason.trim(), &audit).await {
+        Ok(application) => Ok(HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            message: "Application rejected".to_string(),
+            data: Some(application),
+        })),
+        Err(e) => Ok(application_error_response(e)),
+    }
+}
+
+/// Largest CSV body the import endpoint accepts
+pub const WHITELIST_IMPORT_MAX_BYTES: usize = 10 * 1024 * 1024;
+
+/// Bulk import from a CSV body. Nothing is saved unless every row is valid;
+/// with `?dry_run=true` nothing is saved either way.
+pub async fn import_whitelist(
+    admin: AdminAuth<Operators>,
+    meta: RequestMeta,
+    query: web::Query<WhitelistImportQuery>,
+    body: web::Bytes,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    if body.is_empty() {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()> {
+            success: false,
+            message: "Empty CSV body".to_string(),
+            data: None,
+        }));
+    }
+
+    let audit = meta.audit(AuditActor::Admin(admin.admin.id));
+    match import_whitelist_csv(&data.db, &body, query.dry_run, &audit).await {
+        Ok(report) if !report.errors.is_empty() => Ok(HttpResponse::UnprocessableEntity().json(ApiResponse {
+            success: false,
+            message: format!("{} invalid rows, nothing imported", report.errors.len()),
+            data: Some(report),
+        })),
+        Ok(report) => {
+            if report.committed {
+                refresh_merkle_after_change(&data).await;
+            }
+            Ok(HttpResponse::Ok().json(ApiResponse {
+                success: true,
+                message: if report.dry_run {
+                    format!("Dry run: {} rows valid", report.rows)
+                } else {
+                    format!("Imported {} rows", report.rows)
+                },
+                data: Some(report),
+            }))
+        }
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Import failed, nothing imported: {}", e),
+            data: None,
+        })),
+    }
+}
+
+/// All whitelist entries as a CSV download
+pub async fn export_whitelist(_admin: AdminAuth<Operators>, data: web::Data<AppState>) -> ActixResult<HttpResponse> {
+    match export_whitelist_csv(&data.db).await {
+        Ok(csv) => Ok(HttpResponse::Ok()
+            .content_type("text/csv; charset=utf-8")
+            .insert_header((
+                "Content-Disposition",
+                format!("attachment; filename=\"whitelist-{}.csv\"", chrono::Utc::now().format("%Y%m%d-%H%M%S")),
+            ))
+            .body(csv)),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Export failed: {}", e),
+            data: None,
+        })),
+    }
+}
+
+/// Root of the latest whitelist Merkle tree version
+pub async fn get_whitelist_merkle_root(data: web::Data<AppState>) -> ActixResult<HttpResponse> {
+    match latest_whitelist_merkle_version(&data.db).await {
+        Ok(Some(version)) => Ok(HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            message: format!("Whitelist Merkle root v{}", version.version),
+            data: Some(version),
+        })),
+        Ok(None) => Ok(HttpResponse::NotFound().json(ApiResponse::<()> {
+            success: false,
+            message: "No whitelist Merkle tree yet".to_string(),
+            data: None,
+        })),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Database error: {}", e),
+            data: None,
+        })),
+    }
+}
+
+/// A wallet's allocation leaf and Merkle proof, in the latest tree version
+/// or the one given by `?version=`
+pub async fn get_whitelist_proof(
+    path: web::Path<String>,
+    query: web::Query<WhitelistProofQuery>,
+    data: web::Data<AppState>,
+) -> ActixResult<HttpResponse> {
+    match whitelist_merkle_proof(&data.db, &path.into_inner(), query.version).await {
+        Ok(Some(proof)) => Ok(HttpResponse::Ok().json(ApiResponse {
+            success: true,
+            message: format!("Proof against root v{}", proof.version),
+            data: Some(proof),
+        })),
+        Ok(None) => Ok(HttpResponse::NotFound().json(ApiResponse::<()> {
+            success: false,
+            message: "Wallet not in the whitelist Merkle tree".to_string(),
+            data: None,
+        })),
+        Err(e) => Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+            success: false,
+            message: format!("Proof error: {}", e),
+            data: None,
+        })),
+    }
+}
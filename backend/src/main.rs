@@ -1,15 +1,16 @@
 use actix_web::{
-    web, App, HttpServer, Responder, HttpResponse, middleware::Logger,
+    web, App, HttpServer, HttpResponse, middleware::Logger,
     middleware::DefaultHeaders, Result as ActixResult
 };
 use actix_files::Files;
 use actix_governor::{Governor, GovernorConfigBuilder};
-use serde::{Deserialize, Serialize};
-use sqlx::{PgPool, Row};
+use serde::Serialize;
+use sqlx::PgPool;
 use std::env;
-use uuid::Uuid;
+use std::path::PathBuf;
 use validator::Validate;
 use chrono::Utc;
+use rust_decimal::Decimal;
 
 mod models;
 mod handlers;
@@ -32,6 +33,9 @@ struct ApiResponse<T = serde_json::Value> {
 struct AppState {
     db: PgPool,
     solana_service: SolanaService,
+    settings: SettingsService,
+    leaderboards: LeaderboardCache,
+    denylist: DenylistService,
 }
 
 // Health check endpoint with database status
@@ -62,8 +66,68 @@ async fn health(data: web::Data<AppState>) -> ActixResult<HttpResponse> {
     }))
 }
 
+fn rule_violation_response(violation: &RuleViolation) -> HttpResponse {
+    HttpResponse::Forbidden().json(ApiResponse {
+        success: false,
+        message: violation.to_string(),
+        data: Some(serde_json::json!(violation)),
+    })
+}
+
+fn screening_error_response(e: &anyhow::Error) -> Option<HttpResponse> {
+    let rejection = e.downcast_ref::<ScreeningRejection>()?;
+    Some(HttpResponse::Forbidden().json(ApiResponse {
+        success: false,
+        message: rejection.to_string(),
+        data: Some(serde_json::json!({ "code": rejection.code() })),
+    }))
+}
+
+fn whitelist_error_response(e: &anyhow::Error) -> HttpResponse {
+    HttpResponse::Forbidden().json(ApiResponse {
+        success: false,
+        message: format!("Whitelist error: {}", e),
+        data: e
+            .downcast_ref::<WhitelistRejection>()
+            .map(|rejection| serde_json::json!({ "code": rejection.code() })),
+    })
+}
+
+fn payment_processed_response() -> HttpResponse {
+    HttpResponse::Conflict().json(ApiResponse {
+        success: false,
+        message: "Payment already processed".to_string(),
+        data: Some(serde_json::json!({ "code": "payment_already_processed" })),
+    })
+}
+
+/// Turn away a payment that already landed, queueing all of it to be sent
+/// back; `response` is what the buyer is told once the refund is recorded
+async fn reject_payment(
+    data: &AppState,
+    req: &CreateTransactionRequest,
+    paid_sol: Decimal,
+    reason: &str,
+    audit: &AuditContext,
+    response: HttpResponse,
+) -> HttpResponse {
+    match refund_rejected_payment(&data.db, &req.signature, &req.buyer, paid_sol, reason, audit).await {
+        Ok(Some(_)) => response,
+        Ok(None) => payment_processed_response(),
+        Err(e) => {
+            log::error!("Failed to queue refund of rejected payment {}: {}", req.signature, e);
+            HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                success: false,
+                message: "Failed to record refund".to_string(),
+                data: None,
+            })
+        }
+    }
+}
+
 // Production purchase confirmation with real SPL token transfer
 async fn confirm_purchase(
+    meta: RequestMeta,
     req: web::Json<CreateTransactionRequest>,
     data: web::Data<AppState>,
 ) -> ActixResult<HttpResponse> {
@@ -76,8 +140,35 @@ async fn confirm_purchase(
         }));
     }
 
-    println!("Processing purchase: signature={}, buyer={}, amount={}", 
-             req.signature, req.buyer, req.amount);
+    log::debug!("Processing purchase {}", req.signature);
+
+    // Denylisted buyers are refused before a user is created for them
+    if let Err(e) = data.denylist.screen(&data.db, &req.buyer, ScreeningRole::Buyer, "purchase", Some(&req.signature)).await {
+        return Ok(screening_error_response(&e).unwrap_or_else(|| {
+            HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                success: false,
+                message: format!("Screening error: {}", e),
+                data: None,
+            })
+        }));
+    }
+
+    // A payment is sold against or refunded once
+    let processed = match data.db.acquire().await {
+        Ok(mut conn) => payment_processed(&mut conn, &req.signature).await,
+        Err(e) => Err(e.into()),
+    };
+    match processed {
+        Ok(false) => {}
+        Ok(true) => return Ok(payment_processed_response()),
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                success: false,
+                message: format!("Payment lookup error: {}", e),
+                data: None,
+            }));
+        }
+    }
 
     // Get or create user
     let user = match get_or_create_user(&data.db, &req.buyer).await {
@@ -91,79 +182,430 @@ async fn confirm_purchase(
         }
     };
 
+    // Verify Solana transaction. The buyer has paid by now, so every
+    // rejection from here on queues the payment to be refunded.
+    let verified_tx = match data.solana_service.verify_transaction(&req.signature, &req.buyer).await {
+        Ok(verified_tx) => verified_tx,
+        Err(e) => {
+            return Ok(HttpResponse::BadRequest().json(ApiResponse::<()> {
+                success: false,
+                message: format!("Transaction verification failed: {}", e),
+                data: None,
+            }));
+        }
+    };
+    let paid_sol = Decimal::from(verified_tx.lamports) / Decimal::from(1_000_000_000u64);
+    let audit = meta.audit(AuditActor::User(req.buyer.clone()));
+
+    let settings = data.settings.current();
+    let requested = Decimal::from_f64_retain(req.amount).unwrap_or_default().round_dp(8);
+
     // Check whitelist if enabled
-    if let Err(e) = check_whitelist_eligibility(&data.db, &user, req.amount).await {
-        return Ok(HttpResponse::Forbidden().json(ApiResponse::<()> {
+    if let Err(e) = check_whitelist_eligibility(&data.db, &settings, &user, requested).await {
+        return Ok(match e.downcast_ref::<WhitelistRejection>() {
+            Some(rejection) => {
+                reject_payment(&data, &req, paid_sol, rejection.code(), &audit, whitelist_error_response(&e)).await
+            }
+            None => whitelist_error_response(&e),
+        });
+    }
+
+    // The buyer's tier adjusts the limits, window, price and vesting
+    let tier = match get_buyer_tier(&data.db, &user).await {
+        Ok(tier) => tier,
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                success: false,
+                message: format!("Whitelist error: {}", e),
+                data: None,
+            }));
+        }
+    };
+
+    let rules = PresaleRules::from_settings(&settings).for_tier(tier.as_ref());
+
+    let wallet_purchased = match data.db.acquire().await {
+        Ok(mut conn) => wallet_purchased_total(&mut conn, &user.id, false).await,
+        Err(e) => Err(e.into()),
+    };
+    let wallet_purchased = match wallet_purchased {
+        Ok(total) => total,
+        Err(e) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
+                success: false,
+                message: format!("Presale rules error: {}", e),
+                data: None,
+            }));
+        }
+    };
+
+    if let Err(violation) = rules
+        .check_amount(requested, wallet_purchased)
+        .and_then(|_| rules.check_kyc(&user.kyc_status, user.whitelist_tier, requested, wallet_purchased))
+    {
+        return Ok(reject_payment(&data, &req, paid_sol, violation.code(), &audit, rule_violation_response(&violation)).await);
+    }
+
+    // The window is judged by when the payment landed on-chain
+    let paid_at = match rules.check_window(verified_tx.block_time) {
+        Ok(paid_at) => paid_at,
+        Err(violation) => {
+            return Ok(reject_payment(&data, &req, paid_sol, violation.code(), &audit, rule_violation_response(&violation)).await);
+        }
+    };
+
+    // Funds moved from a listed address in the same transaction flag the
+    // buyer for review rather than block the purchase
+    let mut screening_flagged = false;
+    if settings.screen_payment_sources {
+        match data.denylist.flag_payment_sources(&data.db, &user, &verified_tx.debited, &req.signature).await {
+            Ok(listed) => screening_flagged = !listed.is_empty(),
+            Err(e) => log::error!("Failed to screen payment sources of {}: {}", req.signature, e),
+        }
+    }
+
+    // USD-priced rounds are converted at the latest price recorded before the
+    // payment landed; the oracle's price by now may have moved since
+    let oracle_price = match (settings.pricing_currency, settings.oracle_price_account) {
+        (PricingCurrency::Usd, Some(price_account)) => {
+            let oracle_price = latest_price_quote(&data.db, &price_account.to_string(), paid_at).await
+                .and_then(|quote| {
+                    let price = quote
+                        .ok_or_else(|| anyhow::anyhow!("No oracle price was recorded before the payment"))?
+                        .oracle_price();
+                    price.check_usable(
+                        paid_at.timestamp(),
+                        settings.oracle_max_staleness_secs,
+                        settings.oracle_max_confidence_bps,
+                    )?;
+                    Ok(price)
+                });
+            match oracle_price {
+                Ok(price) => Some(price),
+                Err(e) => {
+                    let violation = RuleViolation::OraclePriceUnavailable { reason: e.to_string() };
+                    return Ok(reject_payment(&data, &req, paid_sol, violation.code(), &audit, rule_violation_response(&violation)).await);
+                }
+            }
+        }
+        _ => None,
+    };
+
+    let internal_error = |message: &str| {
+        HttpResponse::InternalServerError().json(ApiResponse::<()> {
             success: false,
-            message: format!("Whitelist error: {}", e),
+            message: message.to_string(),
             data: None,
-        }));
-    }
+        })
+    };
 
-    // Verify Solana transaction
-    match data.solana_service.verify_transaction(&req.signature, &req.buyer, req.amount).await {
-        Ok(verified_tx) => {
-            // Create transaction record
-            let transaction = create_transaction(&data.db, &user.id, &req).await
-                .map_err(|e| {
-                    eprintln!("Failed to create transaction: {}", e);
-                    HttpResponse::InternalServerError().json(ApiResponse::<()> {
-                        success: false,
-                        message: "Failed to record transaction".to_string(),
-                        data: None,
-                    })
-                })?;
-
-            // Transfer SPL tokens to buyer
-            match data.solana_service.transfer_tokens(&req.buyer, req.amount).await {
-                Ok(token_signature) => {
-                    // Update transaction as successful
-                    let _ = update_transaction_status(
-                        &data.db, 
-                        &transaction.id, 
-                        "confirmed", 
-                        Some(verified_tx.slot as i64)
-                    ).await;
-
-                    // Process referral bonus if applicable
-                    if let Some(referrer_id) = user.referred_by {
-                        let _ = process_referral_bonus(&data.db, &referrer_id, req.amount).await;
-                    }
-
-                    Ok(HttpResponse::Ok().json(ApiResponse {
-                        success: true,
-                        message: format!("Successfully purchased {} SBT tokens!", req.amount),
-                        data: Some(serde_json::json!({
-                            "transaction_id": transaction.id,
-                            "payment_signature": req.signature,
-                            "token_signature": token_signature,
-                            "buyer": req.buyer,
-                            "amount_tokens": req.amount,
-                            "amount_sol": req.amount * 0.000045, // Get from settings
-                            "status": "confirmed"
-                        })),
-                    }))
+    // A referral code is attributed on the wallet's first (verified) purchase
+    // only, so this purchase already earns the referrer a reward
+    let mut referral_error = None;
+    let referral_code = match &req.referral_code {
+        Some(code) => match data.denylist.screen_referral_code(&data.db, code, "purchase").await {
+            Ok(()) => Some(code),
+            Err(e) => {
+                match e.downcast_ref::<ScreeningRejection>() {
+                    Some(rejection) => referral_error = Some(rejection.code()),
+                    None => log::error!("Failed to screen referral code for purchase {}: {}", req.signature, e),
                 }
-                Err(e) => {
-                    // Update transaction as failed
-                    let _ = update_transaction_status(&data.db, &transaction.id, "failed", None).await;
-                    
-                    Ok(HttpResponse::InternalServerError().json(ApiResponse::<()> {
-                        success: false,
-                        message: format!("Token transfer failed: {}", e),
-                        data: None,
-                    }))
+                None
+            }
+        },
+        None => None,
+    };
+
+    let caps = SupplyCaps::from_settings(&settings);
+
+    // Allocate rounds, reserve supply and record the purchase in one database
+    // transaction so concurrent confirmations can never oversell a round or
+    // the hard cap
+    let mut db_tx = match data.db.begin().await {
+        Ok(db_tx) => db_tx,
+        Err(e) => {
+            log::error!("Failed to start database transaction: {}", e);
+            return Ok(internal_error("Failed to record transaction"));
+        }
+    };
+
+    // Held until the commit, so a concurrent confirmation of the same payment
+    // can't refund it while it is being sold against
+    match lock_payment(&mut db_tx, &req.signature).await {
+        Ok(false) => {}
+        Ok(true) => return Ok(payment_processed_response()),
+        Err(e) => {
+            log::error!("Failed to lock payment {}: {}", req.signature, e);
+            return Ok(internal_error("Failed to record transaction"));
+        }
+    }
+
+    // Attributed in the purchase's transaction so the referral and the
+    // reward it earns commit or roll back together
+    let mut user = user;
+    if let Some(code) = referral_code {
+        match attribute_referral_in(&mut db_tx, &user.id, code).await {
+            Ok(attributed_user) => user = attributed_user,
+            Err(e) => match e.downcast_ref::<ReferralRejection>() {
+                Some(rejection) => referral_error = Some(rejection.code()),
+                None => {
+                    log::error!("Failed to attribute referral for purchase {}: {}", req.signature, e);
+                    return Ok(internal_error("Failed to record transaction"));
                 }
+            },
+        }
+    }
+
+    // Re-check the wallet cap under a lock on the buyer's row so parallel
+    // purchases from one wallet can't each pass against the same total
+    let wallet_purchased = match wallet_purchased_total(&mut db_tx, &user.id, true).await {
+        Ok(total) => total,
+        Err(e) => {
+            log::error!("Failed to load wallet purchase total: {}", e);
+            return Ok(internal_error("Failed to record transaction"));
+        }
+    };
+    if let Err(violation) = rules
+        .check_amount(requested, wallet_purchased)
+        .and_then(|_| rules.check_kyc(&user.kyc_status, user.whitelist_tier, requested, wallet_purchased))
+    {
+        // Rolled back first: the refund takes the payment's lock
+        let _ = db_tx.rollback().await;
+        return Ok(reject_payment(&data, &req, paid_sol, violation.code(), &audit, rule_violation_response(&violation)).await);
+    }
+
+    let rounds = match lock_rounds(&mut db_tx).await {
+        Ok(rounds) => match &oracle_price {
+            Some(oracle) => rounds.into_iter().map(|round| round.priced_from_usd(oracle.price)).collect(),
+            None => rounds,
+        },
+        Err(e) => {
+            log::error!("Failed to load presale rounds: {}", e);
+            return Ok(internal_error("Failed to load presale rounds"));
+        }
+    };
+
+    let fills = match plan_round_fills(&rounds, requested, user.whitelist_tier, tier.as_ref(), paid_at) {
+        Ok(fills) => fills,
+        Err(violation) => {
+            let _ = db_tx.rollback().await;
+            return Ok(reject_payment(&data, &req, paid_sol, violation.code(), &audit, rule_violation_response(&violation)).await);
+        }
+    };
+    let quoted: Decimal = fills.iter().map(|fill| fill.cost).sum();
+    if let Err(violation) = check_payment(quoted, paid_sol) {
+        let _ = db_tx.rollback().await;
+        return Ok(reject_payment(&data, &req, paid_sol, violation.code(), &audit, rule_violation_response(&violation)).await);
+    }
+
+    let reservation = match reserve_supply(&mut db_tx, &caps, fills, requested, paid_sol).await {
+        Ok(reservation) => reservation,
+        Err(e) => {
+            if let Some(rejection) = e.downcast_ref::<CapRejection>() {
+                let _ = db_tx.rollback().await;
+                let response = HttpResponse::Conflict().json(ApiResponse {
+                    success: false,
+                    message: rejection.to_string(),
+                    data: Some(serde_json::json!({ "code": rejection.code() })),
+                });
+                return Ok(reject_payment(&data, &req, paid_sol, rejection.code(), &audit, response).await);
             }
+            log::error!("Failed to reserve supply: {}", e);
+            return Ok(internal_error("Failed to reserve supply"));
+        }
+    };
+
+    if let Err(e) = apply_round_fills(&mut db_tx, &reservation.fills).await {
+        log::error!("Failed to allocate presale rounds: {}", e);
+        return Ok(internal_error("Failed to record transaction"));
+    }
+
+    let transaction = match create_transaction(&mut db_tx, &user.id, &req, &reservation, oracle_price.as_ref()).await {
+        Ok(transaction) => transaction,
+        Err(e) => {
+            log::error!("Failed to create transaction: {}", e);
+            return Ok(internal_error("Failed to record transaction"));
+        }
+    };
+
+    // Taken under a lock on the buyer's entries, so concurrent purchases can't
+    // all spend the same remaining allocation
+    if let Err(e) = consume_whitelist_allocation(&mut db_tx, &settings, &user, &transaction.id, reservation.tokens).await {
+        if let Some(rejection) = e.downcast_ref::<WhitelistRejection>() {
+            let _ = db_tx.rollback().await;
+            return Ok(reject_payment(&data, &req, paid_sol, rejection.code(), &audit, whitelist_error_response(&e)).await);
         }
+        log::error!("Failed to consume whitelist allocation: {}", e);
+        return Ok(internal_error("Failed to record transaction"));
+    }
+
+    if let Err(e) = record_round_fills(&mut db_tx, &transaction.id, &reservation.fills).await {
+        log::error!("Failed to record round fills: {}", e);
+        return Ok(internal_error("Failed to record transaction"));
+    }
+
+    // Purchased tokens vest on their round's terms instead of being sent now
+    if let Err(e) = create_vesting_schedules(&mut db_tx, &user.id, &transaction.id, &reservation.fills, None, paid_at).await {
+        log::error!("Failed to create vesting schedules: {}", e);
+        return Ok(internal_error("Failed to record transaction"));
+    }
+
+    let referral_rewards = match record_referral_rewards(
+        &mut db_tx,
+        &settings,
+        &data.denylist,
+        &user.id,
+        &transaction.id,
+        &reservation.fills,
+        reservation.sol,
+        paid_at,
+    )
+    .await
+    {
+        Ok(rewards) => rewards,
         Err(e) => {
-            Ok(HttpResponse::BadRequest().json(ApiResponse::<()> {
-                success: false,
-                message: format!("Transaction verification failed: {}", e),
-                data: None,
-            }))
+            log::error!("Failed to record referral rewards: {}", e);
+            return Ok(internal_error("Failed to record transaction"));
+        }
+    };
+
+    if let Err(e) = update_transaction_status(&mut *db_tx, &transaction.id, "confirmed", Some(verified_tx.slot as i64)).await {
+        log::error!("Failed to confirm transaction: {}", e);
+        return Ok(internal_error("Failed to record transaction"));
+    }
+
+    // Whatever the hard cap cut off, or the buyer overpaid, is sent back by
+    // the refund worker
+    let refund = if reservation.refund_sol > Decimal::ZERO {
+        match queue_payment_refund_in(
+            &mut db_tx,
+            &req.signature,
+            &req.buyer,
+            Some(&transaction.id),
+            reservation.refund_sol,
+            if reservation.is_partial() { "partial_fill" } else { "overpayment" },
+        )
+        .await
+        {
+            Ok(refund) => Some(refund),
+            Err(e) => {
+                log::error!("Failed to queue refund: {}", e);
+                return Ok(internal_error("Failed to record transaction"));
+            }
         }
+    } else {
+        None
+    };
+
+    let entry = AuditEntry {
+        action: "purchase.confirmed",
+        entity_type: "transaction",
+        entity_id: transaction.id.to_string(),
+        before: None,
+        after: Some(serde_json::json!({
+            "user_id": user.id,
+            "payment_signature": req.signature,
+            "amount_requested": requested,
+            "amount_tokens": reservation.tokens,
+            "amount_sol": reservation.sol,
+            "refund_amount_sol": reservation.refund_sol,
+            "refund_id": refund.as_ref().map(|refund| refund.id),
+            "rounds": reservation.fills,
+            "whitelist_tier": tier.as_ref().map(|tier| tier.tier),
+            "referred_by": user.referred_by,
+            "oracle_price": oracle_price.as_ref().map(|oracle| oracle.price),
+            "referral_rewards": referral_rewards.len(),
+            "block_height": verified_tx.slot,
+            "status": "confirmed",
+        })),
+    };
+    if let Err(e) = record_audit_event_in(&mut db_tx, &audit, entry).await {
+        log::error!("Failed to audit purchase: {}", e);
+        return Ok(internal_error("Failed to record transaction"));
+    }
+
+    if let Err(e) = db_tx.commit().await {
+        log::error!("Failed to commit purchase: {}", e);
+        return Ok(internal_error("Failed to record transaction"));
+    }
+
+    let message = if reservation.is_partial() {
+        format!(
+            "Allocation exhausted: purchased {} of {} requested SBT tokens, {} SOL will be refunded",
+            reservation.tokens, requested, reservation.refund_sol
+        )
+    } else if reservation.refund_sol > Decimal::ZERO {
+        format!(
+            "Successfully purchased {} SBT tokens! {} SOL overpaid will be refunded",
+            reservation.tokens, reservation.refund_sol
+        )
+    } else {
+        format!("Successfully purchased {} SBT tokens!", reservation.tokens)
+    };
+
+    Ok(HttpResponse::Ok().json(ApiResponse {
+        success: true,
+        message,
+        data: Some(serde_json::json!({
+            "transaction_id": transaction.id,
+            "payment_signature": req.signature,
+            "buyer": req.buyer,
+            "amount_requested": requested,
+            "amount_tokens": reservation.tokens,
+            "amount_sol": reservation.sol,
+            "rounds": reservation.fills,
+            "whitelist_tier": tier.as_ref().map(|tier| &tier.name),
+            "oracle_price": oracle_price,
+            "refund_amount_sol": reservation.refund_sol,
+            "refund_id": refund.as_ref().map(|refund| refund.id),
+            "screening_flagged": screening_flagged,
+            "soft_cap_reached": reservation.soft_cap_reached,
+            "vesting": format!("/api/vesting/{}", req.buyer),
+            "referred_by": user.referred_by,
+            "referral_error": referral_error,
+            "referral_rewards": referral_rewards.len(),
+            "status": "confirmed"
+        })),
+    }))
+}
+
+/// Bootstrap the first owner. The password comes from
+/// `ADMIN_BOOTSTRAP_PASSWORD` or is read from stdin.
+async fn create_owner_command(pool: &PgPool, username: Option<&String>) -> std::io::Result<()> {
+    let invalid = |message: String| std::io::Error::new(std::io::ErrorKind::InvalidInput, message);
+
+    let Some(username) = username else {
+        return Err(invalid("Usage: create-owner <username>".to_string()));
+    };
+    let password = match env::var("ADMIN_BOOTSTRAP_PASSWORD").ok().filter(|password| !password.is_empty()) {
+        Some(password) => password,
+        None => {
+            println!("Password for {} (12 to 72 characters):", username);
+            let mut line = String::new();
+            std::io::stdin().read_line(&mut line)?;
+            line.trim_end_matches(['\r', '\n']).to_string()
+        }
+    };
+
+    let req = CreateAdminRequest {
+        username: username.clone(),
+        password,
+        role: "owner".to_string(),
+    };
+    if let Err(validation_errors) = req.validate() {
+        return Err(invalid(format!("Validation error: {:?}", validation_errors)));
     }
+
+    let created = bootstrap_owner(pool, &req.username, &req.password)
+        .await
+        .map_err(|e| invalid(e.to_string()))?;
+
+    println!("👑 Owner {} created", created.admin.username);
+    println!("   Add this TOTP secret to an authenticator app; it is not shown again:");
+    println!("   {}", created.totp_secret);
+    println!("   {}", created.totp_uri);
+
+    Ok(())
 }
 
 #[actix_web::main]
@@ -187,14 +629,50 @@ async fn main() -> std::io::Result<()> {
         .run(&pool)
         .await
         .expect("Failed to run migrations");
+
+    // `create-owner <username>` sets up the first admin account and exits
+    let args: Vec<String> = env::args().collect();
+    if args.get(1).map(String::as_str) == Some("create-owner") {
+        return create_owner_command(&pool, args.get(2)).await;
+    }
     
+    // Load presale settings and keep them fresh via LISTEN/NOTIFY
+    let settings = SettingsService::load(&pool)
+        .await
+        .expect("Failed to load presale settings");
+    settings.spawn_listener(pool.clone());
+
+    // Apply scheduled settings changes as they fall due
+    spawn_settings_scheduler(pool.clone());
+
+    // Load the sanctions denylist and keep it fresh
+    let denylist_path = env::var("DENYLIST_PATH").ok().filter(|path| !path.is_empty()).map(PathBuf::from);
+    let denylist = DenylistService::load(&pool, denylist_path)
+        .await
+        .expect("Failed to load denylist");
+    denylist.spawn_reloader(pool.clone(), settings.clone());
+
     // Initialize Solana service
     let solana_service = SolanaService::new().await
         .expect("Failed to initialize Solana service");
+
+    // Record oracle prices for converting USD-priced purchases
+    spawn_oracle_sampler(pool.clone(), solana_service.clone(), settings.clone());
+
+    // Send vested tokens to push-mode schedules and settle pending releases
+    spawn_vesting_worker(pool.clone(), solana_service.clone(), settings.clone(), denylist.clone());
+
+    // Continue a TGE thaw interrupted by a restart
+    if let Err(e) = resume_tge_thaw(&pool, &solana_service, &settings).await {
+        eprintln!("Failed to resume TGE thaw: {}", e);
+    }
     
     let app_state = AppState {
         db: pool,
         solana_service,
+        settings,
+        leaderboards: LeaderboardCache::default(),
+        denylist,
     };
     
     let port = env::var("PORT").unwrap_or_else(|_| "8080".to_string());
@@ -210,13 +688,65 @@ async fn main() -> std::io::Result<()> {
     println!("🚀 Starting Shibartum Presale Backend v2.0.0 at {}", addr);
     println!("📋 Available endpoints:");
     println!("   GET  /api/health - Health check with database status");
-    println!("   POST /api/confirm-purchase - Confirm token purchase (with real SPL tokens)");
-    println!("   GET  /api/user/:wallet - Get user profile");
-    println!("   POST /api/user/register - Register new user");
+    println!("   POST /api/confirm-purchase - Confirm token purchase (tokens vest per round)");
+    println!("   GET  /api/user/:wallet - Get user profile (wallet token)");
+    println!("   POST /api/user/register - Register new user (wallet token)");
     println!("   GET  /api/transactions/:wallet - Get user transactions");
     println!("   GET  /api/stats - Get presale statistics");
+    println!("   GET  /api/rounds - Get presale rounds");
+    println!("   GET  /api/vesting/preview - Preview the vesting timeline of a purchase");
+    println!("   GET  /api/vesting/:wallet - Get vesting schedules (wallet token)");
+    println!("   POST /api/admin/auth/login - Admin sign-in with password and TOTP code");
+    println!("   POST /api/admin/auth/logout - End the admin session (admin)");
+    println!("   GET  /api/admin/me - The signed-in admin (admin)");
+    println!("   GET  /api/admin/users - List admin accounts (owner)");
+    println!("   POST /api/admin/users - Create an admin account (owner)");
+    println!("   PUT  /api/admin/users/:id - Change an admin's role or deactivate them (owner)");
+    println!("   GET  /api/admin/settings - Presale settings and their version (admin)");
+    println!("   PUT  /api/admin/settings - Change presale settings (operator)");
+    println!("   GET  /api/admin/settings/history - Versioned settings change history (admin)");
+    println!("   GET  /api/admin/settings/schedules - Scheduled settings changes (admin)");
+    println!("   POST /api/admin/settings/schedules - Schedule a settings change (operator)");
+    println!("   DELETE /api/admin/settings/schedules/:id - Cancel a scheduled change (operator)");
+    println!("   POST /api/admin/token-accounts/:wallet/freeze - Freeze a token account (operator)");
+    println!("   POST /api/admin/token-accounts/:wallet/thaw - Thaw a token account (operator)");
+    println!("   POST /api/admin/tge/thaw - Thaw all presale token accounts for TGE (operator)");
+    println!("   GET  /api/admin/tge/thaw - TGE thaw progress (admin)");
+    println!("   PUT  /api/admin/referrers/:wallet/commission - Set a referrer's commission terms (operator)");
+    println!("   PUT  /api/admin/referrals/:wallet/rate - Set one referral's commission rate (operator)");
+    println!("   POST /api/vesting/claim - Claim vested tokens (wallet signature)");
     println!("   POST /api/whitelist/apply - Apply for whitelist");
-    println!("   GET  /api/referral/:code - Get referral info");
+    println!("   GET  /api/whitelist/tiers - Whitelist tiers and their rules");
+    println!("   GET  /api/whitelist/status/:wallet - Whitelist application status (wallet token)");
+    println!("   GET  /api/whitelist/merkle-root - Latest whitelist Merkle root");
+    println!("   GET  /api/whitelist/proof/:wallet - Merkle proof of a wallet's allocation");
+    println!("   GET  /api/admin/whitelist/applications - List applications by status and tier (admin)");
+    println!("   POST /api/admin/whitelist/applications/:id/review - Start reviewing an application (reviewer)");
+    println!("   POST /api/admin/whitelist/applications/:id/approve - Approve with a tier and allocation (reviewer)");
+    println!("   POST /api/admin/whitelist/applications/:id/reject - Reject with a reason (reviewer)");
+    println!("   PUT  /api/admin/whitelist/tiers/:tier - Create or update a whitelist tier (operator)");
+    println!("   POST /api/admin/whitelist/import - Bulk import the whitelist from CSV, ?dry_run=true to validate only (operator)");
+    println!("   GET  /api/admin/whitelist/export - Export whitelist entries as CSV (operator)");
+    println!("   POST /api/kyc/session - KYC provider session data (wallet signature)");
+    println!("   POST /api/kyc/webhook - KYC provider status webhook (HMAC signed)");
+    println!("   GET  /api/admin/kyc/:wallet - KYC status and audit trail (admin)");
+    println!("   GET  /api/admin/denylist - Denylist size and load time (admin)");
+    println!("   POST /api/admin/denylist - Add addresses to the denylist (operator)");
+    println!("   DELETE /api/admin/denylist/:address - Remove an address from the denylist (operator)");
+    println!("   POST /api/admin/denylist/reload - Reload the denylist table and file (operator)");
+    println!("   GET  /api/admin/screening/events - Blocked and flagged wallets (admin)");
+    println!("   GET  /api/admin/audit/events - Audit log by actor, action, entity and time (admin)");
+    println!("   GET  /api/admin/audit/verify - Re-hash the audit chain to detect tampering (admin)");
+    println!("   POST /api/auth/nonce - Nonce and Sign-In With Solana message for a wallet");
+    println!("   POST /api/auth/login - Sign in with the signed message, returns JWT and refresh token");
+    println!("   POST /api/auth/refresh - Rotate a refresh token for new tokens");
+    println!("   POST /api/auth/logout - Revoke a refresh token");
+    println!("   GET  /api/referral/:code - Get referral info and conversion stats");
+    println!("   POST /api/referral/:code/click - Track a referral link click");
+    println!("   POST /api/referral/code - Claim a custom referral code (wallet signature)");
+    println!("   GET  /api/referral/rewards/:wallet - Get referral rewards and their vesting (wallet token)");
+    println!("   GET  /api/leaderboard/referrers - Top referrers (paginated)");
+    println!("   GET  /api/leaderboard/contributors - Top contributors (paginated)");
     println!("✨ Features: Real SPL tokens, Database, Rate limiting, Whitelist, Referrals");
     
     HttpServer::new(move || {
@@ -233,12 +763,84 @@ async fn main() -> std::io::Result<()> {
             // API routes
             .service(web::resource("/api/health").route(web::get().to(health)))
             .service(web::resource("/api/confirm-purchase").route(web::post().to(confirm_purchase)))
+            .service(web::resource("/api/auth/nonce").route(web::post().to(auth_nonce)))
+            .service(web::resource("/api/auth/login").route(web::post().to(auth_login)))
+            .service(web::resource("/api/auth/refresh").route(web::post().to(auth_refresh)))
+            .service(web::resource("/api/auth/logout").route(web::post().to(auth_logout)))
             .service(web::resource("/api/user/register").route(web::post().to(register_user)))
             .service(web::resource("/api/user/{wallet}").route(web::get().to(get_user)))
             .service(web::resource("/api/transactions/{wallet}").route(web::get().to(get_user_transactions)))
             .service(web::resource("/api/stats").route(web::get().to(get_presale_stats)))
+            .service(web::resource("/api/rounds").route(web::get().to(get_rounds)))
+            .service(web::resource("/api/rounds/quote").route(web::get().to(get_price_quote)))
+            .service(web::resource("/api/vesting/claim").route(web::post().to(claim_vesting)))
+            .service(web::resource("/api/vesting/preview").route(web::get().to(preview_vesting)))
+            .service(web::resource("/api/vesting/{wallet}").route(web::get().to(get_vesting)))
+            .service(web::resource("/api/admin/auth/login").route(web::post().to(login_admin)))
+            .service(web::resource("/api/admin/auth/logout").route(web::post().to(logout_admin)))
+            .service(web::resource("/api/admin/me").route(web::get().to(get_admin_me)))
+            .service(
+                web::resource("/api/admin/users")
+                    .route(web::get().to(list_admins))
+                    .route(web::post().to(create_admin)),
+            )
+            .service(web::resource("/api/admin/users/{id}").route(web::put().to(update_admin)))
+            .service(
+                web::resource("/api/admin/settings")
+                    .route(web::get().to(get_settings))
+                    .route(web::put().to(update_settings)),
+            )
+            .service(web::resource("/api/admin/settings/history").route(web::get().to(get_settings_history)))
+            .service(
+                web::resource("/api/admin/settings/schedules")
+                    .route(web::get().to(get_settings_schedules))
+                    .route(web::post().to(schedule_settings)),
+            )
+            .service(web::resource("/api/admin/settings/schedules/{id}").route(web::delete().to(cancel_settings_change)))
+            .service(web::resource("/api/admin/token-accounts/{wallet}/freeze").route(web::post().to(freeze_token_account)))
+            .service(web::resource("/api/admin/token-accounts/{wallet}/thaw").route(web::post().to(thaw_token_account)))
+            .service(
+                web::resource("/api/admin/tge/thaw")
+                    .route(web::post().to(start_tge))
+                    .route(web::get().to(get_tge_status))
+            )
+            .service(web::resource("/api/admin/referrers/{wallet}/commission").route(web::put().to(set_referrer_terms)))
+            .service(web::resource("/api/admin/referrals/{wallet}/rate").route(web::put().to(set_referral_terms)))
+            .service(web::resource("/api/kyc/session").route(web::post().to(start_kyc_session)))
+            .service(web::resource("/api/kyc/webhook").route(web::post().to(kyc_webhook)))
+            .service(web::resource("/api/admin/kyc/{wallet}").route(web::get().to(get_kyc_events)))
+            .service(
+                web::resource("/api/admin/denylist")
+                    .route(web::get().to(get_denylist))
+                    .route(web::post().to(add_to_denylist)),
+            )
+            .service(web::resource("/api/admin/denylist/reload").route(web::post().to(reload_denylist)))
+            .service(web::resource("/api/admin/denylist/{address}").route(web::delete().to(remove_from_denylist)))
+            .service(web::resource("/api/admin/screening/events").route(web::get().to(get_screening_events)))
+            .service(web::resource("/api/admin/audit/events").route(web::get().to(get_audit_events)))
+            .service(web::resource("/api/admin/audit/verify").route(web::get().to(verify_audit_log)))
             .service(web::resource("/api/whitelist/apply").route(web::post().to(apply_whitelist)))
+            .service(web::resource("/api/whitelist/tiers").route(web::get().to(get_whitelist_tiers)))
+            .service(web::resource("/api/whitelist/status/{wallet}").route(web::get().to(get_whitelist_application_status)))
+            .service(web::resource("/api/whitelist/merkle-root").route(web::get().to(get_whitelist_merkle_root)))
+            .service(web::resource("/api/whitelist/proof/{wallet}").route(web::get().to(get_whitelist_proof)))
+            .service(web::resource("/api/admin/whitelist/applications").route(web::get().to(list_applications)))
+            .service(web::resource("/api/admin/whitelist/applications/{id}/review").route(web::post().to(review_application)))
+            .service(web::resource("/api/admin/whitelist/applications/{id}/approve").route(web::post().to(approve_application)))
+            .service(web::resource("/api/admin/whitelist/applications/{id}/reject").route(web::post().to(reject_application)))
+            .service(web::resource("/api/admin/whitelist/tiers/{tier}").route(web::put().to(save_whitelist_tier)))
+            .service(
+                web::resource("/api/admin/whitelist/import")
+                    .app_data(web::PayloadConfig::new(WHITELIST_IMPORT_MAX_BYTES))
+                    .route(web::post().to(import_whitelist)),
+            )
+            .service(web::resource("/api/admin/whitelist/export").route(web::get().to(export_whitelist)))
+            .service(web::resource("/api/referral/rewards/{wallet}").route(web::get().to(get_referrer_rewards)))
+            .service(web::resource("/api/referral/code").route(web::post().to(claim_vanity_code)))
+            .service(web::resource("/api/referral/{code}/click").route(web::post().to(track_referral_click)))
             .service(web::resource("/api/referral/{code}").route(web::get().to(get_referral_info)))
+            .service(web::resource("/api/leaderboard/referrers").route(web::get().to(get_referrer_leaderboard)))
+            .service(web::resource("/api/leaderboard/contributors").route(web::get().to(get_contributor_leaderboard)))
             // Serve static files (frontend build)
             .service(Files::new("/", "./frontend/dist").index_file("index.html"))
     })
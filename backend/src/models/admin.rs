@@ -0,0 +1,68 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use validator::Validate;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct AdminUser {
+    pub id: Uuid,
+    pub username: String,
+    #[serde(skip_serializing)]
+    pub password_hash: String,
+    pub role: String, // owner, operator, reviewer, support
+    #[serde(skip_serializing)]
+    pub totp_secret: String,
+    #[serde(skip_serializing)]
+    pub totp_last_step: Option<i64>,
+    pub is_active: bool,
+    pub failed_logins: i32,
+    pub locked_until: Option<DateTime<Utc>>,
+    pub last_login_at: Option<DateTime<Utc>>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct AdminLoginRequest {
+    #[validate(length(min = 1, max = 50))]
+    pub username: String,
+    #[validate(length(min = 1, max = 72))]
+    pub password: String,
+    /// Current code from the authenticator app
+    #[validate(length(equal = 6))]
+    pub totp_code: String,
+}
+
+/// A signed-in admin's bearer token
+#[derive(Debug, Clone, Serialize)]
+pub struct AdminSession {
+    pub token: String,
+    pub expires_at: DateTime<Utc>,
+    pub admin: AdminUser,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateAdminRequest {
+    #[validate(length(min = 3, max = 50))]
+    pub username: String,
+    /// bcrypt only uses the first 72 bytes
+    #[validate(length(min = 12, max = 72))]
+    pub password: String,
+    pub role: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateAdminRequest {
+    pub role: Option<String>,
+    pub is_active: Option<bool>,
+}
+
+/// A new admin account with its TOTP secret, which is not shown again
+#[derive(Debug, Clone, Serialize)]
+pub struct CreatedAdmin {
+    pub admin: AdminUser,
+    pub totp_secret: String,
+    /// `otpauth://` URI for authenticator apps (usually shown as a QR code)
+    pub totp_uri: String,
+}
@@ -0,0 +1,52 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct AuditEvent {
+    pub id: i64,
+    pub actor_type: String, // user, admin, system
+    pub actor_id: String,
+    pub action: String,
+    pub entity_type: String,
+    pub entity_id: String,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+    pub request_id: Option<String>,
+    /// `X-Request-Id` as sent by the caller, unverified
+    pub client_request_id: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub prev_hash: String,
+    pub hash: String,
+}
+
+/// Filters for the audit log; events come newest first, and `before_id`
+/// pages back from an earlier result
+#[derive(Debug, Deserialize)]
+pub struct AuditEventQuery {
+    pub actor_type: Option<String>,
+    pub actor_id: Option<String>,
+    pub action: Option<String>,
+    pub entity_type: Option<String>,
+    pub entity_id: Option<String>,
+    pub request_id: Option<String>,
+    pub client_request_id: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub before_id: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+/// Outcome of re-hashing the audit chain
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditChainReport {
+    pub valid: bool,
+    pub events_checked: i64,
+    /// Last event checked; publishing its hash elsewhere also makes removing
+    /// events from the end evident
+    pub head_id: Option<i64>,
+    pub head_hash: Option<String>,
+    pub broken_at: Option<i64>,
+    pub problem: Option<String>,
+}
@@ -0,0 +1,58 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct AuthNonceRequest {
+    #[validate(length(min = 32, max = 44))]
+    pub wallet_address: String,
+}
+
+/// A login nonce and the Sign-In With Solana message to sign with it
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthNonce {
+    pub nonce: String,
+    /// The exact text the wallet signs
+    pub message: String,
+    pub issued_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct WalletLoginRequest {
+    #[validate(length(min = 32, max = 44))]
+    pub wallet_address: String,
+    /// The signed SIWS message, as returned with the nonce
+    #[validate(length(min = 1, max = 2000))]
+    pub message: String,
+    /// Base58 ed25519 signature of `message`
+    #[validate(length(min = 80, max = 90))]
+    pub signature: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct RefreshTokenRequest {
+    #[validate(length(min = 1, max = 200))]
+    pub refresh_token: String,
+}
+
+/// Claims of a wallet access token
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WalletClaims {
+    /// Wallet address
+    pub sub: String,
+    pub iat: i64,
+    pub exp: i64,
+}
+
+/// Tokens issued on login and refresh
+#[derive(Debug, Clone, Serialize)]
+pub struct AuthTokens {
+    pub wallet_address: String,
+    pub access_token: String,
+    pub token_type: String,
+    /// Seconds until the access token expires
+    pub expires_in: i64,
+    pub refresh_token: String,
+    pub refresh_expires_at: DateTime<Utc>,
+}
@@ -0,0 +1,62 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use sqlx::FromRow;
+use uuid::Uuid;
+use validator::Validate;
+
+/// Status update posted by the KYC provider
+#[derive(Debug, Clone, Deserialize)]
+pub struct KycWebhookEvent {
+    pub event_id: String,
+    /// Our user id, passed to the provider when the session started
+    pub external_user_id: Uuid,
+    pub applicant_id: Option<String>,
+    pub status: String, // pending, approved, rejected
+    pub reason: Option<String>,
+    /// When the provider decided; defaults to when the event arrived
+    pub occurred_at: Option<DateTime<Utc>>,
+}
+
+/// Audit record of a webhook event
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct KycEvent {
+    pub id: Uuid,
+    pub event_id: String,
+    pub user_id: Uuid,
+    pub provider: String,
+    pub applicant_id: Option<String>,
+    pub previous_status: String,
+    pub new_status: String,
+    pub reason: Option<String>,
+    pub applied: bool,
+    pub occurred_at: DateTime<Utc>,
+    pub payload: Json<serde_json::Value>,
+    pub received_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct KycSessionRequest {
+    #[validate(length(min = 32, max = 44))]
+    pub wallet_address: String,
+}
+
+/// What the frontend needs to start the provider's verification flow
+#[derive(Debug, Clone, Serialize)]
+pub struct KycSession {
+    pub provider: String,
+    pub level: String,
+    pub external_user_id: Uuid,
+    pub applicant_id: Option<String>,
+    pub kyc_status: String,
+    /// `<expires>.<hmac>` over the user id and level, for the provider (or
+    /// a local stand-in) to check the session came from this backend
+    pub session_token: String,
+    pub expires_at: DateTime<Utc>,
+    /// Whether the wallet's tier needs approval to buy at all
+    pub required_for_tier: bool,
+    /// Cumulative tokens the wallet may buy without approval
+    pub threshold_tokens: Option<Decimal>,
+    pub purchased_tokens: Decimal,
+}
@@ -4,6 +4,14 @@ pub mod whitelist;
 pub mod referral;
 pub mod vesting;
 pub mod presale_settings;
+pub mod round;
+pub mod token_account;
+pub mod kyc;
+pub mod screening;
+pub mod auth;
+pub mod admin;
+pub mod settings_change;
+pub mod audit;
 
 pub use user::*;
 pub use transaction::*;
@@ -11,3 +19,11 @@ pub use whitelist::*;
 pub use referral::*;
 pub use vesting::*;
 pub use presale_settings::*;
+pub use round::*;
+pub use token_account::*;
+pub use kyc::*;
+pub use screening::*;
+pub use auth::*;
+pub use admin::*;
+pub use settings_change::*;
+pub use audit::*;
@@ -0,0 +1,369 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use sqlx::FromRow;
+use std::collections::HashMap;
+use std::str::FromStr;
+use uuid::Uuid;
+
+/// Raw `presale_settings` row
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct PresaleSetting {
+    pub id: Uuid,
+    pub key: String,
+    pub value: String,
+    pub description: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// What to do with a purchase that would cross the hard cap
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CapOverflowMode {
+    /// Deliver what is left and refund the SOL for the rest
+    PartialRefund,
+    /// Reject the whole purchase
+    Reject,
+}
+
+impl FromStr for CapOverflowMode {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "partial_refund" => Ok(Self::PartialRefund),
+            "reject" => Ok(Self::Reject),
+            other => Err(anyhow!("expected partial_refund or reject, got {}", other)),
+        }
+    }
+}
+
+/// Currency presale rounds are priced in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PricingCurrency {
+    /// Round `price_sol` is charged directly
+    Sol,
+    /// Round `price_usd` is converted to SOL with the latest oracle price
+    /// quoted before the payment
+    Usd,
+}
+
+impl FromStr for PricingCurrency {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "sol" => Ok(Self::Sol),
+            "usd" => Ok(Self::Usd),
+            other => Err(anyhow!("expected sol or usd, got {}", other)),
+        }
+    }
+}
+
+/// How vested tokens reach their holder
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DistributionMode {
+    /// Released when the holder claims
+    Claim,
+    /// Sent by the vesting worker
+    Push,
+}
+
+impl DistributionMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Claim => "claim",
+            Self::Push => "push",
+        }
+    }
+}
+
+impl FromStr for DistributionMode {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "claim" => Ok(Self::Claim),
+            "push" => Ok(Self::Push),
+            other => Err(anyhow!("expected claim or push, got {}", other)),
+        }
+    }
+}
+
+/// What referral commissions are paid in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CommissionCurrency {
+    /// Tokens, vesting on the purchase's round terms
+    Token,
+    /// A share of the SOL paid, sent by the distribution worker
+    Sol,
+}
+
+impl CommissionCurrency {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Token => "token",
+            Self::Sol => "sol",
+        }
+    }
+}
+
+impl FromStr for CommissionCurrency {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "token" => Ok(Self::Token),
+            "sol" => Ok(Self::Sol),
+            other => Err(anyhow!("expected token or sol, got {}", other)),
+        }
+    }
+}
+
+/// Every key [`PresaleSettings::from_map`] reads; others can't be set
+/// through the admin API.
+///
+/// Not every key is seeded by the migrations: `oracle_price_account` (006)
+/// has no sensible default and must be set before `pricing_currency` is
+/// switched to usd, and `kyc_threshold_tokens` (020) is left unset so there
+/// is no threshold until one is configured.
+pub const PRESALE_SETTING_KEYS: &[&str] = &[
+    "max_supply",
+    "presale_start",
+    "presale_end",
+    "min_purchase",
+    "max_purchase",
+    "max_wallet_purchase",
+    "whitelist_enabled",
+    "referral_bonus",
+    "referral_reward_mode",
+    "referral_upline_rates",
+    "referral_payout_currency",
+    "hard_cap_tokens",
+    "hard_cap_sol",
+    "soft_cap_sol",
+    "cap_overflow_mode",
+    "pricing_currency",
+    "oracle_price_account",
+    "oracle_max_staleness_secs",
+    "oracle_max_confidence_bps",
+    "vesting_push_interval_secs",
+    "vesting_push_batch_size",
+    "freeze_until_tge",
+    "tge_thaw_batch_size",
+    "leaderboard_cache_secs",
+    "vanity_code_min_length",
+    "vanity_code_max_length",
+    "kyc_threshold_tokens",
+    "kyc_required_tiers",
+    "kyc_provider",
+    "kyc_level",
+    "denylist_file_poll_secs",
+    "screen_payment_sources",
+];
+
+/// Typed, validated view of the `presale_settings` table.
+///
+/// This is the single source for presale configuration; nothing should read
+/// these keys from the environment or cast them in SQL.
+#[derive(Debug, Clone, Serialize)]
+pub struct PresaleSettings {
+    pub max_supply: Decimal,
+    pub presale_start: DateTime<Utc>,
+    pub presale_end: DateTime<Utc>,
+    pub min_purchase: Decimal,
+    pub max_purchase: Decimal,
+    pub max_wallet_purchase: Option<Decimal>,
+    pub whitelist_enabled: bool,
+    pub referral_bonus: Decimal,
+    pub referral_reward_mode: DistributionMode,
+    /// Commission rates for level 2 and up; empty pays direct referrers only
+    pub referral_upline_rates: Vec<Decimal>,
+    pub referral_payout_currency: CommissionCurrency,
+    pub hard_cap_tokens: Decimal,
+    pub hard_cap_sol: Decimal,
+    pub soft_cap_sol: Decimal,
+    pub cap_overflow_mode: CapOverflowMode,
+    pub pricing_currency: PricingCurrency,
+    pub oracle_price_account: Option<Pubkey>,
+    pub oracle_max_staleness_secs: i64,
+    pub oracle_max_confidence_bps: Decimal,
+    pub vesting_push_interval_secs: u64,
+    pub vesting_push_batch_size: i64,
+    pub freeze_until_tge: bool,
+    pub tge_thaw_batch_size: i64,
+    /// 0 disables leaderboard caching
+    pub leaderboard_cache_secs: u64,
+    pub vanity_code_min_length: usize,
+    pub vanity_code_max_length: usize,
+    /// Cumulative tokens a wallet may buy before KYC approval is required;
+    /// `None` sets no threshold
+    pub kyc_threshold_tokens: Option<Decimal>,
+    /// Whitelist tiers whose members need KYC approval to buy at all
+    pub kyc_required_tiers: Vec<i32>,
+    pub kyc_provider: String,
+    /// Verification level the provider's flow is started with
+    pub kyc_level: String,
+    pub denylist_file_poll_secs: u64,
+    /// Flag buyers whose payment moved funds from a listed address
+    pub screen_payment_sources: bool,
+}
+
+impl PresaleSettings {
+    pub fn from_rows(rows: &[PresaleSetting]) -> Result<Self> {
+        let values: HashMap<&str, &str> = rows
+            .iter()
+            .map(|row| (row.key.as_str(), row.value.as_str()))
+            .collect();
+        Self::from_map(&values)
+    }
+
+    pub fn from_map(values: &HashMap<&str, &str>) -> Result<Self> {
+        let settings = Self {
+            max_supply: required(values, "max_supply")?,
+            presale_start: required(values, "presale_start")?,
+            presale_end: required(values, "presale_end")?,
+            min_purchase: required(values, "min_purchase")?,
+            max_purchase: required(values, "max_purchase")?,
+            max_wallet_purchase: optional(values, "max_wallet_purchase")?,
+            whitelist_enabled: required(values, "whitelist_enabled")?,
+            referral_bonus: required(values, "referral_bonus")?,
+            referral_reward_mode: optional(values, "referral_reward_mode")?.unwrap_or(DistributionMode::Claim),
+            referral_upline_rates: list(values, "referral_upline_rates")?,
+            referral_payout_currency: optional(values, "referral_payout_currency")?
+                .unwrap_or(CommissionCurrency::Token),
+            hard_cap_tokens: required(values, "hard_cap_tokens")?,
+            hard_cap_sol: required(values, "hard_cap_sol")?,
+            soft_cap_sol: required(values, "soft_cap_sol")?,
+            cap_overflow_mode: required(values, "cap_overflow_mode")?,
+            pricing_currency: optional(values, "pricing_currency")?.unwrap_or(PricingCurrency::Sol),
+            oracle_price_account: optional(values, "oracle_price_account")?,
+            oracle_max_staleness_secs: optional(values, "oracle_max_staleness_secs")?.unwrap_or(60),
+            oracle_max_confidence_bps: optional(values, "oracle_max_confidence_bps")?
+                .unwrap_or(Decimal::from(200)),
+            vesting_push_interval_secs: optional(values, "vesting_push_interval_secs")?.unwrap_or(3600),
+            vesting_push_batch_size: optional(values, "vesting_push_batch_size")?.unwrap_or(50),
+            freeze_until_tge: optional(values, "freeze_until_tge")?.unwrap_or(false),
+            tge_thaw_batch_size: optional(values, "tge_thaw_batch_size")?.unwrap_or(20),
+            leaderboard_cache_secs: optional(values, "leaderboard_cache_secs")?.unwrap_or(60),
+            vanity_code_min_length: optional(values, "vanity_code_min_length")?.unwrap_or(4),
+            vanity_code_max_length: optional(values, "vanity_code_max_length")?.unwrap_or(16),
+            kyc_threshold_tokens: optional(values, "kyc_threshold_tokens")?,
+            kyc_required_tiers: list(values, "kyc_required_tiers")?,
+            kyc_provider: optional(values, "kyc_provider")?.unwrap_or_else(|| "local".to_string()),
+            kyc_level: optional(values, "kyc_level")?.unwrap_or_else(|| "basic".to_string()),
+            denylist_file_poll_secs: optional(values, "denylist_file_poll_secs")?.unwrap_or(60),
+            screen_payment_sources: optional(values, "screen_payment_sources")?.unwrap_or(true),
+        };
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.presale_end <= self.presale_start {
+            return Err(anyhow!("presale_end must be after presale_start"));
+        }
+        if self.min_purchase < Decimal::ZERO || self.min_purchase > self.max_purchase {
+            return Err(anyhow!("min_purchase must be between 0 and max_purchase"));
+        }
+        if let Some(cap) = self.max_wallet_purchase {
+            if cap < self.min_purchase {
+                return Err(anyhow!("max_wallet_purchase must be at least min_purchase"));
+            }
+        }
+        if self.referral_bonus < Decimal::ZERO || self.referral_bonus > Decimal::from(100) {
+            return Err(anyhow!("referral_bonus must be a percentage between 0 and 100"));
+        }
+        if self.referral_upline_rates.iter().any(|rate| *rate < Decimal::ZERO || *rate > Decimal::from(100)) {
+            return Err(anyhow!("referral_upline_rates must be percentages between 0 and 100"));
+        }
+        if self.hard_cap_tokens <= Decimal::ZERO || self.hard_cap_tokens > self.max_supply {
+            return Err(anyhow!("hard_cap_tokens must be positive and at most max_supply"));
+        }
+        if self.soft_cap_sol < Decimal::ZERO || self.soft_cap_sol > self.hard_cap_sol {
+            return Err(anyhow!("soft_cap_sol must be between 0 and hard_cap_sol"));
+        }
+        if self.pricing_currency == PricingCurrency::Usd && self.oracle_price_account.is_none() {
+            return Err(anyhow!("pricing_currency usd requires oracle_price_account"));
+        }
+        if self.oracle_max_staleness_secs <= 0 || self.oracle_max_confidence_bps <= Decimal::ZERO {
+            return Err(anyhow!("oracle staleness and confidence limits must be positive"));
+        }
+        if self.vesting_push_interval_secs == 0 || self.vesting_push_batch_size <= 0 {
+            return Err(anyhow!("vesting push interval and batch size must be positive"));
+        }
+        if self.tge_thaw_batch_size <= 0 || self.tge_thaw_batch_size > 25 {
+            return Err(anyhow!("tge_thaw_batch_size must be between 1 and 25"));
+        }
+        // users.referral_code is VARCHAR(20)
+        if self.vanity_code_min_length == 0
+            || self.vanity_code_min_length > self.vanity_code_max_length
+            || self.vanity_code_max_length > 20
+        {
+            return Err(anyhow!("vanity code lengths must satisfy 1 <= min <= max <= 20"));
+        }
+        if self.kyc_threshold_tokens.is_some_and(|threshold| threshold < Decimal::ZERO) {
+            return Err(anyhow!("kyc_threshold_tokens can't be negative"));
+        }
+        if self.kyc_provider.is_empty() || self.kyc_level.is_empty() {
+            return Err(anyhow!("kyc_provider and kyc_level can't be empty"));
+        }
+        if self.denylist_file_poll_secs == 0 {
+            return Err(anyhow!("denylist_file_poll_secs must be positive"));
+        }
+        Ok(())
+    }
+}
+
+fn optional<T>(values: &HashMap<&str, &str>, key: &str) -> Result<Option<T>>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    values
+        .get(key)
+        .map(|value| {
+            value
+                .trim()
+                .parse::<T>()
+                .map_err(|e| anyhow!("Invalid presale setting {}={:?}: {}", key, value, e))
+        })
+        .transpose()
+}
+
+/// Comma-separated list; missing or blank is empty
+fn list<T>(values: &HashMap<&str, &str>, key: &str) -> Result<Vec<T>>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    let value = match values.get(key) {
+        Some(value) => value.trim(),
+        None => return Ok(Vec::new()),
+    };
+    if value.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    value
+        .split(',')
+        .map(|item| {
+            item.trim()
+                .parse::<T>()
+                .map_err(|e| anyhow!("Invalid presale setting {}={:?}: {}", key, value, e))
+        })
+        .collect()
+}
+
+fn required<T>(values: &HashMap<&str, &str>, key: &str) -> Result<T>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    optional(values, key)?.ok_or_else(|| anyhow!("Missing presale setting {}", key))
+}
@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use validator::Validate;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Referral {
+    pub id: Uuid,
+    pub referrer_id: Uuid,
+    pub referred_id: Uuid,
+    pub bonus_tokens: Decimal,
+    /// Rate for this referral only; `None` uses the referrer's rate
+    pub bonus_percentage: Option<Decimal>,
+    pub is_active: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Ledger entry: a referrer's reward for one purchase by a referred user
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ReferralReward {
+    pub id: Uuid,
+    pub referral_id: Uuid,
+    pub referrer_id: Uuid,
+    pub referred_id: Uuid,
+    pub transaction_id: Uuid,
+    pub purchase_tokens: Decimal,
+    pub rate_percent: Decimal,
+    pub amount_tokens: Decimal,
+    pub created_at: DateTime<Utc>,
+    /// 1 for the buyer's referrer, 2 for theirs, ...
+    pub level: i32,
+    pub currency: String, // token, sol
+    pub purchase_sol: Option<Decimal>,
+    pub amount_sol: Decimal,
+    pub payout_status: Option<String>, // pending, sending, paid, blocked
+    pub payout_signature: Option<String>,
+    pub payout_blockhash: Option<String>,
+    pub paid_at: Option<DateTime<Utc>>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Admin update of a referrer's negotiated terms; `None` clears a field back
+/// to the global setting
+#[derive(Debug, Deserialize)]
+pub struct ReferrerCommissionRequest {
+    pub rate_percent: Option<Decimal>,
+    pub payout_currency: Option<String>, // token, sol
+}
+
+/// Admin override of the direct commission rate for one referral
+#[derive(Debug, Deserialize)]
+pub struct ReferralRateRequest {
+    pub rate_percent: Option<Decimal>,
+}
+
+#[derive(Debug, Default, Deserialize, Validate)]
+pub struct ReferralClickRequest {
+    #[validate(length(max = 255))]
+    pub landing_page: Option<String>,
+}
+
+/// Conversion funnel and earnings for one referral code
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ReferralStats {
+    pub clicks: i64,
+    pub unique_visitors: i64,
+    pub registrations: i64,
+    pub purchasers: i64,
+    pub volume_tokens: Decimal,
+    pub volume_sol: Decimal,
+    pub earned_tokens: Decimal,
+    pub earned_sol: Decimal,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LeaderboardQuery {
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+/// Referrer ranked by the confirmed purchase volume of the users they referred
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ReferrerRanking {
+    pub rank: i64,
+    pub wallet: String, // truncated
+    pub referral_code: Option<String>,
+    pub registrations: i64,
+    pub purchasers: i64,
+    pub volume_sol: Decimal,
+    pub earned_tokens: Decimal,
+}
+
+/// Buyer ranked by confirmed purchase volume
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ContributorRanking {
+    pub rank: i64,
+    pub wallet: String, // truncated
+    pub purchases: i64,
+    pub total_tokens: Decimal,
+    pub total_sol: Decimal,
+}
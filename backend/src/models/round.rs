@@ -0,0 +1,140 @@
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use sqlx::FromRow;
+use uuid::Uuid;
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::Decimal;
+
+use crate::models::UnlockPoint;
+use crate::services::{OraclePrice, VestingTerms};
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct PresaleRound {
+    pub id: Uuid,
+    pub name: String,
+    pub sequence: i32,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub price_sol: Decimal,
+    pub price_usd: Option<Decimal>,
+    pub token_allocation: Decimal,
+    pub tokens_sold: Decimal,
+    pub required_whitelist_tier: Option<i32>,
+    pub cliff_duration_days: i32,
+    pub vesting_duration_days: i32,
+    pub tge_unlock_percent: Decimal,
+    pub release_kind: String, // linear, monthly_step, custom
+    pub unlock_table: Option<Json<Vec<UnlockPoint>>>,
+    pub distribution_mode: String, // claim, push
+    pub activated_at: Option<DateTime<Utc>>,
+    pub closed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl PresaleRound {
+    /// Re-price the round in SOL from its USD price and a USD-per-SOL rate.
+    /// Rounds without a USD price keep their SOL price.
+    pub fn priced_from_usd(mut self, usd_per_sol: Decimal) -> Self {
+        if let Some(price_usd) = self.price_usd {
+            self.price_sol = (price_usd / usd_per_sol).round_dp(10);
+        }
+        self
+    }
+
+    pub fn vesting_terms(&self) -> VestingTerms {
+        VestingTerms::for_round(self)
+    }
+
+    pub fn remaining(&self) -> Decimal {
+        (self.token_allocation - self.tokens_sold).max(Decimal::ZERO)
+    }
+
+    /// Past its start time at `at`, for a buyer with `early_access`
+    pub fn has_started(&self, at: DateTime<Utc>, early_access: Duration) -> bool {
+        self.starts_at - early_access <= at
+    }
+
+    /// Still selling at `at`: not closed, not sold out and not past its end
+    pub fn is_open_at(&self, at: DateTime<Utc>) -> bool {
+        self.closed_at.is_none() && self.remaining() > Decimal::ZERO && at < self.ends_at
+    }
+}
+
+/// An oracle price recorded when a buyer asked for a quote
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct OraclePriceQuote {
+    pub id: Uuid,
+    pub price_account: String,
+    /// USD per SOL
+    pub price: Decimal,
+    pub confidence: Decimal,
+    pub publish_time: DateTime<Utc>,
+    pub publish_slot: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+impl OraclePriceQuote {
+    pub fn oracle_price(&self) -> OraclePrice {
+        OraclePrice {
+            price: self.price,
+            confidence: self.confidence,
+            publish_time: self.publish_time.timestamp(),
+            publish_slot: self.publish_slot as u64,
+        }
+    }
+}
+
+/// The part of a purchase priced and allocated in one round
+#[derive(Debug, Clone, Serialize)]
+pub struct RoundFill {
+    pub round_id: Uuid,
+    pub round_name: String,
+    pub tokens: Decimal,
+    pub price: Decimal,
+    pub cost: Decimal,
+    /// The round's terms, copied onto the schedules created for this fill
+    pub vesting: VestingTerms,
+    pub distribution_mode: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RoundResponse {
+    pub id: Uuid,
+    pub name: String,
+    pub sequence: i32,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub price_sol: Decimal,
+    pub price_usd: Option<Decimal>,
+    pub token_allocation: Decimal,
+    pub tokens_sold: Decimal,
+    pub required_whitelist_tier: Option<i32>,
+    pub cliff_duration_days: i32,
+    pub vesting_duration_days: i32,
+    pub vesting: VestingTerms,
+    pub distribution_mode: String,
+    pub is_active: bool,
+}
+
+impl RoundResponse {
+    pub fn from_round(round: PresaleRound, is_active: bool) -> Self {
+        let vesting = round.vesting_terms();
+        Self {
+            id: round.id,
+            name: round.name,
+            sequence: round.sequence,
+            starts_at: round.starts_at,
+            ends_at: round.ends_at,
+            price_sol: round.price_sol,
+            price_usd: round.price_usd,
+            token_allocation: round.token_allocation,
+            tokens_sold: round.tokens_sold,
+            required_whitelist_tier: round.required_whitelist_tier,
+            cliff_duration_days: round.cliff_duration_days,
+            vesting_duration_days: round.vesting_duration_days,
+            vesting,
+            distribution_mode: round.distribution_mode,
+            is_active,
+        }
+    }
+}
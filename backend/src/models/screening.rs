@@ -0,0 +1,42 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use validator::Validate;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DenylistEntry {
+    pub address: String,
+    pub source: String,
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct AddDenylistRequest {
+    #[validate(length(min = 1, max = 1000))]
+    pub addresses: Vec<String>,
+    #[validate(length(min = 1, max = 50))]
+    pub source: Option<String>,
+    pub reason: Option<String>,
+}
+
+/// Audit record of a denylist match
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ScreeningEvent {
+    pub id: Uuid,
+    pub wallet_address: String,
+    pub role: String, // buyer, referrer, refund_destination, payment_source
+    pub context: String, // registration, purchase, refund, commission
+    pub action: String, // blocked, flagged
+    pub matched_address: String,
+    pub list_source: String,
+    pub reference: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScreeningEventQuery {
+    pub wallet: Option<String>,
+    pub limit: Option<i64>,
+}
@@ -0,0 +1,74 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use sqlx::FromRow;
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+/// New values by key. Strings, numbers and booleans are stored as text;
+/// `null` removes an optional key.
+pub type SettingsChanges = BTreeMap<String, serde_json::Value>;
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateSettingsRequest {
+    pub changes: SettingsChanges,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScheduleSettingsRequest {
+    pub changes: SettingsChanges,
+    pub effective_at: DateTime<Utc>,
+    pub reason: Option<String>,
+}
+
+/// One key's change in the settings history
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct PresaleSettingChange {
+    pub id: Uuid,
+    pub version: i64,
+    pub key: String,
+    pub old_value: Option<String>,
+    pub new_value: Option<String>,
+    pub changed_by: Option<Uuid>,
+    pub changed_by_name: String,
+    pub source: String, // admin, schedule, system
+    pub schedule_id: Option<Uuid>,
+    pub reason: Option<String>,
+    pub changed_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct PresaleSettingSchedule {
+    pub id: Uuid,
+    pub changes: Json<BTreeMap<String, Option<String>>>,
+    pub effective_at: DateTime<Utc>,
+    pub status: String, // pending, applied, cancelled, failed
+    pub reason: Option<String>,
+    pub created_by: Option<Uuid>,
+    pub created_by_name: String,
+    pub applied_version: Option<i64>,
+    pub error: Option<String>,
+    pub cancelled_by_name: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SettingsHistoryQuery {
+    pub key: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SettingsScheduleQuery {
+    pub status: Option<String>,
+}
+
+/// An applied change set
+#[derive(Debug, Clone, Serialize)]
+pub struct SettingsVersion {
+    /// `None` when every value was already current
+    pub version: Option<i64>,
+    pub changes: Vec<PresaleSettingChange>,
+}
@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct TgeThawRun {
+    pub id: Uuid,
+    pub status: String, // running, completed, failed
+    pub total_accounts: i32,
+    pub thawed_accounts: i32,
+    pub batches_sent: i32,
+    pub last_error: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
@@ -2,7 +2,7 @@ use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
-use validator::Validate;
+use validator::{Validate, ValidationError};
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct Transaction {
@@ -11,7 +11,13 @@ pub struct Transaction {
     pub solana_signature: String,
     pub amount_tokens: rust_decimal::Decimal,
     pub amount_sol: rust_decimal::Decimal,
+    pub amount_requested: Option<rust_decimal::Decimal>,
+    pub refund_amount_sol: rust_decimal::Decimal,
+    pub refund_signature: Option<String>,
     pub payment_method: String,
+    pub round_id: Option<Uuid>,
+    pub oracle_price: Option<rust_decimal::Decimal>,
+    pub oracle_publish_time: Option<DateTime<Utc>>,
     pub status: String,
     pub block_height: Option<i64>,
     pub processed_at: Option<DateTime<Utc>>,
@@ -26,7 +32,20 @@ pub struct CreateTransactionRequest {
     pub buyer: String,
     #[validate(range(min = 1.0))]
     pub amount: f64,
+    /// Payments are verified as native SOL transfers, so only "SOL" is accepted
+    #[validate(custom(function = "validate_payment_method"))]
     pub payment_method: Option<String>,
+    /// Applied only on the wallet's first purchase
+    #[validate(length(min = 1, max = 20))]
+    pub referral_code: Option<String>,
+}
+
+fn validate_payment_method(payment_method: &str) -> Result<(), ValidationError> {
+    if payment_method == "SOL" {
+        Ok(())
+    } else {
+        Err(ValidationError::new("unsupported_payment_method"))
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -35,7 +54,10 @@ pub struct TransactionResponse {
     pub signature: String,
     pub amount_tokens: f64,
     pub amount_sol: f64,
+    pub refund_amount_sol: f64,
+    pub refund_signature: Option<String>,
     pub payment_method: String,
+    pub round_id: Option<Uuid>,
     pub status: String,
     pub block_height: Option<i64>,
     pub processed_at: Option<DateTime<Utc>>,
@@ -59,7 +81,10 @@ impl From<Transaction> for TransactionResponse {
             signature: tx.solana_signature,
             amount_tokens: tx.amount_tokens.to_string().parse().unwrap_or(0.0),
             amount_sol: tx.amount_sol.to_string().parse().unwrap_or(0.0),
+            refund_amount_sol: tx.refund_amount_sol.to_string().parse().unwrap_or(0.0),
+            refund_signature: tx.refund_signature,
             payment_method: tx.payment_method,
+            round_id: tx.round_id,
             status: tx.status,
             block_height: tx.block_height,
             processed_at: tx.processed_at,
@@ -67,3 +92,22 @@ impl From<Transaction> for TransactionResponse {
         }
     }
 }
+
+/// SOL owed back for a payment that wasn't (fully) sold against
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct PaymentRefund {
+    pub id: Uuid,
+    pub payment_signature: String,
+    pub wallet_address: String,
+    pub transaction_id: Option<Uuid>,
+    pub amount_sol: rust_decimal::Decimal,
+    pub reason: String,
+    pub status: String, // pending, sending, refunded, withheld
+    pub refund_signature: Option<String>,
+    pub refund_blockhash: Option<String>,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub refunded_at: Option<DateTime<Utc>>,
+}
@@ -15,7 +15,16 @@ pub struct User {
     pub referred_by: Option<Uuid>,
     pub is_whitelisted: bool,
     pub whitelist_tier: i32,
-    pub kyc_status: String,
+    pub kyc_status: String, // pending, approved, rejected
+    pub kyc_applicant_id: Option<String>,
+    pub kyc_updated_at: Option<DateTime<Utc>>,
+    /// Negotiated direct commission rate, overriding the global referral_bonus
+    pub referral_rate_percent: Option<rust_decimal::Decimal>,
+    pub referral_payout_currency: Option<String>, // token, sol
+    /// Set once the user has replaced their generated code with a custom one
+    pub referral_code_claimed_at: Option<DateTime<Utc>>,
+    /// Set when a payment was funded directly by a denylisted address
+    pub screening_flagged_at: Option<DateTime<Utc>>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -28,17 +37,10 @@ pub struct CreateUserRequest {
     pub email: Option<String>,
     pub discord_username: Option<String>,
     pub twitter_username: Option<String>,
+    #[validate(length(min = 1, max = 20))]
     pub referral_code: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Validate)]
-pub struct UpdateUserRequest {
-    #[validate(email)]
-    pub email: Option<String>,
-    pub discord_username: Option<String>,
-    pub twitter_username: Option<String>,
-}
-
 #[derive(Debug, Serialize)]
 pub struct UserResponse {
     pub id: Uuid,
@@ -47,6 +49,7 @@ pub struct UserResponse {
     pub discord_username: Option<String>,
     pub twitter_username: Option<String>,
     pub referral_code: Option<String>,
+    pub referred_by: Option<Uuid>,
     pub is_whitelisted: bool,
     pub whitelist_tier: i32,
     pub kyc_status: String,
@@ -54,10 +57,20 @@ pub struct UserResponse {
     pub referral_count: Option<i64>,
 }
 
+#[derive(Debug, Deserialize, Validate)]
+pub struct ClaimReferralCodeRequest {
+    #[validate(length(min = 32, max = 44))]
+    pub wallet_address: String,
+    #[validate(length(min = 1, max = 20))]
+    pub referral_code: String,
+}
+
 impl User {
+    /// Random code from an alphabet without look-alike characters (0/O,
+    /// 1/I/L), so codes survive being read aloud or retyped
     pub fn generate_referral_code() -> String {
         use rand::Rng;
-        const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+        const CHARSET: &[u8] = b"ABCDEFGHJKMNPQRSTUVWXYZ23456789";
         let mut rng = rand::thread_rng();
         (0..8)
             .map(|_| {
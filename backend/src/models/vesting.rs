@@ -0,0 +1,128 @@
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use sqlx::FromRow;
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use validator::Validate;
+
+use crate::services::VestingTerms;
+
+/// How the post-TGE remainder of a schedule is released
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReleaseKind {
+    /// Continuously, from the start date over the vesting duration
+    Linear,
+    /// In equal calendar-month installments over the vesting duration
+    MonthlyStep,
+    /// Following a custom unlock table
+    Custom,
+}
+
+impl ReleaseKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Linear => "linear",
+            Self::MonthlyStep => "monthly_step",
+            Self::Custom => "custom",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "linear" => Some(Self::Linear),
+            "monthly_step" => Some(Self::MonthlyStep),
+            "custom" => Some(Self::Custom),
+            _ => None,
+        }
+    }
+}
+
+/// Custom unlock table entry: cumulative percent of the post-TGE remainder
+/// unlocked once `offset_days` have passed since the start date
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct UnlockPoint {
+    pub offset_days: i64,
+    pub percent: Decimal,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct VestingSchedule {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub transaction_id: Uuid,
+    pub round_id: Option<Uuid>,
+    pub total_tokens: Decimal,
+    pub released_tokens: Decimal,
+    pub cliff_duration_days: i32,
+    pub vesting_duration_days: i32,
+    pub tge_unlock_percent: Decimal,
+    pub release_kind: String, // linear, monthly_step, custom
+    pub unlock_table: Option<Json<Vec<UnlockPoint>>>,
+    pub source: String, // purchase, referral_bonus
+    /// `claim`: released when the holder claims; `push`: sent by the vesting worker
+    pub distribution_mode: String,
+    pub referral_reward_id: Option<Uuid>,
+    pub start_date: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl VestingSchedule {
+    pub fn terms(&self) -> VestingTerms {
+        VestingTerms::for_schedule(self)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct VestingScheduleResponse {
+    pub id: Uuid,
+    pub transaction_id: Uuid,
+    pub round_id: Option<Uuid>,
+    pub total_tokens: Decimal,
+    pub vested_tokens: Decimal,
+    pub released_tokens: Decimal,
+    pub claimable_tokens: Decimal,
+    pub locked_tokens: Decimal,
+    pub tge_unlock_percent: Decimal,
+    pub release_kind: String,
+    pub source: String,
+    pub distribution_mode: String,
+    pub start_date: DateTime<Utc>,
+    pub cliff_ends_at: DateTime<Utc>,
+    pub vesting_ends_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VestingSummary {
+    pub wallet_address: String,
+    pub total_tokens: Decimal,
+    pub vested_tokens: Decimal,
+    pub released_tokens: Decimal,
+    pub claimable_tokens: Decimal,
+    pub locked_tokens: Decimal,
+    pub schedules: Vec<VestingScheduleResponse>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ClaimVestingRequest {
+    #[validate(length(min = 32, max = 44))]
+    pub wallet_address: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VestingPreviewQuery {
+    pub amount: Decimal,
+    pub round_id: Option<Uuid>,
+    /// The buyer, whose referrer's commission is previewed
+    pub wallet: Option<String>,
+    /// The code a buyer without a referrer is about to use
+    pub referral_code: Option<String>,
+}
+
+/// Cumulative vested amount at a point in a schedule's timeline
+#[derive(Debug, Clone, Serialize)]
+pub struct UnlockEvent {
+    pub at: DateTime<Utc>,
+    pub vested_tokens: Decimal,
+}
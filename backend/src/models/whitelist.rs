@@ -0,0 +1,250 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json;
+use sqlx::FromRow;
+use uuid::Uuid;
+use chrono::{DateTime, Duration, Utc};
+use rust_decimal::Decimal;
+use validator::Validate;
+
+use crate::models::ReleaseKind;
+use crate::services::VestingTerms;
+
+/// A whitelist tier and the rules its members buy under
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct WhitelistTier {
+    pub tier: i32,
+    pub name: String,
+    pub default_max_allocation: Decimal,
+    /// Overrides the global `min_purchase` when set
+    pub min_purchase: Option<Decimal>,
+    pub early_access_secs: i32,
+    pub price_discount_percent: Decimal,
+    pub vesting_tge_unlock_percent: Option<Decimal>,
+    pub vesting_cliff_days: Option<i32>,
+    pub vesting_duration_days: Option<i32>,
+    pub vesting_release_kind: Option<String>, // linear, monthly_step
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl WhitelistTier {
+    /// How long before the presale and each round start members may buy
+    pub fn early_access(&self) -> Duration {
+        Duration::seconds(self.early_access_secs as i64)
+    }
+
+    pub fn discounted_price(&self, price: Decimal) -> Decimal {
+        (price * (Decimal::ONE_HUNDRED - self.price_discount_percent) / Decimal::ONE_HUNDRED).round_dp(10)
+    }
+
+    /// A round's vesting terms with this tier's overrides applied
+    pub fn vesting_for(&self, mut terms: VestingTerms) -> VestingTerms {
+        if let Some(percent) = self.vesting_tge_unlock_percent {
+            terms.tge_unlock_percent = percent;
+        }
+        if let Some(days) = self.vesting_cliff_days {
+            terms.cliff_days = days as i64;
+        }
+        if let Some(days) = self.vesting_duration_days {
+            terms.duration_days = days as i64;
+        }
+        if let Some(release) = self.vesting_release_kind.as_deref().and_then(ReleaseKind::parse) {
+            terms.release = release;
+            terms.unlock_table.clear();
+        }
+        terms
+    }
+}
+
+/// Admin create-or-replace of a tier
+#[derive(Debug, Deserialize)]
+pub struct UpsertWhitelistTierRequest {
+    pub name: String,
+    pub default_max_allocation: Decimal,
+    pub min_purchase: Option<Decimal>,
+    #[serde(default)]
+    pub early_access_secs: i32,
+    #[serde(default)]
+    pub price_discount_percent: Decimal,
+    pub vesting_tge_unlock_percent: Option<Decimal>,
+    pub vesting_cliff_days: Option<i32>,
+    pub vesting_duration_days: Option<i32>,
+    pub vesting_release_kind: Option<String>,
+}
+
+impl UpsertWhitelistTierRequest {
+    pub fn check(&self) -> Result<()> {
+        if self.name.trim().is_empty() || self.name.len() > 50 {
+            return Err(anyhow!("name must be 1 to 50 characters"));
+        }
+        if self.default_max_allocation < Decimal::ZERO {
+            return Err(anyhow!("default_max_allocation can't be negative"));
+        }
+        if self.min_purchase.is_some_and(|min| min <= Decimal::ZERO) {
+            return Err(anyhow!("min_purchase must be positive"));
+        }
+        if self.early_access_secs < 0 {
+            return Err(anyhow!("early_access_secs can't be negative"));
+        }
+        if self.price_discount_percent < Decimal::ZERO || self.price_discount_percent >= Decimal::ONE_HUNDRED {
+            return Err(anyhow!("price_discount_percent must be at least 0 and below 100"));
+        }
+        if self
+            .vesting_tge_unlock_percent
+            .is_some_and(|percent| percent < Decimal::ZERO || percent > Decimal::ONE_HUNDRED)
+        {
+            return Err(anyhow!("vesting_tge_unlock_percent must be between 0 and 100"));
+        }
+        if self.vesting_cliff_days.is_some_and(|days| days < 0) || self.vesting_duration_days.is_some_and(|days| days < 0) {
+            return Err(anyhow!("vesting days can't be negative"));
+        }
+        match self.vesting_release_kind.as_deref().map(ReleaseKind::parse) {
+            None | Some(Some(ReleaseKind::Linear)) | Some(Some(ReleaseKind::MonthlyStep)) => Ok(()),
+            _ => Err(anyhow!("vesting_release_kind must be linear or monthly_step")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct WhitelistApplication {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub requested_tier: Option<i32>,
+    pub answers: Json<serde_json::Value>,
+    pub email: Option<String>,
+    pub discord_username: Option<String>,
+    pub twitter_username: Option<String>,
+    pub telegram_username: Option<String>,
+    pub status: String, // submitted, under_review, approved, rejected
+    pub approved_tier: Option<i32>,
+    pub approved_allocation: Option<Decimal>,
+    pub whitelist_entry_id: Option<Uuid>,
+    pub rejection_reason: Option<String>,
+    pub submitted_at: DateTime<Utc>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub decided_at: Option<DateTime<Utc>>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct WhitelistApplicationRequest {
+    #[validate(length(min = 32, max = 44))]
+    pub wallet_address: String,
+    #[serde(alias = "tier")]
+    pub requested_tier: Option<i32>,
+    /// Free-form answers to the application questions
+    pub answers: Option<serde_json::Value>,
+    #[validate(email)]
+    pub email: Option<String>,
+    #[validate(length(max = 100))]
+    pub discord_username: Option<String>,
+    #[validate(length(max = 100))]
+    pub twitter_username: Option<String>,
+    #[validate(length(max = 100))]
+    pub telegram_username: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WhitelistApplicationQuery {
+    pub status: Option<String>,
+    pub tier: Option<i32>,
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApproveApplicationRequest {
+    pub tier: i32,
+    /// Defaults to the tier's allocation, and then follows it
+    pub max_allocation: Option<Decimal>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct RejectApplicationRequest {
+    #[validate(length(min = 1, max = 1000))]
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WhitelistImportQuery {
+    /// Validate and report without saving anything
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// A CSV row that failed validation; `line` is the 1-based line in the file
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportRowError {
+    pub line: u64,
+    pub wallet: Option<String>,
+    pub field: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WhitelistImportReport {
+    pub dry_run: bool,
+    /// False when any row failed validation or in a dry run
+    pub committed: bool,
+    pub rows: usize,
+    pub created_users: usize,
+    pub created_entries: usize,
+    pub updated_entries: usize,
+    pub errors: Vec<ImportRowError>,
+}
+
+/// One whitelist entry with its owner, as exported
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct WhitelistExportRow {
+    pub wallet: String,
+    pub tier: i32,
+    pub tier_name: String,
+    pub max_allocation: Decimal,
+    pub used_allocation: Decimal,
+    pub remaining_allocation: Decimal,
+    pub custom_allocation: bool,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A snapshot of the whitelist Merkle tree
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct WhitelistMerkleVersion {
+    pub version: i32,
+    pub root: String,
+    pub leaf_count: i32,
+    pub total_allocation: Decimal,
+    pub token_decimals: i16,
+    pub created_at: DateTime<Utc>,
+}
+
+/// One wallet's allocation as committed to in a tree version
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct WhitelistMerkleLeaf {
+    pub wallet_address: String,
+    pub tier: i32,
+    pub max_allocation: Decimal,
+    /// `max_allocation` in token base units
+    pub amount_units: Decimal,
+    pub leaf: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WhitelistProofQuery {
+    /// Defaults to the latest version
+    pub version: Option<i32>,
+}
+
+/// What a wallet presents on-chain to prove its allocation
+#[derive(Debug, Clone, Serialize)]
+pub struct WhitelistProof {
+    pub version: i32,
+    pub root: String,
+    pub token_decimals: i16,
+    #[serde(flatten)]
+    pub leaf: WhitelistMerkleLeaf,
+    /// Sibling hashes from the leaf up to the root
+    pub proof: Vec<String>,
+}
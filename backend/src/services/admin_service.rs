@@ -0,0 +1,389 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, Utc};
+use rand::distributions::{Alphanumeric, DistString};
+use sha2::{Digest, Sha256};
+use sqlx::{PgConnection, PgPool};
+use std::fmt;
+use std::str::FromStr;
+use uuid::Uuid;
+
+use crate::models::{AdminLoginRequest, AdminSession, AdminUser, CreateAdminRequest, CreatedAdmin, UpdateAdminRequest};
+use crate::services::{
+    generate_totp_secret, record_audit_event_in, totp_uri, verify_totp, AuditContext, AuditEntry,
+};
+
+/// Issuer shown in authenticator apps
+pub const ADMIN_TOTP_ISSUER: &str = "Shibartum Presale";
+
+const ADMIN_SESSION_TTL_HOURS: i64 = 8;
+
+/// Failed logins in a row before an account is locked, and for how long
+const ADMIN_MAX_FAILED_LOGINS: i32 = 5;
+const ADMIN_LOCKOUT_MINS: i64 = 15;
+
+/// Serializes changes that must leave at least one active owner
+const ADMIN_OWNERS_LOCK_KEY: i64 = 0x4144_4d49_4e4f_574e; // "ADMINOWN"
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdminRole {
+    /// Everything, including managing admin accounts
+    Owner,
+    /// Runs the presale: token accounts, TGE, tiers, imports, denylist
+    Operator,
+    /// Reviews whitelist applications
+    Reviewer,
+    /// Read-only access for user support
+    Support,
+}
+
+impl AdminRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Owner => "owner",
+            Self::Operator => "operator",
+            Self::Reviewer => "reviewer",
+            Self::Support => "support",
+        }
+    }
+}
+
+impl FromStr for AdminRole {
+    type Err = anyhow::Error;
+
+    fn from_str(role: &str) -> Result<Self> {
+        match role {
+            "owner" => Ok(Self::Owner),
+            "operator" => Ok(Self::Operator),
+            "reviewer" => Ok(Self::Reviewer),
+            "support" => Ok(Self::Support),
+            _ => Err(anyhow!("Unknown admin role {}", role)),
+        }
+    }
+}
+
+/// Reason an admin login was refused. Wrong passwords and wrong codes are
+/// not told apart.
+#[derive(Debug, Clone)]
+pub enum AdminLoginRejection {
+    InvalidCredentials,
+    Locked { until: DateTime<Utc> },
+}
+
+impl AdminLoginRejection {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidCredentials => "invalid_credentials",
+            Self::Locked { .. } => "account_locked",
+        }
+    }
+}
+
+impl fmt::Display for AdminLoginRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidCredentials => write!(f, "Invalid username, password or code"),
+            Self::Locked { until } => write!(f, "Too many failed logins, locked until {}", until),
+        }
+    }
+}
+
+impl std::error::Error for AdminLoginRejection {}
+
+fn session_token_hash(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn normalize_username(username: &str) -> String {
+    username.trim().to_lowercase()
+}
+
+/// bcrypt is deliberately slow, so it runs off the async workers
+async fn hash_admin_password(password: &str) -> Result<String> {
+    let password = password.to_string();
+    Ok(tokio::task::spawn_blocking(move || bcrypt::hash(password, bcrypt::DEFAULT_COST)).await??)
+}
+
+async fn verify_admin_password(password: &str, hash: &str) -> Result<bool> {
+    let (password, hash) = (password.to_string(), hash.to_string());
+    Ok(tokio::task::spawn_blocking(move || bcrypt::verify(password, &hash)).await??)
+}
+
+/// Check password and TOTP code and open a session
+pub async fn admin_login(pool: &PgPool, req: &AdminLoginRequest, now: DateTime<Utc>) -> Result<AdminSession> {
+    let admin = sqlx::query_as::<_, AdminUser>("SELECT * FROM admin_users WHERE username = $1")
+        .bind(normalize_username(&req.username))
+        .fetch_optional(pool)
+        .await?;
+
+    let Some(admin) = admin else {
+        // Spend the same time as a real check so usernames can't be probed
+        hash_admin_password(&req.password).await?;
+        return Err(AdminLoginRejection::InvalidCredentials.into());
+    };
+    if let Some(until) = admin.locked_until.filter(|until| *until > now) {
+        return Err(AdminLoginRejection::Locked { until }.into());
+    }
+
+    let password_ok = verify_admin_password(&req.password, &admin.password_hash).await?;
+    let totp_step = verify_totp(&admin.totp_secret, &req.totp_code, now.timestamp(), admin.totp_last_step);
+
+    let Some(totp_step) = totp_step.filter(|_| password_ok && admin.is_active) else {
+        sqlx::query(
+            r#"
+            UPDATE admin_users
+            SET failed_logins = CASE WHEN failed_logins + 1 >= $2 THEN 0 ELSE failed_logins + 1 END,
+                locked_until = CASE WHEN failed_logins + 1 >= $2 THEN $3 ELSE locked_until END,
+                updated_at = NOW()
+            WHERE id = $1
+            "#
+        )
+        .bind(admin.id)
+        .bind(ADMIN_MAX_FAILED_LOGINS)
+        .bind(now + Duration::minutes(ADMIN_LOCKOUT_MINS))
+        .execute(pool)
+        .await?;
+        eprintln!("⚠️ Failed admin login for {}", admin.username);
+        return Err(AdminLoginRejection::InvalidCredentials.into());
+    };
+
+    let mut db_tx = pool.begin().await?;
+
+    // The step condition stops two logins racing with the same code
+    let admin = sqlx::query_as::<_, AdminUser>(
+        r#"
+        UPDATE admin_users
+        SET failed_logins = 0, locked_until = NULL, totp_last_step = $2,
+            last_login_at = $3, updated_at = NOW()
+        WHERE id = $1 AND (totp_last_step IS NULL OR totp_last_step < $2)
+        RETURNING *
+        "#
+    )
+    .bind(admin.id)
+    .bind(totp_step)
+    .bind(now)
+    .fetch_optional(&mut *db_tx)
+    .await?
+    .ok_or(AdminLoginRejection::InvalidCredentials)?;
+
+    let token = Alphanumeric.sample_string(&mut rand::thread_rng(), 48);
+    let expires_at = now + Duration::hours(ADMIN_SESSION_TTL_HOURS);
+    sqlx::query("INSERT INTO admin_sessions (admin_id, token_hash, expires_at, created_at) VALUES ($1, $2, $3, $4)")
+        .bind(admin.id)
+        .bind(session_token_hash(&token))
+        .bind(expires_at)
+        .bind(now)
+        .execute(&mut *db_tx)
+        .await?;
+
+    db_tx.commit().await?;
+    println!("🔐 Admin signed in: {} ({})", admin.username, admin.role);
+
+    Ok(AdminSession { token, expires_at, admin })
+}
+
+/// The active admin and session id behind a session token
+pub async fn authenticate_admin_session(pool: &PgPool, token: &str) -> Result<Option<(AdminUser, Uuid)>> {
+    let session: Option<Uuid> = sqlx::query_scalar(
+        r#"
+        SELECT s.id FROM admin_sessions s
+        JOIN admin_users a ON a.id = s.admin_id
+        WHERE s.token_hash = $1 AND s.revoked_at IS NULL AND s.expires_at > NOW() AND a.is_active
+        "#
+    )
+    .bind(session_token_hash(token))
+    .fetch_optional(pool)
+    .await?;
+    let Some(session_id) = session else {
+        return Ok(None);
+    };
+
+    let admin = sqlx::query_as::<_, AdminUser>(
+        "SELECT a.* FROM admin_users a JOIN admin_sessions s ON s.admin_id = a.id WHERE s.id = $1"
+    )
+    .bind(session_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(Some((admin, session_id)))
+}
+
+pub async fn revoke_admin_session(pool: &PgPool, session_id: &Uuid) -> Result<()> {
+    sqlx::query("UPDATE admin_sessions SET revoked_at = NOW() WHERE id = $1 AND revoked_at IS NULL")
+        .bind(session_id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+async fn insert_admin_user(conn: &mut PgConnection, req: &CreateAdminRequest) -> Result<CreatedAdmin> {
+    let role = AdminRole::from_str(&req.role)?;
+    let username = normalize_username(&req.username);
+    if !username.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-')) {
+        return Err(anyhow!("Usernames may only contain letters, digits, '.', '_' and '-'"));
+    }
+
+    let password_hash = hash_admin_password(&req.password).await?;
+    let totp_secret = generate_totp_secret();
+
+    let admin = sqlx::query_as::<_, AdminUser>(
+        r#"
+        INSERT INTO admin_users (username, password_hash, role, totp_secret)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (username) DO NOTHING
+        RETURNING *
+        "#
+    )
+    .bind(&username)
+    .bind(&password_hash)
+    .bind(role.as_str())
+    .bind(&totp_secret)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or_else(|| anyhow!("Username {} is taken", username))?;
+
+    Ok(CreatedAdmin {
+        totp_uri: totp_uri(ADMIN_TOTP_ISSUER, &admin.username, &totp_secret),
+        totp_secret,
+        admin,
+    })
+}
+
+/// An admin account as recorded in the audit log, without credentials
+fn admin_audit_state(admin: &AdminUser) -> serde_json::Value {
+    serde_json::json!({
+        "username": admin.username,
+        "role": admin.role,
+        "is_active": admin.is_active,
+    })
+}
+
+pub async fn create_admin_user(pool: &PgPool, req: &CreateAdminRequest, audit: &AuditContext) -> Result<CreatedAdmin> {
+    let mut db_tx = pool.begin().await?;
+    let created = insert_admin_user(&mut db_tx, req).await?;
+    let entry = AuditEntry {
+        action: "admin.created",
+        entity_type: "admin_user",
+        entity_id: created.admin.id.to_string(),
+        before: None,
+        after: Some(admin_audit_state(&created.admin)),
+    };
+    record_audit_event_in(&mut db_tx, audit, entry).await?;
+    db_tx.commit().await?;
+
+    println!("👤 Admin account created: {} ({})", created.admin.username, created.admin.role);
+    Ok(created)
+}
+
+/// Create the first owner. Refused once any owner exists; later admins are
+/// created by an owner through the API.
+pub async fn bootstrap_owner(pool: &PgPool, username: &str, password: &str) -> Result<CreatedAdmin> {
+    let mut db_tx = pool.begin().await?;
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+        .bind(ADMIN_OWNERS_LOCK_KEY)
+        .execute(&mut *db_tx)
+        .await?;
+
+    let owners: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM admin_users WHERE role = 'owner'")
+        .fetch_one(&mut *db_tx)
+        .await?;
+    if owners > 0 {
+        return Err(anyhow!("An owner already exists; create further admins through the API"));
+    }
+
+    let req = CreateAdminRequest {
+        username: username.to_string(),
+        password: password.to_string(),
+        role: AdminRole::Owner.as_str().to_string(),
+    };
+    let created = insert_admin_user(&mut db_tx, &req).await?;
+    let entry = AuditEntry {
+        action: "admin.created",
+        entity_type: "admin_user",
+        entity_id: created.admin.id.to_string(),
+        before: None,
+        after: Some(admin_audit_state(&created.admin)),
+    };
+    record_audit_event_in(&mut db_tx, &AuditContext::system("create_owner"), entry).await?;
+    db_tx.commit().await?;
+
+    Ok(created)
+}
+
+pub async fn list_admin_users(pool: &PgPool) -> Result<Vec<AdminUser>> {
+    let admins = sqlx::query_as::<_, AdminUser>("SELECT * FROM admin_users ORDER BY created_at")
+        .fetch_all(pool)
+        .await?;
+
+    Ok(admins)
+}
+
+/// Change an admin's role or deactivate them. Their sessions pick up the
+/// change on the next request. The last active owner can't be demoted or
+/// deactivated.
+pub async fn update_admin_user(
+    pool: &PgPool,
+    id: &Uuid,
+    req: &UpdateAdminRequest,
+    audit: &AuditContext,
+) -> Result<Option<AdminUser>> {
+    let role = req.role.as_deref().map(AdminRole::from_str).transpose()?;
+
+    let mut db_tx = pool.begin().await?;
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+        .bind(ADMIN_OWNERS_LOCK_KEY)
+        .execute(&mut *db_tx)
+        .await?;
+
+    let previous = sqlx::query_as::<_, AdminUser>("SELECT * FROM admin_users WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&mut *db_tx)
+        .await?;
+    let Some(previous) = previous else {
+        return Ok(None);
+    };
+
+    let admin = sqlx::query_as::<_, AdminUser>(
+        r#"
+        UPDATE admin_users
+        SET role = COALESCE($2, role), is_active = COALESCE($3, is_active), updated_at = NOW()
+        WHERE id = $1
+        RETURNING *
+        "#
+    )
+    .bind(id)
+    .bind(role.map(|role| role.as_str()))
+    .bind(req.is_active)
+    .fetch_one(&mut *db_tx)
+    .await?;
+
+    let owners: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM admin_users WHERE role = 'owner' AND is_active")
+        .fetch_one(&mut *db_tx)
+        .await?;
+    if owners == 0 {
+        return Err(anyhow!("At least one active owner is required"));
+    }
+
+    if !admin.is_active {
+        sqlx::query("UPDATE admin_sessions SET revoked_at = NOW() WHERE admin_id = $1 AND revoked_at IS NULL")
+            .bind(admin.id)
+            .execute(&mut *db_tx)
+            .await?;
+    }
+
+    let entry = AuditEntry {
+        action: "admin.updated",
+        entity_type: "admin_user",
+        entity_id: admin.id.to_string(),
+        before: Some(admin_audit_state(&previous)),
+        after: Some(admin_audit_state(&admin)),
+    };
+    record_audit_event_in(&mut db_tx, audit, entry).await?;
+    db_tx.commit().await?;
+    println!("👤 Admin {} is now {}{}", admin.username, admin.role, if admin.is_active { "" } else { " (inactive)" });
+
+    Ok(Some(admin))
+}
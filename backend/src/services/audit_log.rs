@@ -0,0 +1,389 @@
+//! Append-only, hash-chained audit log. Every event's hash covers its own
+//! fields and the previous event's hash, so changing, removing or
+//! reordering a stored event breaks every hash after it.
+
+use anyhow::Result;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::models::{AuditChainReport, AuditEvent, AuditEventQuery};
+
+/// Serializes appends so each event links to the one committed before it
+const AUDIT_LOCK_KEY: i64 = 0x4155_4449_544c_4f47; // "AUDITLOG"
+
+/// `prev_hash` of the first event
+pub const AUDIT_GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+const AUDIT_EVENTS_DEFAULT_LIMIT: i64 = 100;
+const AUDIT_EVENTS_MAX_LIMIT: i64 = 1000;
+const AUDIT_VERIFY_BATCH: i64 = 1000;
+
+/// Who caused a change
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditActor {
+    User(String),
+    Admin(Uuid),
+    /// A background worker or the system acting on its own, by name
+    System(String),
+}
+
+impl AuditActor {
+    pub fn actor_type(&self) -> &'static str {
+        match self {
+            Self::User(_) => "user",
+            Self::Admin(_) => "admin",
+            Self::System(_) => "system",
+        }
+    }
+
+    pub fn actor_id(&self) -> String {
+        match self {
+            Self::User(wallet) => wallet.clone(),
+            Self::Admin(id) => id.to_string(),
+            Self::System(name) => name.clone(),
+        }
+    }
+}
+
+/// The actor and, for API calls, the request an audited change came from
+#[derive(Debug, Clone)]
+pub struct AuditContext {
+    pub actor: AuditActor,
+    pub request_id: Option<String>,
+    /// `X-Request-Id` as sent by the caller, unverified
+    pub client_request_id: Option<String>,
+    pub ip_address: Option<String>,
+}
+
+impl AuditContext {
+    pub fn system(name: &str) -> Self {
+        AuditContext {
+            actor: AuditActor::System(name.to_string()),
+            request_id: None,
+            client_request_id: None,
+            ip_address: None,
+        }
+    }
+}
+
+/// One change to record
+#[derive(Debug, Clone)]
+pub struct AuditEntry<'a> {
+    pub action: &'a str,
+    pub entity_type: &'a str,
+    pub entity_id: String,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+/// SHA-256 (hex) over the previous hash and every field but `id` and `hash`.
+/// Fields are hashed as a JSON array; `serde_json` sorts object keys, so the
+/// `before`/`after` documents hash the same after a round trip through JSONB.
+/// `client_request_id` is appended only when set, so events recorded before
+/// it existed still verify.
+pub fn audit_event_hash(event: &AuditEvent) -> String {
+    let mut canonical = serde_json::json!([
+        event.prev_hash,
+        event.actor_type,
+        event.actor_id,
+        event.action,
+        event.entity_type,
+        event.entity_id,
+        event.before,
+        event.after,
+        event.request_id,
+        event.ip_address,
+        event.created_at.to_rfc3339_opts(SecondsFormat::Micros, true),
+    ]);
+    if let (Some(client_request_id), Value::Array(fields)) = (&event.client_request_id, &mut canonical) {
+        fields.push(Value::String(client_request_id.clone()));
+    }
+
+    Sha256::digest(canonical.to_string().as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Append an event inside the caller's transaction, so it commits (or rolls
+/// back) with the change it describes. Appends are serialized until the
+/// transaction ends, so call this last, just before committing.
+pub async fn record_audit_event_in(
+    conn: &mut PgConnection,
+    context: &AuditContext,
+    entry: AuditEntry<'_>,
+) -> Result<AuditEvent> {
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+        .bind(AUDIT_LOCK_KEY)
+        .execute(&mut *conn)
+        .await?;
+
+    let prev_hash: Option<String> = sqlx::query_scalar("SELECT hash FROM audit_events ORDER BY id DESC LIMIT 1")
+        .fetch_optional(&mut *conn)
+        .await?;
+
+    // Postgres keeps microseconds; hash what will be read back
+    let now = Utc::now();
+    let created_at = DateTime::from_timestamp_micros(now.timestamp_micros()).unwrap_or(now);
+
+    let mut event = AuditEvent {
+        id: 0,
+        actor_type: context.actor.actor_type().to_string(),
+        actor_id: context.actor.actor_id(),
+        action: entry.action.to_string(),
+        entity_type: entry.entity_type.to_string(),
+        entity_id: entry.entity_id,
+        before: entry.before,
+        after: entry.after,
+        request_id: context.request_id.clone(),
+        client_request_id: context.client_request_id.clone(),
+        ip_address: context.ip_address.clone(),
+        created_at,
+        prev_hash: prev_hash.unwrap_or_else(|| AUDIT_GENESIS_HASH.to_string()),
+        hash: String::new(),
+    };
+    event.hash = audit_event_hash(&event);
+
+    let event = sqlx::query_as::<_, AuditEvent>(
+        r#"
+        INSERT INTO audit_events (
+            actor_type, actor_id, action, entity_type, entity_id, before, after,
+            request_id, client_request_id, ip_address, created_at, prev_hash, hash
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+        RETURNING *
+        "#
+    )
+    .bind(&event.actor_type)
+    .bind(&event.actor_id)
+    .bind(&event.action)
+    .bind(&event.entity_type)
+    .bind(&event.entity_id)
+    .bind(&event.before)
+    .bind(&event.after)
+    .bind(&event.request_id)
+    .bind(&event.client_request_id)
+    .bind(&event.ip_address)
+    .bind(event.created_at)
+    .bind(&event.prev_hash)
+    .bind(&event.hash)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(event)
+}
+
+/// Append an event in a transaction of its own, for changes that have
+/// already been committed or happened outside the database
+pub async fn record_audit_event(pool: &PgPool, context: &AuditContext, entry: AuditEntry<'_>) -> Result<AuditEvent> {
+    let mut db_tx = pool.begin().await?;
+    let event = record_audit_event_in(&mut db_tx, context, entry).await?;
+    db_tx.commit().await?;
+
+    Ok(event)
+}
+
+/// Like [`record_audit_event`], for callers that shouldn't fail once the
+/// change itself has gone through; a failure is logged instead
+pub async fn try_record_audit_event(pool: &PgPool, context: &AuditContext, entry: AuditEntry<'_>) {
+    let action = entry.action.to_string();
+    let entity = format!("{} {}", entry.entity_type, entry.entity_id);
+    if let Err(e) = record_audit_event(pool, context, entry).await {
+        eprintln!("❌ Failed to record audit event {} on {}: {}", action, entity, e);
+    }
+}
+
+/// Events matching every given filter, newest first
+pub async fn list_audit_events(pool: &PgPool, query: &AuditEventQuery) -> Result<Vec<AuditEvent>> {
+    let events = sqlx::query_as::<_, AuditEvent>(
+        r#"
+        SELECT * FROM audit_events
+        WHERE ($1::VARCHAR IS NULL OR actor_type = $1)
+          AND ($2::VARCHAR IS NULL OR actor_id = $2)
+          AND ($3::VARCHAR IS NULL OR action = $3)
+          AND ($4::VARCHAR IS NULL OR entity_type = $4)
+          AND ($5::VARCHAR IS NULL OR entity_id = $5)
+          AND ($6::VARCHAR IS NULL OR request_id = $6)
+          AND ($7::VARCHAR IS NULL OR client_request_id = $7)
+          AND ($8::TIMESTAMPTZ IS NULL OR created_at >= $8)
+          AND ($9::TIMESTAMPTZ IS NULL OR created_at < $9)
+          AND ($10::BIGINT IS NULL OR id < $10)
+        ORDER BY id DESC
+        LIMIT $11
+        "#
+    )
+    .bind(&query.actor_type)
+    .bind(&query.actor_id)
+    .bind(&query.action)
+    .bind(&query.entity_type)
+    .bind(&query.entity_id)
+    .bind(&query.request_id)
+    .bind(&query.client_request_id)
+    .bind(query.from)
+    .bind(query.to)
+    .bind(query.before_id)
+    .bind(
+        query
+            .limit
+            .unwrap_or(AUDIT_EVENTS_DEFAULT_LIMIT)
+            .clamp(1, AUDIT_EVENTS_MAX_LIMIT),
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(events)
+}
+
+/// Check each event links to the one before it and still hashes to its
+/// stored `hash`, stopping at the first that doesn't
+pub fn check_audit_chain<'a>(
+    events: impl IntoIterator<Item = &'a AuditEvent>,
+    prev_hash: &str,
+) -> std::result::Result<(), (i64, String)> {
+    let mut prev_hash = prev_hash;
+    for event in events {
+        if event.prev_hash != prev_hash {
+            return Err((event.id, "does not link to the previous event".to_string()));
+        }
+        if audit_event_hash(event) != event.hash {
+            return Err((event.id, "contents do not match its hash".to_string()));
+        }
+        prev_hash = &event.hash;
+    }
+
+    Ok(())
+}
+
+/// Re-hash the whole chain from the first event
+pub async fn verify_audit_chain(pool: &PgPool) -> Result<AuditChainReport> {
+    let mut report = AuditChainReport {
+        valid: true,
+        events_checked: 0,
+        head_id: None,
+        head_hash: None,
+        broken_at: None,
+        problem: None,
+    };
+    let mut after_id = 0;
+    let mut prev_hash = AUDIT_GENESIS_HASH.to_string();
+
+    loop {
+        let events = sqlx::query_as::<_, AuditEvent>(
+            "SELECT * FROM audit_events WHERE id > $1 ORDER BY id LIMIT $2"
+        )
+        .bind(after_id)
+        .bind(AUDIT_VERIFY_BATCH)
+        .fetch_all(pool)
+        .await?;
+        let Some(last) = events.last() else {
+            break;
+        };
+
+        if let Err((id, problem)) = check_audit_chain(&events, &prev_hash) {
+            report.events_checked += events.iter().take_while(|event| event.id < id).count() as i64;
+            report.valid = false;
+            report.broken_at = Some(id);
+            report.problem = Some(format!("Event {} {}", id, problem));
+            eprintln!("🚨 Audit chain broken at event {}: {}", id, problem);
+            return Ok(report);
+        }
+
+        report.events_checked += events.len() as i64;
+        report.head_id = Some(last.id);
+        report.head_hash = Some(last.hash.clone());
+        after_id = last.id;
+        prev_hash = last.hash.clone();
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn event(prev_hash: &str, after: Value) -> AuditEvent {
+        let mut event = AuditEvent {
+            id: 1,
+            actor_type: "admin".to_string(),
+            actor_id: "7d1f0c1e-1c1a-4d0f-8a53-5b0f8c6d2a11".to_string(),
+            action: "whitelist.approved".to_string(),
+            entity_type: "whitelist_application".to_string(),
+            entity_id: "42".to_string(),
+            before: Some(serde_json::json!({ "status": "under_review" })),
+            after: Some(after),
+            request_id: Some("req-1".to_string()),
+            client_request_id: None,
+            ip_address: Some("203.0.113.7".to_string()),
+            created_at: Utc.timestamp_opt(1_700_000_000, 123_456_000).unwrap(),
+            prev_hash: prev_hash.to_string(),
+            hash: String::new(),
+        };
+        event.hash = audit_event_hash(&event);
+        event
+    }
+
+    #[test]
+    fn hash_ignores_key_order() {
+        let a = event(AUDIT_GENESIS_HASH, serde_json::json!({ "status": "approved", "tier": 2 }));
+        let reordered: Value = serde_json::from_str(r#"{"tier": 2, "status": "approved"}"#).unwrap();
+        let b = event(AUDIT_GENESIS_HASH, reordered);
+
+        assert_eq!(a.hash, b.hash);
+        assert_eq!(a.hash.len(), 64);
+    }
+
+    #[test]
+    fn hash_covers_every_field() {
+        let original = event(AUDIT_GENESIS_HASH, serde_json::json!({ "status": "approved" }));
+
+        let mut changed = original.clone();
+        changed.after = Some(serde_json::json!({ "status": "rejected" }));
+        assert_ne!(audit_event_hash(&changed), original.hash);
+
+        let mut changed = original.clone();
+        changed.actor_id = "someone-else".to_string();
+        assert_ne!(audit_event_hash(&changed), original.hash);
+
+        let mut changed = original.clone();
+        changed.created_at += chrono::Duration::microseconds(1);
+        assert_ne!(audit_event_hash(&changed), original.hash);
+
+        let mut changed = original.clone();
+        changed.ip_address = None;
+        assert_ne!(audit_event_hash(&changed), original.hash);
+
+        let mut changed = original.clone();
+        changed.client_request_id = Some("client-1".to_string());
+        assert_ne!(audit_event_hash(&changed), original.hash);
+    }
+
+    #[test]
+    fn chain_detects_tampering() {
+        let first = event(AUDIT_GENESIS_HASH, serde_json::json!({ "status": "approved" }));
+        let mut second = event(&first.hash, serde_json::json!({ "status": "rejected" }));
+        second.id = 2;
+        let mut third = event(&second.hash, serde_json::json!({ "status": "approved" }));
+        third.id = 3;
+
+        let chain = vec![first.clone(), second.clone(), third.clone()];
+        assert_eq!(check_audit_chain(&chain, AUDIT_GENESIS_HASH), Ok(()));
+
+        // Edited in place
+        let mut edited = chain.clone();
+        edited[1].after = Some(serde_json::json!({ "status": "approved" }));
+        assert_eq!(check_audit_chain(&edited, AUDIT_GENESIS_HASH).unwrap_err().0, 2);
+
+        // Edited and re-hashed: the next event no longer links to it
+        edited[1].hash = audit_event_hash(&edited[1]);
+        assert_eq!(check_audit_chain(&edited, AUDIT_GENESIS_HASH).unwrap_err().0, 3);
+
+        // Removed
+        let removed = vec![first, third];
+        assert_eq!(check_audit_chain(&removed, AUDIT_GENESIS_HASH).unwrap_err().0, 3);
+    }
+}
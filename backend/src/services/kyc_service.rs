@@ -0,0 +1,266 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use hmac::{Hmac, Mac};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use sha2::Sha256;
+use sqlx::PgPool;
+use std::fmt;
+use uuid::Uuid;
+
+use crate::models::{KycEvent, KycSession, KycWebhookEvent, PresaleSettings, User};
+use crate::services::{record_audit_event_in, AuditContext, AuditEntry};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// How far a webhook's signed timestamp may drift from server time
+pub const KYC_WEBHOOK_TOLERANCE_SECS: i64 = 300;
+
+/// How long a session token handed to the frontend stays valid
+const KYC_SESSION_TTL_SECS: i64 = 1800;
+
+const KYC_STATUSES: [&str; 3] = ["pending", "approved", "rejected"];
+
+/// Reason a KYC webhook call was refused
+#[derive(Debug, Clone)]
+pub enum KycWebhookRejection {
+    /// Missing, malformed or wrong `X-Kyc-Signature`
+    Signature,
+    /// Signed too long ago (or in the future), so possibly replayed
+    Expired,
+    InvalidPayload(String),
+    UnknownUser,
+}
+
+impl KycWebhookRejection {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Signature => "invalid_signature",
+            Self::Expired => "signature_expired",
+            Self::InvalidPayload(_) => "invalid_payload",
+            Self::UnknownUser => "unknown_user",
+        }
+    }
+}
+
+impl fmt::Display for KycWebhookRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Signature => write!(f, "Invalid webhook signature"),
+            Self::Expired => write!(f, "Webhook signature timestamp out of range"),
+            Self::InvalidPayload(reason) => write!(f, "Invalid webhook payload: {}", reason),
+            Self::UnknownUser => write!(f, "Unknown external_user_id"),
+        }
+    }
+}
+
+impl std::error::Error for KycWebhookRejection {}
+
+fn hmac_hex(secret: &str, message: &[u8]) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(message);
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Check a webhook's `X-Kyc-Signature: t=<unix seconds>,v1=<hex>` header,
+/// where `v1` is the HMAC-SHA256 of `"<t>.<raw body>"` under the shared
+/// secret. Signing the timestamp with the body stops old events from being
+/// replayed.
+pub fn verify_kyc_signature(
+    secret: &str,
+    header: &str,
+    body: &[u8],
+    now: DateTime<Utc>,
+) -> std::result::Result<(), KycWebhookRejection> {
+    let mut timestamp = None;
+    let mut signature = None;
+    for part in header.split(',') {
+        match part.trim().split_once('=') {
+            Some(("t", value)) => timestamp = value.parse::<i64>().ok(),
+            Some(("v1", value)) => signature = Some(value),
+            _ => {}
+        }
+    }
+    let (Some(timestamp), Some(signature)) = (timestamp, signature) else {
+        return Err(KycWebhookRejection::Signature);
+    };
+
+    if (now.timestamp() - timestamp).abs() > KYC_WEBHOOK_TOLERANCE_SECS {
+        return Err(KycWebhookRejection::Expired);
+    }
+
+    let signature = (0..signature.len())
+        .step_by(2)
+        .map(|i| signature.get(i..i + 2).and_then(|byte| u8::from_str_radix(byte, 16).ok()))
+        .collect::<Option<Vec<u8>>>()
+        .ok_or(KycWebhookRejection::Signature)?;
+
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(format!("{}.", timestamp).as_bytes());
+    mac.update(body);
+    // Constant-time comparison
+    mac.verify_slice(&signature).map_err(|_| KycWebhookRejection::Signature)
+}
+
+/// Result of one webhook delivery
+#[derive(Debug, Clone, Serialize)]
+pub struct KycEventOutcome {
+    pub event_id: String,
+    pub kyc_status: String,
+    /// False when a newer event had already been applied
+    pub applied: bool,
+    /// The event had been received before and was ignored
+    pub duplicate: bool,
+}
+
+/// Record a provider event and, unless the user's status is newer, apply
+/// it. Every event is kept in `kyc_events` as the audit trail; redelivered
+/// events (same `event_id`) are recorded once.
+pub async fn apply_kyc_event(
+    pool: &PgPool,
+    provider: &str,
+    event: &KycWebhookEvent,
+    payload: &serde_json::Value,
+    audit: &AuditContext,
+) -> Result<KycEventOutcome> {
+    if !KYC_STATUSES.contains(&event.status.as_str()) {
+        return Err(KycWebhookRejection::InvalidPayload(format!("unknown status {}", event.status)).into());
+    }
+    if event.event_id.is_empty() || event.event_id.len() > 100 {
+        return Err(KycWebhookRejection::InvalidPayload("event_id must be 1 to 100 characters".to_string()).into());
+    }
+    let occurred_at = event.occurred_at.unwrap_or_else(Utc::now);
+
+    let mut db_tx = pool.begin().await?;
+
+    let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = $1 FOR UPDATE")
+        .bind(event.external_user_id)
+        .fetch_optional(&mut *db_tx)
+        .await?
+        .ok_or(KycWebhookRejection::UnknownUser)?;
+
+    // Providers don't guarantee delivery order
+    let applied = user.kyc_updated_at.is_none_or(|updated_at| occurred_at >= updated_at);
+
+    let recorded: Option<Uuid> = sqlx::query_scalar(
+        r#"
+        INSERT INTO kyc_events (
+            event_id, user_id, provider, applicant_id, previous_status, new_status,
+            reason, applied, occurred_at, payload
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        ON CONFLICT (event_id) DO NOTHING
+        RETURNING id
+        "#
+    )
+    .bind(&event.event_id)
+    .bind(user.id)
+    .bind(provider)
+    .bind(&event.applicant_id)
+    .bind(&user.kyc_status)
+    .bind(&event.status)
+    .bind(&event.reason)
+    .bind(applied)
+    .bind(occurred_at)
+    .bind(payload)
+    .fetch_optional(&mut *db_tx)
+    .await?;
+
+    if recorded.is_none() {
+        return Ok(KycEventOutcome {
+            event_id: event.event_id.clone(),
+            kyc_status: user.kyc_status,
+            applied: false,
+            duplicate: true,
+        });
+    }
+
+    let mut kyc_status = user.kyc_status.clone();
+    if applied {
+        sqlx::query(
+            r#"
+            UPDATE users
+            SET kyc_status = $1, kyc_applicant_id = COALESCE($2, kyc_applicant_id),
+                kyc_updated_at = $3, updated_at = NOW()
+            WHERE id = $4
+            "#
+        )
+        .bind(&event.status)
+        .bind(&event.applicant_id)
+        .bind(occurred_at)
+        .bind(user.id)
+        .execute(&mut *db_tx)
+        .await?;
+        kyc_status = event.status.clone();
+
+        if kyc_status != user.kyc_status {
+            let entry = AuditEntry {
+                action: "kyc.status_changed",
+                entity_type: "user",
+                entity_id: user.id.to_string(),
+                before: Some(serde_json::json!({ "kyc_status": user.kyc_status })),
+                after: Some(serde_json::json!({
+                    "kyc_status": kyc_status,
+                    "provider": provider,
+                    "event_id": event.event_id,
+                })),
+            };
+            record_audit_event_in(&mut db_tx, audit, entry).await?;
+        }
+    }
+
+    db_tx.commit().await?;
+
+    if applied && kyc_status != user.kyc_status {
+        println!("🪪 KYC {} -> {} for {}", user.kyc_status, kyc_status, user.wallet_address);
+    }
+
+    Ok(KycEventOutcome {
+        event_id: event.event_id.clone(),
+        kyc_status,
+        applied,
+        duplicate: false,
+    })
+}
+
+/// Session data for the frontend's verification flow, with a token signed
+/// by the webhook secret
+pub fn kyc_session(
+    settings: &PresaleSettings,
+    secret: &str,
+    user: &User,
+    purchased_tokens: Decimal,
+    now: DateTime<Utc>,
+) -> KycSession {
+    let expires_at = now + Duration::seconds(KYC_SESSION_TTL_SECS);
+    let signed = format!("{}.{}.{}", user.id, settings.kyc_level, expires_at.timestamp());
+
+    KycSession {
+        provider: settings.kyc_provider.clone(),
+        level: settings.kyc_level.clone(),
+        external_user_id: user.id,
+        applicant_id: user.kyc_applicant_id.clone(),
+        kyc_status: user.kyc_status.clone(),
+        session_token: format!("{}.{}", expires_at.timestamp(), hmac_hex(secret, signed.as_bytes())),
+        expires_at,
+        required_for_tier: settings.kyc_required_tiers.contains(&user.whitelist_tier),
+        threshold_tokens: settings.kyc_threshold_tokens,
+        purchased_tokens,
+    }
+}
+
+/// A user's KYC audit trail, newest first
+pub async fn list_kyc_events(pool: &PgPool, user_id: &Uuid) -> Result<Vec<KycEvent>> {
+    let events = sqlx::query_as::<_, KycEvent>(
+        "SELECT * FROM kyc_events WHERE user_id = $1 ORDER BY received_at DESC"
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(events)
+}
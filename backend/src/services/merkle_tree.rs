@@ -0,0 +1,202 @@
+//! Merkle tree over whitelist allocations.
+//!
+//! The layout follows the common Solana Merkle distributors so an on-chain
+//! program can verify proofs with `solana_program::keccak`: each leaf is
+//! `keccak(wallet || tier || amount)` with the tier as one byte and the
+//! amount as a little-endian u64, and each parent is the keccak of its two
+//! children in ascending byte order. Because pairs are sorted, a proof is
+//! just the sibling hashes from leaf to root, with no left/right flags. A
+//! node without a sibling moves up a level unchanged.
+
+use solana_sdk::keccak::{hashv, Hash, HASH_BYTES};
+use solana_sdk::pubkey::Pubkey;
+
+/// Leaf committing to one wallet's tier and allocation in token base units
+pub fn allocation_leaf(wallet: &Pubkey, tier: u8, amount: u64) -> Hash {
+    hashv(&[&wallet.to_bytes(), &[tier], &amount.to_le_bytes()])
+}
+
+/// Parent of two nodes, independent of their order
+pub fn hash_pair(a: &Hash, b: &Hash) -> Hash {
+    if a.0 <= b.0 {
+        hashv(&[&a.0, &b.0])
+    } else {
+        hashv(&[&b.0, &a.0])
+    }
+}
+
+/// Lowercase hex, as hashes are stored and served
+pub fn hash_hex(hash: &Hash) -> String {
+    hash.0.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Inverse of [`hash_hex`]
+pub fn parse_hash_hex(hex: &str) -> Option<Hash> {
+    if hex.len() != HASH_BYTES * 2 || !hex.is_ascii() {
+        return None;
+    }
+    let mut bytes = [0u8; HASH_BYTES];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(Hash::new_from_array(bytes))
+}
+
+/// Fold a proof up from `leaf` and compare with `root`
+pub fn verify_proof(proof: &[Hash], root: &Hash, leaf: &Hash) -> bool {
+    proof.iter().fold(*leaf, |node, sibling| hash_pair(&node, sibling)) == *root
+}
+
+#[derive(Debug, Clone)]
+pub struct MerkleTree {
+    /// Leaves first, root last
+    layers: Vec<Vec<Hash>>,
+}
+
+impl MerkleTree {
+    /// Build a tree; leaves are sorted first, so the root doesn't depend on
+    /// the order they were loaded in
+    pub fn new(mut leaves: Vec<Hash>) -> Self {
+        leaves.sort_by_key(|leaf| leaf.0);
+
+        let mut layers = vec![leaves];
+        while let Some(layer) = layers.last().filter(|layer| layer.len() > 1) {
+            let parents = layer
+                .chunks(2)
+                .map(|pair| match pair {
+                    [a, b] => hash_pair(a, b),
+                    [a] => *a,
+                    _ => unreachable!(),
+                })
+                .collect();
+            layers.push(parents);
+        }
+
+        Self { layers }
+    }
+
+    /// Root hash; all zeroes for an empty tree
+    pub fn root(&self) -> Hash {
+        self.layers
+            .last()
+            .and_then(|layer| layer.first())
+            .copied()
+            .unwrap_or(Hash::new_from_array([0; HASH_BYTES]))
+    }
+
+    /// Sibling hashes from `leaf` up to the root, or `None` if the leaf isn't
+    /// in the tree
+    pub fn proof(&self, leaf: &Hash) -> Option<Vec<Hash>> {
+        let mut index = self.layers[0].binary_search_by(|probe| probe.0.cmp(&leaf.0)).ok()?;
+        let mut proof = Vec::with_capacity(self.layers.len());
+
+        for layer in &self.layers[..self.layers.len() - 1] {
+            if let Some(sibling) = layer.get(index ^ 1) {
+                proof.push(*sibling);
+            }
+            index /= 2;
+        }
+
+        Some(proof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: u64 = 1_000_000_000;
+
+    fn wallet(byte: u8) -> Pubkey {
+        Pubkey::new_from_array([byte; 32])
+    }
+
+    /// Leaves for wallets [1; 32], [2; 32] and [3; 32] in tiers 1 to 3 with
+    /// the seeded tier allocations, at 9 decimals
+    fn leaves() -> [Hash; 3] {
+        [
+            allocation_leaf(&wallet(1), 1, 50_000 * TOKEN),
+            allocation_leaf(&wallet(2), 2, 200_000 * TOKEN),
+            allocation_leaf(&wallet(3), 3, 1_000_000 * TOKEN),
+        ]
+    }
+
+    #[test]
+    fn leaf_vectors() {
+        let [a, b, c] = leaves();
+        assert_eq!(hash_hex(&a), "9e0ad523bde7a026019368eb171ed02b8d977ea88eecc41b98bf6f38b035645d");
+        assert_eq!(hash_hex(&b), "296d5848a75b5698a48ff8359103343e07fe66c6ae9c4386455af997c7570076");
+        assert_eq!(hash_hex(&c), "65006bb1dafee6bee1fddd7f8a102e0813936183c4e7b1ce6b409d5b09a9515c");
+    }
+
+    #[test]
+    fn root_vectors() {
+        let [a, b, c] = leaves();
+        assert_eq!(
+            hash_hex(&MerkleTree::new(vec![a, b]).root()),
+            "78233f10c0e7ad1a1aa505bca00ca0f9d27095807fd4ee13d8a30dda9dd64664"
+        );
+        assert_eq!(
+            hash_hex(&MerkleTree::new(vec![a, b, c]).root()),
+            "c209da00c45891e2a711b4572d6d554098bdfafb4e0a9d75d870687a8467e3b8"
+        );
+    }
+
+    #[test]
+    fn hex_round_trips() {
+        let [a, ..] = leaves();
+        assert_eq!(parse_hash_hex(&hash_hex(&a)), Some(a));
+        assert_eq!(parse_hash_hex("zz"), None);
+        assert_eq!(parse_hash_hex(&"g".repeat(64)), None);
+    }
+
+    #[test]
+    fn root_ignores_leaf_order() {
+        let [a, b, c] = leaves();
+        assert_eq!(MerkleTree::new(vec![a, b, c]).root(), MerkleTree::new(vec![c, a, b]).root());
+    }
+
+    #[test]
+    fn proof_vectors() {
+        let [a, b, c] = leaves();
+        let tree = MerkleTree::new(vec![a, b, c]);
+
+        // Sorted leaves are b, c, a: a has no sibling and is promoted
+        assert_eq!(
+            tree.proof(&a).unwrap(),
+            vec![parse_hash_hex("95f3b36421670cf8ba2ce0cab1ea6bd0e0cad799286ab6b5f5d0d3b3e6b28534").unwrap()]
+        );
+        assert_eq!(tree.proof(&b).unwrap(), vec![c, a]);
+        assert_eq!(tree.proof(&c).unwrap(), vec![b, a]);
+    }
+
+    #[test]
+    fn every_proof_verifies() {
+        let leaves: Vec<Hash> = (1..=17).map(|i| allocation_leaf(&wallet(i), i % 4, i as u64 * TOKEN)).collect();
+        let tree = MerkleTree::new(leaves.clone());
+        for leaf in &leaves {
+            assert!(verify_proof(&tree.proof(leaf).unwrap(), &tree.root(), leaf));
+        }
+    }
+
+    #[test]
+    fn proof_rejects_other_allocation() {
+        let [a, b, c] = leaves();
+        let tree = MerkleTree::new(vec![a, b, c]);
+        let inflated = allocation_leaf(&wallet(1), 1, 50_001 * TOKEN);
+        assert!(tree.proof(&inflated).is_none());
+        assert!(!verify_proof(&tree.proof(&a).unwrap(), &tree.root(), &inflated));
+    }
+
+    #[test]
+    fn single_and_empty_trees() {
+        let [a, ..] = leaves();
+        let single = MerkleTree::new(vec![a]);
+        assert_eq!(single.root(), a);
+        assert!(single.proof(&a).unwrap().is_empty());
+
+        let empty = MerkleTree::new(Vec::new());
+        assert_eq!(empty.root(), Hash::new_from_array([0; HASH_BYTES]));
+        assert!(empty.proof(&a).is_none());
+    }
+}
@@ -1,7 +1,53 @@
 pub mod solana_service;
-pub mod user_service;
 pub mod transaction_service;
+pub mod refund_service;
+pub mod supply_service;
+pub mod presale_rules;
+pub mod round_service;
+pub mod settings_service;
+pub mod oracle;
+pub mod vesting_calculator;
+pub mod vesting_service;
+pub mod vesting_worker;
+pub mod token_freeze_service;
+pub mod referral_service;
+pub mod referral_analytics;
+pub mod whitelist_service;
+pub mod whitelist_csv;
+pub mod merkle_tree;
+pub mod whitelist_merkle;
+pub mod kyc_service;
+pub mod screening_service;
+pub mod siws;
+pub mod wallet_auth_service;
+pub mod totp;
+pub mod admin_service;
+pub mod settings_admin;
+pub mod audit_log;
 
 pub use solana_service::*;
-pub use user_service::*;
 pub use transaction_service::*;
+pub use refund_service::*;
+pub use supply_service::*;
+pub use presale_rules::*;
+pub use round_service::*;
+pub use settings_service::*;
+pub use oracle::*;
+pub use vesting_calculator::*;
+pub use vesting_service::*;
+pub use vesting_worker::*;
+pub use token_freeze_service::*;
+pub use referral_service::*;
+pub use referral_analytics::*;
+pub use whitelist_service::*;
+pub use whitelist_csv::*;
+pub use merkle_tree::*;
+pub use whitelist_merkle::*;
+pub use kyc_service::*;
+pub use screening_service::*;
+pub use siws::*;
+pub use wallet_auth_service::*;
+pub use totp::*;
+pub use admin_service::*;
+pub use settings_admin::*;
+pub use audit_log::*;
@@ -0,0 +1,299 @@
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::fmt;
+
+/// Pyth v2 price account header
+const PYTH_MAGIC: u32 = 0xa1b2c3d4;
+const PYTH_VERSION: u32 = 2;
+const PYTH_ACCOUNT_TYPE_PRICE: u32 = 3;
+const PYTH_STATUS_TRADING: u32 = 1;
+
+// Byte offsets into the price account
+const OFFSET_MAGIC: usize = 0;
+const OFFSET_VERSION: usize = 4;
+const OFFSET_ACCOUNT_TYPE: usize = 8;
+const OFFSET_EXPONENT: usize = 20;
+const OFFSET_TIMESTAMP: usize = 96;
+const OFFSET_AGG_PRICE: usize = 208;
+const OFFSET_AGG_CONF: usize = 216;
+const OFFSET_AGG_STATUS: usize = 224;
+const OFFSET_AGG_PUB_SLOT: usize = 232;
+const PRICE_ACCOUNT_MIN_LEN: usize = 240;
+
+/// Aggregate price decoded from a Pyth price account
+#[derive(Debug, Clone, Serialize)]
+pub struct OraclePrice {
+    /// USD per SOL
+    pub price: Decimal,
+    /// Confidence interval, in the same units as `price`
+    pub confidence: Decimal,
+    /// Unix time the aggregate was published
+    pub publish_time: i64,
+    pub publish_slot: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OracleError {
+    InvalidAccount(String),
+    NotTrading { status: u32 },
+    PublishedAfter { publish_time: i64, at: i64 },
+    Stale { age_secs: i64, max_age_secs: i64 },
+    ConfidenceTooWide { confidence_bps: Decimal, max_bps: Decimal },
+}
+
+impl fmt::Display for OracleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidAccount(reason) => write!(f, "Invalid price account: {}", reason),
+            Self::NotTrading { status } => write!(f, "Oracle price not trading (status {})", status),
+            Self::PublishedAfter { publish_time, at } => write!(
+                f,
+                "Oracle price was published at {}, after the payment at {}",
+                publish_time, at
+            ),
+            Self::Stale { age_secs, max_age_secs } => write!(
+                f,
+                "Oracle price is {}s old, older than the {}s limit",
+                age_secs, max_age_secs
+            ),
+            Self::ConfidenceTooWide { confidence_bps, max_bps } => write!(
+                f,
+                "Oracle confidence interval {} bps exceeds {} bps",
+                confidence_bps, max_bps
+            ),
+        }
+    }
+}
+
+impl std::error::Error for OracleError {}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_i32(data: &[u8], offset: usize) -> i32 {
+    i32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+fn read_i64(data: &[u8], offset: usize) -> i64 {
+    i64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+/// Scale a raw Pyth integer by its exponent
+fn scale(value: i128, exponent: i32) -> Result<Decimal, OracleError> {
+    if exponent <= 0 {
+        Decimal::try_from_i128_with_scale(value, exponent.unsigned_abs())
+            .map_err(|e| OracleError::InvalidAccount(e.to_string()))
+    } else {
+        10u64
+            .checked_pow(exponent as u32)
+            .and_then(|factor| Decimal::from_i128_with_scale(value, 0).checked_mul(Decimal::from(factor)))
+            .ok_or_else(|| OracleError::InvalidAccount("price overflow".to_string()))
+    }
+}
+
+impl OraclePrice {
+    /// Decode the aggregate price from raw Pyth price account data
+    pub fn decode_pyth(data: &[u8]) -> Result<Self, OracleError> {
+        if data.len() < PRICE_ACCOUNT_MIN_LEN {
+            return Err(OracleError::InvalidAccount(format!(
+                "expected at least {} bytes, got {}",
+                PRICE_ACCOUNT_MIN_LEN,
+                data.len()
+            )));
+        }
+        if read_u32(data, OFFSET_MAGIC) != PYTH_MAGIC {
+            return Err(OracleError::InvalidAccount("bad magic number".to_string()));
+        }
+        if read_u32(data, OFFSET_VERSION) != PYTH_VERSION {
+            return Err(OracleError::InvalidAccount("unsupported version".to_string()));
+        }
+        if read_u32(data, OFFSET_ACCOUNT_TYPE) != PYTH_ACCOUNT_TYPE_PRICE {
+            return Err(OracleError::InvalidAccount("not a price account".to_string()));
+        }
+
+        let status = read_u32(data, OFFSET_AGG_STATUS);
+        if status != PYTH_STATUS_TRADING {
+            return Err(OracleError::NotTrading { status });
+        }
+
+        let exponent = read_i32(data, OFFSET_EXPONENT);
+        let raw_price = read_i64(data, OFFSET_AGG_PRICE);
+        if raw_price <= 0 {
+            return Err(OracleError::InvalidAccount("non-positive price".to_string()));
+        }
+
+        Ok(Self {
+            price: scale(raw_price as i128, exponent)?,
+            confidence: scale(read_u64(data, OFFSET_AGG_CONF) as i128, exponent)?,
+            publish_time: read_i64(data, OFFSET_TIMESTAMP),
+            publish_slot: read_u64(data, OFFSET_AGG_PUB_SLOT),
+        })
+    }
+
+    /// Confidence interval relative to the price, in basis points
+    pub fn confidence_bps(&self) -> Decimal {
+        self.confidence * Decimal::from(10_000) / self.price
+    }
+
+    /// Reject prices that weren't yet published at `at` (e.g. the payment's
+    /// block time), were published too long before it, or have too wide a
+    /// confidence interval
+    pub fn check_usable(
+        &self,
+        at: i64,
+        max_age_secs: i64,
+        max_confidence_bps: Decimal,
+    ) -> Result<(), OracleError> {
+        if self.publish_time > at {
+            return Err(OracleError::PublishedAfter { publish_time: self.publish_time, at });
+        }
+        let age_secs = at - self.publish_time;
+        if age_secs > max_age_secs {
+            return Err(OracleError::Stale { age_secs, max_age_secs });
+        }
+
+        let confidence_bps = self.confidence_bps();
+        if confidence_bps > max_confidence_bps {
+            return Err(OracleError::ConfidenceTooWide {
+                confidence_bps: confidence_bps.round_dp(2),
+                max_bps: max_confidence_bps,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    const SOL_USD: &[u8] = include_bytes!("../../tests/fixtures/pyth_sol_usd_price_account.bin");
+
+    /// Mainnet Pyth SOL/USD price account
+    const SOL_USD_ACCOUNT: &str = "H6ARHf6YXhGYeQfUzQNGk6rDNnLBQKrenN712K4AQJEG";
+
+    fn fixture() -> Vec<u8> {
+        SOL_USD.to_vec()
+    }
+
+    fn dec(value: &str) -> Decimal {
+        Decimal::from_str(value).unwrap()
+    }
+
+    #[test]
+    fn decodes_fixture() {
+        let price = OraclePrice::decode_pyth(SOL_USD).unwrap();
+        assert_eq!(price.price, dec("145.23"));
+        assert_eq!(price.confidence, dec("0.07"));
+        assert_eq!(price.publish_time, 1_700_000_000);
+        assert_eq!(price.publish_slot, 250_000_000);
+    }
+
+    #[test]
+    fn rejects_truncated_and_foreign_accounts() {
+        let err = OraclePrice::decode_pyth(&SOL_USD[..100]).unwrap_err();
+        assert!(matches!(err, OracleError::InvalidAccount(_)));
+
+        let mut data = fixture();
+        data[0] = 0;
+        assert!(matches!(
+            OraclePrice::decode_pyth(&data).unwrap_err(),
+            OracleError::InvalidAccount(_)
+        ));
+
+        let mut data = fixture();
+        data[OFFSET_ACCOUNT_TYPE..OFFSET_ACCOUNT_TYPE + 4].copy_from_slice(&2u32.to_le_bytes());
+        assert!(matches!(
+            OraclePrice::decode_pyth(&data).unwrap_err(),
+            OracleError::InvalidAccount(_)
+        ));
+    }
+
+    #[test]
+    fn rejects_exponent_out_of_range() {
+        let mut data = fixture();
+        data[OFFSET_EXPONENT..OFFSET_EXPONENT + 4].copy_from_slice(&2i32.to_le_bytes());
+        assert_eq!(OraclePrice::decode_pyth(&data).unwrap().price, dec("1452300000000"));
+
+        for exponent in [20, i32::MAX, -29, i32::MIN] {
+            let mut data = fixture();
+            data[OFFSET_EXPONENT..OFFSET_EXPONENT + 4].copy_from_slice(&exponent.to_le_bytes());
+            assert!(matches!(
+                OraclePrice::decode_pyth(&data).unwrap_err(),
+                OracleError::InvalidAccount(_)
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_halted_price() {
+        let mut data = fixture();
+        data[OFFSET_AGG_STATUS..OFFSET_AGG_STATUS + 4].copy_from_slice(&0u32.to_le_bytes());
+        assert_eq!(
+            OraclePrice::decode_pyth(&data).unwrap_err(),
+            OracleError::NotTrading { status: 0 }
+        );
+    }
+
+    #[test]
+    fn rejects_stale_price() {
+        let price = OraclePrice::decode_pyth(SOL_USD).unwrap();
+        assert!(price.check_usable(1_700_000_030, 60, dec("100")).is_ok());
+        assert_eq!(
+            price.check_usable(1_700_000_090, 60, dec("100")).unwrap_err(),
+            OracleError::Stale { age_secs: 90, max_age_secs: 60 }
+        );
+    }
+
+    #[test]
+    fn rejects_price_published_after_payment() {
+        let price = OraclePrice::decode_pyth(SOL_USD).unwrap();
+        assert_eq!(
+            price.check_usable(1_699_999_990, 60, dec("100")).unwrap_err(),
+            OracleError::PublishedAfter { publish_time: 1_700_000_000, at: 1_699_999_990 }
+        );
+    }
+
+    #[test]
+    fn rejects_wide_confidence() {
+        let mut data = fixture();
+        // 2.9046 USD confidence on 145.23 = 200 bps
+        data[OFFSET_AGG_CONF..OFFSET_AGG_CONF + 8].copy_from_slice(&290_460_000u64.to_le_bytes());
+        let price = OraclePrice::decode_pyth(&data).unwrap();
+
+        assert_eq!(price.confidence_bps(), dec("200"));
+        assert!(price.check_usable(1_700_000_000, 60, dec("200")).is_ok());
+        assert!(matches!(
+            price.check_usable(1_700_000_000, 60, dec("150")).unwrap_err(),
+            OracleError::ConfidenceTooWide { .. }
+        ));
+    }
+
+    /// Needs mainnet RPC access: `cargo test -- --ignored live_sol_usd`.
+    /// With `PYTH_FIXTURE_OUT` set, the dump is also written there, to
+    /// replace the fixture.
+    #[test]
+    #[ignore]
+    fn live_sol_usd_account_decodes() {
+        use solana_client::rpc_client::RpcClient;
+        use solana_sdk::pubkey::Pubkey;
+
+        let client = RpcClient::new("https://api.mainnet-beta.solana.com".to_string());
+        let data = client.get_account_data(&Pubkey::from_str(SOL_USD_ACCOUNT).unwrap()).unwrap();
+        let price = OraclePrice::decode_pyth(&data).unwrap();
+        println!("{:?}", price);
+        assert!(price.price > Decimal::ONE && price.price < dec("10000"));
+        assert!(price.confidence_bps() < dec("100"));
+
+        if let Ok(path) = std::env::var("PYTH_FIXTURE_OUT") {
+            std::fs::write(path, &data).unwrap();
+        }
+    }
+}
@@ -0,0 +1,373 @@
+use anyhow::Result;
+use chrono::{DateTime, TimeZone, Utc};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use sqlx::PgConnection;
+use std::fmt;
+use uuid::Uuid;
+
+use crate::models::{PresaleSettings, WhitelistTier};
+
+/// Why a purchase was refused by the presale rules.
+///
+/// Serialized with a `code` tag so clients can branch on the reason.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum RuleViolation {
+    PresaleNotStarted { starts_at: DateTime<Utc> },
+    PresaleEnded { ended_at: DateTime<Utc> },
+    BlockTimeUnavailable,
+    BelowMinimum { minimum: Decimal },
+    AboveMaximum { maximum: Decimal },
+    WalletCapExceeded { cap: Decimal, remaining: Decimal },
+    NoActiveRound,
+    RoundRequiresTier { round: String, required_tier: i32 },
+    InsufficientPayment { expected: Decimal, paid: Decimal },
+    OraclePriceUnavailable { reason: String },
+    /// `threshold` or `tier` says which rule requires approval
+    KycRequired { kyc_status: String, threshold: Option<Decimal>, tier: Option<i32> },
+}
+
+impl RuleViolation {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::PresaleNotStarted { .. } => "presale_not_started",
+            Self::PresaleEnded { .. } => "presale_ended",
+            Self::BlockTimeUnavailable => "block_time_unavailable",
+            Self::BelowMinimum { .. } => "below_minimum",
+            Self::AboveMaximum { .. } => "above_maximum",
+            Self::WalletCapExceeded { .. } => "wallet_cap_exceeded",
+            Self::NoActiveRound => "no_active_round",
+            Self::RoundRequiresTier { .. } => "round_requires_tier",
+            Self::InsufficientPayment { .. } => "insufficient_payment",
+            Self::OraclePriceUnavailable { .. } => "oracle_price_unavailable",
+            Self::KycRequired { .. } => "kyc_required",
+        }
+    }
+}
+
+impl fmt::Display for RuleViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::PresaleNotStarted { starts_at } => write!(f, "Presale starts at {}", starts_at),
+            Self::PresaleEnded { ended_at } => write!(f, "Presale ended at {}", ended_at),
+            Self::BlockTimeUnavailable => write!(f, "Payment block time not available yet"),
+            Self::BelowMinimum { minimum } => write!(f, "Minimum purchase is {} tokens", minimum),
+            Self::AboveMaximum { maximum } => write!(f, "Maximum purchase is {} tokens", maximum),
+            Self::WalletCapExceeded { cap, remaining } => write!(
+                f,
+                "Wallet purchase cap of {} tokens exceeded. Remaining: {}",
+                cap, remaining
+            ),
+            Self::NoActiveRound => write!(f, "No presale round is currently open"),
+            Self::RoundRequiresTier { round, required_tier } => write!(
+                f,
+                "The {} round requires whitelist tier {} or higher",
+                round, required_tier
+            ),
+            Self::InsufficientPayment { expected, paid } => write!(
+                f,
+                "Insufficient payment: expected {}, got {}",
+                expected, paid
+            ),
+            Self::OraclePriceUnavailable { reason } => {
+                write!(f, "USD price conversion unavailable: {}", reason)
+            }
+            Self::KycRequired { kyc_status, tier: Some(tier), .. } => write!(
+                f,
+                "Whitelist tier {} requires KYC approval (KYC status: {})",
+                tier, kyc_status
+            ),
+            Self::KycRequired { kyc_status, threshold, .. } => write!(
+                f,
+                "Purchases past {} tokens require KYC approval (KYC status: {})",
+                threshold.unwrap_or_default(),
+                kyc_status
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RuleViolation {}
+
+/// Presale window and purchase limits from `presale_settings`
+#[derive(Debug, Clone)]
+pub struct PresaleRules {
+    pub presale_start: DateTime<Utc>,
+    pub presale_end: DateTime<Utc>,
+    pub min_purchase: Decimal,
+    pub max_purchase: Decimal,
+    pub max_wallet_purchase: Option<Decimal>,
+    pub kyc_threshold_tokens: Option<Decimal>,
+    pub kyc_required_tiers: Vec<i32>,
+}
+
+impl PresaleRules {
+    pub fn from_settings(settings: &PresaleSettings) -> Self {
+        Self {
+            presale_start: settings.presale_start,
+            presale_end: settings.presale_end,
+            min_purchase: settings.min_purchase,
+            max_purchase: settings.max_purchase,
+            max_wallet_purchase: settings.max_wallet_purchase,
+            kyc_threshold_tokens: settings.kyc_threshold_tokens,
+            kyc_required_tiers: settings.kyc_required_tiers.clone(),
+        }
+    }
+
+    /// Apply a buyer's tier: its minimum purchase and early access to the
+    /// presale window
+    pub fn for_tier(mut self, tier: Option<&WhitelistTier>) -> Self {
+        if let Some(tier) = tier {
+            if let Some(min_purchase) = tier.min_purchase {
+                self.min_purchase = min_purchase;
+            }
+            self.presale_start -= tier.early_access();
+        }
+        self
+    }
+
+    /// Per-purchase and per-wallet limits
+    pub fn check_amount(
+        &self,
+        amount: Decimal,
+        wallet_purchased: Decimal,
+    ) -> std::result::Result<(), RuleViolation> {
+        if amount < self.min_purchase {
+            return Err(RuleViolation::BelowMinimum { minimum: self.min_purchase });
+        }
+
+        if amount > self.max_purchase {
+            return Err(RuleViolation::AboveMaximum { maximum: self.max_purchase });
+        }
+
+        if let Some(cap) = self.max_wallet_purchase {
+            if wallet_purchased + amount > cap {
+                return Err(RuleViolation::WalletCapExceeded {
+                    cap,
+                    remaining: (cap - wallet_purchased).max(Decimal::ZERO),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// KYC approval, needed by members of the listed tiers and by wallets
+    /// whose purchases would take them past the threshold
+    pub fn check_kyc(
+        &self,
+        kyc_status: &str,
+        tier: i32,
+        amount: Decimal,
+        wallet_purchased: Decimal,
+    ) -> std::result::Result<(), RuleViolation> {
+        if kyc_status == "approved" {
+            return Ok(());
+        }
+
+        if self.kyc_required_tiers.contains(&tier) {
+            return Err(RuleViolation::KycRequired {
+                kyc_status: kyc_status.to_string(),
+                threshold: None,
+                tier: Some(tier),
+            });
+        }
+
+        if let Some(threshold) = self.kyc_threshold_tokens {
+            if wallet_purchased + amount > threshold {
+                return Err(RuleViolation::KycRequired {
+                    kyc_status: kyc_status.to_string(),
+                    threshold: Some(threshold),
+                    tier: None,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Presale window, judged by the payment's on-chain `blockTime`
+    /// rather than the time it reached this server. Returns the payment time.
+    pub fn check_window(
+        &self,
+        block_time: Option<i64>,
+    ) -> std::result::Result<DateTime<Utc>, RuleViolation> {
+        let paid_at = block_time
+            .and_then(|ts| Utc.timestamp_opt(ts, 0).single())
+            .ok_or(RuleViolation::BlockTimeUnavailable)?;
+
+        if paid_at < self.presale_start {
+            return Err(RuleViolation::PresaleNotStarted { starts_at: self.presale_start });
+        }
+
+        if paid_at >= self.presale_end {
+            return Err(RuleViolation::PresaleEnded { ended_at: self.presale_end });
+        }
+
+        Ok(paid_at)
+    }
+}
+
+/// Compare what the buyer paid with the quote for their purchase. Fees are
+/// paid on top of the transfer, so anything short of the quote is refused.
+pub fn check_payment(quoted: Decimal, paid: Decimal) -> std::result::Result<(), RuleViolation> {
+    if paid < quoted {
+        return Err(RuleViolation::InsufficientPayment { expected: quoted, paid });
+    }
+    Ok(())
+}
+
+/// Tokens a wallet has bought or has in flight, for the lifetime cap.
+///
+/// Call with `lock = true` inside the purchase transaction to serialize
+/// concurrent purchases from the same wallet.
+pub async fn wallet_purchased_total(
+    conn: &mut PgConnection,
+    user_id: &Uuid,
+    lock: bool,
+) -> Result<Decimal> {
+    if lock {
+        sqlx::query("SELECT id FROM users WHERE id = $1 FOR UPDATE")
+            .bind(user_id)
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    let total: Decimal = sqlx::query_scalar(
+        r#"
+        SELECT COALESCE(SUM(amount_tokens), 0)
+        FROM transactions
+        WHERE user_id = $1 AND status IN ('pending', 'confirmed')
+        "#
+    )
+    .bind(user_id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use std::str::FromStr;
+
+    fn dec(value: &str) -> Decimal {
+        Decimal::from_str(value).unwrap()
+    }
+
+    fn rules() -> PresaleRules {
+        PresaleRules {
+            presale_start: Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap(),
+            presale_end: Utc.with_ymd_and_hms(2026, 2, 1, 0, 0, 0).unwrap(),
+            min_purchase: dec("100"),
+            max_purchase: dec("10000"),
+            max_wallet_purchase: Some(dec("500")),
+            kyc_threshold_tokens: Some(dec("1000")),
+            kyc_required_tiers: vec![3],
+        }
+    }
+
+    fn tier(min_purchase: Option<&str>, early_access_secs: i32) -> WhitelistTier {
+        WhitelistTier {
+            tier: 2,
+            name: "Gold".to_string(),
+            default_max_allocation: dec("1000"),
+            min_purchase: min_purchase.map(dec),
+            early_access_secs,
+            price_discount_percent: Decimal::ZERO,
+            vesting_tge_unlock_percent: None,
+            vesting_cliff_days: None,
+            vesting_duration_days: None,
+            vesting_release_kind: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn amount_limits_are_inclusive() {
+        let rules = PresaleRules { max_wallet_purchase: None, ..rules() };
+        rules.check_amount(dec("100"), Decimal::ZERO).unwrap();
+        rules.check_amount(dec("10000"), Decimal::ZERO).unwrap();
+        assert!(matches!(
+            rules.check_amount(dec("99.99"), Decimal::ZERO),
+            Err(RuleViolation::BelowMinimum { .. })
+        ));
+        assert!(matches!(
+            rules.check_amount(dec("10000.01"), Decimal::ZERO),
+            Err(RuleViolation::AboveMaximum { .. })
+        ));
+    }
+
+    #[test]
+    fn wallet_cap_allows_reaching_it_exactly() {
+        let rules = rules();
+        rules.check_amount(dec("200"), dec("300")).unwrap();
+        assert!(matches!(
+            rules.check_amount(dec("250"), dec("300")),
+            Err(RuleViolation::WalletCapExceeded { remaining, .. }) if remaining == dec("200")
+        ));
+        // Bought past the cap before it was lowered: nothing remains
+        assert!(matches!(
+            rules.check_amount(dec("100"), dec("600")),
+            Err(RuleViolation::WalletCapExceeded { remaining, .. }) if remaining == Decimal::ZERO
+        ));
+    }
+
+    #[test]
+    fn kyc_is_required_past_the_threshold_and_for_listed_tiers() {
+        let rules = rules();
+        rules.check_kyc("none", 0, dec("400"), dec("600")).unwrap();
+        assert!(matches!(
+            rules.check_kyc("pending", 0, dec("401"), dec("600")),
+            Err(RuleViolation::KycRequired { threshold: Some(_), tier: None, .. })
+        ));
+        assert!(matches!(
+            rules.check_kyc("none", 3, dec("100"), Decimal::ZERO),
+            Err(RuleViolation::KycRequired { threshold: None, tier: Some(3), .. })
+        ));
+        rules.check_kyc("approved", 3, dec("5000"), dec("600")).unwrap();
+    }
+
+    #[test]
+    fn window_includes_start_and_excludes_end() {
+        let rules = rules();
+        let start = rules.presale_start.timestamp();
+        let end = rules.presale_end.timestamp();
+
+        assert_eq!(rules.check_window(Some(start)).unwrap(), rules.presale_start);
+        rules.check_window(Some(end - 1)).unwrap();
+        assert!(matches!(rules.check_window(Some(start - 1)), Err(RuleViolation::PresaleNotStarted { .. })));
+        assert!(matches!(rules.check_window(Some(end)), Err(RuleViolation::PresaleEnded { .. })));
+        assert!(matches!(rules.check_window(None), Err(RuleViolation::BlockTimeUnavailable)));
+    }
+
+    #[test]
+    fn payment_must_cover_the_quote() {
+        check_payment(dec("1.5"), dec("1.5")).unwrap();
+        check_payment(dec("1.5"), dec("2")).unwrap();
+        assert!(matches!(
+            check_payment(dec("1.5"), dec("1.49999999")),
+            Err(RuleViolation::InsufficientPayment { .. })
+        ));
+    }
+
+    #[test]
+    fn tier_shifts_the_start_and_overrides_the_minimum() {
+        let base = rules();
+        let start = base.presale_start;
+        let rules = base.clone().for_tier(Some(&tier(Some("10"), 3600)));
+
+        assert_eq!(rules.presale_start, start - Duration::hours(1));
+        assert_eq!(rules.presale_end, base.presale_end);
+        assert_eq!(rules.min_purchase, dec("10"));
+        rules.check_window(Some(start.timestamp() - 3600)).unwrap();
+
+        let rules = base.clone().for_tier(Some(&tier(None, 0)));
+        assert_eq!((rules.presale_start, rules.min_purchase), (start, base.min_purchase));
+        assert_eq!(base.clone().for_tier(None).presale_start, start);
+    }
+}
@@ -0,0 +1,217 @@
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::Value;
+use solana_sdk::hash::hashv;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::models::{ContributorRanking, LeaderboardQuery, ReferralStats, ReferrerRanking};
+
+pub const LEADERBOARD_DEFAULT_PAGE_SIZE: i64 = 20;
+pub const LEADERBOARD_MAX_PAGE_SIZE: i64 = 100;
+
+/// One page of a leaderboard
+#[derive(Debug, Clone, Serialize)]
+pub struct Leaderboard<T> {
+    pub page: i64,
+    pub per_page: i64,
+    pub has_more: bool,
+    pub entries: Vec<T>,
+}
+
+/// Rendered leaderboard pages, shared through `AppState` so the frontend can
+/// poll without every request re-aggregating the transactions table
+#[derive(Clone, Default)]
+pub struct LeaderboardCache {
+    pages: Arc<RwLock<HashMap<String, (Instant, Value)>>>,
+}
+
+impl LeaderboardCache {
+    /// Cached page if it is younger than `ttl_secs`
+    pub fn get(&self, key: &str, ttl_secs: u64) -> Option<Value> {
+        let pages = self.pages.read().unwrap();
+        pages
+            .get(key)
+            .filter(|(cached_at, _)| cached_at.elapsed() < Duration::from_secs(ttl_secs))
+            .map(|(_, page)| page.clone())
+    }
+
+    /// Store a page, dropping any that have expired
+    pub fn put(&self, key: String, page: Value, ttl_secs: u64) {
+        let mut pages = self.pages.write().unwrap();
+        pages.retain(|_, (cached_at, _)| cached_at.elapsed() < Duration::from_secs(ttl_secs));
+        if ttl_secs > 0 {
+            pages.insert(key, (Instant::now(), page));
+        }
+    }
+}
+
+/// 1-based page and clamped page size from a leaderboard query
+pub fn leaderboard_page(query: &LeaderboardQuery) -> (i64, i64) {
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query
+        .per_page
+        .unwrap_or(LEADERBOARD_DEFAULT_PAGE_SIZE)
+        .clamp(1, LEADERBOARD_MAX_PAGE_SIZE);
+    (page, per_page)
+}
+
+/// Public form of a wallet address: first and last four characters
+pub fn truncate_wallet(wallet: &str) -> String {
+    if wallet.len() <= 8 {
+        return wallet.to_string();
+    }
+    format!("{}…{}", &wallet[..4], &wallet[wallet.len() - 4..])
+}
+
+/// Stable per-visitor key for counting unique clicks without storing the
+/// client's address
+pub fn visitor_hash(client_ip: &str, user_agent: &str) -> String {
+    hashv(&[client_ip.as_bytes(), b"\n", user_agent.as_bytes()])
+        .to_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+pub async fn record_referral_click(
+    pool: &PgPool,
+    referrer_id: &Uuid,
+    referral_code: &str,
+    visitor_hash: &str,
+    landing_page: Option<&str>,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO referral_clicks (referrer_id, referral_code, visitor_hash, landing_page)
+        VALUES ($1, $2, $3, $4)
+        "#
+    )
+    .bind(referrer_id)
+    .bind(referral_code)
+    .bind(visitor_hash)
+    .bind(landing_page)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Clicks, registrations, purchases and earnings attributed to a referrer
+pub async fn get_referral_stats(pool: &PgPool, referrer_id: &Uuid) -> Result<ReferralStats> {
+    let stats = sqlx::query_as::<_, ReferralStats>(
+        r#"
+        WITH referred_purchases AS (
+            SELECT t.user_id, t.amount_tokens, t.amount_sol
+            FROM transactions t
+            JOIN referrals r ON r.referred_id = t.user_id
+            WHERE r.referrer_id = $1 AND t.status = 'confirmed'
+        )
+        SELECT
+            (SELECT COUNT(*) FROM referral_clicks WHERE referrer_id = $1) AS clicks,
+            (SELECT COUNT(DISTINCT visitor_hash) FROM referral_clicks WHERE referrer_id = $1) AS unique_visitors,
+            (SELECT COUNT(*) FROM referrals WHERE referrer_id = $1) AS registrations,
+            (SELECT COUNT(DISTINCT user_id) FROM referred_purchases) AS purchasers,
+            (SELECT COALESCE(SUM(amount_tokens), 0) FROM referred_purchases) AS volume_tokens,
+            (SELECT COALESCE(SUM(amount_sol), 0) FROM referred_purchases) AS volume_sol,
+            (SELECT COALESCE(SUM(amount_tokens), 0) FROM referral_rewards WHERE referrer_id = $1) AS earned_tokens,
+            (SELECT COALESCE(SUM(amount_sol), 0) FROM referral_rewards WHERE referrer_id = $1) AS earned_sol
+        "#
+    )
+    .bind(referrer_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(stats)
+}
+
+/// Referrers ranked by the confirmed purchase volume they brought in
+pub async fn top_referrers(pool: &PgPool, page: i64, per_page: i64) -> Result<Leaderboard<ReferrerRanking>> {
+    let entries = sqlx::query_as::<_, ReferrerRanking>(
+        r#"
+        WITH purchases AS (
+            SELECT user_id, SUM(amount_sol) AS volume_sol
+            FROM transactions
+            WHERE status = 'confirmed'
+            GROUP BY user_id
+        ),
+        referred AS (
+            SELECT r.referrer_id,
+                   COUNT(*) AS registrations,
+                   COUNT(p.user_id) AS purchasers,
+                   COALESCE(SUM(p.volume_sol), 0) AS volume_sol
+            FROM referrals r
+            LEFT JOIN purchases p ON p.user_id = r.referred_id
+            GROUP BY r.referrer_id
+        ),
+        earned AS (
+            SELECT referrer_id, SUM(amount_tokens) AS earned_tokens
+            FROM referral_rewards
+            GROUP BY referrer_id
+        )
+        SELECT RANK() OVER (ORDER BY referred.volume_sol DESC, referred.registrations DESC) AS rank,
+               u.wallet_address AS wallet,
+               u.referral_code,
+               referred.registrations,
+               referred.purchasers,
+               referred.volume_sol,
+               COALESCE(earned.earned_tokens, 0) AS earned_tokens
+        FROM referred
+        JOIN users u ON u.id = referred.referrer_id
+        LEFT JOIN earned ON earned.referrer_id = referred.referrer_id
+        ORDER BY rank, u.id
+        LIMIT $1 OFFSET $2
+        "#
+    )
+    .bind(per_page + 1)
+    .bind((page - 1) * per_page)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(paginate(page, per_page, entries, |entry| &mut entry.wallet))
+}
+
+/// Buyers ranked by confirmed purchase volume
+pub async fn top_contributors(pool: &PgPool, page: i64, per_page: i64) -> Result<Leaderboard<ContributorRanking>> {
+    let entries = sqlx::query_as::<_, ContributorRanking>(
+        r#"
+        SELECT RANK() OVER (ORDER BY SUM(t.amount_sol) DESC) AS rank,
+               u.wallet_address AS wallet,
+               COUNT(*) AS purchases,
+               SUM(t.amount_tokens) AS total_tokens,
+               SUM(t.amount_sol) AS total_sol
+        FROM transactions t
+        JOIN users u ON u.id = t.user_id
+        WHERE t.status = 'confirmed'
+        GROUP BY u.id, u.wallet_address
+        ORDER BY rank, u.id
+        LIMIT $1 OFFSET $2
+        "#
+    )
+    .bind(per_page + 1)
+    .bind((page - 1) * per_page)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(paginate(page, per_page, entries, |entry| &mut entry.wallet))
+}
+
+/// Trim the look-ahead row used to detect a next page, and truncate wallets
+fn paginate<T>(page: i64, per_page: i64, mut entries: Vec<T>, wallet: impl Fn(&mut T) -> &mut String) -> Leaderboard<T> {
+    let has_more = entries.len() as i64 > per_page;
+    entries.truncate(per_page as usize);
+    for entry in &mut entries {
+        let wallet = wallet(entry);
+        *wallet = truncate_wallet(wallet);
+    }
+
+    Leaderboard {
+        page,
+        per_page,
+        has_more,
+        entries,
+    }
+}
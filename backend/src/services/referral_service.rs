@@ -0,0 +1,1001 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use solana_sdk::hash::Hash;
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+use solana_sdk::signature::Signature;
+use sqlx::{FromRow, PgConnection, PgPool};
+use std::fmt;
+use std::str::FromStr;
+use uuid::Uuid;
+
+use crate::models::{
+    CommissionCurrency, DistributionMode, PresaleSettings, Referral, ReferralReward, RoundFill, User,
+};
+use crate::services::{
+    create_vesting_schedules, record_audit_event_in, wallet_purchased_total, AuditContext, AuditEntry, DenylistService, ScreeningRejection,
+    ScreeningRole, SendOutcome, SolanaService,
+};
+
+/// Serializes attributions so two users can't refer each other concurrently
+const ATTRIBUTION_LOCK_KEY: i64 = 0x5245_4645_5252_414c; // "REFERRAL"
+
+/// Reason a referral code could not be attributed to a user
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReferralRejection {
+    UnknownCode,
+    SelfReferral,
+    /// The referrer was (directly or indirectly) referred by this user
+    Cycle,
+    /// The user already has a different referrer
+    AlreadyReferred,
+    /// The user bought without a code, so attribution is closed
+    AlreadyPurchased,
+}
+
+impl ReferralRejection {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::UnknownCode => "unknown_referral_code",
+            Self::SelfReferral => "self_referral",
+            Self::Cycle => "referral_cycle",
+            Self::AlreadyReferred => "already_referred",
+            Self::AlreadyPurchased => "already_purchased",
+        }
+    }
+}
+
+impl fmt::Display for ReferralRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownCode => write!(f, "Unknown referral code"),
+            Self::SelfReferral => write!(f, "You can't use your own referral code"),
+            Self::Cycle => write!(f, "Referral code belongs to a wallet you referred"),
+            Self::AlreadyReferred => write!(f, "Wallet already has a referrer"),
+            Self::AlreadyPurchased => write!(f, "Referral codes can only be applied before the first purchase"),
+        }
+    }
+}
+
+impl std::error::Error for ReferralRejection {}
+
+/// Referral codes are generated upper case; accept any case and padding
+pub fn normalize_referral_code(code: &str) -> String {
+    code.trim().to_uppercase()
+}
+
+/// Codes that could pass for an official link or break routing, also
+/// when spelled with digits for letters
+const RESERVED_CODES: &[&str] = &[
+    "ADMIN", "API", "CLICK", "CODE", "HELP", "MOD", "MODERATOR", "NULL", "OFFICIAL", "PRESALE",
+    "REFERRAL", "REWARDS", "ROOT", "SHIBARTUM", "STAFF", "SUPPORT", "SYSTEM", "TEAM",
+    "UNDEFINED", "WHITELIST",
+];
+
+/// Matched anywhere in a code, after undoing digit-for-letter substitutions
+const BLOCKED_WORDS: &[&str] = &[
+    "ASSHOLE", "BASTARD", "BITCH", "COCK", "CUNT", "DICK", "FAGGOT", "FUCK", "HITLER", "NAZI",
+    "NIGGA", "NIGGER", "PORN", "PUSSY", "RAPE", "RETARD", "SCAM", "SHIT", "SLUT", "WHORE",
+];
+
+/// Reason a custom referral code can't be claimed
+#[derive(Debug, Clone)]
+pub enum ReferralCodeRejection {
+    Length { min: usize, max: usize },
+    /// Only letters and digits are allowed
+    Characters,
+    Reserved,
+    Profane,
+    Taken,
+    /// The wallet already replaced its generated code
+    AlreadyClaimed,
+}
+
+impl ReferralCodeRejection {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Length { .. } => "invalid_length",
+            Self::Characters => "invalid_characters",
+            Self::Reserved => "reserved_code",
+            Self::Profane => "blocked_word",
+            Self::Taken => "code_taken",
+            Self::AlreadyClaimed => "code_already_claimed",
+        }
+    }
+}
+
+impl fmt::Display for ReferralCodeRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Length { min, max } => write!(f, "Referral code must be {} to {} characters", min, max),
+            Self::Characters => write!(f, "Referral code may only contain letters and digits"),
+            Self::Reserved => write!(f, "Referral code is reserved"),
+            Self::Profane => write!(f, "Referral code contains a blocked word"),
+            Self::Taken => write!(f, "Referral code is already taken"),
+            Self::AlreadyClaimed => write!(f, "Wallet already claimed a custom referral code"),
+        }
+    }
+}
+
+impl std::error::Error for ReferralCodeRejection {}
+
+/// Normalize a requested custom code and check it against the length
+/// limits, reserved words and the blocklist
+pub fn validate_vanity_code(code: &str, settings: &PresaleSettings) -> std::result::Result<String, ReferralCodeRejection> {
+    let code = normalize_referral_code(code);
+    let (min, max) = (settings.vanity_code_min_length, settings.vanity_code_max_length);
+
+    if code.len() < min || code.len() > max {
+        return Err(ReferralCodeRejection::Length { min, max });
+    }
+    if !code.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(ReferralCodeRejection::Characters);
+    }
+    let plain: String = code
+        .chars()
+        .map(|c| match c {
+            '0' => 'O',
+            '1' => 'I',
+            '3' => 'E',
+            '4' => 'A',
+            '5' => 'S',
+            '7' => 'T',
+            c => c,
+        })
+        .collect();
+    if RESERVED_CODES.iter().any(|word| code == *word || plain == *word) {
+        return Err(ReferralCodeRejection::Reserved);
+    }
+    if BLOCKED_WORDS.iter().any(|word| code.contains(word) || plain.contains(word)) {
+        return Err(ReferralCodeRejection::Profane);
+    }
+
+    Ok(code)
+}
+
+/// Replace a user's generated referral code with a custom one. Links using
+/// the old code stop resolving, so each wallet gets one claim.
+/// Rejections are returned as a [`ReferralCodeRejection`] inside the error.
+pub async fn claim_referral_code(pool: &PgPool, user_id: &Uuid, code: &str, settings: &PresaleSettings) -> Result<User> {
+    let code = validate_vanity_code(code, settings)?;
+
+    let claimed = sqlx::query_as::<_, User>(
+        r#"
+        UPDATE users
+        SET referral_code = $1, referral_code_claimed_at = NOW(), updated_at = NOW()
+        WHERE id = $2 AND referral_code_claimed_at IS NULL
+        RETURNING *
+        "#
+    )
+    .bind(&code)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await;
+
+    match claimed {
+        Ok(Some(user)) => {
+            println!("🏷️  {} claimed referral code {}", user.wallet_address, code);
+            Ok(user)
+        }
+        Ok(None) => Err(ReferralCodeRejection::AlreadyClaimed.into()),
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Err(ReferralCodeRejection::Taken.into()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The user a referral code belongs to
+pub async fn find_referrer(pool: &PgPool, referral_code: &str) -> Result<Option<User>> {
+    let referrer = sqlx::query_as::<_, User>("SELECT * FROM users WHERE referral_code = $1")
+        .bind(normalize_referral_code(referral_code))
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(referrer)
+}
+
+/// Attribute `user` to the owner of `referral_code`: set `referred_by` and
+/// insert the `referrals` row. Returns the updated user.
+///
+/// Re-applying the code of the existing referrer is a no-op; any other code
+/// on an attributed user is rejected, so attribution never changes, and so
+/// is a first code on a wallet that already bought. Rejections are returned as a [`ReferralRejection`] inside the error.
+pub async fn attribute_referral(pool: &PgPool, user_id: &Uuid, referral_code: &str) -> Result<User> {
+    let mut db_tx = pool.begin().await?;
+    let user = attribute_referral_in(&mut db_tx, user_id, referral_code).await?;
+    db_tx.commit().await?;
+
+    println!("🤝 {} referred with code {}", user.wallet_address, normalize_referral_code(referral_code));
+    Ok(user)
+}
+
+/// Whether a user, currently referred by `referred_by`, may be attributed
+/// to `referrer_id`, whose chain of referrers is `referrer_upline`. Returns
+/// `false` when the referrer is already the user's and nothing changes.
+pub fn check_attribution(
+    user_id: &Uuid,
+    referred_by: Option<Uuid>,
+    has_purchased: bool,
+    referrer_id: &Uuid,
+    referrer_upline: &[Uuid],
+) -> std::result::Result<bool, ReferralRejection> {
+    if let Some(existing) = referred_by {
+        if existing == *referrer_id {
+            return Ok(false);
+        }
+        return Err(ReferralRejection::AlreadyReferred);
+    }
+    // A purchase without a code already settled who (if anyone) is rewarded
+    if has_purchased {
+        return Err(ReferralRejection::AlreadyPurchased);
+    }
+    if referrer_id == user_id {
+        return Err(ReferralRejection::SelfReferral);
+    }
+    if referrer_upline.contains(user_id) {
+        return Err(ReferralRejection::Cycle);
+    }
+    Ok(true)
+}
+
+/// [`attribute_referral`] inside the caller's transaction, e.g. a purchase,
+/// so the attribution commits or rolls back with it. Takes the attribution
+/// lock and then the user's row, so call it before locking that row.
+pub async fn attribute_referral_in(conn: &mut PgConnection, user_id: &Uuid, referral_code: &str) -> Result<User> {
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+        .bind(ATTRIBUTION_LOCK_KEY)
+        .execute(&mut *conn)
+        .await?;
+
+    let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = $1 FOR UPDATE")
+        .bind(user_id)
+        .fetch_one(&mut *conn)
+        .await?;
+
+    let referrer = sqlx::query_as::<_, User>("SELECT * FROM users WHERE referral_code = $1")
+        .bind(normalize_referral_code(referral_code))
+        .fetch_optional(&mut *conn)
+        .await?
+        .ok_or(ReferralRejection::UnknownCode)?;
+
+    let has_purchased = wallet_purchased_total(&mut *conn, &user.id, false).await? > Decimal::ZERO;
+    let referrer_upline = referrer_chain(&mut *conn, &referrer.id).await?;
+    if !check_attribution(&user.id, user.referred_by, has_purchased, &referrer.id, &referrer_upline)? {
+        return Ok(user);
+    }
+
+    let user = sqlx::query_as::<_, User>(
+        r#"
+        UPDATE users SET referred_by = $1, updated_at = NOW()
+        WHERE id = $2 AND referred_by IS NULL
+        RETURNING *
+        "#
+    )
+    .bind(referrer.id)
+    .bind(user.id)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO referrals (referrer_id, referred_id)
+        VALUES ($1, $2)
+        "#
+    )
+    .bind(referrer.id)
+    .bind(user.id)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(user)
+}
+
+/// Everyone who referred `user_id`, directly or through other users
+async fn referrer_chain(conn: &mut PgConnection, user_id: &Uuid) -> Result<Vec<Uuid>> {
+    let chain: Vec<Uuid> = sqlx::query_scalar(
+        r#"
+        WITH RECURSIVE upline AS (
+            SELECT referred_by FROM users WHERE id = $1
+            UNION
+            SELECT u.referred_by FROM users u JOIN upline ON u.id = upline.referred_by
+        )
+        SELECT referred_by FROM upline WHERE referred_by IS NOT NULL
+        "#
+    )
+    .bind(user_id)
+    .fetch_all(conn)
+    .await?;
+
+    Ok(chain)
+}
+
+/// A referrer's reward on each fill of a purchase, vesting on the same terms
+pub fn referral_bonus_fills(
+    fills: &[RoundFill],
+    rate_percent: Decimal,
+    mode: DistributionMode,
+) -> Vec<RoundFill> {
+    fills
+        .iter()
+        .map(|fill| RoundFill {
+            tokens: (fill.tokens * rate_percent / Decimal::ONE_HUNDRED).round_dp(8),
+            distribution_mode: mode.as_str().to_string(),
+            ..fill.clone()
+        })
+        .filter(|fill| fill.tokens > Decimal::ZERO)
+        .collect()
+}
+
+/// One referrer in a buyer's upline with the terms that apply to them
+#[derive(Debug, FromRow)]
+struct UplineReferrer {
+    referral_id: Uuid,
+    referrer_id: Uuid,
+    wallet_address: String,
+    level: i32,
+    bonus_percentage: Option<Decimal>,
+    referral_rate_percent: Option<Decimal>,
+    referral_payout_currency: Option<String>,
+}
+
+impl UplineReferrer {
+    /// A direct referrer not yet attributed to the buyer, e.g. the owner of
+    /// the code they are about to use; `None` stands for any referrer
+    fn prospective(referrer: Option<&User>) -> Self {
+        Self {
+            referral_id: Uuid::nil(),
+            referrer_id: referrer.map(|referrer| referrer.id).unwrap_or_default(),
+            wallet_address: referrer.map(|referrer| referrer.wallet_address.clone()).unwrap_or_default(),
+            level: 1,
+            bonus_percentage: None,
+            referral_rate_percent: referrer.and_then(|referrer| referrer.referral_rate_percent),
+            referral_payout_currency: referrer.and_then(|referrer| referrer.referral_payout_currency.clone()),
+        }
+    }
+
+    /// Direct referrers earn the referral's rate, else their negotiated
+    /// rate, else the global bonus; higher levels earn the upline rates
+    fn rate(&self, settings: &PresaleSettings) -> Decimal {
+        if self.level == 1 {
+            self.bonus_percentage
+                .or(self.referral_rate_percent)
+                .unwrap_or(settings.referral_bonus)
+        } else {
+            settings
+                .referral_upline_rates
+                .get(self.level as usize - 2)
+                .copied()
+                .unwrap_or(Decimal::ZERO)
+        }
+    }
+
+    fn currency(&self, settings: &PresaleSettings) -> CommissionCurrency {
+        self.referral_payout_currency
+            .as_deref()
+            .and_then(|currency| currency.parse().ok())
+            .unwrap_or(settings.referral_payout_currency)
+    }
+}
+
+/// Rate and currency of the direct commission a purchase would earn,
+/// resolved like [`record_referral_rewards`]: for `wallet`'s referrer if it
+/// has one, else for the owner of `referral_code`, else for a referrer with
+/// no negotiated rate. `None` when the wallet has no referrer and no code is
+/// given. An unknown code is returned as a [`ReferralRejection`].
+pub async fn preview_direct_commission(
+    pool: &PgPool,
+    settings: &PresaleSettings,
+    wallet: Option<&str>,
+    referral_code: Option<&str>,
+) -> Result<Option<(Decimal, CommissionCurrency)>> {
+    if let Some(wallet) = wallet {
+        let referrer = sqlx::query_as::<_, UplineReferrer>(
+            r#"
+            SELECT r.id AS referral_id, r.referrer_id, u.wallet_address, 1 AS level,
+                   r.bonus_percentage, u.referral_rate_percent, u.referral_payout_currency
+            FROM users buyer
+            JOIN referrals r ON r.referred_id = buyer.id AND r.is_active = true
+            JOIN users u ON u.id = r.referrer_id
+            WHERE buyer.wallet_address = $1
+            "#
+        )
+        .bind(wallet)
+        .fetch_optional(pool)
+        .await?;
+        if let Some(referrer) = referrer {
+            return Ok(Some((referrer.rate(settings), referrer.currency(settings))));
+        }
+        if referral_code.is_none() {
+            return Ok(None);
+        }
+    }
+
+    let referrer = match referral_code {
+        Some(code) => Some(find_referrer(pool, code).await?.ok_or(ReferralRejection::UnknownCode)?),
+        None => None,
+    };
+    let referrer = UplineReferrer::prospective(referrer.as_ref());
+    Ok(Some((referrer.rate(settings), referrer.currency(settings))))
+}
+
+/// Credit the buyer's upline for a purchase, as part of the purchase's
+/// database transaction: one ledger entry per referrer, up to
+/// `1 + referral_upline_rates.len()` levels.
+///
+/// Token commissions are paid out as vesting schedules on the purchase's
+/// round terms; SOL commissions are a share of `purchase_sol`, left pending
+/// for the distribution worker. Purchases already rewarded are skipped, and
+/// so are denylisted referrers (recorded as blocked).
+#[allow(clippy::too_many_arguments)]
+pub async fn record_referral_rewards(
+    conn: &mut PgConnection,
+    settings: &PresaleSettings,
+    denylist: &DenylistService,
+    referred_id: &Uuid,
+    transaction_id: &Uuid,
+    fills: &[RoundFill],
+    purchase_sol: Decimal,
+    start_date: DateTime<Utc>,
+) -> Result<Vec<ReferralReward>> {
+    let max_level = 1 + settings.referral_upline_rates.len() as i32;
+    let upline = sqlx::query_as::<_, UplineReferrer>(
+        r#"
+        WITH RECURSIVE upline AS (
+            SELECT r.id AS referral_id, r.referrer_id, r.bonus_percentage, 1 AS level
+            FROM referrals r
+            WHERE r.referred_id = $1 AND r.is_active = true
+            UNION ALL
+            SELECT r.id, r.referrer_id, r.bonus_percentage, upline.level + 1
+            FROM referrals r
+            JOIN upline ON r.referred_id = upline.referrer_id
+            WHERE r.is_active = true AND upline.level < $2
+        )
+        SELECT upline.referral_id, upline.referrer_id, u.wallet_address, upline.level,
+               upline.bonus_percentage, u.referral_rate_percent, u.referral_payout_currency
+        FROM upline
+        JOIN users u ON u.id = upline.referrer_id
+        ORDER BY upline.level
+        "#
+    )
+    .bind(referred_id)
+    .bind(max_level)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    let purchase_tokens: Decimal = fills.iter().map(|fill| fill.tokens).sum();
+    let mut rewards = Vec::with_capacity(upline.len());
+
+    for referrer in upline {
+        let rate = referrer.rate(settings);
+        let currency = referrer.currency(settings);
+        if rate <= Decimal::ZERO {
+            continue;
+        }
+
+        let transaction_ref = transaction_id.to_string();
+        match denylist
+            .screen(&mut *conn, &referrer.wallet_address, ScreeningRole::Referrer, "purchase", Some(&transaction_ref))
+            .await
+        {
+            Ok(()) => {}
+            Err(e) if e.downcast_ref::<ScreeningRejection>().is_some() => continue,
+            Err(e) => return Err(e),
+        }
+
+        let (reward_fills, amount_tokens, amount_sol) = match currency {
+            CommissionCurrency::Token => {
+                let reward_fills = referral_bonus_fills(fills, rate, settings.referral_reward_mode);
+                let amount: Decimal = reward_fills.iter().map(|fill| fill.tokens).sum();
+                (reward_fills, amount, Decimal::ZERO)
+            }
+            CommissionCurrency::Sol => {
+                let amount = (purchase_sol * rate / Decimal::ONE_HUNDRED).round_dp(8);
+                (Vec::new(), Decimal::ZERO, amount)
+            }
+        };
+        if amount_tokens <= Decimal::ZERO && amount_sol <= Decimal::ZERO {
+            continue;
+        }
+
+        let reward = sqlx::query_as::<_, ReferralReward>(
+            r#"
+            INSERT INTO referral_rewards (
+                referral_id, referrer_id, referred_id, transaction_id, level, currency,
+                purchase_tokens, purchase_sol, rate_percent, amount_tokens, amount_sol, payout_status
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+            ON CONFLICT (transaction_id, referrer_id) DO NOTHING
+            RETURNING *
+            "#
+        )
+        .bind(referrer.referral_id)
+        .bind(referrer.referrer_id)
+        .bind(referred_id)
+        .bind(transaction_id)
+        .bind(referrer.level)
+        .bind(currency.as_str())
+        .bind(purchase_tokens)
+        .bind(purchase_sol)
+        .bind(rate)
+        .bind(amount_tokens)
+        .bind(amount_sol)
+        .bind((currency == CommissionCurrency::Sol).then_some("pending"))
+        .fetch_optional(&mut *conn)
+        .await?;
+        let reward = match reward {
+            Some(reward) => reward,
+            None => continue,
+        };
+
+        if !reward_fills.is_empty() {
+            create_vesting_schedules(
+                conn,
+                &referrer.referrer_id,
+                transaction_id,
+                &reward_fills,
+                Some(&reward.id),
+                start_date,
+            )
+            .await?;
+        }
+
+        sqlx::query("UPDATE referrals SET bonus_tokens = bonus_tokens + $1 WHERE id = $2")
+            .bind(amount_tokens)
+            .bind(referrer.referral_id)
+            .execute(&mut *conn)
+            .await?;
+
+        rewards.push(reward);
+    }
+
+    Ok(rewards)
+}
+
+/// A referrer's ledger, newest first
+pub async fn get_referral_rewards(pool: &PgPool, referrer_id: &Uuid) -> Result<Vec<ReferralReward>> {
+    let rewards = sqlx::query_as::<_, ReferralReward>(
+        "SELECT * FROM referral_rewards WHERE referrer_id = $1 ORDER BY created_at DESC"
+    )
+    .bind(referrer_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rewards)
+}
+
+/// Send pending SOL commissions, one transfer per ledger entry. Each payout
+/// is marked `sending` under its pre-signed transaction's signature before it
+/// goes out, so a crash can't pay a commission twice. Commissions owed to
+/// denylisted wallets are marked `blocked` instead. Returns how many were paid.
+pub async fn pay_referral_commissions(
+    pool: &PgPool,
+    solana_service: &SolanaService,
+    denylist: &DenylistService,
+    limit: i64,
+    audit: &AuditContext,
+) -> Result<usize> {
+    let mut paid = 0;
+
+    for _ in 0..limit {
+        let mut db_tx = pool.begin().await?;
+
+        let pending = sqlx::query_as::<_, (Uuid, String, Decimal)>(
+            r#"
+            SELECT rr.id, u.wallet_address, rr.amount_sol
+            FROM referral_rewards rr
+            JOIN users u ON u.id = rr.referrer_id
+            WHERE rr.payout_status = 'pending'
+            ORDER BY rr.created_at
+            LIMIT 1
+            FOR UPDATE OF rr SKIP LOCKED
+            "#
+        )
+        .fetch_optional(&mut *db_tx)
+        .await?;
+        let (reward_id, wallet_address, amount_sol) = match pending {
+            Some(pending) => pending,
+            None => break,
+        };
+
+        // Commissions owed to a wallet listed since are never sent
+        let reward_ref = reward_id.to_string();
+        match denylist
+            .screen(pool, &wallet_address, ScreeningRole::Referrer, "commission", Some(&reward_ref))
+            .await
+        {
+            Ok(()) => {}
+            Err(e) if e.downcast_ref::<ScreeningRejection>().is_some() => {
+                sqlx::query("UPDATE referral_rewards SET payout_status = 'blocked', updated_at = NOW() WHERE id = $1")
+                    .bind(reward_id)
+                    .execute(&mut *db_tx)
+                    .await?;
+                let entry = AuditEntry {
+                    action: "commission.blocked",
+                    entity_type: "referral_reward",
+                    entity_id: reward_id.to_string(),
+                    before: Some(serde_json::json!({ "payout_status": "pending" })),
+                    after: Some(serde_json::json!({
+                        "payout_status": "blocked",
+                        "wallet_address": wallet_address,
+                        "amount_sol": amount_sol,
+                    })),
+                };
+                record_audit_event_in(&mut db_tx, audit, entry).await?;
+                db_tx.commit().await?;
+                continue;
+            }
+            Err(e) => return Err(e),
+        }
+
+        let lamports = (amount_sol * Decimal::from(LAMPORTS_PER_SOL))
+            .trunc()
+            .to_u64()
+            .unwrap_or(0);
+        let transaction = match solana_service.build_sol_transfer(&wallet_address, lamports).await {
+            Ok(transaction) => transaction,
+            Err(e) => {
+                // Most likely the RPC node; the entry stays pending for the next run
+                eprintln!("Failed to prepare referral commission {} to {}: {}", reward_id, wallet_address, e);
+                break;
+            }
+        };
+        let signature = transaction.signatures[0].to_string();
+
+        sqlx::query(
+            r#"
+            UPDATE referral_rewards
+            SET payout_status = 'sending', payout_signature = $1, payout_blockhash = $2, updated_at = NOW()
+            WHERE id = $3
+            "#
+        )
+        .bind(&signature)
+        .bind(transaction.message.recent_blockhash.to_string())
+        .bind(reward_id)
+        .execute(&mut *db_tx)
+        .await?;
+        let entry = AuditEntry {
+            action: "commission.sending",
+            entity_type: "referral_reward",
+            entity_id: reward_id.to_string(),
+            before: Some(serde_json::json!({ "payout_status": "pending" })),
+            after: Some(serde_json::json!({
+                "payout_status": "sending",
+                "wallet_address": wallet_address,
+                "amount_sol": amount_sol,
+                "payout_signature": signature,
+            })),
+        };
+        record_audit_event_in(&mut db_tx, audit, entry).await?;
+        db_tx.commit().await?;
+
+        match solana_service.send_prepared(&transaction).await {
+            SendOutcome::Confirmed => {
+                settle_commission(pool, &signature, true, audit).await?;
+                println!("✅ Paid {} SOL referral commission to {}, signature: {}", amount_sol, wallet_address, signature);
+                paid += 1;
+            }
+            SendOutcome::Failed(reason) => {
+                settle_commission(pool, &signature, false, audit).await?;
+                eprintln!("Referral commission to {} failed: {}", wallet_address, reason);
+            }
+            // Left as sending for the next reconciliation
+            SendOutcome::Unknown => {}
+        }
+    }
+
+    Ok(paid)
+}
+
+/// Settle commission payouts whose send outcome was never recorded
+pub async fn reconcile_referral_commissions(
+    pool: &PgPool,
+    solana_service: &SolanaService,
+    older_than: DateTime<Utc>,
+    audit: &AuditContext,
+) -> Result<()> {
+    let sending = sqlx::query_as::<_, (String, Option<String>)>(
+        r#"
+        SELECT payout_signature, payout_blockhash FROM referral_rewards
+        WHERE payout_status = 'sending' AND payout_signature IS NOT NULL AND updated_at < $1
+        "#
+    )
+    .bind(older_than)
+    .fetch_all(pool)
+    .await?;
+
+    for (signature, blockhash) in sending {
+        let parsed = (Signature::from_str(&signature), blockhash.as_deref().map(Hash::from_str));
+        let outcome = match parsed {
+            (Ok(sig), Some(Ok(blockhash))) => solana_service.check_signature(&sig, &blockhash),
+            _ => {
+                eprintln!("Can't reconcile referral commission {}: missing or invalid blockhash", signature);
+                continue;
+            }
+        };
+
+        match outcome {
+            SendOutcome::Confirmed => settle_commission(pool, &signature, true, audit).await?,
+            SendOutcome::Failed(reason) => {
+                settle_commission(pool, &signature, false, audit).await?;
+                println!("↩️  Referral commission {} returned to pending: {}", signature, reason);
+            }
+            SendOutcome::Unknown => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Mark a commission payout paid, or put it back in the queue
+async fn settle_commission(pool: &PgPool, signature: &str, landed: bool, audit: &AuditContext) -> Result<()> {
+    let query = if landed {
+        r#"
+        UPDATE referral_rewards SET payout_status = 'paid', paid_at = NOW(), updated_at = NOW()
+        WHERE payout_signature = $1
+        RETURNING id
+        "#
+    } else {
+        r#"
+        UPDATE referral_rewards
+        SET payout_status = 'pending', payout_signature = NULL, payout_blockhash = NULL, updated_at = NOW()
+        WHERE payout_signature = $1 AND payout_status = 'sending'
+        RETURNING id
+        "#
+    };
+
+    let mut db_tx = pool.begin().await?;
+    let settled: Vec<Uuid> = sqlx::query_scalar(query).bind(signature).fetch_all(&mut *db_tx).await?;
+    for reward_id in settled {
+        let entry = AuditEntry {
+            action: if landed { "commission.paid" } else { "commission.returned" },
+            entity_type: "referral_reward",
+            entity_id: reward_id.to_string(),
+            before: Some(serde_json::json!({ "payout_status": "sending", "payout_signature": signature })),
+            after: Some(serde_json::json!({ "payout_status": if landed { "paid" } else { "pending" } })),
+        };
+        record_audit_event_in(&mut db_tx, audit, entry).await?;
+    }
+    db_tx.commit().await?;
+
+    Ok(())
+}
+
+/// Set or clear a referrer's negotiated commission rate and payout currency
+pub async fn set_referrer_commission(
+    pool: &PgPool,
+    wallet_address: &str,
+    rate_percent: Option<Decimal>,
+    payout_currency: Option<CommissionCurrency>,
+    audit: &AuditContext,
+) -> Result<Option<User>> {
+    let mut db_tx = pool.begin().await?;
+
+    let previous = sqlx::query_as::<_, User>("SELECT * FROM users WHERE wallet_address = $1 FOR UPDATE")
+        .bind(wallet_address)
+        .fetch_optional(&mut *db_tx)
+        .await?;
+    let Some(previous) = previous else {
+        return Ok(None);
+    };
+
+    let user = sqlx::query_as::<_, User>(
+        r#"
+        UPDATE users
+        SET referral_rate_percent = $1, referral_payout_currency = $2, updated_at = NOW()
+        WHERE id = $3
+        RETURNING *
+        "#
+    )
+    .bind(rate_percent)
+    .bind(payout_currency.map(|currency| currency.as_str()))
+    .bind(previous.id)
+    .fetch_one(&mut *db_tx)
+    .await?;
+
+    let terms = |user: &User| {
+        serde_json::json!({
+            "referral_rate_percent": user.referral_rate_percent,
+            "referral_payout_currency": user.referral_payout_currency,
+        })
+    };
+    let entry = AuditEntry {
+        action: "referral.commission_terms_set",
+        entity_type: "user",
+        entity_id: user.id.to_string(),
+        before: Some(terms(&previous)),
+        after: Some(terms(&user)),
+    };
+    record_audit_event_in(&mut db_tx, audit, entry).await?;
+    db_tx.commit().await?;
+
+    Ok(Some(user))
+}
+
+/// Set or clear the direct commission rate of the referral that brought in
+/// `referred_wallet`
+pub async fn set_referral_rate(
+    pool: &PgPool,
+    referred_wallet: &str,
+    rate_percent: Option<Decimal>,
+    audit: &AuditContext,
+) -> Result<Option<Referral>> {
+    let mut db_tx = pool.begin().await?;
+
+    let previous = sqlx::query_as::<_, Referral>(
+        r#"
+        SELECT * FROM referrals
+        WHERE referred_id = (SELECT id FROM users WHERE wallet_address = $1)
+        FOR UPDATE
+        "#
+    )
+    .bind(referred_wallet)
+    .fetch_optional(&mut *db_tx)
+    .await?;
+    let Some(previous) = previous else {
+        return Ok(None);
+    };
+
+    let referral = sqlx::query_as::<_, Referral>(
+        "UPDATE referrals SET bonus_percentage = $1 WHERE id = $2 RETURNING *"
+    )
+    .bind(rate_percent)
+    .bind(previous.id)
+    .fetch_one(&mut *db_tx)
+    .await?;
+
+    let entry = AuditEntry {
+        action: "referral.rate_set",
+        entity_type: "referral",
+        entity_id: referral.id.to_string(),
+        before: Some(serde_json::json!({ "bonus_percentage": previous.bonus_percentage })),
+        after: Some(serde_json::json!({ "bonus_percentage": referral.bonus_percentage })),
+    };
+    record_audit_event_in(&mut db_tx, audit, entry).await?;
+    db_tx.commit().await?;
+
+    Ok(Some(referral))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn dec(value: &str) -> Decimal {
+        Decimal::from_str(value).unwrap()
+    }
+
+    fn user(n: u128) -> Uuid {
+        Uuid::from_u128(n)
+    }
+
+    fn settings(referral_bonus: &str, upline_rates: &str) -> PresaleSettings {
+        let values: HashMap<&str, &str> = [
+            ("max_supply", "1000000"),
+            ("presale_start", "2026-01-01T00:00:00Z"),
+            ("presale_end", "2026-02-01T00:00:00Z"),
+            ("min_purchase", "1"),
+            ("max_purchase", "1000"),
+            ("whitelist_enabled", "false"),
+            ("referral_bonus", referral_bonus),
+            ("referral_upline_rates", upline_rates),
+            ("hard_cap_tokens", "1000000"),
+            ("hard_cap_sol", "1000"),
+            ("soft_cap_sol", "100"),
+            ("cap_overflow_mode", "reject"),
+        ]
+        .into_iter()
+        .collect();
+        PresaleSettings::from_map(&values).unwrap()
+    }
+
+    fn referrer(level: i32, bonus_percentage: Option<&str>, referral_rate_percent: Option<&str>) -> UplineReferrer {
+        UplineReferrer {
+            referral_id: Uuid::nil(),
+            referrer_id: Uuid::nil(),
+            wallet_address: String::new(),
+            level,
+            bonus_percentage: bonus_percentage.map(dec),
+            referral_rate_percent: referral_rate_percent.map(dec),
+            referral_payout_currency: None,
+        }
+    }
+
+    #[test]
+    fn vanity_codes() {
+        let settings = settings("5", "2,1");
+        let cases = [
+            // Case folding and padding
+            ("moonboy", Ok("MOONBOY")),
+            ("  MoonBoy7 ", Ok("MOONBOY7")),
+            // Length limits, 4 to 16 by default, counted after trimming
+            ("abc", Err("invalid_length")),
+            (" abc ", Err("invalid_length")),
+            ("abcd", Ok("ABCD")),
+            ("abcdefghijklmnop", Ok("ABCDEFGHIJKLMNOP")),
+            ("abcdefghijklmnopq", Err("invalid_length")),
+            ("", Err("invalid_length")),
+            // Letters and digits only
+            ("moon-boy", Err("invalid_characters")),
+            ("moon boy", Err("invalid_characters")),
+            ("möönboy", Err("invalid_characters")),
+            // Reserved words, in any case and with digits for letters
+            ("admin", Err("reserved_code")),
+            ("Official", Err("reserved_code")),
+            ("0FF1C14L", Err("reserved_code")),
+            ("SUPP0RT", Err("reserved_code")),
+            ("ADMINS", Ok("ADMINS")),
+            // Blocked words, anywhere in the code and with digits for letters
+            ("scam", Err("blocked_word")),
+            ("xxscamxx", Err("blocked_word")),
+            ("5CAM", Err("blocked_word")),
+            ("N4Z1GANG", Err("blocked_word")),
+            // Digits that aren't look-alikes stay as they are
+            ("SC8M", Ok("SC8M")),
+        ];
+        for (input, expected) in cases {
+            let result = validate_vanity_code(input, &settings);
+            assert_eq!(result.as_deref().map_err(ReferralCodeRejection::code), expected, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn new_user_is_attributed() {
+        // B was referred by C; A joins with B's code
+        assert_eq!(check_attribution(&user(1), None, false, &user(2), &[user(3)]), Ok(true));
+    }
+
+    #[test]
+    fn own_code_is_rejected() {
+        assert_eq!(
+            check_attribution(&user(1), None, false, &user(1), &[]),
+            Err(ReferralRejection::SelfReferral)
+        );
+    }
+
+    #[test]
+    fn code_from_own_downline_is_a_cycle() {
+        // A referred B: B's code can't be applied to A
+        assert_eq!(
+            check_attribution(&user(1), None, false, &user(2), &[user(1)]),
+            Err(ReferralRejection::Cycle)
+        );
+        // Nor can the code of someone further down A's downline
+        assert_eq!(
+            check_attribution(&user(1), None, false, &user(3), &[user(2), user(1), user(9)]),
+            Err(ReferralRejection::Cycle)
+        );
+    }
+
+    #[test]
+    fn attribution_never_changes() {
+        assert_eq!(check_attribution(&user(1), Some(user(2)), true, &user(2), &[]), Ok(false));
+        assert_eq!(
+            check_attribution(&user(1), Some(user(2)), false, &user(3), &[]),
+            Err(ReferralRejection::AlreadyReferred)
+        );
+    }
+
+    #[test]
+    fn first_code_after_a_purchase_is_rejected() {
+        assert_eq!(
+            check_attribution(&user(1), None, true, &user(2), &[]),
+            Err(ReferralRejection::AlreadyPurchased)
+        );
+    }
+
+    #[test]
+    fn direct_rate_prefers_the_referral_then_the_referrer() {
+        let settings = settings("5", "2,1");
+        assert_eq!(referrer(1, Some("12"), Some("8")).rate(&settings), dec("12"));
+        assert_eq!(referrer(1, None, Some("8")).rate(&settings), dec("8"));
+        assert_eq!(referrer(1, None, None).rate(&settings), dec("5"));
+    }
+
+    #[test]
+    fn upline_rates_ignore_negotiated_rates() {
+        let settings = settings("5", "2,1");
+        assert_eq!(referrer(2, Some("12"), Some("8")).rate(&settings), dec("2"));
+        assert_eq!(referrer(3, None, None).rate(&settings), dec("1"));
+        assert_eq!(referrer(4, None, Some("8")).rate(&settings), Decimal::ZERO);
+    }
+}
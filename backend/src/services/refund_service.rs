@@ -0,0 +1,491 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use solana_sdk::hash::Hash;
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+use solana_sdk::signature::Signature;
+use sqlx::{PgConnection, PgPool};
+use std::str::FromStr;
+use uuid::Uuid;
+
+use crate::models::PaymentRefund;
+use crate::services::{
+    record_audit_event_in, AuditContext, AuditEntry, DenylistService, ScreeningRejection, ScreeningRole,
+    SendOutcome, SolanaService,
+};
+
+/// Advisory lock class for per-payment locks; the second key is a hash of
+/// the payment signature. Two-key locks never collide with the single-key
+/// ones used elsewhere.
+const PAYMENT_LOCK_CLASS: i32 = 0x5041_594d; // "PAYM"
+
+/// A step in a refund's life. Refunds are queued `pending`, then either
+/// `withheld` for review or sent: `sending` ends `refunded`, or back in
+/// `pending` if the transfer failed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RefundEvent {
+    /// The destination is on the denylist
+    Withheld(String),
+    /// Recorded under its pre-signed signature, about to be sent
+    Sending,
+    /// The transfer landed
+    Sent,
+    /// The transfer definitely didn't land
+    Returned(String),
+}
+
+impl RefundEvent {
+    /// How a send settles a refund; an unknown outcome leaves it `sending`
+    /// for reconciliation
+    pub fn settling(outcome: SendOutcome) -> Option<Self> {
+        match outcome {
+            SendOutcome::Confirmed => Some(Self::Sent),
+            SendOutcome::Failed(reason) => Some(Self::Returned(reason)),
+            SendOutcome::Unknown => None,
+        }
+    }
+
+    /// How screening the destination affects a refund about to be sent.
+    /// Errors other than a denylist match are passed on.
+    pub fn screening(result: Result<()>) -> Result<Option<Self>> {
+        match result {
+            Ok(()) => Ok(None),
+            Err(e) if e.downcast_ref::<ScreeningRejection>().is_some() => Ok(Some(Self::Withheld(e.to_string()))),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The status a refund must be in for this event to apply
+    pub fn status_before(&self) -> &'static str {
+        match self {
+            Self::Withheld(_) | Self::Sending => "pending",
+            Self::Sent | Self::Returned(_) => "sending",
+        }
+    }
+
+    pub fn status_after(&self) -> &'static str {
+        match self {
+            Self::Withheld(_) => "withheld",
+            Self::Sending => "sending",
+            Self::Sent => "refunded",
+            Self::Returned(_) => "pending",
+        }
+    }
+
+    pub fn audit_action(&self) -> &'static str {
+        match self {
+            Self::Withheld(_) => "refund.withheld",
+            Self::Sending => "refund.sending",
+            Self::Sent => "refund.sent",
+            Self::Returned(_) => "refund.returned",
+        }
+    }
+
+    /// Error recorded on the refund after this event
+    pub fn last_error(&self) -> Option<&str> {
+        match self {
+            Self::Withheld(reason) | Self::Returned(reason) => Some(reason),
+            Self::Sending | Self::Sent => None,
+        }
+    }
+}
+
+/// A refund amount as queued: SOL has 9 decimals
+pub fn refund_amount(amount_sol: Decimal) -> Decimal {
+    amount_sol.round_dp(9)
+}
+
+/// Lamports sent for a queued refund
+pub fn refund_lamports(amount_sol: Decimal) -> u64 {
+    (amount_sol * Decimal::from(LAMPORTS_PER_SOL)).trunc().to_u64().unwrap_or(0)
+}
+
+/// Whether a payment was already sold against or refunded
+pub async fn payment_processed(conn: &mut PgConnection, payment_signature: &str) -> Result<bool> {
+    let processed: bool = sqlx::query_scalar(
+        r#"
+        SELECT EXISTS (SELECT 1 FROM transactions WHERE solana_signature = $1)
+            OR EXISTS (SELECT 1 FROM payment_refunds WHERE payment_signature = $1)
+        "#
+    )
+    .bind(payment_signature)
+    .fetch_one(conn)
+    .await?;
+
+    Ok(processed)
+}
+
+/// Lock a payment for the rest of the surrounding transaction, so it can't
+/// be both sold against and refunded by concurrent confirmations. Returns
+/// whether it was already processed.
+pub async fn lock_payment(conn: &mut PgConnection, payment_signature: &str) -> Result<bool> {
+    sqlx::query("SELECT pg_advisory_xact_lock($1, hashtext($2))")
+        .bind(PAYMENT_LOCK_CLASS)
+        .bind(payment_signature)
+        .execute(&mut *conn)
+        .await?;
+
+    payment_processed(conn, payment_signature).await
+}
+
+/// Queue SOL to be sent back for a payment. The caller holds the payment's
+/// lock and audits the refund with whatever it belongs to.
+pub async fn queue_payment_refund_in(
+    conn: &mut PgConnection,
+    payment_signature: &str,
+    wallet_address: &str,
+    transaction_id: Option<&Uuid>,
+    amount_sol: Decimal,
+    reason: &str,
+) -> Result<PaymentRefund> {
+    let refund = sqlx::query_as::<_, PaymentRefund>(
+        r#"
+        INSERT INTO payment_refunds (payment_signature, wallet_address, transaction_id, amount_sol, reason)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING *
+        "#
+    )
+    .bind(payment_signature)
+    .bind(wallet_address)
+    .bind(transaction_id)
+    .bind(refund_amount(amount_sol))
+    .bind(reason)
+    .fetch_one(conn)
+    .await?;
+
+    Ok(refund)
+}
+
+/// Refund the whole of a verified payment that was turned away. Returns
+/// `None` when the payment was already sold against or refunded.
+pub async fn refund_rejected_payment(
+    pool: &PgPool,
+    payment_signature: &str,
+    wallet_address: &str,
+    amount_sol: Decimal,
+    reason: &str,
+    audit: &AuditContext,
+) -> Result<Option<PaymentRefund>> {
+    let mut db_tx = pool.begin().await?;
+
+    if lock_payment(&mut db_tx, payment_signature).await? {
+        return Ok(None);
+    }
+    let refund = queue_payment_refund_in(&mut db_tx, payment_signature, wallet_address, None, amount_sol, reason).await?;
+
+    let entry = AuditEntry {
+        action: "payment.rejected",
+        entity_type: "payment_refund",
+        entity_id: refund.id.to_string(),
+        before: None,
+        after: Some(serde_json::json!({
+            "payment_signature": payment_signature,
+            "wallet_address": wallet_address,
+            "amount_sol": refund.amount_sol,
+            "reason": reason,
+            "status": refund.status,
+        })),
+    };
+    record_audit_event_in(&mut db_tx, audit, entry).await?;
+    db_tx.commit().await?;
+
+    log::info!("Payment {} rejected ({}), queued for refund", payment_signature, reason);
+    Ok(Some(refund))
+}
+
+/// Send up to `limit` queued refunds. Returns how many landed.
+///
+/// Each transfer is recorded under its pre-signed signature before it is
+/// sent, like commission payouts, so a crash or an undecided send is
+/// reconciled later instead of being sent twice.
+pub async fn send_payment_refunds(
+    pool: &PgPool,
+    solana_service: &SolanaService,
+    denylist: &DenylistService,
+    limit: i64,
+    audit: &AuditContext,
+) -> Result<usize> {
+    let mut refunded = 0;
+
+    for _ in 0..limit {
+        let mut db_tx = pool.begin().await?;
+
+        let pending = sqlx::query_as::<_, PaymentRefund>(
+            r#"
+            SELECT * FROM payment_refunds
+            WHERE status = 'pending'
+            ORDER BY updated_at
+            LIMIT 1
+            FOR UPDATE SKIP LOCKED
+            "#
+        )
+        .fetch_optional(&mut *db_tx)
+        .await?;
+        let Some(refund) = pending else {
+            break;
+        };
+
+        // Refunds to a listed wallet are held for review instead of sent
+        let refund_ref = refund.id.to_string();
+        let screened = denylist
+            .screen(pool, &refund.wallet_address, ScreeningRole::RefundDestination, "refund", Some(&refund_ref))
+            .await;
+        if let Some(event) = RefundEvent::screening(screened)? {
+            sqlx::query(
+                "UPDATE payment_refunds SET status = $1, last_error = $2, updated_at = NOW() WHERE id = $3"
+            )
+            .bind(event.status_after())
+            .bind(event.last_error())
+            .bind(refund.id)
+            .execute(&mut *db_tx)
+            .await?;
+            let entry = AuditEntry {
+                action: event.audit_action(),
+                entity_type: "payment_refund",
+                entity_id: refund.id.to_string(),
+                before: Some(serde_json::json!({ "status": event.status_before() })),
+                after: Some(serde_json::json!({
+                    "status": event.status_after(),
+                    "wallet_address": refund.wallet_address,
+                    "amount_sol": refund.amount_sol,
+                    "reason": event.last_error(),
+                })),
+            };
+            record_audit_event_in(&mut db_tx, audit, entry).await?;
+            db_tx.commit().await?;
+            eprintln!("Refund of {} SOL to {} withheld", refund.amount_sol, refund.wallet_address);
+            continue;
+        }
+
+        let lamports = refund_lamports(refund.amount_sol);
+        let transaction = match solana_service.build_sol_transfer(&refund.wallet_address, lamports).await {
+            Ok(transaction) => transaction,
+            Err(e) => {
+                // Most likely the RPC node; the refund stays pending for the next run
+                eprintln!("Failed to prepare refund {} to {}: {}", refund.id, refund.wallet_address, e);
+                break;
+            }
+        };
+        let signature = transaction.signatures[0].to_string();
+
+        let event = RefundEvent::Sending;
+        sqlx::query(
+            r#"
+            UPDATE payment_refunds
+            SET status = $1, refund_signature = $2, refund_blockhash = $3,
+                attempts = attempts + 1, updated_at = NOW()
+            WHERE id = $4
+            "#
+        )
+        .bind(event.status_after())
+        .bind(&signature)
+        .bind(transaction.message.recent_blockhash.to_string())
+        .bind(refund.id)
+        .execute(&mut *db_tx)
+        .await?;
+        let entry = AuditEntry {
+            action: event.audit_action(),
+            entity_type: "payment_refund",
+            entity_id: refund.id.to_string(),
+            before: Some(serde_json::json!({ "status": event.status_before() })),
+            after: Some(serde_json::json!({
+                "status": event.status_after(),
+                "wallet_address": refund.wallet_address,
+                "amount_sol": refund.amount_sol,
+                "refund_signature": signature,
+                "attempt": refund.attempts + 1,
+            })),
+        };
+        record_audit_event_in(&mut db_tx, audit, entry).await?;
+        db_tx.commit().await?;
+
+        // An unknown outcome is left as sending for the next reconciliation
+        match RefundEvent::settling(solana_service.send_prepared(&transaction).await) {
+            Some(RefundEvent::Sent) => {
+                settle_refund(pool, &signature, RefundEvent::Sent, audit).await?;
+                println!("✅ Refunded {} SOL to {}, signature: {}", refund.amount_sol, refund.wallet_address, signature);
+                refunded += 1;
+            }
+            Some(event) => {
+                eprintln!("Refund to {} failed: {}", refund.wallet_address, event.last_error().unwrap_or_default());
+                settle_refund(pool, &signature, event, audit).await?;
+            }
+            None => {}
+        }
+    }
+
+    Ok(refunded)
+}
+
+/// Settle refunds whose send outcome was never recorded
+pub async fn reconcile_payment_refunds(
+    pool: &PgPool,
+    solana_service: &SolanaService,
+    older_than: DateTime<Utc>,
+    audit: &AuditContext,
+) -> Result<()> {
+    let sending = sqlx::query_as::<_, (String, Option<String>)>(
+        r#"
+        SELECT refund_signature, refund_blockhash FROM payment_refunds
+        WHERE status = 'sending' AND refund_signature IS NOT NULL AND updated_at < $1
+        "#
+    )
+    .bind(older_than)
+    .fetch_all(pool)
+    .await?;
+
+    for (signature, blockhash) in sending {
+        let parsed = (Signature::from_str(&signature), blockhash.as_deref().map(Hash::from_str));
+        let outcome = match parsed {
+            (Ok(sig), Some(Ok(blockhash))) => solana_service.check_signature(&sig, &blockhash),
+            _ => {
+                eprintln!("Can't reconcile refund {}: missing or invalid blockhash", signature);
+                continue;
+            }
+        };
+
+        match RefundEvent::settling(outcome) {
+            Some(RefundEvent::Sent) => settle_refund(pool, &signature, RefundEvent::Sent, audit).await?,
+            Some(event) => {
+                println!("↩️  Refund {} returned to pending: {}", signature, event.last_error().unwrap_or_default());
+                settle_refund(pool, &signature, event, audit).await?;
+            }
+            None => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Mark a refund sent, copying the signature onto its purchase if it has
+/// one, or put it back in the queue with the failure recorded
+async fn settle_refund(pool: &PgPool, signature: &str, event: RefundEvent, audit: &AuditContext) -> Result<()> {
+    let mut db_tx = pool.begin().await?;
+
+    // A returned refund drops its signature so the next run sends it afresh
+    let settled = sqlx::query_as::<_, PaymentRefund>(
+        r#"
+        UPDATE payment_refunds
+        SET status = $3, last_error = $4, updated_at = NOW(),
+            refunded_at = CASE WHEN $3 = 'refunded' THEN NOW() ELSE refunded_at END,
+            refund_signature = CASE WHEN $3 = 'refunded' THEN refund_signature END,
+            refund_blockhash = CASE WHEN $3 = 'refunded' THEN refund_blockhash END
+        WHERE refund_signature = $1 AND status = $2
+        RETURNING *
+        "#
+    )
+    .bind(signature)
+    .bind(event.status_before())
+    .bind(event.status_after())
+    .bind(event.last_error())
+    .fetch_optional(&mut *db_tx)
+    .await?;
+    let Some(refund) = settled else {
+        return Ok(());
+    };
+
+    if let (RefundEvent::Sent, Some(transaction_id)) = (&event, refund.transaction_id) {
+        sqlx::query("UPDATE transactions SET refund_signature = $1, updated_at = NOW() WHERE id = $2")
+            .bind(signature)
+            .bind(transaction_id)
+            .execute(&mut *db_tx)
+            .await?;
+    }
+
+    let entry = AuditEntry {
+        action: event.audit_action(),
+        entity_type: "payment_refund",
+        entity_id: refund.id.to_string(),
+        before: Some(serde_json::json!({ "status": event.status_before(), "refund_signature": signature })),
+        after: Some(serde_json::json!({
+            "status": refund.status,
+            "transaction_id": refund.transaction_id,
+            "amount_sol": refund.amount_sol,
+            "last_error": refund.last_error,
+        })),
+    };
+    record_audit_event_in(&mut db_tx, audit, entry).await?;
+    db_tx.commit().await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    fn dec(value: &str) -> Decimal {
+        Decimal::from_str(value).unwrap()
+    }
+
+    /// Follow `events` from a freshly queued refund; `None` once an event
+    /// meets a refund in a status it doesn't apply to
+    fn walk(events: &[RefundEvent]) -> Option<&'static str> {
+        events.iter().try_fold("pending", |status, event| {
+            (event.status_before() == status).then(|| event.status_after())
+        })
+    }
+
+    #[test]
+    fn queued_amounts_keep_lamport_precision() {
+        assert_eq!(refund_amount(dec("0.1234567894")), dec("0.123456789"));
+        assert_eq!(refund_amount(dec("0.1234567895")), dec("0.123456790"));
+        assert_eq!(refund_lamports(refund_amount(dec("0.3"))), 300_000_000);
+        assert_eq!(refund_lamports(dec("0.0000000019")), 1);
+    }
+
+    #[test]
+    fn send_outcomes_settle_a_sending_refund() {
+        assert_eq!(RefundEvent::settling(SendOutcome::Confirmed), Some(RefundEvent::Sent));
+        assert_eq!(
+            RefundEvent::settling(SendOutcome::Failed("blockhash expired".to_string())),
+            Some(RefundEvent::Returned("blockhash expired".to_string()))
+        );
+        assert_eq!(RefundEvent::settling(SendOutcome::Unknown), None);
+    }
+
+    #[test]
+    fn sent_refund_ends_refunded() {
+        assert_eq!(walk(&[RefundEvent::Sending, RefundEvent::Sent]), Some("refunded"));
+        assert_eq!(RefundEvent::Sent.audit_action(), "refund.sent");
+        assert_eq!(RefundEvent::Sent.last_error(), None);
+    }
+
+    #[test]
+    fn failed_refund_is_queued_again_and_retried() {
+        let failed = RefundEvent::Returned("insufficient funds".to_string());
+        assert_eq!(walk(&[RefundEvent::Sending, failed.clone()]), Some("pending"));
+        assert_eq!(failed.audit_action(), "refund.returned");
+        assert_eq!(failed.last_error(), Some("insufficient funds"));
+
+        assert_eq!(
+            walk(&[RefundEvent::Sending, failed, RefundEvent::Sending, RefundEvent::Sent]),
+            Some("refunded")
+        );
+    }
+
+    #[test]
+    fn listed_destination_is_withheld_before_sending() {
+        let rejection = anyhow::Error::new(ScreeningRejection { role: ScreeningRole::RefundDestination });
+        let event = RefundEvent::screening(Err(rejection)).unwrap().unwrap();
+
+        assert!(matches!(event, RefundEvent::Withheld(_)));
+        assert_eq!(walk(std::slice::from_ref(&event)), Some("withheld"));
+        assert_eq!(walk(&[event.clone(), RefundEvent::Sending]), None);
+        assert_eq!(event.audit_action(), "refund.withheld");
+        assert!(event.last_error().is_some());
+
+        assert_eq!(RefundEvent::screening(Ok(())).unwrap(), None);
+        assert!(RefundEvent::screening(Err(anyhow!("database unavailable"))).is_err());
+    }
+
+    #[test]
+    fn reconciliation_settles_only_refunds_still_sending() {
+        // A late outcome can't reopen a refund that was already settled
+        let expired = RefundEvent::Returned("blockhash expired".to_string());
+        assert_eq!(walk(&[RefundEvent::Sending, RefundEvent::Sent, expired.clone()]), None);
+        assert_eq!(walk(&[RefundEvent::Sending, expired.clone(), RefundEvent::Sent]), None);
+        assert_eq!(walk(&[expired]), None);
+    }
+}
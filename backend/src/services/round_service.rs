@@ -0,0 +1,376 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use rust_decimal::Decimal;
+use sqlx::{PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::models::{OraclePriceQuote, PresaleRound, PricingCurrency, RoundFill, WhitelistTier};
+use crate::services::{OraclePrice, RuleViolation, SettingsService, SolanaService};
+
+/// Seconds between oracle samples; well inside the default 60s staleness
+/// limit, so every payment has a usable price recorded shortly before it
+const ORACLE_SAMPLE_INTERVAL_SECS: u64 = 10;
+
+/// All rounds in sequence order
+pub async fn list_rounds(pool: &PgPool) -> Result<Vec<PresaleRound>> {
+    let rounds = sqlx::query_as::<_, PresaleRound>(
+        "SELECT * FROM presale_rounds ORDER BY sequence"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rounds)
+}
+
+/// All rounds in sequence order, locked for the surrounding transaction so
+/// concurrent purchases see each other's allocation
+pub async fn lock_rounds(conn: &mut PgConnection) -> Result<Vec<PresaleRound>> {
+    let rounds = sqlx::query_as::<_, PresaleRound>(
+        "SELECT * FROM presale_rounds ORDER BY sequence FOR UPDATE"
+    )
+    .fetch_all(conn)
+    .await?;
+
+    Ok(rounds)
+}
+
+/// Record an oracle price, sampled by the server or quoted to a buyer. Each
+/// published price is stored once, so repeated reads of the same slot return
+/// the same row.
+pub async fn record_price_quote(pool: &PgPool, price_account: &str, price: &OraclePrice) -> Result<OraclePriceQuote> {
+    let publish_time = Utc
+        .timestamp_opt(price.publish_time, 0)
+        .single()
+        .ok_or_else(|| anyhow!("Invalid oracle publish time {}", price.publish_time))?;
+
+    let quote = sqlx::query_as::<_, OraclePriceQuote>(
+        r#"
+        INSERT INTO oracle_price_quotes (price_account, price, confidence, publish_time, publish_slot)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (price_account, publish_slot) DO UPDATE SET price_account = EXCLUDED.price_account
+        RETURNING *
+        "#
+    )
+    .bind(price_account)
+    .bind(price.price.round_dp(8))
+    .bind(price.confidence.round_dp(8))
+    .bind(publish_time)
+    .bind(price.publish_slot as i64)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(quote)
+}
+
+/// Record the oracle's SOL price every few seconds while rounds are priced
+/// in USD, so purchases can be converted at a price the server read itself
+/// rather than one a buyer happened to ask for
+pub fn spawn_oracle_sampler(pool: PgPool, solana_service: SolanaService, settings: SettingsService) {
+    tokio::spawn(async move {
+        loop {
+            let current = settings.current();
+            if let (PricingCurrency::Usd, Some(price_account)) = (current.pricing_currency, current.oracle_price_account) {
+                let sampled = match solana_service.get_oracle_price(&price_account).await {
+                    Ok(price) => record_price_quote(&pool, &price_account.to_string(), &price).await.map(|_| ()),
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = sampled {
+                    eprintln!("Oracle sampling failed: {}", e);
+                }
+            }
+
+            tokio::time::sleep(std::time::Duration::from_secs(ORACLE_SAMPLE_INTERVAL_SECS)).await;
+        }
+    });
+}
+
+/// The latest recorded price from `price_account` published no later than `at`
+pub async fn latest_price_quote(
+    pool: &PgPool,
+    price_account: &str,
+    at: DateTime<Utc>,
+) -> Result<Option<OraclePriceQuote>> {
+    let quote = sqlx::query_as::<_, OraclePriceQuote>(
+        r#"
+        SELECT * FROM oracle_price_quotes
+        WHERE price_account = $1 AND publish_time <= $2
+        ORDER BY publish_time DESC, publish_slot DESC
+        LIMIT 1
+        "#
+    )
+    .bind(price_account)
+    .bind(at)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(quote)
+}
+
+/// Index of the round currently selling at `at`.
+///
+/// The first open round is active once its start time has passed, or early
+/// if the round before it sold out its allocation. Buyers with early access
+/// may enter a round that much before its start time.
+pub fn active_round_index(rounds: &[PresaleRound], at: DateTime<Utc>, early_access: Duration) -> Option<usize> {
+    let mut previous_sold_out = false;
+    for (index, round) in rounds.iter().enumerate() {
+        if round.is_open_at(at) {
+            return if round.has_started(at, early_access) || previous_sold_out {
+                Some(index)
+            } else {
+                None
+            };
+        }
+        previous_sold_out = round.remaining() <= Decimal::ZERO;
+    }
+    None
+}
+
+/// Split a purchase across rounds, starting at the active round and
+/// spilling into the following rounds as each allocation runs out.
+///
+/// The fills may cover less than `requested` when the remaining rounds
+/// can't take it all; the caller decides whether that is acceptable.
+/// Spilling stops at a round that hasn't started yet, judged by the same
+/// time and early access as the active round.
+///
+/// A buyer's tier adds early access, discounts the round price and may
+/// override the vesting terms.
+pub fn plan_round_fills(
+    rounds: &[PresaleRound],
+    requested: Decimal,
+    whitelist_tier: i32,
+    tier: Option<&WhitelistTier>,
+    at: DateTime<Utc>,
+) -> std::result::Result<Vec<RoundFill>, RuleViolation> {
+    let early_access = tier.map(|tier| tier.early_access()).unwrap_or_else(Duration::zero);
+    let start = active_round_index(rounds, at, early_access).ok_or(RuleViolation::NoActiveRound)?;
+
+    let mut fills: Vec<RoundFill> = Vec::new();
+    let mut remaining = requested;
+
+    for (index, round) in rounds.iter().enumerate().skip(start) {
+        if remaining <= Decimal::ZERO {
+            break;
+        }
+        if !round.is_open_at(at) {
+            continue;
+        }
+        if index > start && !round.has_started(at, early_access) {
+            break;
+        }
+
+        if let Some(required_tier) = round.required_whitelist_tier {
+            if whitelist_tier < required_tier {
+                if fills.is_empty() {
+                    return Err(RuleViolation::RoundRequiresTier {
+                        round: round.name.clone(),
+                        required_tier,
+                    });
+                }
+                break;
+            }
+        }
+
+        let price = tier.map_or(round.price_sol, |tier| tier.discounted_price(round.price_sol));
+
+        let tokens = remaining.min(round.remaining());
+        fills.push(RoundFill {
+            round_id: round.id,
+            round_name: round.name.clone(),
+            tokens,
+            price,
+            cost: (tokens * price).round_dp(8),
+            vesting: tier.map_or_else(|| round.vesting_terms(), |tier| tier.vesting_for(round.vesting_terms())),
+            distribution_mode: round.distribution_mode.clone(),
+        });
+        remaining -= tokens;
+    }
+
+    Ok(fills)
+}
+
+/// Book fills against their rounds, closing any round whose allocation is used up
+pub async fn apply_round_fills(conn: &mut PgConnection, fills: &[RoundFill]) -> Result<()> {
+    for fill in fills {
+        sqlx::query(
+            r#"
+            UPDATE presale_rounds
+            SET tokens_sold = tokens_sold + $1,
+                activated_at = COALESCE(activated_at, NOW()),
+                closed_at = CASE
+                    WHEN tokens_sold + $1 >= token_allocation THEN NOW()
+                    ELSE closed_at
+                END
+            WHERE id = $2
+            "#
+        )
+        .bind(fill.tokens)
+        .bind(fill.round_id)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Record the per-round breakdown of a purchase
+pub async fn record_round_fills(
+    conn: &mut PgConnection,
+    transaction_id: &Uuid,
+    fills: &[RoundFill],
+) -> Result<()> {
+    for fill in fills {
+        sqlx::query(
+            r#"
+            INSERT INTO transaction_round_fills (transaction_id, round_id, amount_tokens, price, amount_paid)
+            VALUES ($1, $2, $3, $4, $5)
+            "#
+        )
+        .bind(transaction_id)
+        .bind(fill.round_id)
+        .bind(fill.tokens)
+        .bind(fill.price)
+        .bind(fill.cost)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn dec(value: &str) -> Decimal {
+        Decimal::from_str(value).unwrap()
+    }
+
+    fn day(day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, day, 0, 0, 0).unwrap()
+    }
+
+    /// Round `sequence` runs from day `starts` to day `ends` of the month
+    fn round(sequence: i32, starts: u32, ends: u32, allocation: &str, sold: &str, price: &str) -> PresaleRound {
+        PresaleRound {
+            id: Uuid::from_u128(sequence as u128),
+            name: format!("Round {}", sequence),
+            sequence,
+            starts_at: day(starts),
+            ends_at: day(ends),
+            price_sol: dec(price),
+            price_usd: None,
+            token_allocation: dec(allocation),
+            tokens_sold: dec(sold),
+            required_whitelist_tier: None,
+            cliff_duration_days: 0,
+            vesting_duration_days: 0,
+            tge_unlock_percent: Decimal::ONE_HUNDRED,
+            release_kind: "linear".to_string(),
+            unlock_table: None,
+            distribution_mode: "claim".to_string(),
+            activated_at: None,
+            closed_at: None,
+            created_at: day(1),
+        }
+    }
+
+    fn tier(tier: i32, early_access_days: i32) -> WhitelistTier {
+        WhitelistTier {
+            tier,
+            name: "Gold".to_string(),
+            default_max_allocation: dec("1000"),
+            min_purchase: None,
+            early_access_secs: early_access_days * 86_400,
+            price_discount_percent: Decimal::ZERO,
+            vesting_tge_unlock_percent: None,
+            vesting_cliff_days: None,
+            vesting_duration_days: None,
+            vesting_release_kind: None,
+            created_at: day(1),
+            updated_at: day(1),
+        }
+    }
+
+    fn split(fills: &[RoundFill]) -> Vec<(i32, Decimal, Decimal)> {
+        fills
+            .iter()
+            .map(|fill| (fill.round_name[6..].parse().unwrap(), fill.tokens, fill.cost))
+            .collect()
+    }
+
+    #[test]
+    fn purchase_within_one_round() {
+        let rounds = vec![round(1, 1, 10, "1000", "0", "0.1"), round(2, 10, 20, "1000", "0", "0.2")];
+        let fills = plan_round_fills(&rounds, dec("400"), 0, None, day(5)).unwrap();
+        assert_eq!(split(&fills), vec![(1, dec("400"), dec("40"))]);
+    }
+
+    #[test]
+    fn purchase_spills_into_a_started_round() {
+        // Round 1 runs on after round 2 starts, so both are selling
+        let rounds = vec![round(1, 1, 15, "1000", "800", "0.1"), round(2, 10, 20, "1000", "0", "0.2")];
+        let fills = plan_round_fills(&rounds, dec("500"), 0, None, day(12)).unwrap();
+        assert_eq!(split(&fills), vec![(1, dec("200"), dec("20")), (2, dec("300"), dec("60"))]);
+    }
+
+    #[test]
+    fn spill_stops_at_a_round_not_yet_started() {
+        let rounds = vec![round(1, 1, 15, "1000", "800", "0.1"), round(2, 10, 20, "1000", "0", "0.2")];
+        let fills = plan_round_fills(&rounds, dec("500"), 0, None, day(5)).unwrap();
+        assert_eq!(split(&fills), vec![(1, dec("200"), dec("20"))]);
+
+        // Early access moves the next round's start for this buyer too
+        let gold = tier(1, 5);
+        let fills = plan_round_fills(&rounds, dec("500"), 1, Some(&gold), day(5)).unwrap();
+        assert_eq!(split(&fills), vec![(1, dec("200"), dec("20")), (2, dec("300"), dec("60"))]);
+    }
+
+    #[test]
+    fn exhausted_rounds_fill_what_they_can() {
+        let rounds = vec![round(1, 1, 15, "1000", "900", "0.1"), round(2, 2, 20, "1000", "950", "0.2")];
+        let fills = plan_round_fills(&rounds, dec("500"), 0, None, day(5)).unwrap();
+        assert_eq!(split(&fills), vec![(1, dec("100"), dec("10")), (2, dec("50"), dec("10"))]);
+
+        let sold_out = vec![round(1, 1, 15, "1000", "1000", "0.1")];
+        assert!(matches!(
+            plan_round_fills(&sold_out, dec("10"), 0, None, day(5)),
+            Err(RuleViolation::NoActiveRound)
+        ));
+    }
+
+    #[test]
+    fn nothing_sells_before_the_first_round_starts() {
+        let rounds = vec![round(1, 10, 20, "1000", "0", "0.1")];
+        assert!(matches!(
+            plan_round_fills(&rounds, dec("10"), 0, None, day(5)),
+            Err(RuleViolation::NoActiveRound)
+        ));
+
+        let gold = tier(1, 5);
+        let fills = plan_round_fills(&rounds, dec("10"), 1, Some(&gold), day(5)).unwrap();
+        assert_eq!(split(&fills), vec![(1, dec("10"), dec("1"))]);
+    }
+
+    #[test]
+    fn tier_gated_rounds_need_the_tier() {
+        let mut gated = round(1, 1, 15, "1000", "800", "0.1");
+        gated.required_whitelist_tier = Some(2);
+        let rounds = vec![gated, round(2, 2, 20, "1000", "0", "0.2")];
+
+        assert!(matches!(
+            plan_round_fills(&rounds, dec("10"), 1, None, day(5)),
+            Err(RuleViolation::RoundRequiresTier { required_tier: 2, .. })
+        ));
+        let fills = plan_round_fills(&rounds, dec("300"), 2, None, day(5)).unwrap();
+        assert_eq!(split(&fills), vec![(1, dec("200"), dec("20")), (2, dec("100"), dec("20"))]);
+
+        // A gated round after the active one ends the spill instead
+        let mut rounds = vec![round(1, 1, 15, "1000", "800", "0.1"), round(2, 2, 20, "1000", "0", "0.2")];
+        rounds[1].required_whitelist_tier = Some(2);
+        let fills = plan_round_fills(&rounds, dec("300"), 1, None, day(5)).unwrap();
+        assert_eq!(split(&fills), vec![(1, dec("200"), dec("20"))]);
+    }
+}
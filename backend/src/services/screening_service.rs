@@ -0,0 +1,404 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use solana_sdk::pubkey::Pubkey;
+use sqlx::postgres::PgListener;
+use sqlx::{PgExecutor, PgPool};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+use tokio::time::{sleep, Duration};
+
+use crate::models::{AddDenylistRequest, DenylistEntry, ScreeningEvent, ScreeningEventQuery, User};
+use crate::services::{find_referrer, record_audit_event_in, AuditContext, AuditEntry, SettingsService};
+
+/// Channel the `denylist_entries` trigger notifies on every change
+pub const DENYLIST_CHANNEL: &str = "denylist_changed";
+
+const SCREENING_EVENTS_DEFAULT_LIMIT: i64 = 100;
+const SCREENING_EVENTS_MAX_LIMIT: i64 = 1000;
+
+/// Whose address matched, and so what a block stops
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScreeningRole {
+    Buyer,
+    Referrer,
+    RefundDestination,
+    PaymentSource,
+}
+
+impl ScreeningRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Buyer => "buyer",
+            Self::Referrer => "referrer",
+            Self::RefundDestination => "refund_destination",
+            Self::PaymentSource => "payment_source",
+        }
+    }
+}
+
+/// A wallet refused because it is on the denylist
+#[derive(Debug, Clone)]
+pub struct ScreeningRejection {
+    pub role: ScreeningRole,
+}
+
+impl ScreeningRejection {
+    pub fn code(&self) -> &'static str {
+        "address_blocked"
+    }
+}
+
+impl fmt::Display for ScreeningRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.role {
+            ScreeningRole::Buyer => write!(f, "Wallet is not permitted to participate"),
+            ScreeningRole::Referrer => write!(f, "Referrer is not permitted to participate"),
+            ScreeningRole::RefundDestination => write!(f, "Refunds to this wallet are not permitted"),
+            ScreeningRole::PaymentSource => write!(f, "Payment was funded by a blocked address"),
+        }
+    }
+}
+
+impl std::error::Error for ScreeningRejection {}
+
+/// A loaded denylist: each address with the list it came from
+#[derive(Debug, Default)]
+pub struct Denylist {
+    addresses: HashMap<String, String>,
+    file_modified: Option<SystemTime>,
+    pub loaded_at: Option<DateTime<Utc>>,
+}
+
+impl Denylist {
+    pub fn address_count(&self) -> usize {
+        self.addresses.len()
+    }
+}
+
+/// The denylist, shared through `AppState`.
+///
+/// Loaded from `denylist_entries` and, if `DENYLIST_PATH` is set, a file of
+/// one address per line (`#` starts a comment; anything after a comma is
+/// ignored, so `address,reason` lines work). Table changes are picked up
+/// through `LISTEN denylist_changed`, file changes by polling its
+/// modification time. A failed reload keeps the previous list.
+#[derive(Clone)]
+pub struct DenylistService {
+    current: Arc<RwLock<Arc<Denylist>>>,
+    file: Option<PathBuf>,
+}
+
+impl DenylistService {
+    pub async fn load(pool: &PgPool, file: Option<PathBuf>) -> Result<Self> {
+        let service = Self {
+            current: Arc::new(RwLock::new(Arc::new(Denylist::default()))),
+            file,
+        };
+        service.reload(pool).await?;
+        Ok(service)
+    }
+
+    pub fn current(&self) -> Arc<Denylist> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// Source list of a denylisted address
+    pub fn lookup(&self, address: &str) -> Option<String> {
+        self.current.read().unwrap().addresses.get(address.trim()).cloned()
+    }
+
+    pub async fn reload(&self, pool: &PgPool) -> Result<usize> {
+        let mut addresses = HashMap::new();
+        let mut file_modified = None;
+
+        if let Some(path) = &self.file {
+            let (listed, modified) = read_denylist_file(path)?;
+            addresses.extend(listed);
+            file_modified = Some(modified);
+        }
+
+        let rows = sqlx::query_as::<_, (String, String)>("SELECT address, source FROM denylist_entries")
+            .fetch_all(pool)
+            .await?;
+        addresses.extend(rows);
+
+        let count = addresses.len();
+        *self.current.write().unwrap() = Arc::new(Denylist {
+            addresses,
+            file_modified,
+            loaded_at: Some(Utc::now()),
+        });
+        println!("🚫 Denylist loaded: {} addresses", count);
+
+        Ok(count)
+    }
+
+    async fn reload_logged(&self, pool: &PgPool) {
+        if let Err(e) = self.reload(pool).await {
+            eprintln!("Keeping previous denylist, reload failed: {}", e);
+        }
+    }
+
+    /// Hot-reload on `LISTEN denylist_changed` and when the file changes
+    pub fn spawn_reloader(&self, pool: PgPool, settings: SettingsService) {
+        let service = self.clone();
+        let listener_pool = pool.clone();
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = service.listen(&listener_pool).await {
+                    eprintln!("Denylist listener error: {}", e);
+                }
+                sleep(Duration::from_secs(5)).await;
+            }
+        });
+
+        let Some(path) = self.file.clone() else {
+            return;
+        };
+        let service = self.clone();
+        tokio::spawn(async move {
+            loop {
+                sleep(Duration::from_secs(settings.current().denylist_file_poll_secs)).await;
+                let modified = std::fs::metadata(&path).and_then(|metadata| metadata.modified()).ok();
+                if modified.is_some() && modified != service.current().file_modified {
+                    service.reload_logged(&pool).await;
+                }
+            }
+        });
+    }
+
+    async fn listen(&self, pool: &PgPool) -> Result<()> {
+        let mut listener = PgListener::connect_with(pool).await?;
+        listener.listen(DENYLIST_CHANNEL).await?;
+
+        // Catch anything that changed while we weren't listening
+        self.reload_logged(pool).await;
+
+        loop {
+            listener.recv().await?;
+            self.reload_logged(pool).await;
+        }
+    }
+
+    /// Refuse a denylisted wallet: the match is recorded and a
+    /// [`ScreeningRejection`] returned inside the error.
+    ///
+    /// Record with the pool rather than a transaction that the rejection
+    /// will roll back, or the audit entry is lost with it.
+    pub async fn screen<'e>(
+        &self,
+        executor: impl PgExecutor<'e>,
+        wallet: &str,
+        role: ScreeningRole,
+        context: &str,
+        reference: Option<&str>,
+    ) -> Result<()> {
+        let Some(source) = self.lookup(wallet) else {
+            return Ok(());
+        };
+
+        record_screening_event(executor, wallet, role, context, "blocked", wallet, &source, reference).await?;
+        eprintln!("🚫 Blocked denylisted {} {} at {}", role.as_str(), wallet, context);
+
+        Err(ScreeningRejection { role }.into())
+    }
+
+    /// Refuse a referral code whose owner is denylisted
+    pub async fn screen_referral_code(&self, pool: &PgPool, referral_code: &str, context: &str) -> Result<()> {
+        match find_referrer(pool, referral_code).await? {
+            Some(referrer) => {
+                self.screen(pool, &referrer.wallet_address, ScreeningRole::Referrer, context, Some(referral_code))
+                    .await
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Flag a buyer whose payment transaction also moved funds out of listed
+    /// addresses. Returns the listed addresses found.
+    pub async fn flag_payment_sources(
+        &self,
+        pool: &PgPool,
+        user: &User,
+        debited: &[String],
+        signature: &str,
+    ) -> Result<Vec<String>> {
+        let listed: Vec<(String, String)> = debited
+            .iter()
+            .filter_map(|address| self.lookup(address).map(|source| (address.clone(), source)))
+            .collect();
+        if listed.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut db_tx = pool.begin().await?;
+        for (address, source) in &listed {
+            record_screening_event(
+                &mut *db_tx,
+                &user.wallet_address,
+                ScreeningRole::PaymentSource,
+                "purchase",
+                "flagged",
+                address,
+                source,
+                Some(signature),
+            )
+            .await?;
+        }
+        sqlx::query("UPDATE users SET screening_flagged_at = NOW(), updated_at = NOW() WHERE id = $1")
+            .bind(user.id)
+            .execute(&mut *db_tx)
+            .await?;
+        db_tx.commit().await?;
+
+        eprintln!(
+            "⚠️ Flagged {}: payment {} funded by denylisted {}",
+            user.wallet_address,
+            signature,
+            listed.iter().map(|(address, _)| address.as_str()).collect::<Vec<_>>().join(", ")
+        );
+
+        Ok(listed.into_iter().map(|(address, _)| address).collect())
+    }
+}
+
+/// Addresses in a denylist file and the file's modification time. Lines
+/// that aren't Solana addresses (other chains' entries on a shared list)
+/// are skipped.
+fn read_denylist_file(path: &Path) -> Result<(HashMap<String, String>, SystemTime)> {
+    let modified = std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .map_err(|e| anyhow!("Failed to read denylist file {}: {}", path.display(), e))?;
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("Failed to read denylist file {}: {}", path.display(), e))?;
+
+    let addresses = contents
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or("").split(',').next().unwrap_or("").trim())
+        .filter(|address| Pubkey::from_str(address).is_ok())
+        .map(|address| (address.to_string(), "file".to_string()))
+        .collect();
+
+    Ok((addresses, modified))
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn record_screening_event<'e>(
+    executor: impl PgExecutor<'e>,
+    wallet: &str,
+    role: ScreeningRole,
+    context: &str,
+    action: &str,
+    matched_address: &str,
+    list_source: &str,
+    reference: Option<&str>,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO screening_events (
+            wallet_address, role, context, action, matched_address, list_source, reference
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#
+    )
+    .bind(wallet)
+    .bind(role.as_str())
+    .bind(context)
+    .bind(action)
+    .bind(matched_address)
+    .bind(list_source)
+    .bind(reference)
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+/// Add addresses to the table; existing entries are updated. Returns how
+/// many were written.
+pub async fn add_denylist_entries(pool: &PgPool, req: &AddDenylistRequest, audit: &AuditContext) -> Result<u64> {
+    let addresses: Vec<String> = req.addresses.iter().map(|address| address.trim().to_string()).collect();
+    if let Some(invalid) = addresses.iter().find(|address| Pubkey::from_str(address).is_err()) {
+        return Err(anyhow!("Not a valid Solana address: {}", invalid));
+    }
+
+    let mut db_tx = pool.begin().await?;
+
+    let result = sqlx::query(
+        r#"
+        INSERT INTO denylist_entries (address, source, reason)
+        SELECT address, $2, $3 FROM UNNEST($1::varchar[]) AS address
+        ON CONFLICT (address) DO UPDATE SET source = EXCLUDED.source, reason = EXCLUDED.reason
+        "#
+    )
+    .bind(&addresses)
+    .bind(req.source.as_deref().unwrap_or("manual"))
+    .bind(&req.reason)
+    .execute(&mut *db_tx)
+    .await?;
+
+    let entry = AuditEntry {
+        action: "denylist.added",
+        entity_type: "denylist",
+        entity_id: req.source.as_deref().unwrap_or("manual").to_string(),
+        before: None,
+        after: Some(serde_json::json!({ "addresses": addresses, "reason": req.reason })),
+    };
+    record_audit_event_in(&mut db_tx, audit, entry).await?;
+    db_tx.commit().await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Remove an address from the table. Addresses listed in the file stay
+/// blocked until removed there.
+pub async fn remove_denylist_entry(pool: &PgPool, address: &str, audit: &AuditContext) -> Result<bool> {
+    let mut db_tx = pool.begin().await?;
+
+    let removed = sqlx::query_as::<_, DenylistEntry>("DELETE FROM denylist_entries WHERE address = $1 RETURNING *")
+        .bind(address)
+        .fetch_optional(&mut *db_tx)
+        .await?;
+    let Some(removed) = removed else {
+        return Ok(false);
+    };
+
+    let entry = AuditEntry {
+        action: "denylist.removed",
+        entity_type: "denylist_entry",
+        entity_id: removed.address.clone(),
+        before: Some(serde_json::to_value(&removed)?),
+        after: None,
+    };
+    record_audit_event_in(&mut db_tx, audit, entry).await?;
+    db_tx.commit().await?;
+
+    Ok(true)
+}
+
+/// Recent screening matches, newest first, optionally for one wallet
+pub async fn list_screening_events(pool: &PgPool, query: &ScreeningEventQuery) -> Result<Vec<ScreeningEvent>> {
+    let events = sqlx::query_as::<_, ScreeningEvent>(
+        r#"
+        SELECT * FROM screening_events
+        WHERE $1::varchar IS NULL OR wallet_address = $1
+        ORDER BY created_at DESC
+        LIMIT $2
+        "#
+    )
+    .bind(&query.wallet)
+    .bind(
+        query
+            .limit
+            .unwrap_or(SCREENING_EVENTS_DEFAULT_LIMIT)
+            .clamp(1, SCREENING_EVENTS_MAX_LIMIT),
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(events)
+}
@@ -0,0 +1,499 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::types::Json;
+use sqlx::{PgConnection, PgPool};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use tokio::time::{sleep, Duration};
+use uuid::Uuid;
+
+use crate::models::{
+    PresaleSetting, PresaleSettingChange, PresaleSettingSchedule, PresaleSettings, SettingsChanges,
+    SettingsHistoryQuery, SettingsScheduleQuery, SettingsVersion, PRESALE_SETTING_KEYS,
+};
+use crate::services::{record_audit_event_in, AuditContext, AuditEntry};
+
+/// Serializes settings changes so each validates against the one before
+const SETTINGS_LOCK_KEY: i64 = 0x5345_5454_494e_4753; // "SETTINGS"
+
+/// Longest the scheduler sleeps; it wakes earlier for a change falling due
+const SETTINGS_SCHEDULER_MAX_SLEEP_SECS: i64 = 10;
+
+const SETTINGS_HISTORY_DEFAULT_LIMIT: i64 = 100;
+const SETTINGS_HISTORY_MAX_LIMIT: i64 = 1000;
+
+/// Reason a settings change was refused
+#[derive(Debug, Clone)]
+pub enum SettingsChangeRejection {
+    Empty,
+    UnknownKey(String),
+    /// Arrays and objects can't be stored
+    UnsupportedValue(String),
+    /// The settings wouldn't parse or validate with the change applied
+    Invalid(String),
+    /// Scheduled changes must take effect in the future
+    NotInFuture,
+}
+
+impl SettingsChangeRejection {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Empty => "no_changes",
+            Self::UnknownKey(_) => "unknown_key",
+            Self::UnsupportedValue(_) => "unsupported_value",
+            Self::Invalid(_) => "invalid_settings",
+            Self::NotInFuture => "effective_at_not_in_future",
+        }
+    }
+}
+
+impl fmt::Display for SettingsChangeRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => write!(f, "No changes given"),
+            Self::UnknownKey(key) => write!(f, "Unknown presale setting {}", key),
+            Self::UnsupportedValue(key) => {
+                write!(f, "{} must be a string, number, boolean or null", key)
+            }
+            Self::Invalid(reason) => write!(f, "{}", reason),
+            Self::NotInFuture => write!(f, "effective_at must be in the future"),
+        }
+    }
+}
+
+impl std::error::Error for SettingsChangeRejection {}
+
+/// Who is changing settings, recorded on each history entry and in the
+/// audit log
+pub struct SettingsChangeContext<'a> {
+    pub admin_id: Option<Uuid>,
+    pub name: &'a str,
+    pub source: &'a str, // admin, schedule, system
+    pub schedule_id: Option<Uuid>,
+    pub reason: Option<&'a str>,
+    pub audit: &'a AuditContext,
+}
+
+/// Check keys and turn JSON values into the stored text
+pub fn normalize_settings_changes(
+    changes: &SettingsChanges,
+) -> std::result::Result<BTreeMap<String, Option<String>>, SettingsChangeRejection> {
+    if changes.is_empty() {
+        return Err(SettingsChangeRejection::Empty);
+    }
+
+    changes
+        .iter()
+        .map(|(key, value)| {
+            if !PRESALE_SETTING_KEYS.contains(&key.as_str()) {
+                return Err(SettingsChangeRejection::UnknownKey(key.clone()));
+            }
+            let text = match value {
+                serde_json::Value::Null => None,
+                serde_json::Value::String(text) => Some(text.trim().to_string()),
+                serde_json::Value::Number(number) => Some(number.to_string()),
+                serde_json::Value::Bool(flag) => Some(flag.to_string()),
+                _ => return Err(SettingsChangeRejection::UnsupportedValue(key.clone())),
+            };
+            Ok((key.clone(), text))
+        })
+        .collect()
+}
+
+async fn current_setting_values(conn: &mut PgConnection) -> Result<HashMap<String, String>> {
+    let rows = sqlx::query_as::<_, PresaleSetting>("SELECT * FROM presale_settings")
+        .fetch_all(&mut *conn)
+        .await?;
+
+    Ok(rows.into_iter().map(|row| (row.key, row.value)).collect())
+}
+
+/// Parse and validate the settings as they would be with `changes` applied
+fn validate_settings_changes(
+    current: &HashMap<String, String>,
+    changes: &BTreeMap<String, Option<String>>,
+) -> std::result::Result<(), SettingsChangeRejection> {
+    let mut values: HashMap<&str, &str> = current
+        .iter()
+        .map(|(key, value)| (key.as_str(), value.as_str()))
+        .collect();
+    for (key, value) in changes {
+        match value {
+            Some(value) => values.insert(key.as_str(), value.as_str()),
+            None => values.remove(key.as_str()),
+        };
+    }
+
+    PresaleSettings::from_map(&values)
+        .map(|_| ())
+        .map_err(|e| SettingsChangeRejection::Invalid(e.to_string()))
+}
+
+/// Apply a change set inside the caller's transaction and record it as one
+/// version. Values that are already current are left out; if nothing
+/// changes no version is used.
+///
+/// The settings cache reloads through the table's NOTIFY trigger once the
+/// transaction commits.
+pub async fn apply_settings_changes_in(
+    conn: &mut PgConnection,
+    changes: &BTreeMap<String, Option<String>>,
+    context: &SettingsChangeContext<'_>,
+) -> Result<SettingsVersion> {
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+        .bind(SETTINGS_LOCK_KEY)
+        .execute(&mut *conn)
+        .await?;
+
+    let current = current_setting_values(conn).await?;
+    let changed: Vec<(&String, &Option<String>)> = changes
+        .iter()
+        .filter(|(key, value)| current.get(*key) != value.as_ref())
+        .collect();
+    if changed.is_empty() {
+        return Ok(SettingsVersion { version: None, changes: Vec::new() });
+    }
+    validate_settings_changes(&current, changes)?;
+
+    let version: i64 = sqlx::query_scalar("SELECT nextval('presale_settings_version_seq')")
+        .fetch_one(&mut *conn)
+        .await?;
+
+    let mut recorded = Vec::with_capacity(changed.len());
+    for (key, value) in changed {
+        match value {
+            Some(value) => {
+                sqlx::query(
+                    r#"
+                    INSERT INTO presale_settings (key, value, updated_at)
+                    VALUES ($1, $2, NOW())
+                    ON CONFLICT (key) DO UPDATE SET value = EXCLUDED.value, updated_at = NOW()
+                    "#
+                )
+                .bind(key)
+                .bind(value)
+                .execute(&mut *conn)
+                .await?;
+            }
+            None => {
+                sqlx::query("DELETE FROM presale_settings WHERE key = $1")
+                    .bind(key)
+                    .execute(&mut *conn)
+                    .await?;
+            }
+        }
+
+        let change = sqlx::query_as::<_, PresaleSettingChange>(
+            r#"
+            INSERT INTO presale_setting_history (
+                version, key, old_value, new_value, changed_by, changed_by_name, source, schedule_id, reason
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            RETURNING *
+            "#
+        )
+        .bind(version)
+        .bind(key)
+        .bind(current.get(key))
+        .bind(value)
+        .bind(context.admin_id)
+        .bind(context.name)
+        .bind(context.source)
+        .bind(context.schedule_id)
+        .bind(context.reason)
+        .fetch_one(&mut *conn)
+        .await?;
+        recorded.push(change);
+    }
+
+    let mut before = serde_json::Map::new();
+    let mut after = serde_json::Map::new();
+    for change in &recorded {
+        before.insert(change.key.clone(), serde_json::json!(change.old_value));
+        after.insert(change.key.clone(), serde_json::json!(change.new_value));
+    }
+    let entry = AuditEntry {
+        action: "settings.changed",
+        entity_type: "settings",
+        entity_id: version.to_string(),
+        before: Some(before.into()),
+        after: Some(after.into()),
+    };
+    record_audit_event_in(conn, context.audit, entry).await?;
+
+    Ok(SettingsVersion { version: Some(version), changes: recorded })
+}
+
+pub async fn apply_settings_changes(
+    pool: &PgPool,
+    changes: &SettingsChanges,
+    context: &SettingsChangeContext<'_>,
+) -> Result<SettingsVersion> {
+    let changes = normalize_settings_changes(changes)?;
+
+    let mut db_tx = pool.begin().await?;
+    let applied = apply_settings_changes_in(&mut db_tx, &changes, context).await?;
+    db_tx.commit().await?;
+
+    if let Some(version) = applied.version {
+        println!("⚙️ Presale settings v{} by {}: {}", version, context.name, changed_keys(&applied));
+    }
+
+    Ok(applied)
+}
+
+fn changed_keys(applied: &SettingsVersion) -> String {
+    applied.changes.iter().map(|change| change.key.as_str()).collect::<Vec<_>>().join(", ")
+}
+
+/// Current rows with the latest version number
+pub async fn list_presale_settings(pool: &PgPool) -> Result<(Vec<PresaleSetting>, Option<i64>)> {
+    let rows = sqlx::query_as::<_, PresaleSetting>("SELECT * FROM presale_settings ORDER BY key")
+        .fetch_all(pool)
+        .await?;
+    let version: Option<i64> = sqlx::query_scalar("SELECT MAX(version) FROM presale_setting_history")
+        .fetch_one(pool)
+        .await?;
+
+    Ok((rows, version))
+}
+
+/// Settings history, newest first, optionally for one key
+pub async fn list_settings_history(pool: &PgPool, query: &SettingsHistoryQuery) -> Result<Vec<PresaleSettingChange>> {
+    let changes = sqlx::query_as::<_, PresaleSettingChange>(
+        r#"
+        SELECT * FROM presale_setting_history
+        WHERE $1::varchar IS NULL OR key = $1
+        ORDER BY version DESC, key
+        LIMIT $2
+        "#
+    )
+    .bind(&query.key)
+    .bind(
+        query
+            .limit
+            .unwrap_or(SETTINGS_HISTORY_DEFAULT_LIMIT)
+            .clamp(1, SETTINGS_HISTORY_MAX_LIMIT),
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(changes)
+}
+
+/// Schedule a change set. It is validated against the current settings now
+/// and again when it falls due.
+pub async fn schedule_settings_changes(
+    pool: &PgPool,
+    changes: &SettingsChanges,
+    effective_at: DateTime<Utc>,
+    context: &SettingsChangeContext<'_>,
+) -> Result<PresaleSettingSchedule> {
+    if effective_at <= Utc::now() {
+        return Err(SettingsChangeRejection::NotInFuture.into());
+    }
+    let changes = normalize_settings_changes(changes)?;
+
+    let mut db_tx = pool.begin().await?;
+    let current = current_setting_values(&mut db_tx).await?;
+    validate_settings_changes(&current, &changes)?;
+
+    let schedule = sqlx::query_as::<_, PresaleSettingSchedule>(
+        r#"
+        INSERT INTO presale_setting_schedules (changes, effective_at, reason, created_by, created_by_name)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING *
+        "#
+    )
+    .bind(Json(&changes))
+    .bind(effective_at)
+    .bind(context.reason)
+    .bind(context.admin_id)
+    .bind(context.name)
+    .fetch_one(&mut *db_tx)
+    .await?;
+
+    let entry = AuditEntry {
+        action: "settings.scheduled",
+        entity_type: "settings_schedule",
+        entity_id: schedule.id.to_string(),
+        before: None,
+        after: Some(serde_json::to_value(&schedule)?),
+    };
+    record_audit_event_in(&mut db_tx, context.audit, entry).await?;
+    db_tx.commit().await?;
+
+    println!(
+        "🗓️ Presale settings change scheduled for {} by {}: {}",
+        schedule.effective_at,
+        context.name,
+        changes.keys().cloned().collect::<Vec<_>>().join(", ")
+    );
+
+    Ok(schedule)
+}
+
+pub async fn list_settings_schedules(
+    pool: &PgPool,
+    query: &SettingsScheduleQuery,
+) -> Result<Vec<PresaleSettingSchedule>> {
+    let schedules = sqlx::query_as::<_, PresaleSettingSchedule>(
+        r#"
+        SELECT * FROM presale_setting_schedules
+        WHERE $1::varchar IS NULL OR status = $1
+        ORDER BY effective_at
+        "#
+    )
+    .bind(&query.status)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(schedules)
+}
+
+/// Cancel a pending change. `None` if there is no such pending change.
+pub async fn cancel_settings_schedule(
+    pool: &PgPool,
+    id: &Uuid,
+    cancelled_by: &str,
+    audit: &AuditContext,
+) -> Result<Option<PresaleSettingSchedule>> {
+    let mut db_tx = pool.begin().await?;
+
+    let schedule = sqlx::query_as::<_, PresaleSettingSchedule>(
+        r#"
+        UPDATE presale_setting_schedules
+        SET status = 'cancelled', cancelled_by_name = $2, resolved_at = NOW()
+        WHERE id = $1 AND status = 'pending'
+        RETURNING *
+        "#
+    )
+    .bind(id)
+    .bind(cancelled_by)
+    .fetch_optional(&mut *db_tx)
+    .await?;
+    let Some(schedule) = schedule else {
+        return Ok(None);
+    };
+
+    let entry = AuditEntry {
+        action: "settings.schedule_cancelled",
+        entity_type: "settings_schedule",
+        entity_id: schedule.id.to_string(),
+        before: Some(serde_json::json!({ "status": "pending" })),
+        after: Some(serde_json::json!({ "status": schedule.status })),
+    };
+    record_audit_event_in(&mut db_tx, audit, entry).await?;
+    db_tx.commit().await?;
+
+    Ok(Some(schedule))
+}
+
+/// Apply the earliest due change, if any. A change that no longer
+/// validates is marked failed rather than retried.
+async fn apply_due_settings_schedule(pool: &PgPool) -> Result<Option<PresaleSettingSchedule>> {
+    let mut db_tx = pool.begin().await?;
+
+    let schedule = sqlx::query_as::<_, PresaleSettingSchedule>(
+        r#"
+        SELECT * FROM presale_setting_schedules
+        WHERE status = 'pending' AND effective_at <= NOW()
+        ORDER BY effective_at
+        LIMIT 1
+        FOR UPDATE SKIP LOCKED
+        "#
+    )
+    .fetch_optional(&mut *db_tx)
+    .await?;
+    let Some(schedule) = schedule else {
+        return Ok(None);
+    };
+
+    let audit = AuditContext::system("settings_scheduler");
+    let context = SettingsChangeContext {
+        admin_id: schedule.created_by,
+        name: &schedule.created_by_name,
+        source: "schedule",
+        schedule_id: Some(schedule.id),
+        reason: schedule.reason.as_deref(),
+        audit: &audit,
+    };
+    let (status, version, error) = match apply_settings_changes_in(&mut db_tx, &schedule.changes.0, &context).await {
+        Ok(applied) => ("applied", applied.version, None),
+        Err(e) => match e.downcast_ref::<SettingsChangeRejection>() {
+            Some(rejection) => ("failed", None, Some(rejection.to_string())),
+            None => return Err(e),
+        },
+    };
+
+    let schedule = sqlx::query_as::<_, PresaleSettingSchedule>(
+        r#"
+        UPDATE presale_setting_schedules
+        SET status = $2, applied_version = $3, error = $4, resolved_at = NOW()
+        WHERE id = $1
+        RETURNING *
+        "#
+    )
+    .bind(schedule.id)
+    .bind(status)
+    .bind(version)
+    .bind(&error)
+    .fetch_one(&mut *db_tx)
+    .await?;
+
+    if schedule.status == "failed" {
+        let entry = AuditEntry {
+            action: "settings.schedule_failed",
+            entity_type: "settings_schedule",
+            entity_id: schedule.id.to_string(),
+            before: Some(serde_json::json!({ "status": "pending" })),
+            after: Some(serde_json::json!({ "status": schedule.status, "error": schedule.error })),
+        };
+        record_audit_event_in(&mut db_tx, &audit, entry).await?;
+    }
+
+    db_tx.commit().await?;
+
+    match &schedule.error {
+        Some(error) => eprintln!("Scheduled settings change {} failed: {}", schedule.id, error),
+        None => println!(
+            "⚙️ Scheduled settings change {} applied as v{}",
+            schedule.id,
+            schedule.applied_version.map_or_else(|| "-".to_string(), |version| version.to_string())
+        ),
+    }
+
+    Ok(Some(schedule))
+}
+
+/// Apply scheduled settings changes as they fall due
+pub fn spawn_settings_scheduler(pool: PgPool) {
+    tokio::spawn(async move {
+        loop {
+            loop {
+                match apply_due_settings_schedule(&pool).await {
+                    Ok(Some(_)) => continue,
+                    Ok(None) => break,
+                    Err(e) => {
+                        eprintln!("Settings scheduler error: {}", e);
+                        break;
+                    }
+                }
+            }
+
+            // Wake for the next change rather than a fixed tick, so a change
+            // set for 12:00:00 goes live then
+            let next: Option<DateTime<Utc>> = sqlx::query_scalar(
+                "SELECT MIN(effective_at) FROM presale_setting_schedules WHERE status = 'pending'"
+            )
+            .fetch_one(&pool)
+            .await
+            .unwrap_or(None);
+            let wait_ms = next
+                .map(|next| (next - Utc::now()).num_milliseconds())
+                .unwrap_or(i64::MAX)
+                .clamp(0, SETTINGS_SCHEDULER_MAX_SLEEP_SECS * 1000);
+            sleep(Duration::from_millis(wait_ms as u64)).await;
+        }
+    });
+}
@@ -0,0 +1,82 @@
+use anyhow::Result;
+use sqlx::postgres::PgListener;
+use sqlx::PgPool;
+use std::sync::{Arc, RwLock};
+use tokio::time::{sleep, Duration};
+
+use crate::models::{PresaleSetting, PresaleSettings};
+
+/// Channel the `presale_settings` trigger notifies on every change
+pub const SETTINGS_CHANNEL: &str = "presale_settings_changed";
+
+/// Cached presale settings, shared through `AppState`.
+///
+/// Readers take a cheap `Arc` snapshot; a background listener swaps in a
+/// freshly loaded copy whenever the table changes.
+#[derive(Clone)]
+pub struct SettingsService {
+    current: Arc<RwLock<Arc<PresaleSettings>>>,
+}
+
+impl SettingsService {
+    pub async fn load(pool: &PgPool) -> Result<Self> {
+        let settings = fetch_settings(pool).await?;
+        Ok(Self {
+            current: Arc::new(RwLock::new(Arc::new(settings))),
+        })
+    }
+
+    /// Snapshot of the current settings
+    pub fn current(&self) -> Arc<PresaleSettings> {
+        self.current.read().unwrap().clone()
+    }
+
+    /// Reload from the database, keeping the previous settings if the new
+    /// values don't validate
+    pub async fn reload(&self, pool: &PgPool) -> Result<()> {
+        let settings = fetch_settings(pool).await?;
+        *self.current.write().unwrap() = Arc::new(settings);
+        Ok(())
+    }
+
+    /// Hot-reload on `LISTEN presale_settings_changed`
+    pub fn spawn_listener(&self, pool: PgPool) {
+        let service = self.clone();
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = service.listen(&pool).await {
+                    eprintln!("Presale settings listener error: {}", e);
+                }
+                sleep(Duration::from_secs(5)).await;
+            }
+        });
+    }
+
+    async fn listen(&self, pool: &PgPool) -> Result<()> {
+        let mut listener = PgListener::connect_with(pool).await?;
+        listener.listen(SETTINGS_CHANNEL).await?;
+
+        // Catch anything that changed while we weren't listening
+        self.reload_logged(pool).await;
+
+        loop {
+            let notification = listener.recv().await?;
+            println!("🔄 Presale setting changed: {}", notification.payload());
+            self.reload_logged(pool).await;
+        }
+    }
+
+    async fn reload_logged(&self, pool: &PgPool) {
+        if let Err(e) = self.reload(pool).await {
+            eprintln!("Keeping previous presale settings, reload failed: {}", e);
+        }
+    }
+}
+
+async fn fetch_settings(pool: &PgPool) -> Result<PresaleSettings> {
+    let rows = sqlx::query_as::<_, PresaleSetting>("SELECT * FROM presale_settings")
+        .fetch_all(pool)
+        .await?;
+
+    PresaleSettings::from_rows(&rows)
+}
@@ -0,0 +1,192 @@
+//! Sign-In With Solana messages.
+//!
+//! The text follows the SIWS layout (after EIP-4361 / CAIP-122) that wallets
+//! recognise and display:
+//!
+//! ```text
+//! presale.example wants you to sign in with your Solana account:
+//! <base58 address>
+//!
+//! <statement>
+//!
+//! URI: https://presale.example
+//! Version: 1
+//! Nonce: <nonce>
+//! Issued At: 2026-01-01T00:00:00Z
+//! Expiration Time: 2026-01-01T00:10:00Z
+//! ```
+//!
+//! The statement and URI are optional; the nonce and both times are
+//! required here, since a login is only accepted once and before it expires.
+
+use chrono::{DateTime, SecondsFormat, Utc};
+
+const SIWS_HEADER_SUFFIX: &str = " wants you to sign in with your Solana account:";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SiwsMessage {
+    pub domain: String,
+    pub address: String,
+    pub statement: Option<String>,
+    pub uri: Option<String>,
+    pub nonce: String,
+    pub issued_at: DateTime<Utc>,
+    pub expiration_time: DateTime<Utc>,
+}
+
+fn siws_time(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn parse_siws_time(field: &str, value: &str) -> Result<DateTime<Utc>, String> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|time| time.with_timezone(&Utc))
+        .map_err(|_| format!("{} is not an RFC 3339 time", field))
+}
+
+impl SiwsMessage {
+    /// The text a wallet signs
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{}{}\n{}\n", self.domain, SIWS_HEADER_SUFFIX, self.address);
+        if let Some(statement) = &self.statement {
+            text.push_str(&format!("\n{}\n", statement));
+        }
+        text.push('\n');
+        if let Some(uri) = &self.uri {
+            text.push_str(&format!("URI: {}\n", uri));
+        }
+        text.push_str("Version: 1\n");
+        text.push_str(&format!("Nonce: {}\n", self.nonce));
+        text.push_str(&format!("Issued At: {}\n", siws_time(&self.issued_at)));
+        text.push_str(&format!("Expiration Time: {}", siws_time(&self.expiration_time)));
+        text
+    }
+
+    /// Parse a signed message. Fields other than the ones above (chain id,
+    /// request id, not-before, resources) are not accepted.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines();
+        let domain = lines
+            .next()
+            .and_then(|header| header.strip_suffix(SIWS_HEADER_SUFFIX))
+            .filter(|domain| !domain.is_empty())
+            .ok_or("missing sign-in header")?;
+        let address = lines
+            .next()
+            .filter(|address| !address.is_empty())
+            .ok_or("missing address")?;
+
+        let mut statement = None;
+        let mut uri = None;
+        let mut version = None;
+        let mut nonce = None;
+        let mut issued_at = None;
+        let mut expiration_time = None;
+        let mut fields_started = false;
+
+        for line in lines.filter(|line| !line.is_empty()) {
+            let field = match line.split_once(": ") {
+                Some(("URI", _)) => &mut uri,
+                Some(("Version", value)) => {
+                    if value != "1" {
+                        return Err(format!("unsupported version {}", value));
+                    }
+                    &mut version
+                }
+                Some(("Nonce", _)) => &mut nonce,
+                Some(("Issued At", _)) => &mut issued_at,
+                Some(("Expiration Time", _)) => &mut expiration_time,
+                _ => {
+                    // Free text is only allowed once, before the fields
+                    if statement.is_some() || fields_started {
+                        return Err(format!("unexpected line: {}", line));
+                    }
+                    statement = Some(line.to_string());
+                    continue;
+                }
+            };
+            if field.is_some() {
+                return Err(format!("repeated field: {}", line));
+            }
+            fields_started = true;
+            *field = line.split_once(": ").map(|(_, value)| value.to_string());
+        }
+
+        let nonce = nonce.filter(|nonce| nonce.len() >= 8).ok_or("missing or short nonce")?;
+        let issued_at = issued_at.ok_or("missing Issued At")?;
+        let expiration_time = expiration_time.ok_or("missing Expiration Time")?;
+
+        Ok(Self {
+            domain: domain.to_string(),
+            address: address.to_string(),
+            statement,
+            uri,
+            nonce,
+            issued_at: parse_siws_time("Issued At", &issued_at)?,
+            expiration_time: parse_siws_time("Expiration Time", &expiration_time)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn message() -> SiwsMessage {
+        SiwsMessage {
+            domain: "presale.example".to_string(),
+            address: "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM".to_string(),
+            statement: Some("Sign in to the presale".to_string()),
+            uri: Some("https://presale.example".to_string()),
+            nonce: "k3J9sQ2mXv8PzR1t".to_string(),
+            issued_at: Utc.with_ymd_and_hms(2026, 1, 1, 0, 0, 0).unwrap(),
+            expiration_time: Utc.with_ymd_and_hms(2026, 1, 1, 0, 10, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn text_layout() {
+        assert_eq!(
+            message().to_text(),
+            "presale.example wants you to sign in with your Solana account:\n\
+             9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM\n\
+             \n\
+             Sign in to the presale\n\
+             \n\
+             URI: https://presale.example\n\
+             Version: 1\n\
+             Nonce: k3J9sQ2mXv8PzR1t\n\
+             Issued At: 2026-01-01T00:00:00Z\n\
+             Expiration Time: 2026-01-01T00:10:00Z"
+        );
+    }
+
+    #[test]
+    fn round_trips() {
+        let full = message();
+        assert_eq!(SiwsMessage::parse(&full.to_text()), Ok(full));
+
+        let bare = SiwsMessage { statement: None, uri: None, ..message() };
+        assert_eq!(SiwsMessage::parse(&bare.to_text()), Ok(bare));
+    }
+
+    #[test]
+    fn statement_may_contain_a_colon() {
+        let with_colon = SiwsMessage { statement: Some("Note: presale terms apply".to_string()), ..message() };
+        assert_eq!(SiwsMessage::parse(&with_colon.to_text()), Ok(with_colon));
+    }
+
+    #[test]
+    fn rejects_malformed_messages() {
+        let text = message().to_text();
+
+        assert!(SiwsMessage::parse(&text.replace("Solana", "Ethereum")).is_err());
+        assert!(SiwsMessage::parse(&text.replace("Nonce: k3J9sQ2mXv8PzR1t\n", "")).is_err());
+        assert!(SiwsMessage::parse(&text.replace("Nonce: k3J9sQ2mXv8PzR1t", "Nonce: short")).is_err());
+        assert!(SiwsMessage::parse(&text.replace("Version: 1", "Version: 2")).is_err());
+        assert!(SiwsMessage::parse(&text.replace("2026-01-01T00:10:00Z", "tomorrow")).is_err());
+        assert!(SiwsMessage::parse(&format!("{}\nNonce: k3J9sQ2mXv8PzR1t", text)).is_err());
+        assert!(SiwsMessage::parse(&format!("{}\nextra text", text)).is_err());
+    }
+}
@@ -1,7 +1,10 @@
 use anyhow::{Result, anyhow};
-use solana_client::rpc_client::RpcClient;
+use solana_client::{rpc_client::RpcClient, rpc_config::RpcTransactionConfig};
 use solana_sdk::{
     commitment_config::CommitmentConfig,
+    instruction::Instruction,
+    program_pack::Pack,
+    hash::Hash,
     pubkey::Pubkey,
     signature::Signature,
     transaction::Transaction,
@@ -9,28 +12,43 @@ use solana_sdk::{
     signer::{keypair::Keypair, Signer},
 };
 use spl_token::{
-    instruction::{initialize_mint, mint_to, create_associated_token_account},
-    state::Mint,
+    instruction::{mint_to, freeze_account, thaw_account},
+    state::{Account as TokenAccount, Mint},
 };
-use spl_associated_token_account::get_associated_token_address;
-use std::{env, str::FromStr};
+use spl_associated_token_account::{get_associated_token_address, instruction::create_associated_token_account};
+use solana_transaction_status::UiTransactionEncoding;
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use std::{env, str::FromStr, sync::Arc};
 use tokio::time::{sleep, Duration};
 
+use crate::services::OraclePrice;
+
 #[derive(Clone)]
 pub struct SolanaService {
-    client: RpcClient,
-    owner_keypair: Keypair,
+    client: Arc<RpcClient>,
+    owner_keypair: Arc<Keypair>,
     token_mint: Pubkey,
-    network: String,
+}
+
+/// Result of sending a pre-signed transaction
+#[derive(Debug, Clone, PartialEq)]
+pub enum SendOutcome {
+    Confirmed,
+    /// Definitely not landed; safe to undo the bookkeeping
+    Failed(String),
+    /// Might still land; leave for reconciliation
+    Unknown,
 }
 
 #[derive(Debug)]
 pub struct VerifiedTransaction {
-    pub signature: String,
     pub slot: u64,
-    pub amount: f64,
-    pub from: String,
-    pub to: String,
+    /// On-chain `blockTime` of the payment (unix seconds)
+    pub block_time: Option<i64>,
+    /// Lamports received by the owner
+    pub lamports: u64,
+    /// Other accounts whose SOL balance went down in the same transaction
+    pub debited: Vec<String>,
 }
 
 impl SolanaService {
@@ -73,19 +91,20 @@ impl SolanaService {
         println!("   Token Mint: {}", token_mint);
 
         Ok(Self {
-            client,
-            owner_keypair,
+            client: Arc::new(client),
+            owner_keypair: Arc::new(owner_keypair),
             token_mint,
-            network,
         })
     }
 
-    /// Verify a Solana transaction exists and matches expected parameters
+    /// Verify a Solana payment to the owner exists and report how much it paid.
+    ///
+    /// Pricing is checked by the caller once the purchase has been allocated
+    /// to presale rounds.
     pub async fn verify_transaction(
         &self,
         signature: &str,
-        expected_recipient: &str,
-        expected_amount_tokens: f64,
+        expected_sender: &str,
     ) -> Result<VerifiedTransaction> {
         let sig = Signature::from_str(signature)
             .map_err(|e| anyhow!("Invalid signature format: {}", e))?;
@@ -94,8 +113,13 @@ impl SolanaService {
         let mut attempts = 0;
         let max_attempts = 5;
         
+        let config = RpcTransactionConfig {
+            encoding: Some(UiTransactionEncoding::Base64),
+            commitment: Some(CommitmentConfig::confirmed()),
+            max_supported_transaction_version: Some(0),
+        };
         let transaction = loop {
-            match self.client.get_transaction(&sig, solana_client::rpc_config::RpcTransactionConfig::default()) {
+            match self.client.get_transaction_with_config(&sig, config) {
                 Ok(tx) => break tx,
                 Err(e) if attempts < max_attempts => {
                     attempts += 1;
@@ -116,23 +140,16 @@ impl SolanaService {
         }
 
         // Parse transaction to verify payment details
-        let tx_data = transaction.transaction.transaction;
-        let message = tx_data.message;
+        let tx_data = transaction.transaction.transaction.decode()
+            .ok_or_else(|| anyhow!("Failed to decode transaction"))?;
+        let account_keys = tx_data.message.static_account_keys();
         
         // Find the recipient (owner) in account keys
         let owner_pubkey = self.owner_keypair.pubkey();
-        let recipient_index = message.account_keys.iter()
+        let recipient_index = account_keys.iter()
             .position(|key| *key == owner_pubkey)
             .ok_or_else(|| anyhow!("Owner not found in transaction recipients"))?;
 
-        // Calculate expected SOL amount
-        let token_price_sol: f64 = env::var("TOKEN_PRICE_SOL")
-            .unwrap_or_else(|_| "0.000045".to_string())
-            .parse()
-            .unwrap_or(0.000045);
-        
-        let expected_lamports = (expected_amount_tokens * token_price_sol * 1_000_000_000.0) as u64;
-        
         // Check balance changes
         let pre_balances = &tx_meta.pre_balances;
         let post_balances = &tx_meta.post_balances;
@@ -142,38 +159,50 @@ impl SolanaService {
         }
 
         let balance_change = post_balances[recipient_index] as i64 - pre_balances[recipient_index] as i64;
-        
-        // Allow for small variations due to fees (within 1%)
-        let tolerance = (expected_lamports as f64 * 0.01) as i64;
-        if balance_change < (expected_lamports as i64 - tolerance) {
-            return Err(anyhow!(
-                "Insufficient payment: expected ~{} lamports, got {} lamports change",
-                expected_lamports, balance_change
-            ));
+        if balance_change <= 0 {
+            return Err(anyhow!("No payment received by owner"));
         }
 
         // Verify sender
-        let sender_pubkey = message.account_keys.get(0)
+        let sender_pubkey = account_keys.first()
             .ok_or_else(|| anyhow!("No sender found in transaction"))?;
         
-        let expected_sender = Pubkey::from_str(expected_recipient)
-            .map_err(|e| anyhow!("Invalid expected recipient pubkey: {}", e))?;
+        let expected_sender = Pubkey::from_str(expected_sender)
+            .map_err(|e| anyhow!("Invalid expected sender pubkey: {}", e))?;
         
         if *sender_pubkey != expected_sender {
             return Err(anyhow!("Transaction sender mismatch"));
         }
 
+        let debited = account_keys.iter()
+            .enumerate()
+            .filter(|(index, key)| {
+                *key != sender_pubkey
+                    && matches!(
+                        (pre_balances.get(*index), post_balances.get(*index)),
+                        (Some(pre), Some(post)) if post < pre
+                    )
+            })
+            .map(|(_, key)| key.to_string())
+            .collect();
+
         Ok(VerifiedTransaction {
-            signature: signature.to_string(),
             slot: transaction.slot,
-            amount: expected_amount_tokens,
-            from: sender_pubkey.to_string(),
-            to: owner_pubkey.to_string(),
+            block_time: transaction.block_time,
+            lamports: balance_change as u64,
+            debited,
         })
     }
 
-    /// Transfer SPL tokens to buyer
-    pub async fn transfer_tokens(&self, recipient: &str, amount: f64) -> Result<String> {
+    /// Build and sign, but don't send, a transaction minting `amount` tokens
+    /// to the recipient's associated token account.
+    ///
+    /// The signature is known before sending, so callers can record it first
+    /// and later tell whether the distribution landed.
+    ///
+    /// With `freeze` the account is frozen right after minting. An account
+    /// that is already frozen is thawed for the mint and frozen again.
+    pub async fn build_distribution(&self, recipient: &str, amount: Decimal, freeze: bool) -> Result<Transaction> {
         let recipient_pubkey = Pubkey::from_str(recipient)
             .map_err(|e| anyhow!("Invalid recipient pubkey: {}", e))?;
 
@@ -181,13 +210,23 @@ impl SolanaService {
         let recipient_ata = get_associated_token_address(&recipient_pubkey, &self.token_mint);
         
         // Check if ATA exists
-        let ata_exists = match self.client.get_account(&recipient_ata) {
-            Ok(_) => true,
-            Err(_) => false,
+        let ata = self.client.get_account(&recipient_ata).ok();
+        let ata_exists = ata.is_some();
+        let was_frozen = match &ata {
+            Some(account) => TokenAccount::unpack(&account.data)?.is_frozen(),
+            None => false,
         };
+        let freeze = freeze || was_frozen;
+        if freeze {
+            self.ensure_freeze_authority()?;
+        }
 
         let mut instructions = Vec::new();
 
+        if was_frozen {
+            instructions.push(self.freeze_instruction(&recipient_ata, false)?);
+        }
+
         // Create ATA if it doesn't exist
         if !ata_exists {
             let create_ata_ix = create_associated_token_account(
@@ -201,7 +240,10 @@ impl SolanaService {
 
         // Convert token amount to smallest unit (considering decimals)
         let token_decimals = self.get_token_decimals().await?;
-        let amount_units = (amount * 10_f64.powi(token_decimals as i32)) as u64;
+        let amount_units = (amount * Decimal::from(10u64.pow(token_decimals as u32)))
+            .trunc()
+            .to_u64()
+            .ok_or_else(|| anyhow!("Token amount out of range: {}", amount))?;
 
         // Create mint instruction
         let mint_ix = mint_to(
@@ -214,48 +256,177 @@ impl SolanaService {
         )?;
         instructions.push(mint_ix);
 
-        // Create and send transaction
+        if freeze {
+            instructions.push(self.freeze_instruction(&recipient_ata, true)?);
+        }
+
         let recent_blockhash = self.client.get_latest_blockhash()?;
-        let transaction = Transaction::new_signed_with_payer(
+        Ok(Transaction::new_signed_with_payer(
             &instructions,
             Some(&self.owner_keypair.pubkey()),
+            &[&*self.owner_keypair],
+            recent_blockhash,
+        ))
+    }
+
+    /// Send a prepared transaction and classify the result.
+    ///
+    /// An error from the RPC node doesn't prove the transaction failed, so
+    /// it is only reported as `Failed` once its blockhash has expired without
+    /// the signature landing.
+    pub async fn send_prepared(&self, transaction: &Transaction) -> SendOutcome {
+        let signature = transaction.signatures[0];
+        match self.client.send_and_confirm_transaction(transaction) {
+            Ok(_) => SendOutcome::Confirmed,
+            Err(e) => {
+                eprintln!("Sending transaction {} failed: {}", signature, e);
+                self.check_signature(&signature, &transaction.message.recent_blockhash)
+            }
+        }
+    }
+
+    /// Look up a previously signed transaction: landed, definitely dropped,
+    /// or still undecided
+    pub fn check_signature(&self, signature: &Signature, blockhash: &Hash) -> SendOutcome {
+        match self.client.get_signature_status(signature) {
+            Ok(Some(Ok(()))) => SendOutcome::Confirmed,
+            Ok(Some(Err(e))) => SendOutcome::Failed(e.to_string()),
+            Ok(None) => match self.client.is_blockhash_valid(blockhash, CommitmentConfig::processed()) {
+                Ok(false) => SendOutcome::Failed("blockhash expired before landing".to_string()),
+                _ => SendOutcome::Unknown,
+            },
+            Err(_) => SendOutcome::Unknown,
+        }
+    }
+
+    /// Build and sign, but don't send, a SOL transfer from the owner
+    pub async fn build_sol_transfer(&self, recipient: &str, lamports: u64) -> Result<Transaction> {
+        let recipient_pubkey = Pubkey::from_str(recipient)
+            .map_err(|e| anyhow!("Invalid recipient pubkey: {}", e))?;
+
+        let transfer_ix = system_instruction::transfer(
+            &self.owner_keypair.pubkey(),
+            &recipient_pubkey,
+            lamports,
+        );
+
+        let recent_blockhash = self.client.get_latest_blockhash()?;
+        Ok(Transaction::new_signed_with_payer(
+            &[transfer_ix],
+            Some(&self.owner_keypair.pubkey()),
             &[&self.owner_keypair],
             recent_blockhash,
+        ))
+    }
+
+    /// Read and decode a Pyth price account
+    pub async fn get_oracle_price(&self, price_account: &Pubkey) -> Result<OraclePrice> {
+        let account = self.client.get_account(price_account)
+            .map_err(|e| anyhow!("Failed to fetch oracle account {}: {}", price_account, e))?;
+
+        Ok(OraclePrice::decode_pyth(&account.data)?)
+    }
+
+    /// The presale token account of a wallet
+    pub fn token_account_for(&self, wallet: &str) -> Result<Pubkey> {
+        let wallet_pubkey = Pubkey::from_str(wallet)
+            .map_err(|e| anyhow!("Invalid wallet address: {}", e))?;
+        Ok(get_associated_token_address(&wallet_pubkey, &self.token_mint))
+    }
+
+    /// Freeze or thaw a single wallet's token account. Returns `None` if the
+    /// account was already in that state.
+    pub async fn set_account_frozen(&self, wallet: &str, frozen: bool) -> Result<Option<String>> {
+        self.ensure_freeze_authority()?;
+        let token_account = self.token_account_for(wallet)?;
+
+        let account = self.client.get_account(&token_account)
+            .map_err(|e| anyhow!("Token account {} not found: {}", token_account, e))?;
+        if TokenAccount::unpack(&account.data)?.is_frozen() == frozen {
+            return Ok(None);
+        }
+
+        let instruction = self.freeze_instruction(&token_account, frozen)?;
+        let signature = self.send_instructions(&[instruction])?;
+        println!(
+            "✅ {} token account {} of {}, signature: {}",
+            if frozen { "Froze" } else { "Thawed" }, token_account, wallet, signature
         );
 
-        let signature = self.client.send_and_confirm_transaction(&transaction)?;
-        
-        println!("✅ Transferred {} tokens to {}, signature: {}", amount, recipient, signature);
-        
-        Ok(signature.to_string())
+        Ok(Some(signature))
     }
 
-    /// Get token mint decimals
-    async fn get_token_decimals(&self) -> Result<u8> {
+    /// Thaw a batch of token accounts in one transaction, skipping any that
+    /// are missing or not frozen on chain. Returns `None` if nothing needed
+    /// thawing; either way every account in the batch is unfrozen afterwards.
+    pub async fn thaw_token_accounts(&self, token_accounts: &[Pubkey]) -> Result<Option<String>> {
+        self.ensure_freeze_authority()?;
+
+        let accounts = self.client.get_multiple_accounts(token_accounts)?;
+        let mut instructions = Vec::new();
+        for (token_account, account) in token_accounts.iter().zip(accounts) {
+            if let Some(account) = account {
+                if TokenAccount::unpack(&account.data)?.is_frozen() {
+                    instructions.push(self.freeze_instruction(token_account, false)?);
+                }
+            }
+        }
+
+        if instructions.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(self.send_instructions(&instructions)?))
+    }
+
+    fn freeze_instruction(&self, token_account: &Pubkey, frozen: bool) -> Result<Instruction> {
+        let authority = self.owner_keypair.pubkey();
+        let instruction = if frozen {
+            freeze_account(&spl_token::id(), token_account, &self.token_mint, &authority, &[&authority])?
+        } else {
+            thaw_account(&spl_token::id(), token_account, &self.token_mint, &authority, &[&authority])?
+        };
+        Ok(instruction)
+    }
+
+    /// The owner keypair must be the mint's freeze authority
+    fn ensure_freeze_authority(&self) -> Result<()> {
         let mint_account = self.client.get_account(&self.token_mint)?;
         let mint_data = Mint::unpack(&mint_account.data)?;
-        Ok(mint_data.decimals)
+        if Option::<Pubkey>::from(mint_data.freeze_authority) != Some(self.owner_keypair.pubkey()) {
+            return Err(anyhow!("Owner {} is not the token mint's freeze authority", self.owner_keypair.pubkey()));
+        }
+        Ok(())
     }
 
-    /// Create a new SPL token mint (for initial setup)
-    pub async fn create_token_mint(&self, decimals: u8, initial_supply: u64) -> Result<Pubkey> {
-        // This would be used for initial token creation
-        // Implementation depends on your specific token requirements
-        todo!("Implement token mint creation if needed")
+    fn send_instructions(&self, instructions: &[Instruction]) -> Result<String> {
+        let recent_blockhash = self.client.get_latest_blockhash()?;
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.owner_keypair.pubkey()),
+            &[&self.owner_keypair],
+            recent_blockhash,
+        );
+        Ok(self.client.send_and_confirm_transaction(&transaction)?.to_string())
     }
 
-    /// Get current token supply and other stats
-    pub async fn get_token_stats(&self) -> Result<serde_json::Value> {
+    /// Get token mint decimals
+    pub async fn get_token_decimals(&self) -> Result<u8> {
         let mint_account = self.client.get_account(&self.token_mint)?;
         let mint_data = Mint::unpack(&mint_account.data)?;
-        
-        Ok(serde_json::json!({
-            "mint_address": self.token_mint.to_string(),
-            "supply": mint_data.supply,
-            "decimals": mint_data.decimals,
-            "is_initialized": mint_data.is_initialized,
-            "freeze_authority": mint_data.freeze_authority.map(|k| k.to_string()),
-            "mint_authority": mint_data.mint_authority.map(|k| k.to_string()),
-        }))
+        Ok(mint_data.decimals)
     }
 }
+
+/// Check that `signature` (base58) is `wallet`'s ed25519 signature of `message`
+pub fn verify_wallet_signature(wallet: &str, message: &str, signature: &str) -> Result<()> {
+    let pubkey = Pubkey::from_str(wallet)
+        .map_err(|e| anyhow!("Invalid wallet address: {}", e))?;
+    let signature = Signature::from_str(signature)
+        .map_err(|e| anyhow!("Invalid signature format: {}", e))?;
+
+    if !signature.verify(pubkey.as_ref(), message.as_bytes()) {
+        return Err(anyhow!("Signature does not match wallet"));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,367 @@
+use anyhow::Result;
+use chrono::Utc;
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::Serialize;
+use sqlx::{PgConnection, PgPool, Row};
+use std::fmt;
+
+use crate::models::{CapOverflowMode, PresaleSettings, RoundFill};
+
+/// Paid SOL above the cost is refunded only past this much; smaller
+/// amounts would cost more in fees to send back than they return
+const MIN_REFUND_SOL: Decimal = Decimal::from_parts(1, 0, 0, false, 5); // 0.00001
+
+#[derive(Debug, Clone)]
+pub struct SupplyCaps {
+    pub hard_cap_tokens: Decimal,
+    pub hard_cap_sol: Decimal,
+    pub soft_cap_sol: Decimal,
+    pub overflow_mode: CapOverflowMode,
+}
+
+impl SupplyCaps {
+    pub fn from_settings(settings: &PresaleSettings) -> Self {
+        Self {
+            hard_cap_tokens: settings.hard_cap_tokens,
+            hard_cap_sol: settings.hard_cap_sol,
+            soft_cap_sol: settings.soft_cap_sol,
+            overflow_mode: settings.cap_overflow_mode,
+        }
+    }
+}
+
+/// Supply reserved for a single purchase
+#[derive(Debug, Clone)]
+pub struct SupplyReservation {
+    pub requested_tokens: Decimal,
+    pub tokens: Decimal,
+    /// SOL kept for the purchase: what was paid, less the refund
+    pub sol: Decimal,
+    /// SOL paid beyond the cost of the tokens filled: for tokens that could
+    /// not be filled, or simply overpaid
+    pub refund_sol: Decimal,
+    pub soft_cap_reached: bool,
+    pub fills: Vec<RoundFill>,
+}
+
+impl SupplyReservation {
+    pub fn is_partial(&self) -> bool {
+        self.tokens < self.requested_tokens
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SupplyStatus {
+    pub tokens_sold: Decimal,
+    pub sol_raised: Decimal,
+    pub hard_cap_tokens: Decimal,
+    pub hard_cap_sol: Decimal,
+    pub soft_cap_sol: Decimal,
+    pub soft_cap_reached: bool,
+    pub sold_out: bool,
+    pub outcome: PresaleOutcome,
+}
+
+/// Post-presale state driven by the soft cap flag
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PresaleOutcome {
+    /// Still selling
+    Active,
+    /// Ended (or sold out) with the soft cap reached: proceed to TGE
+    Succeeded,
+    /// Ended without reaching the soft cap: buyers are due refunds
+    Failed,
+}
+
+/// Reason a purchase could not be reserved against the caps
+#[derive(Debug, Clone)]
+pub enum CapRejection {
+    SoldOut,
+    ExceedsRemaining { remaining_tokens: Decimal },
+}
+
+impl CapRejection {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::SoldOut => "sold_out",
+            Self::ExceedsRemaining { .. } => "exceeds_hard_cap",
+        }
+    }
+}
+
+impl fmt::Display for CapRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SoldOut => write!(f, "Presale hard cap reached"),
+            Self::ExceedsRemaining { remaining_tokens } => write!(
+                f,
+                "Purchase exceeds hard cap. Remaining: {} tokens",
+                remaining_tokens
+            ),
+        }
+    }
+}
+
+impl std::error::Error for CapRejection {}
+
+/// Trim round fills so the purchase fits under the hard caps.
+///
+/// Fills are consumed in order, so the cheaper early rounds are kept and the
+/// tail of the purchase is what gets cut.
+pub fn fit_fills_to_caps(
+    caps: &SupplyCaps,
+    tokens_sold: Decimal,
+    sol_raised: Decimal,
+    fills: Vec<RoundFill>,
+    requested_tokens: Decimal,
+) -> std::result::Result<Vec<RoundFill>, CapRejection> {
+    let mut remaining_tokens = (caps.hard_cap_tokens - tokens_sold).max(Decimal::ZERO);
+    let mut remaining_sol = (caps.hard_cap_sol - sol_raised).max(Decimal::ZERO);
+
+    let mut fitted = Vec::with_capacity(fills.len());
+    for mut fill in fills {
+        let affordable_tokens = remaining_sol
+            .checked_div(fill.price)
+            .map(|tokens| tokens.round_dp_with_strategy(8, RoundingStrategy::ToZero))
+            .unwrap_or(remaining_tokens);
+        let tokens = fill.tokens.min(remaining_tokens).min(affordable_tokens);
+        if tokens <= Decimal::ZERO {
+            break;
+        }
+
+        if tokens < fill.tokens {
+            fill.tokens = tokens;
+            fill.cost = (tokens * fill.price).round_dp(8);
+        }
+        remaining_tokens -= fill.tokens;
+        remaining_sol -= fill.cost;
+        fitted.push(fill);
+    }
+
+    let filled: Decimal = fitted.iter().map(|fill| fill.tokens).sum();
+    if filled <= Decimal::ZERO {
+        return Err(CapRejection::SoldOut);
+    }
+    if filled < requested_tokens && caps.overflow_mode == CapOverflowMode::Reject {
+        return Err(CapRejection::ExceedsRemaining { remaining_tokens: filled });
+    }
+
+    Ok(fitted)
+}
+
+/// Atomically reserve supply for a purchase already split into round fills.
+///
+/// Locks the `presale_supply` row for the rest of the surrounding database
+/// transaction, so the caller must commit (or roll back) promptly.
+pub async fn reserve_supply(
+    conn: &mut PgConnection,
+    caps: &SupplyCaps,
+    fills: Vec<RoundFill>,
+    requested_tokens: Decimal,
+    paid_sol: Decimal,
+) -> Result<SupplyReservation> {
+    let row = sqlx::query(
+        "SELECT tokens_sold, sol_raised FROM presale_supply WHERE id FOR UPDATE"
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let tokens_sold: Decimal = row.get("tokens_sold");
+    let sol_raised: Decimal = row.get("sol_raised");
+
+    let fills = fit_fills_to_caps(caps, tokens_sold, sol_raised, fills, requested_tokens)?;
+    let tokens: Decimal = fills.iter().map(|fill| fill.tokens).sum();
+    let cost: Decimal = fills.iter().map(|fill| fill.cost).sum();
+    let (sol, refund_sol) = split_payment(paid_sol, cost);
+
+    let soft_cap_reached: bool = sqlx::query_scalar(
+        r#"
+        UPDATE presale_supply
+        SET tokens_sold = tokens_sold + $1,
+            sol_raised = sol_raised + $2,
+            soft_cap_reached = soft_cap_reached OR sol_raised + $2 >= $3,
+            soft_cap_reached_at = CASE
+                WHEN NOT soft_cap_reached AND sol_raised + $2 >= $3 THEN NOW()
+                ELSE soft_cap_reached_at
+            END,
+            updated_at = NOW()
+        WHERE id
+        RETURNING soft_cap_reached
+        "#
+    )
+    .bind(tokens)
+    .bind(sol)
+    .bind(caps.soft_cap_sol)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok(SupplyReservation {
+        requested_tokens,
+        tokens,
+        sol,
+        refund_sol,
+        soft_cap_reached,
+        fills,
+    })
+}
+
+/// Split what was paid for fills costing `cost` into the SOL kept (which
+/// counts as raised) and the SOL to refund
+pub fn split_payment(paid: Decimal, cost: Decimal) -> (Decimal, Decimal) {
+    let refund = refundable_excess(paid, cost);
+    (paid - refund, refund)
+}
+
+/// SOL to send back when `paid` exceeds the `cost` of what was filled
+pub fn refundable_excess(paid: Decimal, cost: Decimal) -> Decimal {
+    let excess = paid - cost;
+    if excess >= MIN_REFUND_SOL {
+        excess
+    } else {
+        Decimal::ZERO
+    }
+}
+
+/// Current supply counters alongside the configured caps
+pub async fn get_supply_status(pool: &PgPool, settings: &PresaleSettings) -> Result<SupplyStatus> {
+    let caps = SupplyCaps::from_settings(settings);
+    let row = sqlx::query(
+        "SELECT tokens_sold, sol_raised, soft_cap_reached FROM presale_supply WHERE id"
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let presale_ended = settings.presale_end <= Utc::now();
+    let tokens_sold: Decimal = row.get("tokens_sold");
+    let sol_raised: Decimal = row.get("sol_raised");
+    let soft_cap_reached: bool = row.get("soft_cap_reached");
+    let sold_out = tokens_sold >= caps.hard_cap_tokens || sol_raised >= caps.hard_cap_sol;
+
+    let outcome = match (presale_ended || sold_out, soft_cap_reached) {
+        (false, _) => PresaleOutcome::Active,
+        (true, true) => PresaleOutcome::Succeeded,
+        (true, false) => PresaleOutcome::Failed,
+    };
+
+    Ok(SupplyStatus {
+        tokens_sold,
+        sol_raised,
+        hard_cap_tokens: caps.hard_cap_tokens,
+        hard_cap_sol: caps.hard_cap_sol,
+        soft_cap_sol: caps.soft_cap_sol,
+        soft_cap_reached,
+        sold_out,
+        outcome,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ReleaseKind;
+    use crate::services::VestingTerms;
+    use std::str::FromStr;
+    use uuid::Uuid;
+
+    fn dec(value: &str) -> Decimal {
+        Decimal::from_str(value).unwrap()
+    }
+
+    fn caps(hard_cap_tokens: &str, hard_cap_sol: &str, overflow_mode: CapOverflowMode) -> SupplyCaps {
+        SupplyCaps {
+            hard_cap_tokens: dec(hard_cap_tokens),
+            hard_cap_sol: dec(hard_cap_sol),
+            soft_cap_sol: Decimal::ZERO,
+            overflow_mode,
+        }
+    }
+
+    fn fill(tokens: &str, price: &str) -> RoundFill {
+        let tokens = dec(tokens);
+        let price = dec(price);
+        RoundFill {
+            round_id: Uuid::nil(),
+            round_name: "Seed".to_string(),
+            tokens,
+            price,
+            cost: (tokens * price).round_dp(8),
+            vesting: VestingTerms {
+                tge_unlock_percent: Decimal::ZERO,
+                cliff_days: 0,
+                duration_days: 0,
+                release: ReleaseKind::Linear,
+                unlock_table: Vec::new(),
+            },
+            distribution_mode: "claim".to_string(),
+        }
+    }
+
+    fn filled(fills: &[RoundFill]) -> (Decimal, Decimal) {
+        (fills.iter().map(|fill| fill.tokens).sum(), fills.iter().map(|fill| fill.cost).sum())
+    }
+
+    #[test]
+    fn purchase_exactly_at_cap_is_filled() {
+        let caps = caps("1000", "100", CapOverflowMode::Reject);
+        let fills = fit_fills_to_caps(&caps, dec("600"), dec("60"), vec![fill("400", "0.1")], dec("400")).unwrap();
+        assert_eq!(filled(&fills), (dec("400"), dec("40")));
+    }
+
+    #[test]
+    fn purchase_past_cap_is_cut_at_the_tail() {
+        let caps = caps("1000", "1000", CapOverflowMode::PartialRefund);
+        let fills = fit_fills_to_caps(
+            &caps,
+            dec("700"),
+            Decimal::ZERO,
+            vec![fill("200", "0.1"), fill("300", "0.2")],
+            dec("500"),
+        )
+        .unwrap();
+        assert_eq!(fills.len(), 2);
+        assert_eq!(fills[1].tokens, dec("100"));
+        assert_eq!(filled(&fills), (dec("300"), dec("40")));
+
+        let caps = SupplyCaps { overflow_mode: CapOverflowMode::Reject, ..caps };
+        assert!(matches!(
+            fit_fills_to_caps(&caps, dec("700"), Decimal::ZERO, vec![fill("500", "0.1")], dec("500")),
+            Err(CapRejection::ExceedsRemaining { remaining_tokens }) if remaining_tokens == dec("300")
+        ));
+    }
+
+    #[test]
+    fn nothing_remaining_is_sold_out() {
+        let caps = caps("1000", "100", CapOverflowMode::PartialRefund);
+        assert!(matches!(
+            fit_fills_to_caps(&caps, dec("1000"), dec("50"), vec![fill("10", "0.1")], dec("10")),
+            Err(CapRejection::SoldOut)
+        ));
+        // The SOL cap alone can sell out the presale too
+        assert!(matches!(
+            fit_fills_to_caps(&caps, dec("500"), dec("100"), vec![fill("10", "0.1")], dec("10")),
+            Err(CapRejection::SoldOut)
+        ));
+    }
+
+    #[test]
+    fn only_excess_past_fees_is_refunded() {
+        assert_eq!(refundable_excess(dec("1.5"), dec("1.2")), dec("0.3"));
+        assert_eq!(refundable_excess(dec("1.2000099"), dec("1.2")), Decimal::ZERO);
+        assert_eq!(refundable_excess(dec("1.19"), dec("1.2")), Decimal::ZERO);
+    }
+
+    #[test]
+    fn sol_paid_is_kept_less_the_refund() {
+        assert_eq!(split_payment(dec("1.5"), dec("1.2")), (dec("1.2"), dec("0.3")));
+        // Dust too small to refund stays with the purchase
+        assert_eq!(split_payment(dec("1.2000099"), dec("1.2")), (dec("1.2000099"), Decimal::ZERO));
+    }
+
+    #[test]
+    fn sol_cap_cuts_a_purchase_the_token_cap_would_take() {
+        let caps = caps("10000", "100", CapOverflowMode::PartialRefund);
+        let fills = fit_fills_to_caps(&caps, dec("5000"), dec("85"), vec![fill("200", "0.1")], dec("200")).unwrap();
+        assert_eq!(filled(&fills), (dec("150"), dec("15")));
+    }
+}
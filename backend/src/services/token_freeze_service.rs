@@ -0,0 +1,244 @@
+use anyhow::{anyhow, Result};
+use solana_sdk::pubkey::Pubkey;
+use sqlx::{PgConnection, PgPool};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use uuid::Uuid;
+
+use crate::models::TgeThawRun;
+use crate::services::{
+    apply_settings_changes_in, get_supply_status, record_audit_event_in, AuditContext, AuditEntry, PresaleOutcome,
+    SettingsChangeContext, SettingsService, SolanaService,
+};
+
+/// Remember a wallet's token account and the freeze state a distribution or
+/// admin action left it in
+pub async fn track_token_account(
+    conn: &mut PgConnection,
+    wallet_address: &str,
+    token_account: &Pubkey,
+    frozen: bool,
+    signature: Option<&str>,
+) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO token_accounts (wallet_address, token_account, is_frozen, frozen_at, thawed_at, last_signature)
+        VALUES ($1, $2, $3, CASE WHEN $3 THEN NOW() END, CASE WHEN NOT $3 THEN NOW() END, $4)
+        ON CONFLICT (wallet_address) DO UPDATE SET
+            is_frozen = EXCLUDED.is_frozen,
+            frozen_at = CASE WHEN EXCLUDED.is_frozen THEN NOW() ELSE token_accounts.frozen_at END,
+            thawed_at = CASE WHEN EXCLUDED.is_frozen THEN token_accounts.thawed_at ELSE NOW() END,
+            last_signature = COALESCE(EXCLUDED.last_signature, token_accounts.last_signature),
+            updated_at = NOW()
+        "#
+    )
+    .bind(wallet_address)
+    .bind(token_account.to_string())
+    .bind(frozen)
+    .bind(signature)
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+/// Most recent TGE thaw run, if one was ever started
+pub async fn latest_tge_thaw(pool: &PgPool) -> Result<Option<TgeThawRun>> {
+    let run = sqlx::query_as::<_, TgeThawRun>(
+        "SELECT * FROM tge_thaw_runs ORDER BY started_at DESC LIMIT 1"
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(run)
+}
+
+/// Start the TGE thaw, or resume a failed one.
+///
+/// Only allowed once the presale has succeeded. New distributions stop
+/// freezing from this point, so accounts can't be frozen behind the thaw.
+pub async fn start_tge_thaw(
+    pool: &PgPool,
+    solana_service: &SolanaService,
+    settings: &SettingsService,
+    audit: &AuditContext,
+) -> Result<TgeThawRun> {
+    let current = settings.current();
+    let supply = get_supply_status(pool, &current).await?;
+    if supply.outcome != PresaleOutcome::Succeeded {
+        return Err(anyhow!("TGE thaw requires a successful presale, outcome is {:?}", supply.outcome));
+    }
+
+    if let Some(run) = latest_tge_thaw(pool).await? {
+        if run.status == "running" {
+            return Err(anyhow!("TGE thaw {} is already running", run.id));
+        }
+    }
+
+    let mut db_tx = pool.begin().await?;
+
+    let freeze_off = BTreeMap::from([("freeze_until_tge".to_string(), Some("false".to_string()))]);
+    let context = SettingsChangeContext {
+        admin_id: None,
+        name: "tge_thaw",
+        source: "system",
+        schedule_id: None,
+        reason: Some("TGE thaw started"),
+        audit,
+    };
+    apply_settings_changes_in(&mut db_tx, &freeze_off, &context).await?;
+
+    // Resume the last failed run so its progress carries over
+    let resumed = sqlx::query_as::<_, TgeThawRun>(
+        r#"
+        UPDATE tge_thaw_runs
+        SET status = 'running', last_error = NULL, updated_at = NOW()
+        WHERE id = (SELECT id FROM tge_thaw_runs ORDER BY started_at DESC LIMIT 1)
+          AND status = 'failed'
+        RETURNING *
+        "#
+    )
+    .fetch_optional(&mut *db_tx)
+    .await?;
+
+    let run = match resumed {
+        Some(run) => run,
+        None => {
+            sqlx::query_as::<_, TgeThawRun>(
+                r#"
+                INSERT INTO tge_thaw_runs (total_accounts)
+                SELECT COUNT(*) FROM token_accounts WHERE is_frozen
+                RETURNING *
+                "#
+            )
+            .fetch_one(&mut *db_tx)
+            .await?
+        }
+    };
+
+    let entry = AuditEntry {
+        action: "tge.thaw_started",
+        entity_type: "tge_thaw_run",
+        entity_id: run.id.to_string(),
+        before: None,
+        after: Some(serde_json::to_value(&run)?),
+    };
+    record_audit_event_in(&mut db_tx, audit, entry).await?;
+    db_tx.commit().await?;
+
+    println!("🔓 TGE thaw {} started for {} accounts", run.id, run.total_accounts);
+    spawn_tge_thaw(pool.clone(), solana_service.clone(), settings.clone(), run.id);
+    Ok(run)
+}
+
+/// Pick up a thaw that was running when the server stopped
+pub async fn resume_tge_thaw(pool: &PgPool, solana_service: &SolanaService, settings: &SettingsService) -> Result<()> {
+    if let Some(run) = latest_tge_thaw(pool).await? {
+        if run.status == "running" {
+            println!("🔓 Resuming TGE thaw {} ({}/{} accounts thawed)", run.id, run.thawed_accounts, run.total_accounts);
+            spawn_tge_thaw(pool.clone(), solana_service.clone(), settings.clone(), run.id);
+        }
+    }
+    Ok(())
+}
+
+fn spawn_tge_thaw(pool: PgPool, solana_service: SolanaService, settings: SettingsService, run_id: Uuid) {
+    tokio::spawn(async move {
+        let result = run_tge_thaw(&pool, &solana_service, &settings, run_id).await;
+
+        let (status, error) = match &result {
+            Ok(()) => ("completed", None),
+            Err(e) => ("failed", Some(e.to_string())),
+        };
+        if let Err(e) = sqlx::query(
+            r#"
+            UPDATE tge_thaw_runs
+            SET status = $1, last_error = $2, updated_at = NOW(),
+                finished_at = CASE WHEN $1 = 'completed' THEN NOW() END
+            WHERE id = $3
+            "#
+        )
+        .bind(status)
+        .bind(&error)
+        .bind(run_id)
+        .execute(&pool)
+        .await
+        {
+            eprintln!("Failed to record TGE thaw {} as {}: {}", run_id, status, e);
+        }
+
+        match error {
+            None => println!("✅ TGE thaw {} completed", run_id),
+            Some(e) => eprintln!("TGE thaw {} failed: {}", run_id, e),
+        }
+    });
+}
+
+/// Thaw frozen accounts batch by batch until none are left. Each batch is
+/// checked against the chain first, so re-running a batch after a crash is
+/// harmless.
+async fn run_tge_thaw(
+    pool: &PgPool,
+    solana_service: &SolanaService,
+    settings: &SettingsService,
+    run_id: Uuid,
+) -> Result<()> {
+    loop {
+        let batch_size = settings.current().tge_thaw_batch_size;
+        let batch = sqlx::query_as::<_, (String, String)>(
+            r#"
+            SELECT wallet_address, token_account FROM token_accounts
+            WHERE is_frozen
+            ORDER BY wallet_address
+            LIMIT $1
+            "#
+        )
+        .bind(batch_size)
+        .fetch_all(pool)
+        .await?;
+
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        let token_accounts = batch
+            .iter()
+            .map(|(_, token_account)| Pubkey::from_str(token_account))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(|e| anyhow!("Invalid token account: {}", e))?;
+        let signature = solana_service.thaw_token_accounts(&token_accounts).await?;
+
+        let wallets: Vec<&str> = batch.iter().map(|(wallet, _)| wallet.as_str()).collect();
+        let mut db_tx = pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            UPDATE token_accounts
+            SET is_frozen = false, thawed_at = NOW(),
+                last_signature = COALESCE($1, last_signature), updated_at = NOW()
+            WHERE wallet_address = ANY($2)
+            "#
+        )
+        .bind(&signature)
+        .bind(&wallets)
+        .execute(&mut *db_tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            UPDATE tge_thaw_runs
+            SET thawed_accounts = thawed_accounts + $1,
+                batches_sent = batches_sent + CASE WHEN $2 THEN 1 ELSE 0 END,
+                updated_at = NOW()
+            WHERE id = $3
+            "#
+        )
+        .bind(batch.len() as i32)
+        .bind(signature.is_some())
+        .bind(run_id)
+        .execute(&mut *db_tx)
+        .await?;
+
+        db_tx.commit().await?;
+    }
+}
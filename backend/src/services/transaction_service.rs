@@ -0,0 +1,27 @@
+use anyhow::Result;
+use sqlx::PgPool;
+
+use crate::models::*;
+
+/// Upper bound on the purchase history returned for one wallet
+const WALLET_TRANSACTIONS_LIMIT: i64 = 100;
+
+/// A wallet's purchases, newest first
+pub async fn list_wallet_transactions(pool: &PgPool, wallet_address: &str) -> Result<Vec<Transaction>> {
+    let transactions = sqlx::query_as::<_, Transaction>(
+        r#"
+        SELECT t.*
+        FROM transactions t
+        JOIN users u ON u.id = t.user_id
+        WHERE u.wallet_address = $1
+        ORDER BY t.created_at DESC
+        LIMIT $2
+        "#
+    )
+    .bind(wallet_address)
+    .bind(WALLET_TRANSACTIONS_LIMIT)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(transactions)
+}
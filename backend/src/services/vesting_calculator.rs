@@ -0,0 +1,427 @@
+//! Pure vesting math.
+//!
+//! A schedule unlocks `tge_unlock_percent` of its total at the start date
+//! (TGE or purchase). Nothing more unlocks before the cliff; after it the
+//! remainder is released linearly, in calendar-month steps, or by a custom
+//! unlock table, all measured from the start date. Amounts are exact decimals
+//! rounded down to 8 places, so the total is never exceeded and the vested
+//! amount never decreases over time.
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, Months, Utc};
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::Serialize;
+
+use crate::models::{PresaleRound, ReleaseKind, UnlockEvent, UnlockPoint, VestingSchedule};
+
+const TOKEN_DECIMALS: u32 = 8;
+const DAYS_PER_MONTH_STEP: i64 = 30;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VestingTerms {
+    pub tge_unlock_percent: Decimal,
+    pub cliff_days: i64,
+    /// Length of the release period, counted from the start date
+    pub duration_days: i64,
+    pub release: ReleaseKind,
+    /// Only used by `ReleaseKind::Custom`
+    pub unlock_table: Vec<UnlockPoint>,
+}
+
+fn round_down(amount: Decimal) -> Decimal {
+    amount.round_dp_with_strategy(TOKEN_DECIMALS, RoundingStrategy::ToZero)
+}
+
+impl VestingTerms {
+    pub fn for_round(round: &PresaleRound) -> Self {
+        Self {
+            tge_unlock_percent: round.tge_unlock_percent,
+            cliff_days: round.cliff_duration_days as i64,
+            duration_days: round.vesting_duration_days as i64,
+            release: ReleaseKind::parse(&round.release_kind).unwrap_or(ReleaseKind::Linear),
+            unlock_table: round.unlock_table.as_ref().map(|t| t.0.clone()).unwrap_or_default(),
+        }
+    }
+
+    pub fn for_schedule(schedule: &VestingSchedule) -> Self {
+        Self {
+            tge_unlock_percent: schedule.tge_unlock_percent,
+            cliff_days: schedule.cliff_duration_days as i64,
+            duration_days: schedule.vesting_duration_days as i64,
+            release: ReleaseKind::parse(&schedule.release_kind).unwrap_or(ReleaseKind::Linear),
+            unlock_table: schedule.unlock_table.as_ref().map(|t| t.0.clone()).unwrap_or_default(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.tge_unlock_percent < Decimal::ZERO || self.tge_unlock_percent > Decimal::ONE_HUNDRED {
+            return Err(anyhow!("TGE unlock must be between 0 and 100 percent"));
+        }
+        if self.cliff_days < 0 || self.duration_days < 0 {
+            return Err(anyhow!("Cliff and duration can't be negative"));
+        }
+
+        if self.release == ReleaseKind::Custom {
+            let last = self
+                .unlock_table
+                .last()
+                .ok_or_else(|| anyhow!("Custom vesting needs an unlock table"))?;
+            if last.percent != Decimal::ONE_HUNDRED {
+                return Err(anyhow!("Unlock table must end at 100 percent"));
+            }
+            let mut previous: Option<&UnlockPoint> = None;
+            for point in &self.unlock_table {
+                if point.offset_days < 0 || point.percent < Decimal::ZERO {
+                    return Err(anyhow!("Unlock table entries can't be negative"));
+                }
+                if let Some(previous) = previous {
+                    if point.offset_days <= previous.offset_days || point.percent < previous.percent {
+                        return Err(anyhow!(
+                            "Unlock table offsets must increase and percentages must not decrease"
+                        ));
+                    }
+                }
+                previous = Some(point);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Unlocked at the start date
+    pub fn tge_amount(&self, total: Decimal) -> Decimal {
+        round_down(total * self.tge_unlock_percent / Decimal::ONE_HUNDRED)
+    }
+
+    fn step_count(&self) -> u32 {
+        (self.duration_days / DAYS_PER_MONTH_STEP).max(1) as u32
+    }
+
+    /// Share of the post-TGE remainder released by `at`, as a numerator and
+    /// denominator so callers can multiply before dividing (a third of 900
+    /// must come to exactly 300)
+    fn released_share(&self, start: DateTime<Utc>, at: DateTime<Utc>) -> (Decimal, Decimal) {
+        let elapsed = at - start;
+        if elapsed < Duration::days(self.cliff_days) {
+            return (Decimal::ZERO, Decimal::ONE);
+        }
+
+        match self.release {
+            ReleaseKind::Linear => {
+                let duration = Duration::days(self.duration_days);
+                if elapsed >= duration {
+                    return (Decimal::ONE, Decimal::ONE);
+                }
+                (Decimal::from(elapsed.num_seconds()), Decimal::from(duration.num_seconds()))
+            }
+            ReleaseKind::MonthlyStep => {
+                let steps = self.step_count();
+                let completed = (1..=steps)
+                    .take_while(|step| {
+                        start
+                            .checked_add_months(Months::new(*step))
+                            .is_some_and(|step_at| step_at <= at)
+                    })
+                    .count() as u32;
+                (Decimal::from(completed), Decimal::from(steps))
+            }
+            ReleaseKind::Custom => {
+                let elapsed_days = elapsed.num_days();
+                self.unlock_table
+                    .iter()
+                    .take_while(|point| point.offset_days <= elapsed_days)
+                    .last()
+                    .map(|point| (point.percent, Decimal::ONE_HUNDRED))
+                    .unwrap_or((Decimal::ZERO, Decimal::ONE))
+            }
+        }
+    }
+
+    /// Tokens out of `total` vested at `at` for a schedule starting at `start`
+    pub fn vested_amount(&self, total: Decimal, start: DateTime<Utc>, at: DateTime<Utc>) -> Decimal {
+        if at < start || total <= Decimal::ZERO {
+            return Decimal::ZERO;
+        }
+
+        let tge = self.tge_amount(total);
+        let remainder = total - tge;
+        let (released, out_of) = self.released_share(start, at);
+
+        tge + round_down((remainder * released / out_of).min(remainder))
+    }
+
+    pub fn cliff_ends_at(&self, start: DateTime<Utc>) -> DateTime<Utc> {
+        start + Duration::days(self.cliff_days)
+    }
+
+    /// When the last token unlocks
+    pub fn fully_vested_at(&self, start: DateTime<Utc>) -> DateTime<Utc> {
+        let release_end = match self.release {
+            ReleaseKind::Linear => start + Duration::days(self.duration_days),
+            ReleaseKind::MonthlyStep => start
+                .checked_add_months(Months::new(self.step_count()))
+                .unwrap_or(start + Duration::days(self.duration_days)),
+            ReleaseKind::Custom => start
+                + Duration::days(self.unlock_table.last().map_or(0, |point| point.offset_days)),
+        };
+        release_end.max(self.cliff_ends_at(start))
+    }
+
+    /// Points where the vested amount changes, for previews. Linear release
+    /// is sampled monthly between the cliff and the end.
+    pub fn unlock_events(&self, total: Decimal, start: DateTime<Utc>) -> Vec<UnlockEvent> {
+        let end = self.fully_vested_at(start);
+        let mut points = vec![start, self.cliff_ends_at(start)];
+
+        match self.release {
+            ReleaseKind::Custom => points.extend(
+                self.unlock_table
+                    .iter()
+                    .map(|point| start + Duration::days(point.offset_days)),
+            ),
+            ReleaseKind::Linear | ReleaseKind::MonthlyStep => {
+                let mut month = 1;
+                while let Some(at) = start.checked_add_months(Months::new(month)) {
+                    if at >= end {
+                        break;
+                    }
+                    points.push(at);
+                    month += 1;
+                }
+            }
+        }
+        points.push(end);
+        points.sort();
+        points.dedup();
+
+        let mut events: Vec<UnlockEvent> = Vec::new();
+        for at in points {
+            let vested_tokens = self.vested_amount(total, start, at);
+            if events.last().is_none_or(|last| last.vested_tokens != vested_tokens) {
+                events.push(UnlockEvent { at, vested_tokens });
+            }
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use proptest::prelude::*;
+    use std::str::FromStr;
+
+    fn dec(value: &str) -> Decimal {
+        Decimal::from_str(value).unwrap()
+    }
+
+    fn start() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 1, 31, 12, 0, 0).unwrap()
+    }
+
+    fn linear(cliff_days: i64, duration_days: i64) -> VestingTerms {
+        VestingTerms {
+            tge_unlock_percent: Decimal::ZERO,
+            cliff_days,
+            duration_days,
+            release: ReleaseKind::Linear,
+            unlock_table: Vec::new(),
+        }
+    }
+
+    fn custom(table: &[(i64, &str)]) -> VestingTerms {
+        VestingTerms {
+            tge_unlock_percent: dec("10"),
+            cliff_days: 0,
+            duration_days: 0,
+            release: ReleaseKind::Custom,
+            unlock_table: table
+                .iter()
+                .map(|(offset_days, percent)| UnlockPoint { offset_days: *offset_days, percent: dec(percent) })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn nothing_vests_before_start() {
+        let terms = VestingTerms { tge_unlock_percent: dec("25"), ..linear(0, 100) };
+        assert_eq!(terms.vested_amount(dec("1000"), start(), start() - Duration::seconds(1)), Decimal::ZERO);
+    }
+
+    #[test]
+    fn tge_unlocks_at_start_even_with_cliff() {
+        let terms = VestingTerms { tge_unlock_percent: dec("25"), ..linear(30, 100) };
+        assert_eq!(terms.vested_amount(dec("1000"), start(), start()), dec("250"));
+        assert_eq!(terms.vested_amount(dec("1000"), start(), start() + Duration::days(29)), dec("250"));
+    }
+
+    #[test]
+    fn linear_release_after_cliff() {
+        let terms = linear(30, 100);
+        let total = dec("1000");
+        assert_eq!(terms.vested_amount(total, start(), start() + Duration::days(29)), Decimal::ZERO);
+        assert_eq!(terms.vested_amount(total, start(), start() + Duration::days(30)), dec("300"));
+        assert_eq!(terms.vested_amount(total, start(), start() + Duration::days(50)), dec("500"));
+        assert_eq!(terms.vested_amount(total, start(), start() + Duration::days(100)), total);
+        assert_eq!(terms.vested_amount(total, start(), start() + Duration::days(1000)), total);
+    }
+
+    #[test]
+    fn linear_rounds_down_to_token_decimals() {
+        let terms = linear(0, 3);
+        let vested = terms.vested_amount(dec("1"), start(), start() + Duration::days(1));
+        assert_eq!(vested, dec("0.33333333"));
+    }
+
+    #[test]
+    fn zero_duration_vests_at_cliff() {
+        let terms = linear(10, 0);
+        assert_eq!(terms.vested_amount(dec("50"), start(), start() + Duration::days(9)), Decimal::ZERO);
+        assert_eq!(terms.vested_amount(dec("50"), start(), start() + Duration::days(10)), dec("50"));
+    }
+
+    #[test]
+    fn monthly_steps_follow_calendar_months() {
+        let terms = VestingTerms {
+            tge_unlock_percent: dec("10"),
+            release: ReleaseKind::MonthlyStep,
+            ..linear(0, 90)
+        };
+        let total = dec("1000");
+        // Jan 31 + 1 month clamps to Feb 29 (2024 is a leap year)
+        let first_step = Utc.with_ymd_and_hms(2024, 2, 29, 12, 0, 0).unwrap();
+        assert_eq!(terms.vested_amount(total, start(), first_step - Duration::seconds(1)), dec("100"));
+        assert_eq!(terms.vested_amount(total, start(), first_step), dec("400"));
+        assert_eq!(
+            terms.vested_amount(total, start(), Utc.with_ymd_and_hms(2024, 3, 31, 12, 0, 0).unwrap()),
+            dec("700")
+        );
+        assert_eq!(
+            terms.vested_amount(total, start(), Utc.with_ymd_and_hms(2024, 4, 30, 12, 0, 0).unwrap()),
+            total
+        );
+        assert_eq!(terms.fully_vested_at(start()), Utc.with_ymd_and_hms(2024, 4, 30, 12, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn monthly_steps_respect_cliff() {
+        let terms = VestingTerms { release: ReleaseKind::MonthlyStep, ..linear(45, 120) };
+        let total = dec("400");
+        assert_eq!(terms.vested_amount(total, start(), start() + Duration::days(44)), Decimal::ZERO);
+        // One of the four steps has passed by the time the cliff ends
+        assert_eq!(terms.vested_amount(total, start(), start() + Duration::days(45)), dec("100"));
+    }
+
+    #[test]
+    fn custom_table_releases_cumulative_percentages() {
+        let terms = custom(&[(30, "20"), (90, "50"), (180, "100")]);
+        let total = dec("1000");
+        assert_eq!(terms.vested_amount(total, start(), start()), dec("100"));
+        assert_eq!(terms.vested_amount(total, start(), start() + Duration::days(30)), dec("280"));
+        assert_eq!(terms.vested_amount(total, start(), start() + Duration::days(89)), dec("280"));
+        assert_eq!(terms.vested_amount(total, start(), start() + Duration::days(90)), dec("550"));
+        assert_eq!(terms.vested_amount(total, start(), start() + Duration::days(180)), total);
+        assert_eq!(terms.fully_vested_at(start()), start() + Duration::days(180));
+    }
+
+    #[test]
+    fn validates_terms() {
+        assert!(linear(30, 365).validate().is_ok());
+        assert!(VestingTerms { tge_unlock_percent: dec("100.01"), ..linear(0, 0) }
+            .validate()
+            .is_err());
+        assert!(linear(-1, 10).validate().is_err());
+        assert!(custom(&[]).validate().is_err());
+        assert!(custom(&[(30, "50")]).validate().is_err());
+        assert!(custom(&[(30, "60"), (60, "50"), (90, "100")]).validate().is_err());
+        assert!(custom(&[(60, "50"), (30, "60"), (90, "100")]).validate().is_err());
+        assert!(custom(&[(30, "50"), (90, "100")]).validate().is_ok());
+    }
+
+    #[test]
+    fn unlock_events_end_at_total() {
+        let terms = custom(&[(30, "20"), (90, "50"), (180, "100")]);
+        let events = terms.unlock_events(dec("1000"), start());
+        let amounts: Vec<Decimal> = events.iter().map(|event| event.vested_tokens).collect();
+        assert_eq!(amounts, vec![dec("100"), dec("280"), dec("550"), dec("1000")]);
+
+        let events = linear(30, 365).unlock_events(dec("1000"), start());
+        assert_eq!(events.first().unwrap().vested_tokens, Decimal::ZERO);
+        assert_eq!(events.last().unwrap().vested_tokens, dec("1000"));
+    }
+
+    fn arb_terms() -> impl Strategy<Value = VestingTerms> {
+        let table = prop::collection::vec((1i64..60, 0u32..=2500), 1..8).prop_map(|steps| {
+            let mut offset = 0;
+            let mut percent = 0u32;
+            let mut table: Vec<UnlockPoint> = steps
+                .into_iter()
+                .map(|(gap, bump)| {
+                    offset += gap;
+                    percent = (percent + bump).min(10_000);
+                    UnlockPoint { offset_days: offset, percent: Decimal::new(percent as i64, 2) }
+                })
+                .collect();
+            let last = table.last().unwrap().offset_days;
+            table.push(UnlockPoint { offset_days: last + 1, percent: Decimal::ONE_HUNDRED });
+            table
+        });
+
+        (0u32..=10_000, 0i64..400, 0i64..1500, 0u8..3, table).prop_map(
+            |(tge_bps, cliff_days, duration_days, kind, unlock_table)| VestingTerms {
+                tge_unlock_percent: Decimal::new(tge_bps as i64, 2),
+                cliff_days,
+                duration_days,
+                release: match kind {
+                    0 => ReleaseKind::Linear,
+                    1 => ReleaseKind::MonthlyStep,
+                    _ => ReleaseKind::Custom,
+                },
+                unlock_table,
+            },
+        )
+    }
+
+    fn arb_total() -> impl Strategy<Value = Decimal> {
+        (0i64..100_000_000_000_000_000).prop_map(|units| Decimal::new(units, 8))
+    }
+
+    proptest! {
+        #[test]
+        fn generated_terms_are_valid(terms in arb_terms()) {
+            prop_assert!(terms.validate().is_ok());
+        }
+
+        #[test]
+        fn never_exceeds_total(
+            terms in arb_terms(),
+            total in arb_total(),
+            offset_secs in -86_400i64..(5 * 365 * 86_400),
+        ) {
+            let vested = terms.vested_amount(total, start(), start() + Duration::seconds(offset_secs));
+            prop_assert!(vested >= Decimal::ZERO);
+            prop_assert!(vested <= total);
+        }
+
+        #[test]
+        fn monotonic_over_time(
+            terms in arb_terms(),
+            total in arb_total(),
+            a in -86_400i64..(5 * 365 * 86_400),
+            b in -86_400i64..(5 * 365 * 86_400),
+        ) {
+            let (earlier, later) = (a.min(b), a.max(b));
+            let vested_earlier = terms.vested_amount(total, start(), start() + Duration::seconds(earlier));
+            let vested_later = terms.vested_amount(total, start(), start() + Duration::seconds(later));
+            prop_assert!(vested_earlier <= vested_later);
+        }
+
+        #[test]
+        fn fully_vested_at_the_end(terms in arb_terms(), total in arb_total()) {
+            let end = terms.fully_vested_at(start());
+            prop_assert_eq!(terms.vested_amount(total, start(), end), total);
+            prop_assert_eq!(terms.vested_amount(total, start(), end + Duration::days(3650)), total);
+        }
+    }
+}
@@ -0,0 +1,358 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use sqlx::types::Json;
+use sqlx::{PgConnection, PgPool};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::models::*;
+use crate::services::{record_audit_event_in, AuditContext, AuditEntry};
+
+/// Tokens vested by `at` on the schedule's terms
+pub fn vested_amount(schedule: &VestingSchedule, at: DateTime<Utc>) -> Decimal {
+    schedule
+        .terms()
+        .vested_amount(schedule.total_tokens, schedule.start_date, at)
+}
+
+/// Vested but not yet released
+pub fn claimable_amount(schedule: &VestingSchedule, at: DateTime<Utc>) -> Decimal {
+    (vested_amount(schedule, at) - schedule.released_tokens).max(Decimal::ZERO)
+}
+
+/// Create one schedule per round the purchase was filled in, on that round's
+/// terms: for the buyer, or for a referrer when `referral_reward_id` is set.
+pub async fn create_vesting_schedules(
+    conn: &mut PgConnection,
+    user_id: &Uuid,
+    transaction_id: &Uuid,
+    fills: &[RoundFill],
+    referral_reward_id: Option<&Uuid>,
+    start_date: DateTime<Utc>,
+) -> Result<Vec<VestingSchedule>> {
+    let source = if referral_reward_id.is_some() { "referral_bonus" } else { "purchase" };
+    let mut schedules = Vec::with_capacity(fills.len());
+    for fill in fills {
+        fill.vesting.validate()?;
+
+        let schedule = sqlx::query_as::<_, VestingSchedule>(
+            r#"
+            INSERT INTO vesting_schedules (
+                user_id, transaction_id, round_id, total_tokens,
+                cliff_duration_days, vesting_duration_days, tge_unlock_percent,
+                release_kind, unlock_table, source, distribution_mode, referral_reward_id, start_date
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+            RETURNING *
+            "#
+        )
+        .bind(user_id)
+        .bind(transaction_id)
+        .bind(fill.round_id)
+        .bind(fill.tokens)
+        .bind(fill.vesting.cliff_days as i32)
+        .bind(fill.vesting.duration_days as i32)
+        .bind(fill.vesting.tge_unlock_percent)
+        .bind(fill.vesting.release.as_str())
+        .bind((!fill.vesting.unlock_table.is_empty()).then(|| Json(fill.vesting.unlock_table.clone())))
+        .bind(source)
+        .bind(&fill.distribution_mode)
+        .bind(referral_reward_id)
+        .bind(start_date)
+        .fetch_one(&mut *conn)
+        .await?;
+        schedules.push(schedule);
+    }
+
+    Ok(schedules)
+}
+
+pub async fn get_user_schedules(pool: &PgPool, user_id: &Uuid) -> Result<Vec<VestingSchedule>> {
+    let schedules = sqlx::query_as::<_, VestingSchedule>(
+        "SELECT * FROM vesting_schedules WHERE user_id = $1 ORDER BY start_date"
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(schedules)
+}
+
+/// Locked, vested and claimable amounts across a wallet's schedules
+pub fn summarize_vesting(
+    wallet_address: &str,
+    schedules: Vec<VestingSchedule>,
+    at: DateTime<Utc>,
+) -> VestingSummary {
+    let schedules: Vec<VestingScheduleResponse> = schedules
+        .into_iter()
+        .map(|schedule| {
+            let terms = schedule.terms();
+            let vested = terms.vested_amount(schedule.total_tokens, schedule.start_date, at);
+            VestingScheduleResponse {
+                id: schedule.id,
+                transaction_id: schedule.transaction_id,
+                round_id: schedule.round_id,
+                total_tokens: schedule.total_tokens,
+                vested_tokens: vested,
+                released_tokens: schedule.released_tokens,
+                claimable_tokens: (vested - schedule.released_tokens).max(Decimal::ZERO),
+                locked_tokens: schedule.total_tokens - vested,
+                tge_unlock_percent: schedule.tge_unlock_percent,
+                cliff_ends_at: terms.cliff_ends_at(schedule.start_date),
+                vesting_ends_at: terms.fully_vested_at(schedule.start_date),
+                release_kind: schedule.release_kind,
+                source: schedule.source,
+                distribution_mode: schedule.distribution_mode,
+                start_date: schedule.start_date,
+            }
+        })
+        .collect();
+
+    VestingSummary {
+        wallet_address: wallet_address.to_string(),
+        total_tokens: schedules.iter().map(|s| s.total_tokens).sum(),
+        vested_tokens: schedules.iter().map(|s| s.vested_tokens).sum(),
+        released_tokens: schedules.iter().map(|s| s.released_tokens).sum(),
+        claimable_tokens: schedules.iter().map(|s| s.claimable_tokens).sum(),
+        locked_tokens: schedules.iter().map(|s| s.locked_tokens).sum(),
+        schedules,
+    }
+}
+
+/// Lock a user's schedules in the given distribution mode for the
+/// surrounding transaction and work out what each one can release now
+pub async fn lock_claimable(
+    conn: &mut PgConnection,
+    user_id: &Uuid,
+    distribution_mode: &str,
+    at: DateTime<Utc>,
+) -> Result<Vec<(VestingSchedule, Decimal)>> {
+    let schedules = sqlx::query_as::<_, VestingSchedule>(
+        r#"
+        SELECT * FROM vesting_schedules
+        WHERE user_id = $1 AND distribution_mode = $2
+        ORDER BY start_date
+        FOR UPDATE
+        "#
+    )
+    .bind(user_id)
+    .bind(distribution_mode)
+    .fetch_all(conn)
+    .await?;
+
+    Ok(schedules
+        .into_iter()
+        .filter_map(|schedule| {
+            let amount = claimable_amount(&schedule, at);
+            (amount > Decimal::ZERO).then_some((schedule, amount))
+        })
+        .collect())
+}
+
+/// Record pending releases and bump `released_tokens` before the on-chain
+/// send, so a retried claim or push can never release the same tokens twice.
+/// Also appends the audit event, so call it last before committing.
+pub async fn record_releases(
+    conn: &mut PgConnection,
+    releases: &[(VestingSchedule, Decimal)],
+    kind: &str,
+    signature: &str,
+    recent_blockhash: &str,
+    audit: &AuditContext,
+) -> Result<()> {
+    for (schedule, amount) in releases {
+        sqlx::query(
+            r#"
+            UPDATE vesting_schedules
+            SET released_tokens = released_tokens + $1, updated_at = NOW()
+            WHERE id = $2
+            "#
+        )
+        .bind(amount)
+        .bind(schedule.id)
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO vesting_releases (
+                schedule_id, user_id, amount_tokens, kind, status, solana_signature, recent_blockhash
+            )
+            VALUES ($1, $2, $3, $4, 'pending', $5, $6)
+            "#
+        )
+        .bind(schedule.id)
+        .bind(schedule.user_id)
+        .bind(amount)
+        .bind(kind)
+        .bind(signature)
+        .bind(recent_blockhash)
+        .execute(&mut *conn)
+        .await?;
+    }
+
+    let schedules: Vec<serde_json::Value> = releases
+        .iter()
+        .map(|(schedule, amount)| {
+            serde_json::json!({
+                "schedule_id": schedule.id,
+                "released_tokens": schedule.released_tokens,
+                "amount_tokens": amount,
+            })
+        })
+        .collect();
+    let entry = AuditEntry {
+        action: "distribution.pending",
+        entity_type: "vesting_release",
+        entity_id: signature.to_string(),
+        before: None,
+        after: Some(serde_json::json!({
+            "user_id": releases.first().map(|(schedule, _)| schedule.user_id),
+            "kind": kind,
+            "amount_tokens": releases.iter().map(|(_, amount)| *amount).sum::<Decimal>(),
+            "schedules": schedules,
+            "status": "pending",
+        })),
+    };
+    record_audit_event_in(conn, audit, entry).await?;
+
+    Ok(())
+}
+
+/// Mark the releases sent in `signature` as landed
+pub async fn confirm_releases(pool: &PgPool, signature: &str, audit: &AuditContext) -> Result<()> {
+    let mut db_tx = pool.begin().await?;
+
+    let confirmed = sqlx::query(
+        r#"
+        UPDATE vesting_releases SET status = 'confirmed', updated_at = NOW()
+        WHERE solana_signature = $1 AND status = 'pending'
+        "#
+    )
+    .bind(signature)
+    .execute(&mut *db_tx)
+    .await?
+    .rows_affected();
+
+    if confirmed > 0 {
+        let entry = AuditEntry {
+            action: "distribution.confirmed",
+            entity_type: "vesting_release",
+            entity_id: signature.to_string(),
+            before: Some(serde_json::json!({ "status": "pending" })),
+            after: Some(serde_json::json!({ "status": "confirmed", "releases": confirmed })),
+        };
+        record_audit_event_in(&mut db_tx, audit, entry).await?;
+    }
+
+    db_tx.commit().await?;
+    Ok(())
+}
+
+/// Undo the releases in `signature` once it is known never to have landed
+pub async fn revert_releases(pool: &PgPool, signature: &str, audit: &AuditContext) -> Result<()> {
+    let mut db_tx = pool.begin().await?;
+
+    sqlx::query(
+        r#"
+        UPDATE vesting_schedules s
+        SET released_tokens = GREATEST(s.released_tokens - r.amount_tokens, 0), updated_at = NOW()
+        FROM vesting_releases r
+        WHERE r.schedule_id = s.id AND r.solana_signature = $1 AND r.status = 'pending'
+        "#
+    )
+    .bind(signature)
+    .execute(&mut *db_tx)
+    .await?;
+
+    let reverted = sqlx::query(
+        r#"
+        UPDATE vesting_releases SET status = 'failed', updated_at = NOW()
+        WHERE solana_signature = $1 AND status = 'pending'
+        "#
+    )
+    .bind(signature)
+    .execute(&mut *db_tx)
+    .await?
+    .rows_affected();
+
+    if reverted > 0 {
+        let entry = AuditEntry {
+            action: "distribution.reverted",
+            entity_type: "vesting_release",
+            entity_id: signature.to_string(),
+            before: Some(serde_json::json!({ "status": "pending" })),
+            after: Some(serde_json::json!({ "status": "failed", "releases": reverted })),
+        };
+        record_audit_event_in(&mut db_tx, audit, entry).await?;
+    }
+
+    db_tx.commit().await?;
+    Ok(())
+}
+
+/// Wallets with push-mode tokens vested but not yet released at `at`, up to
+/// `limit`, largest amount first
+pub async fn push_due_wallets(
+    pool: &PgPool,
+    at: DateTime<Utc>,
+    limit: usize,
+) -> Result<Vec<(Uuid, String)>> {
+    let schedules = sqlx::query_as::<_, VestingSchedule>(
+        r#"
+        SELECT * FROM vesting_schedules
+        WHERE distribution_mode = 'push'
+          AND released_tokens < total_tokens
+          AND start_date <= $1
+        "#
+    )
+    .bind(at)
+    .fetch_all(pool)
+    .await?;
+
+    let mut due: HashMap<Uuid, Decimal> = HashMap::new();
+    for schedule in &schedules {
+        let amount = claimable_amount(schedule, at);
+        if amount > Decimal::ZERO {
+            *due.entry(schedule.user_id).or_insert(Decimal::ZERO) += amount;
+        }
+    }
+
+    let mut due: Vec<(Uuid, Decimal)> = due.into_iter().collect();
+    due.sort_by_key(|(_, amount)| std::cmp::Reverse(*amount));
+    let user_ids: Vec<Uuid> = due.into_iter().take(limit).map(|(user_id, _)| user_id).collect();
+    if user_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let wallets = sqlx::query_as::<_, (Uuid, String)>(
+        "SELECT id, wallet_address FROM users WHERE id = ANY($1)"
+    )
+    .bind(&user_ids)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(wallets)
+}
+
+/// Signatures and blockhashes of releases still pending after `older_than`,
+/// e.g. left behind by a crash or an undecided send
+pub async fn pending_release_signatures(
+    pool: &PgPool,
+    older_than: DateTime<Utc>,
+) -> Result<Vec<(String, Option<String>)>> {
+    let rows = sqlx::query_as::<_, (String, Option<String>)>(
+        r#"
+        SELECT DISTINCT solana_signature, recent_blockhash
+        FROM vesting_releases
+        WHERE status = 'pending' AND solana_signature IS NOT NULL AND created_at < $1
+        "#
+    )
+    .bind(older_than)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows)
+}
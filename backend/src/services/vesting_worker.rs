@@ -0,0 +1,176 @@
+use anyhow::Result;
+use chrono::{Duration as ChronoDuration, Utc};
+use rust_decimal::Decimal;
+use solana_sdk::hash::Hash;
+use solana_sdk::signature::Signature;
+use sqlx::PgPool;
+use std::str::FromStr;
+use tokio::time::{sleep, Duration};
+
+use crate::services::*;
+
+/// Pending releases younger than this may still have a send in flight
+const RECONCILE_MIN_AGE_SECS: i64 = 120;
+
+/// Actor name the worker's distributions are audited under
+const VESTING_WORKER_ACTOR: &str = "vesting_worker";
+
+/// Periodically send newly vested tokens to push-mode schedules, pending SOL
+/// referral commissions and queued payment refunds, and snapshot the
+/// whitelist Merkle tree when it changes (including when entries expire).
+///
+/// Each run first resolves releases left pending by a crash or an undecided
+/// send, then pays up to `vesting_push_batch_size` wallets and commissions. A release is
+/// recorded under its pre-signed transaction's signature before sending, so a
+/// restart can always tell whether it landed and never sends it twice.
+pub fn spawn_vesting_worker(
+    pool: PgPool,
+    solana_service: SolanaService,
+    settings: SettingsService,
+    denylist: DenylistService,
+) {
+    tokio::spawn(async move {
+        loop {
+            let current = settings.current();
+            let audit = AuditContext::system(VESTING_WORKER_ACTOR);
+
+            if let Err(e) = reconcile_pending_releases(&pool, &solana_service, &audit).await {
+                eprintln!("Vesting release reconciliation failed: {}", e);
+            }
+            match push_vested_tokens(
+                &pool,
+                &solana_service,
+                current.vesting_push_batch_size as usize,
+                current.freeze_until_tge,
+                &audit,
+            )
+            .await
+            {
+                Ok(0) => {}
+                Ok(sent) => println!("✅ Vesting worker pushed tokens to {} wallets", sent),
+                Err(e) => eprintln!("Vesting push run failed: {}", e),
+            }
+
+            let older_than = Utc::now() - ChronoDuration::seconds(RECONCILE_MIN_AGE_SECS);
+            if let Err(e) = reconcile_referral_commissions(&pool, &solana_service, older_than, &audit).await {
+                eprintln!("Referral commission reconciliation failed: {}", e);
+            }
+            match pay_referral_commissions(&pool, &solana_service, &denylist, current.vesting_push_batch_size, &audit).await {
+                Ok(0) => {}
+                Ok(paid) => println!("✅ Vesting worker paid {} SOL referral commissions", paid),
+                Err(e) => eprintln!("Referral commission run failed: {}", e),
+            }
+
+            if let Err(e) = reconcile_payment_refunds(&pool, &solana_service, older_than, &audit).await {
+                eprintln!("Payment refund reconciliation failed: {}", e);
+            }
+            match send_payment_refunds(&pool, &solana_service, &denylist, current.vesting_push_batch_size, &audit).await {
+                Ok(0) => {}
+                Ok(refunded) => println!("✅ Vesting worker sent {} payment refunds", refunded),
+                Err(e) => eprintln!("Payment refund run failed: {}", e),
+            }
+
+            if let Err(e) = refresh_whitelist_merkle(&pool, &solana_service).await {
+                eprintln!("Whitelist Merkle refresh failed: {}", e);
+            }
+
+            sleep(Duration::from_secs(current.vesting_push_interval_secs)).await;
+        }
+    });
+}
+
+/// Settle pending releases whose send outcome was never recorded
+pub async fn reconcile_pending_releases(
+    pool: &PgPool,
+    solana_service: &SolanaService,
+    audit: &AuditContext,
+) -> Result<()> {
+    let older_than = Utc::now() - ChronoDuration::seconds(RECONCILE_MIN_AGE_SECS);
+
+    for (signature, recent_blockhash) in pending_release_signatures(pool, older_than).await? {
+        let parsed = (
+            Signature::from_str(&signature),
+            recent_blockhash.as_deref().map(Hash::from_str),
+        );
+        let outcome = match parsed {
+            (Ok(sig), Some(Ok(blockhash))) => solana_service.check_signature(&sig, &blockhash),
+            _ => {
+                eprintln!("Can't reconcile vesting release {}: missing or invalid blockhash", signature);
+                continue;
+            }
+        };
+
+        match outcome {
+            SendOutcome::Confirmed => {
+                confirm_releases(pool, &signature, audit).await?;
+                println!("✅ Vesting release {} confirmed on reconciliation", signature);
+            }
+            SendOutcome::Failed(reason) => {
+                revert_releases(pool, &signature, audit).await?;
+                println!("↩️  Vesting release {} reverted: {}", signature, reason);
+            }
+            SendOutcome::Unknown => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Send every due push-mode wallet its vested tokens, one transfer per
+/// wallet, freezing the receiving accounts if `freeze` is set. Returns how
+/// many wallets were paid.
+pub async fn push_vested_tokens(
+    pool: &PgPool,
+    solana_service: &SolanaService,
+    batch_size: usize,
+    freeze: bool,
+    audit: &AuditContext,
+) -> Result<usize> {
+    let now = Utc::now();
+    let mut sent = 0;
+
+    for (user_id, wallet_address) in push_due_wallets(pool, now, batch_size).await? {
+        let mut db_tx = pool.begin().await?;
+
+        // Recomputed under lock: a concurrent run may already have paid this wallet
+        let releases = lock_claimable(&mut db_tx, &user_id, "push", now).await?;
+        let amount: Decimal = releases.iter().map(|(_, amount)| *amount).sum();
+        if amount <= Decimal::ZERO {
+            continue;
+        }
+
+        let transaction = match solana_service.build_distribution(&wallet_address, amount, freeze).await {
+            Ok(transaction) => transaction,
+            Err(e) => {
+                eprintln!("Failed to prepare vesting push to {}: {}", wallet_address, e);
+                continue;
+            }
+        };
+        let signature = transaction.signatures[0].to_string();
+        let recent_blockhash = transaction.message.recent_blockhash.to_string();
+
+        if freeze {
+            let token_account = solana_service.token_account_for(&wallet_address)?;
+            track_token_account(&mut db_tx, &wallet_address, &token_account, true, Some(&signature)).await?;
+        }
+        // Last, as it serializes audited changes until the commit
+        record_releases(&mut db_tx, &releases, "push", &signature, &recent_blockhash, audit).await?;
+        db_tx.commit().await?;
+
+        match solana_service.send_prepared(&transaction).await {
+            SendOutcome::Confirmed => {
+                confirm_releases(pool, &signature, audit).await?;
+                println!("✅ Pushed {} vested tokens to {}, signature: {}", amount, wallet_address, signature);
+                sent += 1;
+            }
+            SendOutcome::Failed(reason) => {
+                revert_releases(pool, &signature, audit).await?;
+                eprintln!("Vesting push to {} failed: {}", wallet_address, reason);
+            }
+            // Left pending for the next reconciliation
+            SendOutcome::Unknown => {}
+        }
+    }
+
+    Ok(sent)
+}
@@ -0,0 +1,295 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{decode, encode, errors::ErrorKind, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use rand::distributions::{Alphanumeric, DistString};
+use sha2::{Digest, Sha256};
+use solana_sdk::pubkey::Pubkey;
+use sqlx::{PgConnection, PgPool};
+use std::fmt;
+use std::str::FromStr;
+use uuid::Uuid;
+
+use crate::models::{AuthNonce, AuthTokens, WalletClaims, WalletLoginRequest};
+use crate::services::{verify_wallet_signature, SiwsMessage};
+
+/// How long a nonce (and the message issued with it) can be used to log in
+const AUTH_NONCE_TTL_SECS: i64 = 600;
+
+/// Lifetime of an access token; clients refresh rather than sign again
+pub const ACCESS_TOKEN_TTL_SECS: i64 = 900;
+
+const REFRESH_TOKEN_TTL_DAYS: i64 = 30;
+
+/// Clock skew allowed on a message's issue time
+const SIWS_CLOCK_SKEW_SECS: i64 = 60;
+
+const SIWS_STATEMENT: &str = "Sign in to the Shibartum presale.";
+
+/// Reason a login, refresh or access token was refused
+#[derive(Debug, Clone)]
+pub enum AuthRejection {
+    InvalidMessage(String),
+    /// The message was issued for another site
+    DomainMismatch,
+    /// The message or token belongs to another wallet
+    WalletMismatch,
+    /// Unknown, used or expired nonce
+    InvalidNonce,
+    MessageExpired,
+    Signature,
+    InvalidToken,
+    TokenExpired,
+}
+
+impl AuthRejection {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::InvalidMessage(_) => "invalid_message",
+            Self::DomainMismatch => "domain_mismatch",
+            Self::WalletMismatch => "wallet_mismatch",
+            Self::InvalidNonce => "invalid_nonce",
+            Self::MessageExpired => "message_expired",
+            Self::Signature => "invalid_signature",
+            Self::InvalidToken => "invalid_token",
+            Self::TokenExpired => "token_expired",
+        }
+    }
+}
+
+impl fmt::Display for AuthRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidMessage(reason) => write!(f, "Invalid sign-in message: {}", reason),
+            Self::DomainMismatch => write!(f, "Sign-in message is for another domain"),
+            Self::WalletMismatch => write!(f, "Not authorized for this wallet"),
+            Self::InvalidNonce => write!(f, "Nonce is unknown, used or expired"),
+            Self::MessageExpired => write!(f, "Sign-in message expired"),
+            Self::Signature => write!(f, "Invalid signature"),
+            Self::InvalidToken => write!(f, "Invalid or revoked token"),
+            Self::TokenExpired => write!(f, "Token expired"),
+        }
+    }
+}
+
+impl std::error::Error for AuthRejection {}
+
+fn sha256_hex(value: &str) -> String {
+    Sha256::digest(value.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Issue a single-use nonce for a wallet, with the message to sign
+pub async fn issue_auth_nonce(
+    pool: &PgPool,
+    domain: &str,
+    uri: &str,
+    wallet_address: &str,
+    now: DateTime<Utc>,
+) -> Result<AuthNonce> {
+    Pubkey::from_str(wallet_address).map_err(|e| anyhow!("Invalid wallet address: {}", e))?;
+
+    sqlx::query("DELETE FROM auth_nonces WHERE expires_at < $1")
+        .bind(now)
+        .execute(pool)
+        .await?;
+
+    let message = SiwsMessage {
+        domain: domain.to_string(),
+        address: wallet_address.to_string(),
+        statement: Some(SIWS_STATEMENT.to_string()),
+        uri: Some(uri.to_string()),
+        nonce: Alphanumeric.sample_string(&mut rand::thread_rng(), 24),
+        issued_at: now,
+        expiration_time: now + Duration::seconds(AUTH_NONCE_TTL_SECS),
+    };
+
+    sqlx::query("INSERT INTO auth_nonces (nonce, wallet_address, issued_at, expires_at) VALUES ($1, $2, $3, $4)")
+        .bind(&message.nonce)
+        .bind(wallet_address)
+        .bind(message.issued_at)
+        .bind(message.expiration_time)
+        .execute(pool)
+        .await?;
+
+    Ok(AuthNonce {
+        message: message.to_text(),
+        nonce: message.nonce,
+        issued_at: message.issued_at,
+        expires_at: message.expiration_time,
+    })
+}
+
+/// Verify a signed sign-in message, use up its nonce and issue tokens
+pub async fn wallet_login(
+    pool: &PgPool,
+    domain: &str,
+    jwt_secret: &str,
+    req: &WalletLoginRequest,
+    now: DateTime<Utc>,
+) -> Result<AuthTokens> {
+    verify_wallet_signature(&req.wallet_address, &req.message, &req.signature)
+        .map_err(|_| AuthRejection::Signature)?;
+
+    let message = SiwsMessage::parse(&req.message).map_err(AuthRejection::InvalidMessage)?;
+    if message.domain != domain {
+        return Err(AuthRejection::DomainMismatch.into());
+    }
+    if message.address != req.wallet_address {
+        return Err(AuthRejection::WalletMismatch.into());
+    }
+    if message.issued_at > now + Duration::seconds(SIWS_CLOCK_SKEW_SECS) {
+        return Err(AuthRejection::InvalidMessage("issued in the future".to_string()).into());
+    }
+    if message.expiration_time <= now {
+        return Err(AuthRejection::MessageExpired.into());
+    }
+
+    let mut db_tx = pool.begin().await?;
+
+    let consumed: Option<String> = sqlx::query_scalar(
+        r#"
+        UPDATE auth_nonces SET used_at = $3
+        WHERE nonce = $1 AND wallet_address = $2 AND used_at IS NULL AND expires_at > $3
+        RETURNING nonce
+        "#
+    )
+    .bind(&message.nonce)
+    .bind(&req.wallet_address)
+    .bind(now)
+    .fetch_optional(&mut *db_tx)
+    .await?;
+    if consumed.is_none() {
+        return Err(AuthRejection::InvalidNonce.into());
+    }
+
+    let (tokens, _) = issue_wallet_tokens(&mut db_tx, jwt_secret, &req.wallet_address, now).await?;
+    db_tx.commit().await?;
+
+    println!("🔑 Wallet signed in: {}", req.wallet_address);
+
+    Ok(tokens)
+}
+
+/// Sign an access token and store a new refresh token. Returns the tokens
+/// and the refresh token's id.
+async fn issue_wallet_tokens(
+    conn: &mut PgConnection,
+    jwt_secret: &str,
+    wallet_address: &str,
+    now: DateTime<Utc>,
+) -> Result<(AuthTokens, Uuid)> {
+    let claims = WalletClaims {
+        sub: wallet_address.to_string(),
+        iat: now.timestamp(),
+        exp: now.timestamp() + ACCESS_TOKEN_TTL_SECS,
+    };
+    let access_token = encode(&Header::new(Algorithm::HS256), &claims, &EncodingKey::from_secret(jwt_secret.as_bytes()))?;
+
+    let refresh_token = Alphanumeric.sample_string(&mut rand::thread_rng(), 48);
+    let refresh_expires_at = now + Duration::days(REFRESH_TOKEN_TTL_DAYS);
+    let refresh_id: Uuid = sqlx::query_scalar(
+        r#"
+        INSERT INTO auth_refresh_tokens (wallet_address, token_hash, expires_at, created_at)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id
+        "#
+    )
+    .bind(wallet_address)
+    .bind(sha256_hex(&refresh_token))
+    .bind(refresh_expires_at)
+    .bind(now)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    Ok((
+        AuthTokens {
+            wallet_address: wallet_address.to_string(),
+            access_token,
+            token_type: "Bearer".to_string(),
+            expires_in: ACCESS_TOKEN_TTL_SECS,
+            refresh_token,
+            refresh_expires_at,
+        },
+        refresh_id,
+    ))
+}
+
+/// Exchange a refresh token for new tokens. The token is rotated; reusing
+/// an already rotated token revokes all of the wallet's refresh tokens,
+/// since it means the token was copied.
+pub async fn refresh_wallet_session(
+    pool: &PgPool,
+    jwt_secret: &str,
+    refresh_token: &str,
+    now: DateTime<Utc>,
+) -> Result<AuthTokens> {
+    let mut db_tx = pool.begin().await?;
+
+    let stored = sqlx::query_as::<_, (Uuid, String, DateTime<Utc>, Option<DateTime<Utc>>)>(
+        r#"
+        SELECT id, wallet_address, expires_at, revoked_at
+        FROM auth_refresh_tokens
+        WHERE token_hash = $1
+        FOR UPDATE
+        "#
+    )
+    .bind(sha256_hex(refresh_token))
+    .fetch_optional(&mut *db_tx)
+    .await?;
+    let Some((id, wallet_address, expires_at, revoked_at)) = stored else {
+        return Err(AuthRejection::InvalidToken.into());
+    };
+
+    if revoked_at.is_some() {
+        sqlx::query("UPDATE auth_refresh_tokens SET revoked_at = $2 WHERE wallet_address = $1 AND revoked_at IS NULL")
+            .bind(&wallet_address)
+            .bind(now)
+            .execute(&mut *db_tx)
+            .await?;
+        db_tx.commit().await?;
+        eprintln!("⚠️ Revoked refresh token reused for {}; all sessions revoked", wallet_address);
+        return Err(AuthRejection::InvalidToken.into());
+    }
+    if expires_at <= now {
+        return Err(AuthRejection::TokenExpired.into());
+    }
+
+    let (tokens, replaced_by) = issue_wallet_tokens(&mut db_tx, jwt_secret, &wallet_address, now).await?;
+    sqlx::query("UPDATE auth_refresh_tokens SET revoked_at = $2, replaced_by = $3 WHERE id = $1")
+        .bind(id)
+        .bind(now)
+        .bind(replaced_by)
+        .execute(&mut *db_tx)
+        .await?;
+    db_tx.commit().await?;
+
+    Ok(tokens)
+}
+
+/// Log out: revoke a refresh token. Returns false if it wasn't active.
+pub async fn revoke_refresh_token(pool: &PgPool, refresh_token: &str) -> Result<bool> {
+    let result = sqlx::query(
+        "UPDATE auth_refresh_tokens SET revoked_at = NOW() WHERE token_hash = $1 AND revoked_at IS NULL"
+    )
+    .bind(sha256_hex(refresh_token))
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Check an access token's signature and expiry
+pub fn decode_wallet_token(jwt_secret: &str, token: &str) -> std::result::Result<WalletClaims, AuthRejection> {
+    let mut validation = Validation::new(Algorithm::HS256);
+    validation.leeway = 0;
+    validation.set_required_spec_claims(&["exp", "sub"]);
+
+    decode::<WalletClaims>(token, &DecodingKey::from_secret(jwt_secret.as_bytes()), &validation)
+        .map(|data| data.claims)
+        .map_err(|e| match e.kind() {
+            ErrorKind::ExpiredSignature => AuthRejection::TokenExpired,
+            _ => AuthRejection::InvalidToken,
+        })
+}
@@ -0,0 +1,344 @@
+use anyhow::Result;
+use chrono::{DateTime, NaiveDate, Utc};
+use csv::{ReaderBuilder, StringRecord, Trim, WriterBuilder};
+use rust_decimal::Decimal;
+use solana_sdk::pubkey::Pubkey;
+use sqlx::{PgConnection, PgPool};
+use std::collections::HashMap;
+use std::str::FromStr;
+use uuid::Uuid;
+
+use crate::models::{ImportRowError, WhitelistExportRow, WhitelistImportReport, WhitelistTier};
+use crate::services::{list_whitelist_tiers, record_audit_event_in, AuditContext, AuditEntry};
+use crate::utils::{find_user_by_wallet, get_or_create_user_in};
+
+/// Largest file accepted by the importer, in data rows
+pub const MAX_IMPORT_ROWS: usize = 50_000;
+
+/// A validated import row
+#[derive(Debug)]
+struct ImportRow {
+    wallet: String,
+    tier: i32,
+    /// `None` gives the entry its tier's default allocation
+    max_allocation: Option<Decimal>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+/// Column positions, looked up by header name so column order and extra
+/// columns (such as those of an export) don't matter
+struct Columns {
+    wallet: usize,
+    tier: usize,
+    max_allocation: Option<usize>,
+    expires_at: Option<usize>,
+}
+
+impl Columns {
+    fn from_headers(headers: &StringRecord) -> std::result::Result<Self, ImportRowError> {
+        let find = |name: &str| headers.iter().position(|header| header.eq_ignore_ascii_case(name));
+        let required = |name: &str| {
+            find(name).ok_or_else(|| ImportRowError {
+                line: 1,
+                wallet: None,
+                field: Some(name.to_string()),
+                message: format!("Missing required column {}", name),
+            })
+        };
+
+        Ok(Self {
+            wallet: required("wallet")?,
+            tier: required("tier")?,
+            max_allocation: find("max_allocation"),
+            expires_at: find("expires_at"),
+        })
+    }
+}
+
+/// RFC 3339 timestamp, or a bare date meaning midnight UTC
+fn parse_expiry(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|at| at.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+                .map(|at| at.and_utc())
+        })
+}
+
+/// Parse and validate every row, collecting all errors rather than stopping
+/// at the first
+fn parse_import(
+    data: &[u8],
+    tiers: &HashMap<i32, WhitelistTier>,
+    now: DateTime<Utc>,
+) -> (Vec<ImportRow>, usize, Vec<ImportRowError>) {
+    let mut reader = ReaderBuilder::new().trim(Trim::All).flexible(true).from_reader(data);
+    let mut rows = Vec::new();
+    let mut errors = Vec::new();
+    let mut seen: HashMap<String, u64> = HashMap::new();
+    let mut count = 0;
+
+    let columns = match reader.headers().map_err(|e| e.to_string()).and_then(|headers| {
+        Columns::from_headers(headers).map_err(|e| e.message)
+    }) {
+        Ok(columns) => columns,
+        Err(message) => {
+            errors.push(ImportRowError { line: 1, wallet: None, field: None, message });
+            return (rows, count, errors);
+        }
+    };
+
+    for record in reader.records() {
+        count += 1;
+        if count > MAX_IMPORT_ROWS {
+            errors.push(ImportRowError {
+                line: 0,
+                wallet: None,
+                field: None,
+                message: format!("File has more than {} rows", MAX_IMPORT_ROWS),
+            });
+            break;
+        }
+
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                errors.push(ImportRowError {
+                    line: e.position().map(|position| position.line()).unwrap_or(0),
+                    wallet: None,
+                    field: None,
+                    message: format!("Unreadable row: {}", e),
+                });
+                continue;
+            }
+        };
+        let line = record.position().map(|position| position.line()).unwrap_or(0);
+        let get = |index: Option<usize>| index.and_then(|index| record.get(index)).unwrap_or("");
+
+        let wallet = get(Some(columns.wallet)).to_string();
+        let mut row_errors = Vec::new();
+        let mut error = |field: &str, message: String| {
+            row_errors.push(ImportRowError {
+                line,
+                wallet: Some(wallet.clone()).filter(|wallet| !wallet.is_empty()),
+                field: Some(field.to_string()),
+                message,
+            });
+        };
+
+        if Pubkey::from_str(&wallet).is_err() {
+            error("wallet", "Not a valid Solana address".to_string());
+        } else if let Some(first) = seen.get(&wallet) {
+            error("wallet", format!("Duplicate wallet, first listed on line {}", first));
+        } else {
+            seen.insert(wallet.clone(), line);
+        }
+
+        let tier = match get(Some(columns.tier)).parse::<i32>() {
+            Ok(tier) if tier > 0 && tiers.contains_key(&tier) => Some(tier),
+            Ok(tier) => {
+                error("tier", format!("Unknown whitelist tier {}", tier));
+                None
+            }
+            Err(_) => {
+                error("tier", "Tier must be a number".to_string());
+                None
+            }
+        };
+
+        let max_allocation = match get(columns.max_allocation) {
+            "" => None,
+            value => match Decimal::from_str(value) {
+                Ok(allocation) if allocation >= Decimal::ZERO => Some(allocation),
+                _ => {
+                    error("max_allocation", format!("Invalid allocation {:?}", value));
+                    None
+                }
+            },
+        };
+
+        let expires_at = match get(columns.expires_at) {
+            "" => None,
+            value => match parse_expiry(value) {
+                Some(at) if at > now => Some(at),
+                Some(_) => {
+                    error("expires_at", "Expiry is in the past".to_string());
+                    None
+                }
+                None => {
+                    error("expires_at", format!("Invalid date {:?}, expected RFC 3339 or YYYY-MM-DD", value));
+                    None
+                }
+            },
+        };
+
+        match (row_errors.is_empty(), tier) {
+            (true, Some(tier)) => rows.push(ImportRow { wallet, tier, max_allocation, expires_at }),
+            _ => errors.append(&mut row_errors),
+        }
+    }
+
+    (rows, count, errors)
+}
+
+/// Upsert one row: the user, their tier, and their entry in that tier
+async fn apply_import_row(
+    conn: &mut PgConnection,
+    row: &ImportRow,
+    tier: &WhitelistTier,
+    report: &mut WhitelistImportReport,
+) -> Result<()> {
+    if find_user_by_wallet(&mut *conn, &row.wallet).await?.is_none() {
+        report.created_users += 1;
+    }
+    let user = get_or_create_user_in(conn, &row.wallet).await?;
+
+    sqlx::query("UPDATE users SET is_whitelisted = true, whitelist_tier = $1, updated_at = NOW() WHERE id = $2")
+        .bind(row.tier)
+        .bind(user.id)
+        .execute(&mut *conn)
+        .await?;
+
+    let max_allocation = row.max_allocation.unwrap_or(tier.default_max_allocation);
+    let custom_allocation = row.max_allocation.is_some();
+
+    let updated: Option<Uuid> = sqlx::query_scalar(
+        r#"
+        UPDATE whitelist_entries
+        SET max_allocation = GREATEST($1, used_allocation), custom_allocation = $2, expires_at = $3
+        WHERE id = (
+            SELECT id FROM whitelist_entries
+            WHERE user_id = $4 AND tier = $5
+            ORDER BY created_at DESC
+            LIMIT 1
+        )
+        RETURNING id
+        "#
+    )
+    .bind(max_allocation)
+    .bind(custom_allocation)
+    .bind(row.expires_at)
+    .bind(user.id)
+    .bind(row.tier)
+    .fetch_optional(&mut *conn)
+    .await?;
+
+    if updated.is_some() {
+        report.updated_entries += 1;
+        return Ok(());
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO whitelist_entries (user_id, tier, max_allocation, custom_allocation, expires_at)
+        VALUES ($1, $2, $3, $4, $5)
+        "#
+    )
+    .bind(user.id)
+    .bind(row.tier)
+    .bind(max_allocation)
+    .bind(custom_allocation)
+    .bind(row.expires_at)
+    .execute(&mut *conn)
+    .await?;
+    report.created_entries += 1;
+
+    Ok(())
+}
+
+/// Import a whitelist CSV with columns `wallet`, `tier` and optionally
+/// `max_allocation` (blank: the tier's default) and `expires_at`.
+///
+/// Every row is validated first; if any fails, nothing is written and the
+/// report lists each error. Otherwise all rows are applied in one
+/// transaction, which a dry run rolls back, so its counts are exact.
+pub async fn import_whitelist_csv(
+    pool: &PgPool,
+    data: &[u8],
+    dry_run: bool,
+    audit: &AuditContext,
+) -> Result<WhitelistImportReport> {
+    let tiers: HashMap<i32, WhitelistTier> = list_whitelist_tiers(pool)
+        .await?
+        .into_iter()
+        .map(|tier| (tier.tier, tier))
+        .collect();
+
+    let (rows, count, errors) = parse_import(data, &tiers, Utc::now());
+    let mut report = WhitelistImportReport {
+        dry_run,
+        rows: count,
+        errors,
+        ..Default::default()
+    };
+    if !report.errors.is_empty() {
+        return Ok(report);
+    }
+
+    let mut db_tx = pool.begin().await?;
+    for row in &rows {
+        apply_import_row(&mut db_tx, row, &tiers[&row.tier], &mut report).await?;
+    }
+
+    if dry_run {
+        db_tx.rollback().await?;
+    } else {
+        let entry = AuditEntry {
+            action: "whitelist.imported",
+            entity_type: "whitelist",
+            entity_id: "csv".to_string(),
+            before: None,
+            after: Some(serde_json::json!({
+                "rows": report.rows,
+                "created_users": report.created_users,
+                "created_entries": report.created_entries,
+                "updated_entries": report.updated_entries,
+            })),
+        };
+        record_audit_event_in(&mut db_tx, audit, entry).await?;
+        db_tx.commit().await?;
+        report.committed = true;
+        println!(
+            "📥 Whitelist import: {} rows, {} new users, {} new entries, {} updated entries",
+            report.rows, report.created_users, report.created_entries, report.updated_entries
+        );
+    }
+
+    Ok(report)
+}
+
+/// Every whitelist entry with its used and remaining allocation, as CSV.
+/// The file can be edited and imported again.
+pub async fn export_whitelist_csv(pool: &PgPool) -> Result<Vec<u8>> {
+    let rows = sqlx::query_as::<_, WhitelistExportRow>(
+        r#"
+        SELECT u.wallet_address AS wallet, e.tier, t.name AS tier_name,
+               e.max_allocation, e.used_allocation,
+               GREATEST(e.max_allocation - e.used_allocation, 0) AS remaining_allocation,
+               e.custom_allocation, e.expires_at, e.created_at
+        FROM whitelist_entries e
+        JOIN users u ON u.id = e.user_id
+        JOIN whitelist_tiers t ON t.tier = e.tier
+        ORDER BY u.wallet_address, e.created_at
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut writer = WriterBuilder::new().from_writer(Vec::new());
+    for row in &rows {
+        writer.serialize(row)?;
+    }
+    // An empty export still gets its header
+    if rows.is_empty() {
+        writer.write_record([
+            "wallet", "tier", "tier_name", "max_allocation", "used_allocation",
+            "remaining_allocation", "custom_allocation", "expires_at", "created_at",
+        ])?;
+    }
+
+    Ok(writer.into_inner().map_err(|e| e.into_error())?)
+}
@@ -0,0 +1,206 @@
+use anyhow::{anyhow, Result};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use solana_sdk::pubkey::Pubkey;
+use sqlx::PgPool;
+use std::str::FromStr;
+
+use crate::models::{WhitelistMerkleLeaf, WhitelistMerkleVersion, WhitelistProof};
+use crate::services::{allocation_leaf, hash_hex, parse_hash_hex, verify_proof, MerkleTree, SolanaService};
+
+/// Serializes snapshots so two refreshes can't both record the same change
+const MERKLE_LOCK_KEY: i64 = 0x574c_4d45_524b_4c45; // "WLMERKLE"
+
+/// Each whitelisted wallet's tier and allocation, with its leaf.
+///
+/// The allocation is the same one purchases are held to: the sum of the
+/// wallet's unexpired entries, or its tier's default when it has none.
+/// Wallets without entries in tier 0 have no limit and no leaf.
+async fn current_allocation_leaves(pool: &PgPool, token_decimals: u8) -> Result<Vec<WhitelistMerkleLeaf>> {
+    let allocations = sqlx::query_as::<_, (String, i32, Decimal)>(
+        r#"
+        SELECT u.wallet_address, u.whitelist_tier, COALESCE(e.max_allocation, t.default_max_allocation)
+        FROM users u
+        JOIN whitelist_tiers t ON t.tier = u.whitelist_tier
+        LEFT JOIN (
+            SELECT user_id, SUM(max_allocation) AS max_allocation
+            FROM whitelist_entries
+            WHERE expires_at IS NULL OR expires_at > NOW()
+            GROUP BY user_id
+        ) e ON e.user_id = u.id
+        WHERE u.is_whitelisted AND (e.user_id IS NOT NULL OR u.whitelist_tier > 0)
+        ORDER BY u.wallet_address
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let scale = Decimal::from(10u64.pow(token_decimals as u32));
+    allocations
+        .into_iter()
+        .map(|(wallet_address, tier, max_allocation)| {
+            let wallet = Pubkey::from_str(&wallet_address)
+                .map_err(|_| anyhow!("Whitelisted wallet {} is not a valid address", wallet_address))?;
+            let tier_byte = u8::try_from(tier).map_err(|_| anyhow!("Tier {} doesn't fit in a leaf", tier))?;
+            let amount_units = (max_allocation * scale).trunc();
+            let amount = amount_units
+                .to_u64()
+                .ok_or_else(|| anyhow!("Allocation of {} out of range: {}", wallet_address, max_allocation))?;
+
+            Ok(WhitelistMerkleLeaf {
+                leaf: hash_hex(&allocation_leaf(&wallet, tier_byte, amount)),
+                wallet_address,
+                tier,
+                max_allocation,
+                amount_units,
+            })
+        })
+        .collect()
+}
+
+pub async fn latest_whitelist_merkle_version(pool: &PgPool) -> Result<Option<WhitelistMerkleVersion>> {
+    let version = sqlx::query_as::<_, WhitelistMerkleVersion>(
+        "SELECT * FROM whitelist_merkle_versions ORDER BY version DESC LIMIT 1"
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(version)
+}
+
+/// Rebuild the tree from the current whitelist and record a new version if
+/// its root (or the token decimals) changed. Returns the new version, or
+/// `None` when nothing changed.
+pub async fn refresh_whitelist_merkle(
+    pool: &PgPool,
+    solana_service: &SolanaService,
+) -> Result<Option<WhitelistMerkleVersion>> {
+    let token_decimals = solana_service.get_token_decimals().await?;
+
+    let mut db_tx = pool.begin().await?;
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+        .bind(MERKLE_LOCK_KEY)
+        .execute(&mut *db_tx)
+        .await?;
+
+    // Read under the lock so a refresh never snapshots an older whitelist
+    // after a newer one
+    let leaves = current_allocation_leaves(pool, token_decimals).await?;
+    let hashes = leaves
+        .iter()
+        .map(|leaf| parse_hash_hex(&leaf.leaf).ok_or_else(|| anyhow!("Malformed leaf {}", leaf.leaf)))
+        .collect::<Result<Vec<_>>>()?;
+    let root = hash_hex(&MerkleTree::new(hashes).root());
+
+    let latest: Option<(String, i16)> = sqlx::query_as(
+        "SELECT root, token_decimals FROM whitelist_merkle_versions ORDER BY version DESC LIMIT 1"
+    )
+    .fetch_optional(&mut *db_tx)
+    .await?;
+    if latest.is_some_and(|(latest_root, decimals)| latest_root == root && decimals == token_decimals as i16) {
+        return Ok(None);
+    }
+
+    let version = sqlx::query_as::<_, WhitelistMerkleVersion>(
+        r#"
+        INSERT INTO whitelist_merkle_versions (root, leaf_count, total_allocation, token_decimals)
+        VALUES ($1, $2, $3, $4)
+        RETURNING *
+        "#
+    )
+    .bind(&root)
+    .bind(leaves.len() as i32)
+    .bind(leaves.iter().map(|leaf| leaf.max_allocation).sum::<Decimal>())
+    .bind(token_decimals as i16)
+    .fetch_one(&mut *db_tx)
+    .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO whitelist_merkle_leaves (version, wallet_address, tier, max_allocation, amount_units, leaf)
+        SELECT $1, * FROM UNNEST($2::varchar[], $3::int[], $4::numeric[], $5::numeric[], $6::varchar[])
+        "#
+    )
+    .bind(version.version)
+    .bind(leaves.iter().map(|leaf| leaf.wallet_address.clone()).collect::<Vec<_>>())
+    .bind(leaves.iter().map(|leaf| leaf.tier).collect::<Vec<_>>())
+    .bind(leaves.iter().map(|leaf| leaf.max_allocation).collect::<Vec<_>>())
+    .bind(leaves.iter().map(|leaf| leaf.amount_units).collect::<Vec<_>>())
+    .bind(leaves.iter().map(|leaf| leaf.leaf.clone()).collect::<Vec<_>>())
+    .execute(&mut *db_tx)
+    .await?;
+
+    db_tx.commit().await?;
+    println!(
+        "🌳 Whitelist Merkle root v{}: {} ({} wallets)",
+        version.version, version.root, version.leaf_count
+    );
+
+    Ok(Some(version))
+}
+
+/// A wallet's leaf and proof in the given tree version, or the latest.
+/// `None` if there is no such version or the wallet isn't in it.
+pub async fn whitelist_merkle_proof(
+    pool: &PgPool,
+    wallet_address: &str,
+    version: Option<i32>,
+) -> Result<Option<WhitelistProof>> {
+    let snapshot = sqlx::query_as::<_, WhitelistMerkleVersion>(
+        r#"
+        SELECT * FROM whitelist_merkle_versions
+        WHERE $1::int IS NULL OR version = $1
+        ORDER BY version DESC
+        LIMIT 1
+        "#
+    )
+    .bind(version)
+    .fetch_optional(pool)
+    .await?;
+    let Some(snapshot) = snapshot else {
+        return Ok(None);
+    };
+
+    let leaf = sqlx::query_as::<_, WhitelistMerkleLeaf>(
+        r#"
+        SELECT wallet_address, tier, max_allocation, amount_units, leaf
+        FROM whitelist_merkle_leaves
+        WHERE version = $1 AND wallet_address = $2
+        "#
+    )
+    .bind(snapshot.version)
+    .bind(wallet_address)
+    .fetch_optional(pool)
+    .await?;
+    let Some(leaf) = leaf else {
+        return Ok(None);
+    };
+
+    // Snapshots are immutable, so the tree is rebuilt from its stored leaves
+    let hashes: Vec<String> = sqlx::query_scalar("SELECT leaf FROM whitelist_merkle_leaves WHERE version = $1")
+        .bind(snapshot.version)
+        .fetch_all(pool)
+        .await?;
+    let hashes = hashes
+        .iter()
+        .map(|hash| parse_hash_hex(hash).ok_or_else(|| anyhow!("Malformed leaf {}", hash)))
+        .collect::<Result<Vec<_>>>()?;
+    let tree = MerkleTree::new(hashes);
+
+    let leaf_hash = parse_hash_hex(&leaf.leaf).ok_or_else(|| anyhow!("Malformed leaf {}", leaf.leaf))?;
+    let proof = tree
+        .proof(&leaf_hash)
+        .ok_or_else(|| anyhow!("Leaf of {} missing from version {}", wallet_address, snapshot.version))?;
+    let root = parse_hash_hex(&snapshot.root).ok_or_else(|| anyhow!("Malformed root {}", snapshot.root))?;
+    if !verify_proof(&proof, &root, &leaf_hash) {
+        return Err(anyhow!("Leaves of version {} don't match its root", snapshot.version));
+    }
+
+    Ok(Some(WhitelistProof {
+        version: snapshot.version,
+        root: snapshot.root,
+        token_decimals: snapshot.token_decimals,
+        leaf,
+        proof: proof.iter().map(hash_hex).collect(),
+    }))
+}
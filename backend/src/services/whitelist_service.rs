@@ -0,0 +1,569 @@
+use anyhow::Result;
+use sqlx::types::Json;
+use rust_decimal::Decimal;
+use sqlx::{PgConnection, PgPool};
+use std::fmt;
+use uuid::Uuid;
+
+use crate::models::{
+    ApproveApplicationRequest, PresaleSettings, UpsertWhitelistTierRequest, User, WhitelistApplication,
+    WhitelistApplicationQuery, WhitelistApplicationRequest, WhitelistTier,
+};
+use crate::services::{record_audit_event_in, AuditContext, AuditEntry};
+use crate::utils::WhitelistEntry;
+
+/// Reason a purchase doesn't fit the buyer's whitelist allocation
+#[derive(Debug, Clone)]
+pub enum WhitelistRejection {
+    NotWhitelisted,
+    AllocationExceeded { remaining: Decimal },
+}
+
+impl WhitelistRejection {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::NotWhitelisted => "not_whitelisted",
+            Self::AllocationExceeded { .. } => "allocation_exceeded",
+        }
+    }
+}
+
+impl fmt::Display for WhitelistRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotWhitelisted => write!(f, "User not whitelisted"),
+            Self::AllocationExceeded { remaining } => {
+                write!(f, "Exceeds allocation limit. Remaining: {}", remaining)
+            }
+        }
+    }
+}
+
+impl std::error::Error for WhitelistRejection {}
+
+/// Reason a whitelist application can't be submitted or decided
+#[derive(Debug, Clone)]
+pub enum ApplicationRejection {
+    AlreadyWhitelisted,
+    /// The wallet has an application that hasn't been decided yet
+    AlreadyOpen,
+    NotFound,
+    /// The application is past the state the action applies to
+    InvalidTransition { status: String },
+    UnknownTier,
+}
+
+impl ApplicationRejection {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::AlreadyWhitelisted => "already_whitelisted",
+            Self::AlreadyOpen => "application_open",
+            Self::NotFound => "application_not_found",
+            Self::InvalidTransition { .. } => "invalid_transition",
+            Self::UnknownTier => "unknown_tier",
+        }
+    }
+}
+
+impl fmt::Display for ApplicationRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AlreadyWhitelisted => write!(f, "Wallet is already whitelisted"),
+            Self::AlreadyOpen => write!(f, "Wallet already has an application under review"),
+            Self::NotFound => write!(f, "Application not found"),
+            Self::InvalidTransition { status } => write!(f, "Application is already {}", status),
+            Self::UnknownTier => write!(f, "Unknown whitelist tier"),
+        }
+    }
+}
+
+impl std::error::Error for ApplicationRejection {}
+
+/// Allocation left across the user's unexpired whitelist entries. Users
+/// without entries get their tier's default allocation; `None` means no
+/// limit (no entries and no tier).
+pub async fn remaining_whitelist_allocation(pool: &PgPool, user: &User) -> Result<Option<Decimal>> {
+    let (entries, remaining): (i64, Decimal) = sqlx::query_as(
+        r#"
+        SELECT COUNT(*), COALESCE(SUM(max_allocation - used_allocation), 0)
+        FROM whitelist_entries
+        WHERE user_id = $1 AND (expires_at IS NULL OR expires_at > NOW())
+        "#
+    )
+    .bind(user.id)
+    .fetch_one(pool)
+    .await?;
+
+    if entries > 0 {
+        return Ok(Some(remaining));
+    }
+
+    let tier_default: Option<Decimal> = sqlx::query_scalar(
+        "SELECT default_max_allocation FROM whitelist_tiers WHERE tier = $1 AND tier > 0"
+    )
+    .bind(user.whitelist_tier)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(tier_default)
+}
+
+/// Take `tokens` from the user's whitelist allocation as part of the
+/// purchase's database transaction.
+///
+/// The user's unexpired entries are locked and drawn down soonest-expiring
+/// first, each draw recorded against `transaction_id`. If the transaction
+/// rolls back the allocation is untouched. Users with no entries have
+/// no limit, unless they have a tier: then an entry with the tier's default
+/// allocation is opened for them. A shortfall is returned as a
+/// [`WhitelistRejection`] inside the error.
+pub async fn consume_whitelist_allocation(
+    conn: &mut PgConnection,
+    settings: &PresaleSettings,
+    user: &User,
+    transaction_id: &Uuid,
+    tokens: Decimal,
+) -> Result<()> {
+    if !settings.whitelist_enabled {
+        return Ok(());
+    }
+
+    let mut entries = sqlx::query_as::<_, WhitelistEntry>(
+        r#"
+        SELECT * FROM whitelist_entries
+        WHERE user_id = $1 AND (expires_at IS NULL OR expires_at > NOW())
+        ORDER BY expires_at NULLS LAST, created_at
+        FOR UPDATE
+        "#
+    )
+    .bind(user.id)
+    .fetch_all(&mut *conn)
+    .await?;
+
+    if entries.is_empty() {
+        // The buyer's row is locked by the caller, so this can't race
+        let tier_entry = sqlx::query_as::<_, WhitelistEntry>(
+            r#"
+            INSERT INTO whitelist_entries (user_id, tier, max_allocation)
+            SELECT $1, tier, default_max_allocation FROM whitelist_tiers WHERE tier = $2 AND tier > 0
+            RETURNING *
+            "#
+        )
+        .bind(user.id)
+        .bind(user.whitelist_tier)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        match tier_entry {
+            Some(entry) => entries.push(entry),
+            None => return Ok(()),
+        }
+    }
+
+    let remaining: Decimal = entries.iter().map(|entry| entry.max_allocation - entry.used_allocation).sum();
+    if tokens > remaining {
+        return Err(WhitelistRejection::AllocationExceeded { remaining }.into());
+    }
+
+    let mut outstanding = tokens;
+    for entry in entries {
+        if outstanding <= Decimal::ZERO {
+            break;
+        }
+        let amount = outstanding.min(entry.max_allocation - entry.used_allocation);
+        if amount <= Decimal::ZERO {
+            continue;
+        }
+
+        sqlx::query("UPDATE whitelist_entries SET used_allocation = used_allocation + $1 WHERE id = $2")
+            .bind(amount)
+            .bind(entry.id)
+            .execute(&mut *conn)
+            .await?;
+
+        sqlx::query(
+            "INSERT INTO whitelist_allocation_uses (entry_id, transaction_id, amount) VALUES ($1, $2, $3)"
+        )
+        .bind(entry.id)
+        .bind(transaction_id)
+        .bind(amount)
+        .execute(&mut *conn)
+        .await?;
+
+        outstanding -= amount;
+    }
+
+    Ok(())
+}
+
+/// All tiers, lowest first
+pub async fn list_whitelist_tiers(pool: &PgPool) -> Result<Vec<WhitelistTier>> {
+    let tiers = sqlx::query_as::<_, WhitelistTier>("SELECT * FROM whitelist_tiers ORDER BY tier")
+        .fetch_all(pool)
+        .await?;
+
+    Ok(tiers)
+}
+
+/// The tier a buyer purchases under; users who aren't (or are no longer)
+/// whitelisted get no tier rules
+pub async fn get_buyer_tier(pool: &PgPool, user: &User) -> Result<Option<WhitelistTier>> {
+    if !user.is_whitelisted || user.whitelist_tier <= 0 {
+        return Ok(None);
+    }
+
+    let tier = sqlx::query_as::<_, WhitelistTier>("SELECT * FROM whitelist_tiers WHERE tier = $1")
+        .bind(user.whitelist_tier)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(tier)
+}
+
+/// Create or replace a tier. Members' entries without a custom allocation
+/// move to the new default (never below what they already used); the other
+/// rules are read at purchase time, so they apply to all members at once.
+/// Returns the tier and how many entries were updated.
+pub async fn upsert_whitelist_tier(
+    pool: &PgPool,
+    tier: i32,
+    req: &UpsertWhitelistTierRequest,
+    audit: &AuditContext,
+) -> Result<(WhitelistTier, u64)> {
+    let mut db_tx = pool.begin().await?;
+
+    let previous = sqlx::query_as::<_, WhitelistTier>("SELECT * FROM whitelist_tiers WHERE tier = $1 FOR UPDATE")
+        .bind(tier)
+        .fetch_optional(&mut *db_tx)
+        .await?;
+
+    let updated = sqlx::query_as::<_, WhitelistTier>(
+        r#"
+        INSERT INTO whitelist_tiers (
+            tier, name, default_max_allocation, min_purchase, early_access_secs, price_discount_percent,
+            vesting_tge_unlock_percent, vesting_cliff_days, vesting_duration_days, vesting_release_kind
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        ON CONFLICT (tier) DO UPDATE SET
+            name = EXCLUDED.name,
+            default_max_allocation = EXCLUDED.default_max_allocation,
+            min_purchase = EXCLUDED.min_purchase,
+            early_access_secs = EXCLUDED.early_access_secs,
+            price_discount_percent = EXCLUDED.price_discount_percent,
+            vesting_tge_unlock_percent = EXCLUDED.vesting_tge_unlock_percent,
+            vesting_cliff_days = EXCLUDED.vesting_cliff_days,
+            vesting_duration_days = EXCLUDED.vesting_duration_days,
+            vesting_release_kind = EXCLUDED.vesting_release_kind,
+            updated_at = NOW()
+        RETURNING *
+        "#
+    )
+    .bind(tier)
+    .bind(req.name.trim())
+    .bind(req.default_max_allocation)
+    .bind(req.min_purchase)
+    .bind(req.early_access_secs)
+    .bind(req.price_discount_percent)
+    .bind(req.vesting_tge_unlock_percent)
+    .bind(req.vesting_cliff_days)
+    .bind(req.vesting_duration_days)
+    .bind(&req.vesting_release_kind)
+    .fetch_one(&mut *db_tx)
+    .await?;
+
+    let entries = sqlx::query(
+        r#"
+        UPDATE whitelist_entries
+        SET max_allocation = GREATEST($1, used_allocation)
+        WHERE tier = $2 AND NOT custom_allocation
+        "#
+    )
+    .bind(updated.default_max_allocation)
+    .bind(tier)
+    .execute(&mut *db_tx)
+    .await?
+    .rows_affected();
+
+    let entry = AuditEntry {
+        action: "whitelist.tier_saved",
+        entity_type: "whitelist_tier",
+        entity_id: tier.to_string(),
+        before: previous.map(serde_json::to_value).transpose()?,
+        after: Some(serde_json::to_value(&updated)?),
+    };
+    record_audit_event_in(&mut db_tx, audit, entry).await?;
+    db_tx.commit().await?;
+
+    println!("🎟️  Whitelist tier {} ({}) saved, {} entries updated", tier, updated.name, entries);
+    Ok((updated, entries))
+}
+
+/// File a whitelist application for `user`.
+/// Rejections are returned as an [`ApplicationRejection`] inside the error.
+pub async fn submit_whitelist_application(
+    pool: &PgPool,
+    user: &User,
+    req: &WhitelistApplicationRequest,
+    audit: &AuditContext,
+) -> Result<WhitelistApplication> {
+    if user.is_whitelisted {
+        return Err(ApplicationRejection::AlreadyWhitelisted.into());
+    }
+
+    let mut db_tx = pool.begin().await?;
+
+    let application = sqlx::query_as::<_, WhitelistApplication>(
+        r#"
+        INSERT INTO whitelist_applications (
+            user_id, requested_tier, answers, email, discord_username, twitter_username, telegram_username
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING *
+        "#
+    )
+    .bind(user.id)
+    .bind(req.requested_tier)
+    .bind(Json(req.answers.clone().unwrap_or_else(|| serde_json::json!({}))))
+    .bind(&req.email)
+    .bind(&req.discord_username)
+    .bind(&req.twitter_username)
+    .bind(&req.telegram_username)
+    .fetch_one(&mut *db_tx)
+    .await;
+
+    match application {
+        Ok(application) => {
+            let entry = AuditEntry {
+                action: "whitelist.application_submitted",
+                entity_type: "whitelist_application",
+                entity_id: application.id.to_string(),
+                before: None,
+                after: Some(application_audit_state(&application)),
+            };
+            record_audit_event_in(&mut db_tx, audit, entry).await?;
+            db_tx.commit().await?;
+
+            println!("📝 Whitelist application {} from {}", application.id, user.wallet_address);
+            Ok(application)
+        }
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Err(ApplicationRejection::AlreadyOpen.into()),
+        Err(sqlx::Error::Database(e)) if e.is_foreign_key_violation() => Err(ApplicationRejection::UnknownTier.into()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The decision-relevant fields of an application for the audit log. The
+/// applicant's answers and contact details are left out, since audit events
+/// can never be erased.
+fn application_audit_state(application: &WhitelistApplication) -> serde_json::Value {
+    serde_json::json!({
+        "user_id": application.user_id,
+        "status": application.status,
+        "requested_tier": application.requested_tier,
+        "approved_tier": application.approved_tier,
+        "approved_allocation": application.approved_allocation,
+        "whitelist_entry_id": application.whitelist_entry_id,
+        "rejection_reason": application.rejection_reason,
+    })
+}
+
+/// The user's most recent application, if any
+pub async fn latest_whitelist_application(pool: &PgPool, user_id: &Uuid) -> Result<Option<WhitelistApplication>> {
+    let application = sqlx::query_as::<_, WhitelistApplication>(
+        "SELECT * FROM whitelist_applications WHERE user_id = $1 ORDER BY submitted_at DESC LIMIT 1"
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(application)
+}
+
+/// Applications oldest first, optionally filtered by status and requested tier
+pub async fn list_whitelist_applications(
+    pool: &PgPool,
+    query: &WhitelistApplicationQuery,
+) -> Result<Vec<WhitelistApplication>> {
+    let page = query.page.unwrap_or(1).max(1);
+    let per_page = query.per_page.unwrap_or(50).clamp(1, 200);
+
+    let applications = sqlx::query_as::<_, WhitelistApplication>(
+        r#"
+        SELECT * FROM whitelist_applications
+        WHERE ($1::VARCHAR IS NULL OR status = $1)
+          AND ($2::INTEGER IS NULL OR requested_tier = $2)
+        ORDER BY submitted_at
+        LIMIT $3 OFFSET $4
+        "#
+    )
+    .bind(&query.status)
+    .bind(query.tier)
+    .bind(per_page)
+    .bind((page - 1) * per_page)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(applications)
+}
+
+/// Lock an application and check it is still undecided
+async fn lock_open_application(conn: &mut PgConnection, application_id: &Uuid) -> Result<WhitelistApplication> {
+    let application = sqlx::query_as::<_, WhitelistApplication>(
+        "SELECT * FROM whitelist_applications WHERE id = $1 FOR UPDATE"
+    )
+    .bind(application_id)
+    .fetch_optional(&mut *conn)
+    .await?
+    .ok_or(ApplicationRejection::NotFound)?;
+
+    if application.status != "submitted" && application.status != "under_review" {
+        return Err(ApplicationRejection::InvalidTransition { status: application.status }.into());
+    }
+    Ok(application)
+}
+
+/// Mark a submitted application as being reviewed
+pub async fn start_application_review(
+    pool: &PgPool,
+    application_id: &Uuid,
+    audit: &AuditContext,
+) -> Result<WhitelistApplication> {
+    let mut db_tx = pool.begin().await?;
+
+    let previous = lock_open_application(&mut db_tx, application_id).await?;
+    if previous.status != "submitted" {
+        return Err(ApplicationRejection::InvalidTransition { status: previous.status }.into());
+    }
+
+    let application = sqlx::query_as::<_, WhitelistApplication>(
+        r#"
+        UPDATE whitelist_applications
+        SET status = 'under_review', reviewed_at = NOW(), updated_at = NOW()
+        WHERE id = $1
+        RETURNING *
+        "#
+    )
+    .bind(application_id)
+    .fetch_one(&mut *db_tx)
+    .await?;
+
+    let entry = AuditEntry {
+        action: "whitelist.review_started",
+        entity_type: "whitelist_application",
+        entity_id: application.id.to_string(),
+        before: Some(application_audit_state(&previous)),
+        after: Some(application_audit_state(&application)),
+    };
+    record_audit_event_in(&mut db_tx, audit, entry).await?;
+    db_tx.commit().await?;
+    Ok(application)
+}
+
+/// Approve an application: open a whitelist entry in the given tier and
+/// whitelist the user, all in one transaction
+pub async fn approve_whitelist_application(
+    pool: &PgPool,
+    application_id: &Uuid,
+    req: &ApproveApplicationRequest,
+    audit: &AuditContext,
+) -> Result<WhitelistApplication> {
+    let mut db_tx = pool.begin().await?;
+
+    let previous = lock_open_application(&mut db_tx, application_id).await?;
+
+    let tier = sqlx::query_as::<_, WhitelistTier>("SELECT * FROM whitelist_tiers WHERE tier = $1 AND tier > 0")
+        .bind(req.tier)
+        .fetch_optional(&mut *db_tx)
+        .await?
+        .ok_or(ApplicationRejection::UnknownTier)?;
+    let max_allocation = req.max_allocation.unwrap_or(tier.default_max_allocation);
+
+    let entry_id: Uuid = sqlx::query_scalar(
+        r#"
+        INSERT INTO whitelist_entries (user_id, tier, max_allocation, custom_allocation, expires_at)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id
+        "#
+    )
+    .bind(previous.user_id)
+    .bind(tier.tier)
+    .bind(max_allocation)
+    .bind(req.max_allocation.is_some())
+    .bind(req.expires_at)
+    .fetch_one(&mut *db_tx)
+    .await?;
+
+    sqlx::query(
+        "UPDATE users SET is_whitelisted = true, whitelist_tier = $1, updated_at = NOW() WHERE id = $2"
+    )
+    .bind(tier.tier)
+    .bind(previous.user_id)
+    .execute(&mut *db_tx)
+    .await?;
+
+    let application = sqlx::query_as::<_, WhitelistApplication>(
+        r#"
+        UPDATE whitelist_applications
+        SET status = 'approved', approved_tier = $1, approved_allocation = $2, whitelist_entry_id = $3,
+            reviewed_at = COALESCE(reviewed_at, NOW()), decided_at = NOW(), updated_at = NOW()
+        WHERE id = $4
+        RETURNING *
+        "#
+    )
+    .bind(tier.tier)
+    .bind(max_allocation)
+    .bind(entry_id)
+    .bind(application_id)
+    .fetch_one(&mut *db_tx)
+    .await?;
+
+    let entry = AuditEntry {
+        action: "whitelist.approved",
+        entity_type: "whitelist_application",
+        entity_id: application.id.to_string(),
+        before: Some(application_audit_state(&previous)),
+        after: Some(application_audit_state(&application)),
+    };
+    record_audit_event_in(&mut db_tx, audit, entry).await?;
+    db_tx.commit().await?;
+
+    println!("✅ Whitelist application {} approved for tier {}", application.id, tier.name);
+    Ok(application)
+}
+
+/// Reject an application with a reason shown to the applicant
+pub async fn reject_whitelist_application(
+    pool: &PgPool,
+    application_id: &Uuid,
+    reason: &str,
+    audit: &AuditContext,
+) -> Result<WhitelistApplication> {
+    let mut db_tx = pool.begin().await?;
+
+    let previous = lock_open_application(&mut db_tx, application_id).await?;
+
+    let application = sqlx::query_as::<_, WhitelistApplication>(
+        r#"
+        UPDATE whitelist_applications
+        SET status = 'rejected', rejection_reason = $1,
+            reviewed_at = COALESCE(reviewed_at, NOW()), decided_at = NOW(), updated_at = NOW()
+        WHERE id = $2
+        RETURNING *
+        "#
+    )
+    .bind(reason)
+    .bind(application_id)
+    .fetch_one(&mut *db_tx)
+    .await?;
+
+    let entry = AuditEntry {
+        action: "whitelist.rejected",
+        entity_type: "whitelist_application",
+        entity_id: application.id.to_string(),
+        before: Some(application_audit_state(&previous)),
+        after: Some(application_audit_state(&application)),
+    };
+    record_audit_event_in(&mut db_tx, audit, entry).await?;
+    db_tx.commit().await?;
+    Ok(application)
+}
@@ -1,117 +1,170 @@
-use sqlx::PgPool;
+use sqlx::{PgConnection, PgExecutor, PgPool};
 use uuid::Uuid;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use rust_decimal::Decimal;
+use chrono::{TimeZone, Utc};
 use crate::models::*;
+use crate::services::{remaining_whitelist_allocation, OraclePrice, SupplyReservation, WhitelistRejection};
+
+/// Attempts at drawing an unused referral code before giving up
+const REFERRAL_CODE_ATTEMPTS: usize = 5;
 
 /// Get or create user by wallet address
 pub async fn get_or_create_user(pool: &PgPool, wallet_address: &str) -> Result<User> {
-    // Try to find existing user
-    if let Ok(user) = sqlx::query_as::<_, User>(
-        "SELECT * FROM users WHERE wallet_address = $1"
-    )
-    .bind(wallet_address)
-    .fetch_one(pool)
-    .await
-    {
-        return Ok(user);
+    let mut conn = pool.acquire().await?;
+    get_or_create_user_in(&mut conn, wallet_address).await
+}
+
+/// Get or create user by wallet address on a connection, e.g. inside a
+/// larger transaction
+///
+/// A new user gets a random referral code. If the code is taken (or another
+/// request registers the wallet first) the insert does nothing and is retried
+/// with a fresh code, so a collision never fails registration.
+pub async fn get_or_create_user_in(conn: &mut PgConnection, wallet_address: &str) -> Result<User> {
+    for _ in 0..REFERRAL_CODE_ATTEMPTS {
+        if let Some(user) = find_user_by_wallet(&mut *conn, wallet_address).await? {
+            return Ok(user);
+        }
+
+        let referral_code = User::generate_referral_code();
+        let user = sqlx::query_as::<_, User>(
+            r#"
+            INSERT INTO users (wallet_address, referral_code, created_at, updated_at)
+            VALUES ($1, $2, NOW(), NOW())
+            ON CONFLICT DO NOTHING
+            RETURNING *
+            "#
+        )
+        .bind(wallet_address)
+        .bind(&referral_code)
+        .fetch_optional(&mut *conn)
+        .await?;
+
+        if let Some(user) = user {
+            return Ok(user);
+        }
     }
 
-    // Create new user
-    let referral_code = User::generate_referral_code();
+    Err(anyhow!("Could not allocate a unique referral code for {}", wallet_address))
+}
+
+/// Fill in profile fields supplied at registration, keeping existing values
+/// for fields left out
+pub async fn update_user_profile(pool: &PgPool, user_id: &Uuid, req: &CreateUserRequest) -> Result<User> {
     let user = sqlx::query_as::<_, User>(
         r#"
-        INSERT INTO users (wallet_address, referral_code, created_at, updated_at)
-        VALUES ($1, $2, NOW(), NOW())
+        UPDATE users
+        SET email = COALESCE($1, email),
+            discord_username = COALESCE($2, discord_username),
+            twitter_username = COALESCE($3, twitter_username),
+            updated_at = NOW()
+        WHERE id = $4
         RETURNING *
         "#
     )
-    .bind(wallet_address)
-    .bind(&referral_code)
+    .bind(&req.email)
+    .bind(&req.discord_username)
+    .bind(&req.twitter_username)
+    .bind(user_id)
     .fetch_one(pool)
     .await?;
 
     Ok(user)
 }
 
-/// Check whitelist eligibility
+/// Tokens a user has bought in confirmed purchases, and how many users they referred
+pub async fn get_user_totals(pool: &PgPool, user_id: &Uuid) -> Result<(Decimal, i64)> {
+    let totals = sqlx::query_as::<_, (Decimal, i64)>(
+        r#"
+        SELECT
+            (SELECT COALESCE(SUM(amount_tokens), 0) FROM transactions
+             WHERE user_id = $1 AND status = 'confirmed'),
+            (SELECT COUNT(*) FROM referrals WHERE referrer_id = $1)
+        "#
+    )
+    .bind(user_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(totals)
+}
+
+/// Find a user by wallet address
+pub async fn find_user_by_wallet<'e>(executor: impl PgExecutor<'e>, wallet_address: &str) -> Result<Option<User>> {
+    let user = sqlx::query_as::<_, User>(
+        "SELECT * FROM users WHERE wallet_address = $1"
+    )
+    .bind(wallet_address)
+    .fetch_optional(executor)
+    .await?;
+
+    Ok(user)
+}
+
+/// Check whitelist eligibility ahead of verification. This is advisory; the
+/// allocation is only taken by `consume_whitelist_allocation`.
 pub async fn check_whitelist_eligibility(
     pool: &PgPool, 
+    settings: &PresaleSettings,
     user: &User, 
-    amount: f64
+    amount: Decimal
 ) -> Result<()> {
-    // Check if whitelist is enabled
-    let whitelist_enabled: bool = sqlx::query_scalar(
-        "SELECT value::boolean FROM presale_settings WHERE key = 'whitelist_enabled'"
-    )
-    .fetch_optional(pool)
-    .await?
-    .unwrap_or(false);
-
-    if !whitelist_enabled {
+    if !settings.whitelist_enabled {
         return Ok(());
     }
 
     if !user.is_whitelisted {
-        return Err(anyhow::anyhow!("User not whitelisted"));
+        return Err(WhitelistRejection::NotWhitelisted.into());
     }
 
-    // Check allocation limits
-    if let Ok(entry) = sqlx::query_as::<_, WhitelistEntry>(
-        "SELECT * FROM whitelist_entries WHERE user_id = $1 AND (expires_at IS NULL OR expires_at > NOW())"
-    )
-    .bind(user.id)
-    .fetch_one(pool)
-    .await
-    {
-        let remaining = entry.max_allocation - entry.used_allocation;
-        if amount > remaining.to_string().parse::<f64>().unwrap_or(0.0) {
-            return Err(anyhow::anyhow!(
-                "Exceeds allocation limit. Remaining: {}", 
-                remaining
-            ));
+    if let Some(remaining) = remaining_whitelist_allocation(pool, user).await? {
+        if amount > remaining {
+            return Err(WhitelistRejection::AllocationExceeded { remaining }.into());
         }
     }
 
     Ok(())
 }
 
-/// Create transaction record
+/// Create transaction record for the supply reserved to this purchase
 pub async fn create_transaction(
-    pool: &PgPool,
+    conn: &mut PgConnection,
     user_id: &Uuid,
     req: &CreateTransactionRequest,
+    reservation: &SupplyReservation,
+    oracle_price: Option<&OraclePrice>,
 ) -> Result<Transaction> {
-    let token_price: f64 = sqlx::query_scalar(
-        "SELECT value::float FROM presale_settings WHERE key = 'token_price_sol'"
-    )
-    .fetch_optional(pool)
-    .await?
-    .unwrap_or(0.000045);
-
     let transaction = sqlx::query_as::<_, Transaction>(
         r#"
         INSERT INTO transactions (
-            user_id, solana_signature, amount_tokens, amount_sol, 
-            payment_method, status, created_at
+            user_id, solana_signature, amount_tokens, amount_sol, amount_requested,
+            refund_amount_sol, payment_method, round_id, oracle_price,
+            oracle_publish_time, status, created_at
         )
-        VALUES ($1, $2, $3, $4, $5, 'pending', NOW())
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, 'pending', NOW())
         RETURNING *
         "#
     )
     .bind(user_id)
     .bind(&req.signature)
-    .bind(rust_decimal::Decimal::from_f64_retain(req.amount).unwrap())
-    .bind(rust_decimal::Decimal::from_f64_retain(req.amount * token_price).unwrap())
+    .bind(reservation.tokens)
+    .bind(reservation.sol)
+    .bind(Decimal::from_f64_retain(req.amount).unwrap_or_default())
+    .bind(reservation.refund_sol)
     .bind(req.payment_method.as_deref().unwrap_or("SOL"))
-    .fetch_one(pool)
+    .bind(reservation.fills.first().map(|fill| fill.round_id))
+    .bind(oracle_price.map(|oracle| oracle.price.round_dp(8)))
+    .bind(oracle_price.and_then(|oracle| Utc.timestamp_opt(oracle.publish_time, 0).single()))
+    .fetch_one(conn)
     .await?;
 
     Ok(transaction)
 }
 
 /// Update transaction status
-pub async fn update_transaction_status(
-    pool: &PgPool,
+pub async fn update_transaction_status<'e>(
+    executor: impl PgExecutor<'e>,
     transaction_id: &Uuid,
     status: &str,
     block_height: Option<i64>,
@@ -126,37 +179,7 @@ pub async fn update_transaction_status(
     .bind(status)
     .bind(block_height)
     .bind(transaction_id)
-    .execute(pool)
-    .await?;
-
-    Ok(())
-}
-
-/// Process referral bonus
-pub async fn process_referral_bonus(
-    pool: &PgPool,
-    referrer_id: &Uuid,
-    purchase_amount: f64,
-) -> Result<()> {
-    let bonus_percentage: f64 = sqlx::query_scalar(
-        "SELECT value::float FROM presale_settings WHERE key = 'referral_bonus'"
-    )
-    .fetch_optional(pool)
-    .await?
-    .unwrap_or(5.0);
-
-    let bonus_tokens = purchase_amount * (bonus_percentage / 100.0);
-
-    sqlx::query(
-        r#"
-        UPDATE referrals 
-        SET bonus_tokens = bonus_tokens + $1
-        WHERE referrer_id = $2 AND is_active = true
-        "#
-    )
-    .bind(bonus_tokens)
-    .bind(referrer_id)
-    .execute(pool)
+    .execute(executor)
     .await?;
 
     Ok(())
@@ -165,8 +188,6 @@ pub async fn process_referral_bonus(
 #[derive(sqlx::FromRow)]
 pub struct WhitelistEntry {
     pub id: Uuid,
-    pub user_id: Uuid,
-    pub tier: i32,
     pub max_allocation: rust_decimal::Decimal,
     pub used_allocation: rust_decimal::Decimal,
 }